@@ -249,17 +249,34 @@ END;
 $$ LANGUAGE plpgsql;
 
 -- ROLES
-CREATE ROLE admin;
-CREATE ROLE analyst;
-CREATE ROLE master;
-CREATE ROLE manager;
+-- Роли общие для всего сервера, поэтому создаются, только если их ещё нет.
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT FROM pg_roles WHERE rolname = 'admin') THEN
+        CREATE ROLE admin;
+    END IF;
+    IF NOT EXISTS (SELECT FROM pg_roles WHERE rolname = 'analyst') THEN
+        CREATE ROLE analyst;
+    END IF;
+    IF NOT EXISTS (SELECT FROM pg_roles WHERE rolname = 'master') THEN
+        CREATE ROLE master;
+    END IF;
+    IF NOT EXISTS (SELECT FROM pg_roles WHERE rolname = 'manager') THEN
+        CREATE ROLE manager;
+    END IF;
+END
+$$;
 
 
 -- Администраторы имеют полный доступ к системе и могут управлять пользователями
 GRANT ALL PRIVILEGES ON SCHEMA moto_auto TO admin;
 GRANT ALL PRIVILEGES ON ALL TABLES IN SCHEMA moto_auto TO admin;
 GRANT ALL PRIVILEGES ON ALL SEQUENCES IN SCHEMA moto_auto TO admin;
-GRANT CREATE ON DATABASE moto_auto TO admin;
+DO $$
+BEGIN
+    EXECUTE format('GRANT CREATE ON DATABASE %I TO admin', current_database());
+END
+$$;
 
 -- Аналитики имеют доступ на чтение из любой таблицы.
 GRANT USAGE ON SCHEMA moto_auto TO analyst;
@@ -476,7 +493,13 @@ VALUES
 (2, 2, 2, '2024-12-26 11:00:00', 'pending'),
 (3, 3, 3, '2024-12-27 12:00:00', 'cancelled');
 
-CREATE USER analyst1 PASSWORD 'password1';
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT FROM pg_roles WHERE rolname = 'analyst1') THEN
+        CREATE USER analyst1 PASSWORD 'password1';
+    END IF;
+END
+$$;
 GRANT analyst TO analyst1;
 -- TEST
 COMMIT;
@@ -0,0 +1,36 @@
+BEGIN;
+
+-- Филиалы не удаляются, а закрываются, чтобы сохранить историю заказов, пользователей и прайс-листов.
+ALTER TABLE moto_auto.branch ADD COLUMN closed_at TIMESTAMPTZ;
+
+ALTER TABLE moto_auto.users DROP CONSTRAINT users_role_check;
+ALTER TABLE moto_auto.users ADD CONSTRAINT users_role_check
+    CHECK (role IN ('superadmin', 'admin', 'analyst', 'master', 'manager'));
+
+ALTER TABLE moto_auto.users DROP CONSTRAINT users_branch_id_fkey;
+ALTER TABLE moto_auto.users ADD CONSTRAINT users_branch_id_fkey
+    FOREIGN KEY (branch_id) REFERENCES moto_auto.branch(branch_id) ON DELETE RESTRICT;
+
+ALTER TABLE moto_auto.branch_employee DROP CONSTRAINT branch_employee_branch_id_fkey;
+ALTER TABLE moto_auto.branch_employee ADD CONSTRAINT branch_employee_branch_id_fkey
+    FOREIGN KEY (branch_id) REFERENCES moto_auto.branch(branch_id) ON DELETE RESTRICT;
+
+ALTER TABLE moto_auto.service_branch DROP CONSTRAINT service_branch_branch_id_fkey;
+ALTER TABLE moto_auto.service_branch ADD CONSTRAINT service_branch_branch_id_fkey
+    FOREIGN KEY (branch_id) REFERENCES moto_auto.branch(branch_id) ON DELETE RESTRICT;
+
+ALTER TABLE moto_auto.spare_part_branch DROP CONSTRAINT spare_part_branch_branch_id_fkey;
+ALTER TABLE moto_auto.spare_part_branch ADD CONSTRAINT spare_part_branch_branch_id_fkey
+    FOREIGN KEY (branch_id) REFERENCES moto_auto.branch(branch_id) ON DELETE RESTRICT;
+
+ALTER TABLE moto_auto.schedule DROP CONSTRAINT schedule_branch_id_fkey;
+ALTER TABLE moto_auto.schedule ADD CONSTRAINT schedule_branch_id_fkey
+    FOREIGN KEY (branch_id) REFERENCES moto_auto.branch(branch_id) ON DELETE RESTRICT;
+
+-- Суперадминистратор открывает и закрывает филиалы. Администраторы редактируют только свой филиал.
+-- TEST
+INSERT INTO moto_auto.users (username, passwordhash, role, branch_id)
+VALUES
+('superadmin1', '3dc1a0155b33086998b57cd72b2ba3ecaa65f4faf0a8869d7930ea9fafaf403f', 'superadmin', 1);
+-- TEST
+COMMIT;
@@ -0,0 +1,65 @@
+BEGIN;
+
+-- Заказы, клиенты и каталог не удаляются, а архивируются: по закону сервис обязан хранить историю работ.
+ALTER TABLE moto_auto.orders ADD COLUMN deleted_at TIMESTAMPTZ;
+ALTER TABLE moto_auto.client ADD COLUMN deleted_at TIMESTAMPTZ;
+ALTER TABLE moto_auto.service ADD COLUMN deleted_at TIMESTAMPTZ;
+ALTER TABLE moto_auto.spare_part ADD COLUMN deleted_at TIMESTAMPTZ;
+
+CREATE INDEX idx_orders_deleted_at ON moto_auto.orders(deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX idx_client_deleted_at ON moto_auto.client(deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX idx_service_deleted_at ON moto_auto.service(deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX idx_spare_part_deleted_at ON moto_auto.spare_part(deleted_at) WHERE deleted_at IS NOT NULL;
+
+-- Окончательное удаление архивных записей старше срока хранения. Вызывается только отдельным заданием.
+CREATE OR REPLACE FUNCTION purge_archived_records(retention INTERVAL)
+RETURNS INTEGER AS $$
+DECLARE
+    purged INTEGER := 0;
+    affected INTEGER;
+BEGIN
+    CREATE TEMP TABLE purged_orders ON COMMIT DROP AS
+    SELECT order_id
+    FROM moto_auto.orders
+    WHERE deleted_at < NOW() - retention;
+
+    DELETE FROM moto_auto.order_service_part
+    WHERE order_service_id IN (
+        SELECT order_service_id
+        FROM moto_auto.order_service
+        WHERE order_id IN (SELECT order_id FROM purged_orders)
+    );
+    DELETE FROM moto_auto.order_service
+    WHERE order_id IN (SELECT order_id FROM purged_orders);
+    DELETE FROM moto_auto.schedule
+    WHERE order_id IN (SELECT order_id FROM purged_orders);
+    DELETE FROM moto_auto.orders
+    WHERE order_id IN (SELECT order_id FROM purged_orders);
+    GET DIAGNOSTICS affected = ROW_COUNT;
+    purged := purged + affected;
+
+    DROP TABLE purged_orders;
+
+    DELETE FROM moto_auto.client c
+    WHERE c.deleted_at < NOW() - retention
+      AND NOT EXISTS (SELECT 1 FROM moto_auto.orders o WHERE o.client_id = c.client_id);
+    GET DIAGNOSTICS affected = ROW_COUNT;
+    purged := purged + affected;
+
+    DELETE FROM moto_auto.service s
+    WHERE s.deleted_at < NOW() - retention
+      AND NOT EXISTS (SELECT 1 FROM moto_auto.order_service os WHERE os.service_id = s.service_id);
+    GET DIAGNOSTICS affected = ROW_COUNT;
+    purged := purged + affected;
+
+    DELETE FROM moto_auto.spare_part sp
+    WHERE sp.deleted_at < NOW() - retention
+      AND NOT EXISTS (SELECT 1 FROM moto_auto.order_service_part osp WHERE osp.part_id = sp.part_id);
+    GET DIAGNOSTICS affected = ROW_COUNT;
+    purged := purged + affected;
+
+    RETURN purged;
+END;
+$$ LANGUAGE plpgsql;
+
+COMMIT;
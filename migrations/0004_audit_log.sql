@@ -0,0 +1,124 @@
+BEGIN;
+
+-- Журнал изменений: кто, что и когда поменял в данных.
+-- Приложение передаёт пользователя через SET LOCAL moto_auto.actor в начале транзакции.
+CREATE TABLE moto_auto.audit_log (
+    audit_id BIGSERIAL PRIMARY KEY,
+    actor_user_id INTEGER,
+    entity VARCHAR(50) NOT NULL,
+    entity_id INTEGER NOT NULL,
+    operation VARCHAR(10) NOT NULL CHECK (operation IN ('create', 'update', 'delete', 'restore')),
+    before JSONB,
+    after JSONB,
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_audit_log_entity ON moto_auto.audit_log(entity, entity_id);
+CREATE INDEX idx_audit_log_actor_user_id ON moto_auto.audit_log(actor_user_id);
+CREATE INDEX idx_audit_log_changed_at ON moto_auto.audit_log(changed_at);
+
+-- Первый аргумент триггера: имя столбца первичного ключа.
+CREATE OR REPLACE FUNCTION audit_row_change()
+RETURNS TRIGGER
+SECURITY DEFINER
+AS $$
+DECLARE
+    old_row JSONB;
+    new_row JSONB;
+    audit_operation VARCHAR(10);
+    audit_entity_id INTEGER;
+BEGIN
+    IF TG_OP <> 'INSERT' THEN
+        old_row := to_jsonb(OLD) - 'passwordhash';
+    END IF;
+    IF TG_OP <> 'DELETE' THEN
+        new_row := to_jsonb(NEW) - 'passwordhash';
+    END IF;
+
+    IF TG_OP = 'INSERT' THEN
+        audit_operation := 'create';
+    ELSIF TG_OP = 'DELETE' THEN
+        audit_operation := 'delete';
+    ELSIF old_row = new_row THEN
+        RETURN NULL;
+    ELSIF old_row ->> 'deleted_at' IS NULL AND new_row ->> 'deleted_at' IS NOT NULL THEN
+        audit_operation := 'delete';
+    ELSIF old_row ->> 'deleted_at' IS NOT NULL AND new_row ->> 'deleted_at' IS NULL THEN
+        audit_operation := 'restore';
+    ELSE
+        audit_operation := 'update';
+    END IF;
+
+    audit_entity_id := (COALESCE(new_row, old_row) ->> TG_ARGV[0])::INTEGER;
+
+    INSERT INTO moto_auto.audit_log (actor_user_id, entity, entity_id, operation, before, after)
+    VALUES (
+        NULLIF(current_setting('moto_auto.actor', true), '')::INTEGER,
+        TG_TABLE_NAME,
+        audit_entity_id,
+        audit_operation,
+        old_row,
+        new_row
+    );
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_audit_branch
+AFTER INSERT OR UPDATE OR DELETE ON moto_auto.branch
+FOR EACH ROW EXECUTE FUNCTION audit_row_change('branch_id');
+
+CREATE TRIGGER trigger_audit_users
+AFTER INSERT OR UPDATE OR DELETE ON moto_auto.users
+FOR EACH ROW EXECUTE FUNCTION audit_row_change('user_id');
+
+CREATE TRIGGER trigger_audit_employee
+AFTER INSERT OR UPDATE OR DELETE ON moto_auto.employee
+FOR EACH ROW EXECUTE FUNCTION audit_row_change('employee_id');
+
+CREATE TRIGGER trigger_audit_branch_employee
+AFTER INSERT OR UPDATE OR DELETE ON moto_auto.branch_employee
+FOR EACH ROW EXECUTE FUNCTION audit_row_change('branch_employee_id');
+
+CREATE TRIGGER trigger_audit_client
+AFTER INSERT OR UPDATE OR DELETE ON moto_auto.client
+FOR EACH ROW EXECUTE FUNCTION audit_row_change('client_id');
+
+CREATE TRIGGER trigger_audit_orders
+AFTER INSERT OR UPDATE OR DELETE ON moto_auto.orders
+FOR EACH ROW EXECUTE FUNCTION audit_row_change('order_id');
+
+CREATE TRIGGER trigger_audit_service
+AFTER INSERT OR UPDATE OR DELETE ON moto_auto.service
+FOR EACH ROW EXECUTE FUNCTION audit_row_change('service_id');
+
+CREATE TRIGGER trigger_audit_service_branch
+AFTER INSERT OR UPDATE OR DELETE ON moto_auto.service_branch
+FOR EACH ROW EXECUTE FUNCTION audit_row_change('service_branch_id');
+
+CREATE TRIGGER trigger_audit_spare_part
+AFTER INSERT OR UPDATE OR DELETE ON moto_auto.spare_part
+FOR EACH ROW EXECUTE FUNCTION audit_row_change('part_id');
+
+CREATE TRIGGER trigger_audit_spare_part_branch
+AFTER INSERT OR UPDATE OR DELETE ON moto_auto.spare_part_branch
+FOR EACH ROW EXECUTE FUNCTION audit_row_change('spare_part_branch_id');
+
+CREATE TRIGGER trigger_audit_order_service
+AFTER INSERT OR UPDATE OR DELETE ON moto_auto.order_service
+FOR EACH ROW EXECUTE FUNCTION audit_row_change('order_service_id');
+
+CREATE TRIGGER trigger_audit_order_service_part
+AFTER INSERT OR UPDATE OR DELETE ON moto_auto.order_service_part
+FOR EACH ROW EXECUTE FUNCTION audit_row_change('order_service_part_id');
+
+CREATE TRIGGER trigger_audit_schedule
+AFTER INSERT OR UPDATE OR DELETE ON moto_auto.schedule
+FOR EACH ROW EXECUTE FUNCTION audit_row_change('schedule_id');
+
+-- Журнал доступен на чтение администраторам и аналитикам, изменять его нельзя.
+GRANT SELECT ON moto_auto.audit_log TO admin;
+GRANT SELECT ON moto_auto.audit_log TO analyst;
+
+COMMIT;
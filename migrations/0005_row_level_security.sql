@@ -0,0 +1,118 @@
+BEGIN;
+
+-- Приложение подключается одним пользователем, а в начале каждой транзакции запроса выполняет
+-- SET LOCAL ROLE <роль> и SET LOCAL moto_auto.actor = <user_id>. Политики ниже читают эту
+-- переменную вместо current_user, поэтому база остаётся последней линией проверки прав.
+CREATE OR REPLACE FUNCTION current_app_user_id()
+RETURNS INTEGER
+STABLE
+AS $$
+    SELECT NULLIF(current_setting('moto_auto.actor', true), '')::INTEGER;
+$$ LANGUAGE sql;
+
+CREATE OR REPLACE FUNCTION current_app_branch_id()
+RETURNS INTEGER
+STABLE
+SECURITY DEFINER
+AS $$
+    SELECT branch_id FROM moto_auto.users WHERE user_id = current_app_user_id();
+$$ LANGUAGE sql;
+
+-- Суперадминистратор управляет всеми филиалами, поэтому политики на него не распространяются.
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT FROM pg_roles WHERE rolname = 'superadmin_role') THEN
+        CREATE ROLE superadmin_role BYPASSRLS;
+    END IF;
+END
+$$;
+GRANT ALL PRIVILEGES ON SCHEMA moto_auto TO superadmin_role;
+GRANT ALL PRIVILEGES ON ALL TABLES IN SCHEMA moto_auto TO superadmin_role;
+GRANT ALL PRIVILEGES ON ALL SEQUENCES IN SCHEMA moto_auto TO superadmin_role;
+ALTER DEFAULT PRIVILEGES IN SCHEMA moto_auto GRANT ALL ON TABLES TO superadmin_role;
+ALTER DEFAULT PRIVILEGES IN SCHEMA moto_auto GRANT ALL ON SEQUENCES TO superadmin_role;
+
+-- Пользователь приложения должен иметь право переключаться в роли.
+GRANT superadmin_role, admin, analyst, master, manager TO CURRENT_USER;
+
+ALTER DEFAULT PRIVILEGES IN SCHEMA moto_auto GRANT ALL ON TABLES TO admin;
+ALTER DEFAULT PRIVILEGES IN SCHEMA moto_auto GRANT ALL ON SEQUENCES TO admin;
+ALTER DEFAULT PRIVILEGES IN SCHEMA moto_auto GRANT USAGE ON SEQUENCES TO master, manager;
+
+-- Мастера вносят данные о выполненной работе.
+GRANT SELECT ON moto_auto.service, moto_auto.service_branch TO master;
+GRANT SELECT, INSERT, UPDATE, DELETE ON moto_auto.order_service, moto_auto.order_service_part TO master;
+GRANT USAGE ON ALL SEQUENCES IN SCHEMA moto_auto TO master;
+
+-- Менеджеры работают с клиентами, заказами и записью своего филиала.
+GRANT USAGE ON SCHEMA moto_auto TO manager;
+GRANT SELECT ON moto_auto.branch, moto_auto.service, moto_auto.service_branch,
+    moto_auto.spare_part, moto_auto.spare_part_branch TO manager;
+GRANT SELECT, INSERT, UPDATE ON moto_auto.client, moto_auto.orders, moto_auto.schedule TO manager;
+GRANT SELECT, INSERT, UPDATE, DELETE ON moto_auto.order_service, moto_auto.order_service_part TO manager;
+GRANT USAGE ON ALL SEQUENCES IN SCHEMA moto_auto TO manager;
+
+-- Триггеры пересчитывают суммы и статусы клиентов независимо от прав того, кто изменил заказ.
+ALTER FUNCTION calculate_total_amount() SECURITY DEFINER;
+ALTER FUNCTION update_client_status() SECURITY DEFINER;
+ALTER FUNCTION add_bonus_points_by_status() SECURITY DEFINER;
+ALTER FUNCTION increment_employee_count() SECURITY DEFINER;
+
+DROP POLICY master_client_policy ON moto_auto.client;
+CREATE POLICY master_client_policy ON moto_auto.client
+    FOR SELECT TO master USING (
+        EXISTS (
+            SELECT 1
+            FROM moto_auto.orders
+            WHERE moto_auto.orders.client_id = moto_auto.client.client_id
+              AND moto_auto.orders.master_id = current_app_user_id()
+        )
+    );
+
+DROP POLICY master_orders_policy ON moto_auto.orders;
+CREATE POLICY master_orders_policy ON moto_auto.orders
+    FOR ALL TO master USING (
+        moto_auto.orders.master_id = current_app_user_id()
+    );
+
+DROP POLICY manager_client_policy ON moto_auto.client;
+CREATE POLICY manager_client_policy ON moto_auto.client
+    FOR ALL TO manager USING (
+        EXISTS (
+            SELECT 1
+            FROM moto_auto.branch
+            WHERE moto_auto.branch.branch_id = current_app_branch_id()
+        )
+    );
+
+DROP POLICY manager_orders_policy ON moto_auto.orders;
+CREATE POLICY manager_orders_policy ON moto_auto.orders
+    FOR ALL TO manager USING (
+        moto_auto.orders.branch_id = current_app_branch_id()
+    );
+
+DROP POLICY admin_user_policy ON moto_auto.users;
+CREATE POLICY admin_user_policy ON moto_auto.users
+    FOR ALL TO admin USING (
+        moto_auto.users.branch_id = current_app_branch_id()
+    );
+
+-- Администраторы видят заказы своего филиала и всех клиентов, аналитики читают всё.
+CREATE POLICY admin_client_policy ON moto_auto.client
+    FOR ALL TO admin USING (true);
+
+CREATE POLICY admin_orders_policy ON moto_auto.orders
+    FOR ALL TO admin USING (
+        moto_auto.orders.branch_id = current_app_branch_id()
+    );
+
+CREATE POLICY analyst_client_policy ON moto_auto.client
+    FOR SELECT TO analyst USING (true);
+
+CREATE POLICY analyst_orders_policy ON moto_auto.orders
+    FOR SELECT TO analyst USING (true);
+
+CREATE POLICY analyst_user_policy ON moto_auto.users
+    FOR SELECT TO analyst USING (true);
+
+COMMIT;
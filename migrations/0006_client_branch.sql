@@ -0,0 +1,87 @@
+BEGIN;
+
+-- Клиент принадлежит домашнему филиалу и может быть открыт для других филиалов.
+ALTER TABLE moto_auto.client ADD COLUMN home_branch_id INTEGER REFERENCES moto_auto.branch(branch_id);
+
+UPDATE moto_auto.client c
+SET home_branch_id = COALESCE(
+    (
+        SELECT o.branch_id
+        FROM moto_auto.orders o
+        WHERE o.client_id = c.client_id
+        ORDER BY o.order_date, o.order_id
+        LIMIT 1
+    ),
+    (SELECT MIN(branch_id) FROM moto_auto.branch)
+);
+
+ALTER TABLE moto_auto.client ALTER COLUMN home_branch_id SET NOT NULL;
+
+CREATE INDEX idx_client_home_branch_id ON moto_auto.client(home_branch_id);
+
+CREATE TABLE moto_auto.client_branch (
+    client_id INTEGER NOT NULL REFERENCES moto_auto.client(client_id),
+    branch_id INTEGER NOT NULL REFERENCES moto_auto.branch(branch_id),
+    PRIMARY KEY (client_id, branch_id)
+);
+
+CREATE INDEX idx_client_branch_branch_id ON moto_auto.client_branch(branch_id);
+
+-- Политика client_branch не может читать client напрямую: политика client сама читает client_branch.
+CREATE OR REPLACE FUNCTION client_home_branch_id(check_client_id INTEGER)
+RETURNS INTEGER
+STABLE
+SECURITY DEFINER
+AS $$
+    SELECT home_branch_id FROM moto_auto.client WHERE client_id = check_client_id;
+$$ LANGUAGE sql;
+
+-- Менеджеры видят клиентов своего филиала и клиентов, открытых для их филиала.
+DROP POLICY manager_client_policy ON moto_auto.client;
+CREATE POLICY manager_client_policy ON moto_auto.client
+    FOR ALL TO manager
+    USING (
+        moto_auto.client.home_branch_id = current_app_branch_id()
+        OR EXISTS (
+            SELECT 1
+            FROM moto_auto.client_branch
+            WHERE moto_auto.client_branch.client_id = moto_auto.client.client_id
+              AND moto_auto.client_branch.branch_id = current_app_branch_id()
+        )
+    )
+    WITH CHECK (
+        moto_auto.client.home_branch_id = current_app_branch_id()
+        OR EXISTS (
+            SELECT 1
+            FROM moto_auto.client_branch
+            WHERE moto_auto.client_branch.client_id = moto_auto.client.client_id
+              AND moto_auto.client_branch.branch_id = current_app_branch_id()
+        )
+    );
+
+-- Открыть доступ к клиенту может только менеджер его домашнего филиала.
+ALTER TABLE moto_auto.client_branch ENABLE ROW LEVEL SECURITY;
+CREATE POLICY manager_client_branch_policy ON moto_auto.client_branch
+    FOR ALL TO manager
+    USING (
+        moto_auto.client_branch.branch_id = current_app_branch_id()
+        OR client_home_branch_id(moto_auto.client_branch.client_id) = current_app_branch_id()
+    )
+    WITH CHECK (
+        client_home_branch_id(moto_auto.client_branch.client_id) = current_app_branch_id()
+    );
+
+CREATE POLICY admin_client_branch_policy ON moto_auto.client_branch
+    FOR ALL TO admin USING (true);
+
+CREATE POLICY analyst_client_branch_policy ON moto_auto.client_branch
+    FOR SELECT TO analyst USING (true);
+
+GRANT SELECT, INSERT, DELETE ON moto_auto.client_branch TO manager;
+GRANT SELECT ON moto_auto.client_branch TO master;
+
+CREATE TRIGGER trigger_audit_client_branch
+AFTER INSERT OR UPDATE OR DELETE ON moto_auto.client_branch
+FOR EACH ROW EXECUTE FUNCTION audit_row_change('client_id');
+
+COMMIT;
@@ -0,0 +1,101 @@
+BEGIN;
+
+-- Итог заказа считается только по прайс-листу филиала заказа.
+-- Раньше цена услуги суммировалась по всем филиалам, где она есть.
+CREATE OR REPLACE FUNCTION recalculate_order_total(target_order_id INTEGER)
+RETURNS VOID
+SECURITY DEFINER
+AS $$
+DECLARE
+    service_total NUMERIC(15, 2) := 0;
+    part_total NUMERIC(15, 2) := 0;
+BEGIN
+    IF target_order_id IS NULL THEN
+        RETURN;
+    END IF;
+
+    SELECT COALESCE(SUM(sb.price), 0) INTO service_total
+    FROM moto_auto.order_service os
+    INNER JOIN moto_auto.orders o
+    ON os.order_id = o.order_id
+    INNER JOIN moto_auto.service_branch sb
+    ON os.service_id = sb.service_id AND sb.branch_id = o.branch_id
+    WHERE os.order_id = target_order_id;
+
+    SELECT COALESCE(SUM(spb.price * osp.quantity), 0) INTO part_total
+    FROM moto_auto.order_service_part osp
+    INNER JOIN moto_auto.order_service os
+    ON osp.order_service_id = os.order_service_id
+    INNER JOIN moto_auto.orders o
+    ON os.order_id = o.order_id
+    INNER JOIN moto_auto.spare_part_branch spb
+    ON osp.part_id = spb.part_id AND spb.branch_id = o.branch_id
+    WHERE os.order_id = target_order_id;
+
+    UPDATE moto_auto.orders
+    SET total_amount = service_total + part_total
+    WHERE order_id = target_order_id
+    AND total_amount IS DISTINCT FROM service_total + part_total;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Пересчитывает заказ до и после изменения строки, в том числе при удалении.
+CREATE OR REPLACE FUNCTION calculate_total_amount()
+RETURNS TRIGGER
+SECURITY DEFINER
+AS $$
+DECLARE
+    old_order_id INTEGER;
+    new_order_id INTEGER;
+BEGIN
+    IF TG_TABLE_NAME = 'order_service' THEN
+        IF TG_OP <> 'INSERT' THEN
+            old_order_id := OLD.order_id;
+        END IF;
+        IF TG_OP <> 'DELETE' THEN
+            new_order_id := NEW.order_id;
+        END IF;
+    ELSIF TG_TABLE_NAME = 'order_service_part' THEN
+        IF TG_OP <> 'INSERT' THEN
+            SELECT os.order_id INTO old_order_id
+            FROM moto_auto.order_service os
+            WHERE os.order_service_id = OLD.order_service_id;
+        END IF;
+        IF TG_OP <> 'DELETE' THEN
+            SELECT os.order_id INTO new_order_id
+            FROM moto_auto.order_service os
+            WHERE os.order_service_id = NEW.order_service_id;
+        END IF;
+    END IF;
+
+    PERFORM recalculate_order_total(new_order_id);
+    IF old_order_id IS DISTINCT FROM new_order_id THEN
+        PERFORM recalculate_order_total(old_order_id);
+    END IF;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER trigger_calculate_total_amount_order_service ON moto_auto.order_service;
+CREATE TRIGGER trigger_calculate_total_amount_order_service
+AFTER INSERT OR UPDATE OR DELETE ON moto_auto.order_service
+FOR EACH ROW
+EXECUTE FUNCTION calculate_total_amount();
+
+DROP TRIGGER trigger_calculate_total_amount_order_service_part ON moto_auto.order_service_part;
+CREATE TRIGGER trigger_calculate_total_amount_order_service_part
+AFTER INSERT OR UPDATE OR DELETE ON moto_auto.order_service_part
+FOR EACH ROW
+EXECUTE FUNCTION calculate_total_amount();
+
+-- Исправляем итоги, посчитанные по старой формуле.
+DO $$
+BEGIN
+    PERFORM recalculate_order_total(order_id)
+    FROM moto_auto.orders
+    WHERE order_id IN (SELECT order_id FROM moto_auto.order_service);
+END;
+$$;
+
+COMMIT;
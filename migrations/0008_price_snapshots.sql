@@ -0,0 +1,228 @@
+BEGIN;
+
+-- Строки заказа хранят цену и название на момент добавления,
+-- чтобы изменение прайс-листа не переписывало итоги уже оформленных заказов.
+ALTER TABLE moto_auto.order_service
+    ADD COLUMN unit_price NUMERIC(15, 2),
+    ADD COLUMN service_name VARCHAR(100);
+
+ALTER TABLE moto_auto.order_service_part
+    ADD COLUMN unit_price NUMERIC(15, 2),
+    ADD COLUMN part_name VARCHAR(100);
+
+-- Для существующих строк лучшая оценка: текущий прайс-лист филиала заказа.
+UPDATE moto_auto.order_service os
+SET
+    service_name = s.service_name,
+    unit_price = COALESCE((
+        SELECT sb.price
+        FROM moto_auto.service_branch sb
+        WHERE sb.service_id = os.service_id AND sb.branch_id = o.branch_id
+        ORDER BY sb.service_branch_id
+        LIMIT 1
+    ), 0)
+FROM moto_auto.orders o, moto_auto.service s
+WHERE o.order_id = os.order_id AND s.service_id = os.service_id;
+
+UPDATE moto_auto.order_service_part osp
+SET
+    part_name = sp.part_name,
+    unit_price = COALESCE((
+        SELECT spb.price
+        FROM moto_auto.spare_part_branch spb
+        WHERE spb.part_id = osp.part_id AND spb.branch_id = o.branch_id
+        ORDER BY spb.spare_part_branch_id
+        LIMIT 1
+    ), 0)
+FROM moto_auto.order_service os, moto_auto.orders o, moto_auto.spare_part sp
+WHERE os.order_service_id = osp.order_service_id
+AND o.order_id = os.order_id
+AND sp.part_id = osp.part_id;
+
+ALTER TABLE moto_auto.order_service
+    ALTER COLUMN unit_price SET NOT NULL,
+    ALTER COLUMN service_name SET NOT NULL;
+
+ALTER TABLE moto_auto.order_service_part
+    ALTER COLUMN unit_price SET NOT NULL,
+    ALTER COLUMN part_name SET NOT NULL;
+
+-- Снимок берётся из прайс-листа филиала заказа при добавлении строки или смене услуги.
+-- В остальных случаях снимок не меняется, даже если его пытаются перезаписать.
+-- Услуга без цены в филиале считается бесплатной, как и раньше.
+CREATE OR REPLACE FUNCTION snapshot_order_service()
+RETURNS TRIGGER
+SECURITY DEFINER
+AS $$
+BEGIN
+    IF TG_OP = 'UPDATE'
+        AND NEW.order_id = OLD.order_id
+        AND NEW.service_id = OLD.service_id THEN
+        NEW.unit_price := OLD.unit_price;
+        NEW.service_name := OLD.service_name;
+        RETURN NEW;
+    END IF;
+
+    SELECT s.service_name INTO NEW.service_name
+    FROM moto_auto.service s
+    WHERE s.service_id = NEW.service_id;
+
+    SELECT sb.price INTO NEW.unit_price
+    FROM moto_auto.service_branch sb
+    INNER JOIN moto_auto.orders o
+    ON sb.branch_id = o.branch_id
+    WHERE o.order_id = NEW.order_id AND sb.service_id = NEW.service_id
+    ORDER BY sb.service_branch_id
+    LIMIT 1;
+    NEW.unit_price := COALESCE(NEW.unit_price, 0);
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION snapshot_order_service_part()
+RETURNS TRIGGER
+SECURITY DEFINER
+AS $$
+BEGIN
+    IF TG_OP = 'UPDATE'
+        AND NEW.order_service_id = OLD.order_service_id
+        AND NEW.part_id = OLD.part_id THEN
+        NEW.unit_price := OLD.unit_price;
+        NEW.part_name := OLD.part_name;
+        RETURN NEW;
+    END IF;
+
+    SELECT sp.part_name INTO NEW.part_name
+    FROM moto_auto.spare_part sp
+    WHERE sp.part_id = NEW.part_id;
+
+    SELECT spb.price INTO NEW.unit_price
+    FROM moto_auto.spare_part_branch spb
+    INNER JOIN moto_auto.orders o
+    ON spb.branch_id = o.branch_id
+    INNER JOIN moto_auto.order_service os
+    ON os.order_id = o.order_id
+    WHERE os.order_service_id = NEW.order_service_id AND spb.part_id = NEW.part_id
+    ORDER BY spb.spare_part_branch_id
+    LIMIT 1;
+    NEW.unit_price := COALESCE(NEW.unit_price, 0);
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_snapshot_order_service
+BEFORE INSERT OR UPDATE ON moto_auto.order_service
+FOR EACH ROW
+EXECUTE FUNCTION snapshot_order_service();
+
+CREATE TRIGGER trigger_snapshot_order_service_part
+BEFORE INSERT OR UPDATE ON moto_auto.order_service_part
+FOR EACH ROW
+EXECUTE FUNCTION snapshot_order_service_part();
+
+-- Итог заказа считается по снимкам цен в строках заказа.
+CREATE OR REPLACE FUNCTION recalculate_order_total(target_order_id INTEGER)
+RETURNS VOID
+SECURITY DEFINER
+AS $$
+DECLARE
+    service_total NUMERIC(15, 2) := 0;
+    part_total NUMERIC(15, 2) := 0;
+BEGIN
+    IF target_order_id IS NULL THEN
+        RETURN;
+    END IF;
+
+    SELECT COALESCE(SUM(os.unit_price), 0) INTO service_total
+    FROM moto_auto.order_service os
+    WHERE os.order_id = target_order_id;
+
+    SELECT COALESCE(SUM(osp.unit_price * osp.quantity), 0) INTO part_total
+    FROM moto_auto.order_service_part osp
+    INNER JOIN moto_auto.order_service os
+    ON osp.order_service_id = os.order_service_id
+    WHERE os.order_id = target_order_id;
+
+    UPDATE moto_auto.orders
+    SET total_amount = service_total + part_total
+    WHERE order_id = target_order_id
+    AND total_amount IS DISTINCT FROM service_total + part_total;
+END;
+$$ LANGUAGE plpgsql;
+
+-- История цен прайс-листов филиалов для аналитиков.
+-- old_price пуст для новой позиции, new_price пуст для удалённой.
+-- Записи только добавляются триггерами прайс-листов и сами служат журналом,
+-- поэтому аудита у таблицы нет.
+CREATE TABLE moto_auto.price_history (
+    price_history_id BIGSERIAL PRIMARY KEY,
+    item_type VARCHAR(20) NOT NULL CHECK (item_type IN ('service', 'spare_part')),
+    item_id INTEGER NOT NULL,
+    branch_id INTEGER NOT NULL,
+    old_price NUMERIC(15, 2),
+    new_price NUMERIC(15, 2),
+    actor_user_id INTEGER,
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_price_history_item ON moto_auto.price_history(item_type, item_id, branch_id);
+CREATE INDEX idx_price_history_changed_at ON moto_auto.price_history(changed_at);
+
+CREATE OR REPLACE FUNCTION record_price_change()
+RETURNS TRIGGER
+SECURITY DEFINER
+AS $$
+DECLARE
+    old_row JSONB;
+    new_row JSONB;
+    row_data JSONB;
+BEGIN
+    IF TG_OP <> 'INSERT' THEN
+        old_row := to_jsonb(OLD);
+    END IF;
+    IF TG_OP <> 'DELETE' THEN
+        new_row := to_jsonb(NEW);
+    END IF;
+    IF TG_OP = 'UPDATE' AND NEW.price = OLD.price AND NEW.branch_id = OLD.branch_id THEN
+        RETURN NULL;
+    END IF;
+    row_data := COALESCE(new_row, old_row);
+
+    INSERT INTO moto_auto.price_history (
+        item_type, item_id, branch_id, old_price, new_price, actor_user_id
+    )
+    VALUES (
+        CASE TG_TABLE_NAME WHEN 'service_branch' THEN 'service' ELSE 'spare_part' END,
+        (row_data ->> CASE TG_TABLE_NAME WHEN 'service_branch' THEN 'service_id' ELSE 'part_id' END)::INTEGER,
+        (row_data ->> 'branch_id')::INTEGER,
+        (old_row ->> 'price')::NUMERIC,
+        (new_row ->> 'price')::NUMERIC,
+        NULLIF(current_setting('moto_auto.actor', true), '')::INTEGER
+    );
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_price_history_service_branch
+AFTER INSERT OR UPDATE OR DELETE ON moto_auto.service_branch
+FOR EACH ROW EXECUTE FUNCTION record_price_change();
+
+CREATE TRIGGER trigger_price_history_spare_part_branch
+AFTER INSERT OR UPDATE OR DELETE ON moto_auto.spare_part_branch
+FOR EACH ROW EXECUTE FUNCTION record_price_change();
+
+-- Текущие цены становятся началом истории.
+INSERT INTO moto_auto.price_history (item_type, item_id, branch_id, new_price)
+SELECT 'service', service_id, branch_id, price
+FROM moto_auto.service_branch;
+
+INSERT INTO moto_auto.price_history (item_type, item_id, branch_id, new_price)
+SELECT 'spare_part', part_id, branch_id, price
+FROM moto_auto.spare_part_branch;
+
+GRANT SELECT ON moto_auto.price_history TO analyst;
+
+COMMIT;
@@ -0,0 +1,218 @@
+BEGIN;
+
+-- Настройки ценообразования филиала: ставка НДС в процентах
+-- и доля заказа в процентах, которую можно оплатить бонусными баллами.
+-- НДС по умолчанию нулевой, чтобы итоги существующих заказов не изменились.
+ALTER TABLE moto_auto.branch
+    ADD COLUMN vat_rate NUMERIC(5, 2) NOT NULL DEFAULT 0
+        CHECK (vat_rate >= 0 AND vat_rate <= 100),
+    ADD COLUMN max_bonus_share NUMERIC(5, 2) NOT NULL DEFAULT 30
+        CHECK (max_bonus_share >= 0 AND max_bonus_share <= 100);
+
+-- Скидка на строку заказа: процент или фиксированная сумма, всегда с причиной.
+ALTER TABLE moto_auto.order_service
+    ADD COLUMN discount_type VARCHAR(10) CHECK (discount_type IN ('percent', 'fixed')),
+    ADD COLUMN discount_value NUMERIC(15, 2) NOT NULL DEFAULT 0 CHECK (discount_value >= 0),
+    ADD COLUMN discount_reason TEXT,
+    ADD CONSTRAINT order_service_discount_check CHECK (
+        discount_value = 0
+        OR (discount_type IS NOT NULL AND COALESCE(discount_reason, '') <> '')
+    ),
+    ADD CONSTRAINT order_service_discount_percent_check CHECK (
+        discount_type IS DISTINCT FROM 'percent' OR discount_value <= 100
+    );
+
+ALTER TABLE moto_auto.order_service_part
+    ADD COLUMN discount_type VARCHAR(10) CHECK (discount_type IN ('percent', 'fixed')),
+    ADD COLUMN discount_value NUMERIC(15, 2) NOT NULL DEFAULT 0 CHECK (discount_value >= 0),
+    ADD COLUMN discount_reason TEXT,
+    ADD CONSTRAINT order_service_part_discount_check CHECK (
+        discount_value = 0
+        OR (discount_type IS NOT NULL AND COALESCE(discount_reason, '') <> '')
+    ),
+    ADD CONSTRAINT order_service_part_discount_percent_check CHECK (
+        discount_type IS DISTINCT FROM 'percent' OR discount_value <= 100
+    );
+
+-- Скидка на весь заказ, списание баллов и разбивка итога.
+-- total_amount остаётся суммой к оплате:
+-- subtotal - discount_amount + tax_amount - bonus_points_used.
+ALTER TABLE moto_auto.orders
+    ADD COLUMN discount_type VARCHAR(10) CHECK (discount_type IN ('percent', 'fixed')),
+    ADD COLUMN discount_value NUMERIC(15, 2) NOT NULL DEFAULT 0 CHECK (discount_value >= 0),
+    ADD COLUMN discount_reason TEXT,
+    ADD COLUMN subtotal NUMERIC(15, 2),
+    ADD COLUMN discount_amount NUMERIC(15, 2) NOT NULL DEFAULT 0,
+    ADD COLUMN vat_rate NUMERIC(5, 2) NOT NULL DEFAULT 0,
+    ADD COLUMN tax_amount NUMERIC(15, 2) NOT NULL DEFAULT 0,
+    ADD COLUMN bonus_points_used NUMERIC(15, 2) NOT NULL DEFAULT 0 CHECK (bonus_points_used >= 0),
+    ADD CONSTRAINT orders_discount_check CHECK (
+        discount_value = 0
+        OR (discount_type IS NOT NULL AND COALESCE(discount_reason, '') <> '')
+    ),
+    ADD CONSTRAINT orders_discount_percent_check CHECK (
+        discount_type IS DISTINCT FROM 'percent' OR discount_value <= 100
+    );
+
+-- У старых заказов нет скидок и налога, поэтому подытог равен итогу.
+UPDATE moto_auto.orders
+SET subtotal = COALESCE(total_amount, 0);
+
+ALTER TABLE moto_auto.orders
+    ALTER COLUMN subtotal SET DEFAULT 0,
+    ALTER COLUMN subtotal SET NOT NULL;
+
+-- Размер скидки от суммы base. Фиксированная скидка не больше самой суммы.
+CREATE OR REPLACE FUNCTION discount_of(base NUMERIC, discount_type VARCHAR, discount_value NUMERIC)
+RETURNS NUMERIC
+IMMUTABLE
+AS $$
+    SELECT CASE discount_type
+        WHEN 'percent' THEN ROUND(base * discount_value / 100, 2)
+        WHEN 'fixed' THEN LEAST(discount_value, base)
+        ELSE 0
+    END;
+$$ LANGUAGE sql;
+
+-- Считает разбивку заказа по снимкам цен в строках и возвращает строку заказа с ней.
+-- Сначала применяются скидки строк, затем скидка заказа, затем НДС.
+-- Списание баллов ограничивается долей max_bonus_share от суммы с налогом.
+CREATE OR REPLACE FUNCTION price_order(o moto_auto.orders)
+RETURNS moto_auto.orders
+SECURITY DEFINER
+AS $$
+DECLARE
+    lines_subtotal NUMERIC(15, 2);
+    lines_discount NUMERIC(15, 2);
+    bonus_share NUMERIC(5, 2);
+    bonus_cap NUMERIC(15, 2);
+BEGIN
+    SELECT
+        COALESCE(SUM(line.base), 0),
+        COALESCE(SUM(discount_of(line.base, line.discount_type, line.discount_value)), 0)
+    INTO lines_subtotal, lines_discount
+    FROM (
+        SELECT os.unit_price AS base, os.discount_type, os.discount_value
+        FROM moto_auto.order_service os
+        WHERE os.order_id = o.order_id
+        UNION ALL
+        SELECT osp.unit_price * osp.quantity, osp.discount_type, osp.discount_value
+        FROM moto_auto.order_service_part osp
+        INNER JOIN moto_auto.order_service os
+        ON osp.order_service_id = os.order_service_id
+        WHERE os.order_id = o.order_id
+    ) line;
+
+    o.subtotal := lines_subtotal;
+    o.discount_amount := lines_discount
+        + discount_of(lines_subtotal - lines_discount, o.discount_type, o.discount_value);
+    o.tax_amount := ROUND((o.subtotal - o.discount_amount) * o.vat_rate / 100, 2);
+
+    SELECT b.max_bonus_share INTO bonus_share
+    FROM moto_auto.branch b
+    WHERE b.branch_id = o.branch_id;
+    bonus_cap := ROUND((o.subtotal - o.discount_amount + o.tax_amount) * bonus_share / 100, 2);
+    o.bonus_points_used := LEAST(o.bonus_points_used, bonus_cap);
+
+    o.total_amount := o.subtotal - o.discount_amount + o.tax_amount - o.bonus_points_used;
+    RETURN o;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION recalculate_order_total(target_order_id INTEGER)
+RETURNS VOID
+SECURITY DEFINER
+AS $$
+DECLARE
+    priced moto_auto.orders;
+BEGIN
+    IF target_order_id IS NULL THEN
+        RETURN;
+    END IF;
+
+    priced := price_order((
+        SELECT o FROM moto_auto.orders o WHERE o.order_id = target_order_id
+    ));
+
+    UPDATE moto_auto.orders
+    SET
+        subtotal = priced.subtotal,
+        discount_amount = priced.discount_amount,
+        tax_amount = priced.tax_amount,
+        bonus_points_used = priced.bonus_points_used,
+        total_amount = priced.total_amount
+    WHERE order_id = target_order_id
+    AND (subtotal, discount_amount, tax_amount, bonus_points_used, total_amount)
+        IS DISTINCT FROM
+        (priced.subtotal, priced.discount_amount, priced.tax_amount,
+         priced.bonus_points_used, priced.total_amount);
+END;
+$$ LANGUAGE plpgsql;
+
+-- Ставка НДС фиксируется при создании заказа, как и цены строк.
+-- Баллы можно списать только когда в заказе уже есть строки.
+-- Скидка заказа и списание баллов сразу пересчитывают разбивку.
+CREATE OR REPLACE FUNCTION price_order_row()
+RETURNS TRIGGER
+SECURITY DEFINER
+AS $$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        SELECT b.vat_rate INTO NEW.vat_rate
+        FROM moto_auto.branch b
+        WHERE b.branch_id = NEW.branch_id;
+        NEW.bonus_points_used := 0;
+        RETURN NEW;
+    END IF;
+
+    NEW.vat_rate := OLD.vat_rate;
+    IF (NEW.discount_type, NEW.discount_value, NEW.bonus_points_used)
+        IS DISTINCT FROM (OLD.discount_type, OLD.discount_value, OLD.bonus_points_used) THEN
+        NEW := price_order(NEW);
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_price_order_row
+BEFORE INSERT OR UPDATE ON moto_auto.orders
+FOR EACH ROW
+EXECUTE FUNCTION price_order_row();
+
+-- Списанные по заказу баллы снимаются со счёта клиента, возвращённые зачисляются обратно.
+CREATE OR REPLACE FUNCTION redeem_bonus_points()
+RETURNS TRIGGER
+SECURITY DEFINER
+AS $$
+DECLARE
+    points_delta NUMERIC(15, 2);
+    points_left NUMERIC(15, 2);
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        points_delta := NEW.bonus_points_used;
+    ELSE
+        points_delta := NEW.bonus_points_used - OLD.bonus_points_used;
+    END IF;
+    IF points_delta = 0 THEN
+        RETURN NULL;
+    END IF;
+
+    UPDATE moto_auto.client
+    SET bonus_points = COALESCE(bonus_points, 0) - points_delta
+    WHERE client_id = NEW.client_id
+    RETURNING bonus_points INTO points_left;
+
+    IF points_left < 0 THEN
+        RAISE EXCEPTION 'У клиента % недостаточно бонусных баллов', NEW.client_id;
+    END IF;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_redeem_bonus_points
+AFTER INSERT OR UPDATE ON moto_auto.orders
+FOR EACH ROW
+EXECUTE FUNCTION redeem_bonus_points();
+
+COMMIT;
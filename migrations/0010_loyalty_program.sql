@@ -0,0 +1,249 @@
+BEGIN;
+
+-- Уровни программы лояльности вместо зашитых в триггеры порогов и коэффициентов.
+-- Клиент получает уровень с наибольшим порогом, не превышающим его траты.
+-- accrual_rate: процент суммы заказа, начисляемый баллами;
+-- points_lifetime_days: сколько дней живут начисленные на этом уровне баллы.
+CREATE TABLE moto_auto.loyalty_tier (
+    tier_id SERIAL PRIMARY KEY,
+    name VARCHAR(20) NOT NULL UNIQUE,
+    spend_threshold NUMERIC(15, 2) NOT NULL UNIQUE CHECK (spend_threshold >= 0),
+    accrual_rate NUMERIC(5, 2) NOT NULL CHECK (accrual_rate >= 0 AND accrual_rate <= 100),
+    points_lifetime_days INTEGER NOT NULL DEFAULT 365 CHECK (points_lifetime_days > 0)
+);
+
+INSERT INTO moto_auto.loyalty_tier (name, spend_threshold, accrual_rate)
+VALUES
+('casual', 0, 10),
+('regular', 10000, 20),
+('premium', 50000, 30);
+
+ALTER TABLE moto_auto.client DROP CONSTRAINT client_status_check;
+ALTER TABLE moto_auto.client
+    ADD CONSTRAINT client_status_fkey FOREIGN KEY (status)
+    REFERENCES moto_auto.loyalty_tier(name) ON UPDATE CASCADE;
+
+-- Журнал бонусных баллов. Каждое начисление живёт до expires_at,
+-- remaining показывает, сколько из него ещё не списано и не сгорело.
+-- Сгорание записывается отдельной строкой с отрицательным количеством баллов.
+CREATE TABLE moto_auto.bonus_transaction (
+    transaction_id BIGSERIAL PRIMARY KEY,
+    client_id INTEGER NOT NULL REFERENCES moto_auto.client(client_id) ON DELETE CASCADE,
+    order_id INTEGER REFERENCES moto_auto.orders(order_id) ON DELETE SET NULL,
+    kind VARCHAR(20) NOT NULL CHECK (kind IN ('accrual', 'expiration')),
+    points NUMERIC(15, 2) NOT NULL,
+    remaining NUMERIC(15, 2) NOT NULL DEFAULT 0 CHECK (remaining >= 0),
+    expires_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (kind <> 'accrual' OR (points >= 0 AND expires_at IS NOT NULL))
+);
+
+CREATE INDEX idx_bonus_transaction_client_id ON moto_auto.bonus_transaction(client_id);
+CREATE INDEX idx_bonus_transaction_expires_at ON moto_auto.bonus_transaction(expires_at)
+    WHERE remaining > 0;
+
+-- Накопленные ранее баллы становятся одним начислением со сроком жизни уровня клиента.
+INSERT INTO moto_auto.bonus_transaction (client_id, kind, points, remaining, expires_at)
+SELECT c.client_id, 'accrual', c.bonus_points, c.bonus_points,
+    NOW() + make_interval(days => t.points_lifetime_days)
+FROM moto_auto.client c
+INNER JOIN moto_auto.loyalty_tier t
+ON t.name = c.status
+WHERE c.bonus_points > 0;
+
+-- Начисляет баллы клиенту со сроком жизни его уровня.
+CREATE OR REPLACE FUNCTION accrue_bonus_points(
+    target_client_id INTEGER,
+    target_order_id INTEGER,
+    accrued_points NUMERIC
+)
+RETURNS VOID
+SECURITY DEFINER
+AS $$
+DECLARE
+    lifetime_days INTEGER;
+BEGIN
+    IF accrued_points <= 0 THEN
+        RETURN;
+    END IF;
+
+    SELECT t.points_lifetime_days INTO lifetime_days
+    FROM moto_auto.client c
+    INNER JOIN moto_auto.loyalty_tier t
+    ON t.name = c.status
+    WHERE c.client_id = target_client_id;
+
+    INSERT INTO moto_auto.bonus_transaction (
+        client_id, order_id, kind, points, remaining, expires_at
+    )
+    VALUES (
+        target_client_id, target_order_id, 'accrual', accrued_points, accrued_points,
+        NOW() + make_interval(days => COALESCE(lifetime_days, 365))
+    );
+
+    UPDATE moto_auto.client
+    SET bonus_points = COALESCE(bonus_points, 0) + accrued_points
+    WHERE client_id = target_client_id;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Списывает баллы из начислений, начиная с тех, что сгорают раньше.
+CREATE OR REPLACE FUNCTION consume_bonus_points(target_client_id INTEGER, spent_points NUMERIC)
+RETURNS VOID
+SECURITY DEFINER
+AS $$
+DECLARE
+    lot RECORD;
+    taken NUMERIC(15, 2);
+BEGIN
+    FOR lot IN
+        SELECT transaction_id, remaining
+        FROM moto_auto.bonus_transaction
+        WHERE client_id = target_client_id AND remaining > 0
+        ORDER BY expires_at, transaction_id
+        FOR UPDATE
+    LOOP
+        EXIT WHEN spent_points <= 0;
+        taken := LEAST(lot.remaining, spent_points);
+        UPDATE moto_auto.bonus_transaction
+        SET remaining = remaining - taken
+        WHERE transaction_id = lot.transaction_id;
+        spent_points := spent_points - taken;
+    END LOOP;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION update_client_status()
+RETURNS TRIGGER
+SECURITY DEFINER
+AS $$
+DECLARE
+    client_total_spent NUMERIC(15, 2);
+BEGIN
+    SELECT COALESCE(SUM(total_amount), 0) INTO client_total_spent
+    FROM moto_auto.orders
+    WHERE client_id = NEW.client_id;
+
+    UPDATE moto_auto.client
+    SET total_spent = client_total_spent,
+        status = COALESCE(
+            (
+                SELECT t.name
+                FROM moto_auto.loyalty_tier t
+                WHERE t.spend_threshold <= client_total_spent
+                ORDER BY t.spend_threshold DESC
+                LIMIT 1
+            ),
+            status
+        )
+    WHERE client_id = NEW.client_id;
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION add_bonus_points_by_status()
+RETURNS TRIGGER
+SECURITY DEFINER
+AS $$
+DECLARE
+    rate NUMERIC(5, 2);
+BEGIN
+    IF (NEW.status = 'finished' AND (TG_OP = 'INSERT' OR OLD.status != 'finished')) THEN
+        SELECT t.accrual_rate INTO rate
+        FROM moto_auto.client c
+        INNER JOIN moto_auto.loyalty_tier t
+        ON t.name = c.status
+        WHERE c.client_id = NEW.client_id;
+
+        PERFORM accrue_bonus_points(
+            NEW.client_id,
+            NEW.order_id,
+            FLOOR(NEW.total_amount * COALESCE(rate, 0) / 100)
+        );
+    END IF;
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Списание по заказу расходует начисления, возврат баллов по заказу начисляется заново.
+CREATE OR REPLACE FUNCTION redeem_bonus_points()
+RETURNS TRIGGER
+SECURITY DEFINER
+AS $$
+DECLARE
+    points_delta NUMERIC(15, 2);
+    points_left NUMERIC(15, 2);
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        points_delta := NEW.bonus_points_used;
+    ELSE
+        points_delta := NEW.bonus_points_used - OLD.bonus_points_used;
+    END IF;
+    IF points_delta = 0 THEN
+        RETURN NULL;
+    END IF;
+    IF points_delta < 0 THEN
+        PERFORM accrue_bonus_points(NEW.client_id, NEW.order_id, -points_delta);
+        RETURN NULL;
+    END IF;
+
+    SELECT bonus_points INTO points_left
+    FROM moto_auto.client
+    WHERE client_id = NEW.client_id;
+    IF COALESCE(points_left, 0) < points_delta THEN
+        RAISE EXCEPTION 'У клиента % недостаточно бонусных баллов', NEW.client_id;
+    END IF;
+
+    PERFORM consume_bonus_points(NEW.client_id, points_delta);
+    UPDATE moto_auto.client
+    SET bonus_points = bonus_points - points_delta
+    WHERE client_id = NEW.client_id;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Сжигает только просроченные остатки начислений. Возвращает число сгоревших начислений.
+DROP FUNCTION expire_bonus_points();
+CREATE OR REPLACE FUNCTION expire_bonus_points()
+RETURNS INTEGER
+SECURITY DEFINER
+AS $$
+DECLARE
+    lot RECORD;
+    expired INTEGER := 0;
+BEGIN
+    FOR lot IN
+        SELECT transaction_id, client_id, remaining
+        FROM moto_auto.bonus_transaction
+        WHERE remaining > 0 AND expires_at <= NOW()
+        FOR UPDATE
+    LOOP
+        INSERT INTO moto_auto.bonus_transaction (client_id, kind, points)
+        VALUES (lot.client_id, 'expiration', -lot.remaining);
+
+        UPDATE moto_auto.bonus_transaction
+        SET remaining = 0
+        WHERE transaction_id = lot.transaction_id;
+
+        UPDATE moto_auto.client
+        SET bonus_points = bonus_points - lot.remaining
+        WHERE client_id = lot.client_id;
+
+        expired := expired + 1;
+    END LOOP;
+
+    RETURN expired;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_audit_loyalty_tier
+AFTER INSERT OR UPDATE OR DELETE ON moto_auto.loyalty_tier
+FOR EACH ROW EXECUTE FUNCTION audit_row_change('tier_id');
+
+GRANT SELECT ON moto_auto.loyalty_tier TO master, manager, analyst;
+GRANT SELECT ON moto_auto.bonus_transaction TO analyst;
+
+COMMIT;
@@ -0,0 +1,242 @@
+BEGIN;
+
+-- Журнал хранит все движения баллов: начисления по заказам, списания и их возврат,
+-- ручные корректировки с причиной и сгорание. actor_user_id: кто совершил действие.
+ALTER TABLE moto_auto.bonus_transaction DROP CONSTRAINT bonus_transaction_kind_check;
+ALTER TABLE moto_auto.bonus_transaction
+    ADD CONSTRAINT bonus_transaction_kind_check
+        CHECK (kind IN ('accrual', 'redemption', 'adjustment', 'expiration')),
+    ADD COLUMN actor_user_id INTEGER
+        DEFAULT NULLIF(current_setting('moto_auto.actor', true), '')::INTEGER,
+    ADD COLUMN reason TEXT,
+    ADD CONSTRAINT bonus_transaction_reason_check
+        CHECK (kind <> 'adjustment' OR COALESCE(reason, '') <> ''),
+    ADD CONSTRAINT bonus_transaction_expiry_check
+        CHECK (points <= 0 OR expires_at IS NOT NULL);
+
+CREATE INDEX idx_bonus_transaction_order_id ON moto_auto.bonus_transaction(order_id);
+
+-- Баланс, не объяснённый журналом, становится корректировкой.
+INSERT INTO moto_auto.bonus_transaction (client_id, kind, points, remaining, expires_at, reason)
+SELECT
+    c.client_id,
+    'adjustment',
+    COALESCE(c.bonus_points, 0) - COALESCE(l.points, 0),
+    GREATEST(COALESCE(c.bonus_points, 0) - COALESCE(l.points, 0), 0),
+    NOW() + make_interval(days => t.points_lifetime_days),
+    'Сверка баланса с журналом'
+FROM moto_auto.client c
+INNER JOIN moto_auto.loyalty_tier t
+ON t.name = c.status
+LEFT JOIN (
+    SELECT client_id, SUM(points) AS points
+    FROM moto_auto.bonus_transaction
+    GROUP BY client_id
+) l
+ON l.client_id = c.client_id
+WHERE COALESCE(c.bonus_points, 0) <> COALESCE(l.points, 0);
+
+UPDATE moto_auto.client
+SET bonus_points = 0
+WHERE bonus_points IS NULL;
+
+ALTER TABLE moto_auto.client
+    ALTER COLUMN bonus_points SET NOT NULL;
+
+-- client.bonus_points: сумма журнала, меняется только записями журнала.
+CREATE OR REPLACE FUNCTION apply_bonus_transaction()
+RETURNS TRIGGER
+SECURITY DEFINER
+AS $$
+DECLARE
+    points_left NUMERIC(15, 2);
+BEGIN
+    UPDATE moto_auto.client
+    SET bonus_points = bonus_points + NEW.points
+    WHERE client_id = NEW.client_id
+    RETURNING bonus_points INTO points_left;
+
+    IF points_left < 0 THEN
+        RAISE EXCEPTION 'У клиента % недостаточно бонусных баллов', NEW.client_id;
+    END IF;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_apply_bonus_transaction
+AFTER INSERT ON moto_auto.bonus_transaction
+FOR EACH ROW
+EXECUTE FUNCTION apply_bonus_transaction();
+
+-- Напрямую баланс не меняется: новые клиенты начинают с нуля,
+-- а изменение вне триггера журнала считается ошибкой.
+CREATE OR REPLACE FUNCTION guard_bonus_points()
+RETURNS TRIGGER
+AS $$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        IF COALESCE(NEW.bonus_points, 0) <> 0 THEN
+            RAISE EXCEPTION 'Бонусные баллы начисляются только через журнал';
+        END IF;
+        NEW.bonus_points := 0;
+    ELSIF NEW.bonus_points IS DISTINCT FROM OLD.bonus_points AND pg_trigger_depth() < 2 THEN
+        RAISE EXCEPTION 'Бонусные баллы начисляются только через журнал';
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_guard_bonus_points
+BEFORE INSERT OR UPDATE ON moto_auto.client
+FOR EACH ROW
+EXECUTE FUNCTION guard_bonus_points();
+
+-- Записывает движение баллов. Поступление становится начислением со сроком жизни
+-- уровня клиента, расход списывается с начислений, которые сгорают раньше.
+CREATE OR REPLACE FUNCTION add_bonus_transaction(
+    target_client_id INTEGER,
+    target_order_id INTEGER,
+    transaction_kind VARCHAR,
+    delta NUMERIC,
+    transaction_reason TEXT
+)
+RETURNS moto_auto.bonus_transaction
+SECURITY DEFINER
+AS $$
+DECLARE
+    lifetime_days INTEGER;
+    entry moto_auto.bonus_transaction;
+BEGIN
+    IF delta > 0 THEN
+        SELECT t.points_lifetime_days INTO lifetime_days
+        FROM moto_auto.client c
+        INNER JOIN moto_auto.loyalty_tier t
+        ON t.name = c.status
+        WHERE c.client_id = target_client_id;
+    ELSIF delta < 0 THEN
+        PERFORM consume_bonus_points(target_client_id, -delta);
+    END IF;
+
+    INSERT INTO moto_auto.bonus_transaction (
+        client_id, order_id, kind, points, remaining, expires_at, reason
+    )
+    VALUES (
+        target_client_id,
+        target_order_id,
+        transaction_kind,
+        delta,
+        GREATEST(delta, 0),
+        CASE WHEN delta > 0 THEN NOW() + make_interval(days => COALESCE(lifetime_days, 365)) END,
+        transaction_reason
+    )
+    RETURNING * INTO entry;
+
+    RETURN entry;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION accrue_bonus_points(
+    target_client_id INTEGER,
+    target_order_id INTEGER,
+    accrued_points NUMERIC
+)
+RETURNS VOID
+SECURITY DEFINER
+AS $$
+BEGIN
+    IF accrued_points > 0 THEN
+        PERFORM add_bonus_transaction(target_client_id, target_order_id, 'accrual', accrued_points, NULL);
+    END IF;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Ручная корректировка баланса, причина обязательна.
+CREATE OR REPLACE FUNCTION adjust_bonus_points(
+    target_client_id INTEGER,
+    delta NUMERIC,
+    adjustment_reason TEXT
+)
+RETURNS moto_auto.bonus_transaction
+SECURITY DEFINER
+AS $$
+BEGIN
+    RETURN add_bonus_transaction(target_client_id, NULL, 'adjustment', delta, adjustment_reason);
+END;
+$$ LANGUAGE plpgsql;
+
+-- Списание по заказу и его возврат записываются в журнал со ссылкой на заказ.
+CREATE OR REPLACE FUNCTION redeem_bonus_points()
+RETURNS TRIGGER
+SECURITY DEFINER
+AS $$
+DECLARE
+    points_delta NUMERIC(15, 2);
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        points_delta := NEW.bonus_points_used;
+    ELSE
+        points_delta := NEW.bonus_points_used - OLD.bonus_points_used;
+    END IF;
+    IF points_delta <> 0 THEN
+        PERFORM add_bonus_transaction(NEW.client_id, NEW.order_id, 'redemption', -points_delta, NULL);
+    END IF;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION expire_bonus_points()
+RETURNS INTEGER
+SECURITY DEFINER
+AS $$
+DECLARE
+    lot RECORD;
+    expired INTEGER := 0;
+BEGIN
+    FOR lot IN
+        SELECT transaction_id, client_id, remaining
+        FROM moto_auto.bonus_transaction
+        WHERE remaining > 0 AND expires_at <= NOW()
+        FOR UPDATE
+    LOOP
+        UPDATE moto_auto.bonus_transaction
+        SET remaining = 0
+        WHERE transaction_id = lot.transaction_id;
+
+        INSERT INTO moto_auto.bonus_transaction (client_id, kind, points)
+        VALUES (lot.client_id, 'expiration', -lot.remaining);
+
+        expired := expired + 1;
+    END LOOP;
+
+    RETURN expired;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Остаток начисления меняется при списании и сгорании, поэтому журнал тоже под аудитом.
+CREATE TRIGGER trigger_audit_bonus_transaction
+AFTER INSERT OR UPDATE OR DELETE ON moto_auto.bonus_transaction
+FOR EACH ROW EXECUTE FUNCTION audit_row_change('transaction_id');
+
+-- Выписку видят те же, кто видит клиента.
+ALTER TABLE moto_auto.bonus_transaction ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY manager_bonus_transaction_policy ON moto_auto.bonus_transaction
+    FOR SELECT TO manager USING (
+        EXISTS (
+            SELECT 1
+            FROM moto_auto.client c
+            WHERE c.client_id = moto_auto.bonus_transaction.client_id
+        )
+    );
+
+CREATE POLICY admin_bonus_transaction_policy ON moto_auto.bonus_transaction
+    FOR ALL TO admin USING (true);
+
+CREATE POLICY analyst_bonus_transaction_policy ON moto_auto.bonus_transaction
+    FOR SELECT TO analyst USING (true);
+
+GRANT SELECT ON moto_auto.bonus_transaction TO manager;
+
+COMMIT;
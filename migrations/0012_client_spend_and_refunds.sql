@@ -0,0 +1,202 @@
+BEGIN;
+
+-- Возврат выполненного заказа: отдельный статус с датой и причиной.
+ALTER TABLE moto_auto.orders DROP CONSTRAINT orders_status_check;
+ALTER TABLE moto_auto.orders
+    ADD CONSTRAINT orders_status_check
+        CHECK (status IN ('processing', 'finished', 'cancelled', 'refunded')),
+    ADD COLUMN refunded_at TIMESTAMPTZ,
+    ADD COLUMN refund_reason TEXT,
+    ADD CONSTRAINT orders_refund_check CHECK (
+        status <> 'refunded' OR (refunded_at IS NOT NULL AND COALESCE(refund_reason, '') <> '')
+    );
+
+-- Отмена начисления по заказу. Может увести баланс в минус,
+-- если клиент уже потратил эти баллы.
+ALTER TABLE moto_auto.bonus_transaction DROP CONSTRAINT bonus_transaction_kind_check;
+ALTER TABLE moto_auto.bonus_transaction
+    ADD CONSTRAINT bonus_transaction_kind_check
+        CHECK (kind IN ('accrual', 'redemption', 'adjustment', 'expiration', 'reversal'));
+
+CREATE OR REPLACE FUNCTION apply_bonus_transaction()
+RETURNS TRIGGER
+SECURITY DEFINER
+AS $$
+DECLARE
+    points_left NUMERIC(15, 2);
+BEGIN
+    UPDATE moto_auto.client
+    SET bonus_points = bonus_points + NEW.points
+    WHERE client_id = NEW.client_id
+    RETURNING bonus_points INTO points_left;
+
+    IF points_left < 0 AND NEW.kind <> 'reversal' THEN
+        RAISE EXCEPTION 'У клиента % недостаточно бонусных баллов', NEW.client_id;
+    END IF;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Списание начинается с начислений preferred_order_id, затем идут те, что сгорают раньше.
+DROP FUNCTION consume_bonus_points(INTEGER, NUMERIC);
+CREATE OR REPLACE FUNCTION consume_bonus_points(
+    target_client_id INTEGER,
+    spent_points NUMERIC,
+    preferred_order_id INTEGER DEFAULT NULL
+)
+RETURNS VOID
+SECURITY DEFINER
+AS $$
+DECLARE
+    lot RECORD;
+    taken NUMERIC(15, 2);
+BEGIN
+    FOR lot IN
+        SELECT transaction_id, remaining
+        FROM moto_auto.bonus_transaction
+        WHERE client_id = target_client_id AND remaining > 0
+        ORDER BY order_id IS NOT DISTINCT FROM preferred_order_id DESC, expires_at, transaction_id
+        FOR UPDATE
+    LOOP
+        EXIT WHEN spent_points <= 0;
+        taken := LEAST(lot.remaining, spent_points);
+        UPDATE moto_auto.bonus_transaction
+        SET remaining = remaining - taken
+        WHERE transaction_id = lot.transaction_id;
+        spent_points := spent_points - taken;
+    END LOOP;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Снимает баллы, начисленные за заказ, и, если нужно, возвращает списанные на него.
+CREATE OR REPLACE FUNCTION reverse_order_bonus(
+    target_order_id INTEGER,
+    target_client_id INTEGER,
+    return_redeemed BOOLEAN
+)
+RETURNS VOID
+SECURITY DEFINER
+AS $$
+DECLARE
+    accrued NUMERIC(15, 2);
+    redeemed NUMERIC(15, 2);
+BEGIN
+    SELECT
+        COALESCE(SUM(points) FILTER (WHERE kind IN ('accrual', 'reversal')), 0),
+        -COALESCE(SUM(points) FILTER (WHERE kind = 'redemption'), 0)
+    INTO accrued, redeemed
+    FROM moto_auto.bonus_transaction
+    WHERE order_id = target_order_id AND client_id = target_client_id;
+
+    IF accrued > 0 THEN
+        PERFORM consume_bonus_points(target_client_id, accrued, target_order_id);
+        INSERT INTO moto_auto.bonus_transaction (client_id, order_id, kind, points)
+        VALUES (target_client_id, target_order_id, 'reversal', -accrued);
+    END IF;
+
+    IF return_redeemed AND redeemed > 0 THEN
+        PERFORM add_bonus_transaction(target_client_id, target_order_id, 'redemption', redeemed, NULL);
+    END IF;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Заказ, который перестал быть выполненным, теряет начисленные баллы.
+-- При отмене или возврате клиенту возвращаются списанные на заказ баллы.
+CREATE OR REPLACE FUNCTION reverse_order_bonus_on_status()
+RETURNS TRIGGER
+SECURITY DEFINER
+AS $$
+BEGIN
+    IF OLD.status = 'finished' OR NEW.status IN ('cancelled', 'refunded') THEN
+        PERFORM reverse_order_bonus(
+            NEW.order_id,
+            NEW.client_id,
+            NEW.status IN ('cancelled', 'refunded') AND OLD.status NOT IN ('cancelled', 'refunded')
+        );
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_reverse_order_bonus
+AFTER UPDATE OF status ON moto_auto.orders
+FOR EACH ROW
+WHEN (OLD.status IS DISTINCT FROM NEW.status)
+EXECUTE FUNCTION reverse_order_bonus_on_status();
+
+-- total_spent: сумма выполненных и не удалённых заказов клиента.
+-- Уровень клиента следует за ней в обе стороны.
+CREATE OR REPLACE FUNCTION recalculate_client_spend(target_client_id INTEGER)
+RETURNS VOID
+SECURITY DEFINER
+AS $$
+DECLARE
+    client_total_spent NUMERIC(15, 2);
+    client_tier VARCHAR(20);
+BEGIN
+    SELECT COALESCE(SUM(total_amount), 0) INTO client_total_spent
+    FROM moto_auto.orders
+    WHERE client_id = target_client_id
+    AND status = 'finished'
+    AND deleted_at IS NULL;
+
+    SELECT t.name INTO client_tier
+    FROM moto_auto.loyalty_tier t
+    WHERE t.spend_threshold <= client_total_spent
+    ORDER BY t.spend_threshold DESC
+    LIMIT 1;
+
+    UPDATE moto_auto.client
+    SET total_spent = client_total_spent,
+        status = COALESCE(client_tier, status)
+    WHERE client_id = target_client_id
+    AND (total_spent, status) IS DISTINCT FROM (client_total_spent, COALESCE(client_tier, status));
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION update_client_status()
+RETURNS TRIGGER
+SECURITY DEFINER
+AS $$
+BEGIN
+    IF TG_OP <> 'INSERT' THEN
+        PERFORM recalculate_client_spend(OLD.client_id);
+    END IF;
+    IF TG_OP <> 'DELETE' AND (TG_OP = 'INSERT' OR NEW.client_id <> OLD.client_id) THEN
+        PERFORM recalculate_client_spend(NEW.client_id);
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER trigger_update_client_status ON moto_auto.orders;
+
+CREATE TRIGGER trigger_update_client_status
+AFTER INSERT ON moto_auto.orders
+FOR EACH ROW
+WHEN (NEW.status = 'finished')
+EXECUTE FUNCTION update_client_status();
+
+CREATE TRIGGER trigger_update_client_status_on_change
+AFTER UPDATE ON moto_auto.orders
+FOR EACH ROW
+WHEN (
+    (OLD.status, OLD.total_amount, OLD.client_id, OLD.deleted_at)
+    IS DISTINCT FROM (NEW.status, NEW.total_amount, NEW.client_id, NEW.deleted_at)
+)
+EXECUTE FUNCTION update_client_status();
+
+CREATE TRIGGER trigger_update_client_status_on_delete
+AFTER DELETE ON moto_auto.orders
+FOR EACH ROW
+WHEN (OLD.status = 'finished')
+EXECUTE FUNCTION update_client_status();
+
+-- Пересчёт для всех клиентов: раньше в сумму попадали и невыполненные заказы.
+DO $$
+BEGIN
+    PERFORM recalculate_client_spend(client_id) FROM moto_auto.client;
+END $$;
+
+COMMIT;
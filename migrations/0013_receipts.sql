@@ -0,0 +1,204 @@
+BEGIN;
+
+-- Чек выдаётся при каждом выполнении заказа. Номер сквозной в пределах филиала.
+-- Реквизиты филиала, имя клиента, строки и итоги копируются в чек,
+-- чтобы он не менялся вместе с заказом и справочниками.
+-- Чек и его строки только добавляются при выдаче и больше не меняются,
+-- поэтому аудита у этих таблиц нет.
+CREATE TABLE moto_auto.receipt (
+    receipt_id SERIAL PRIMARY KEY,
+    order_id INTEGER NOT NULL REFERENCES moto_auto.orders(order_id) ON DELETE CASCADE,
+    branch_id INTEGER NOT NULL REFERENCES moto_auto.branch(branch_id) ON DELETE RESTRICT,
+    receipt_number INTEGER NOT NULL,
+    issued_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    client_name VARCHAR(100) NOT NULL,
+    branch_city VARCHAR(100) NOT NULL,
+    branch_address VARCHAR(255) NOT NULL,
+    branch_postal_code VARCHAR(20) NOT NULL,
+    branch_phone_number VARCHAR(20) NOT NULL,
+    subtotal NUMERIC(15, 2) NOT NULL,
+    discount_amount NUMERIC(15, 2) NOT NULL,
+    vat_rate NUMERIC(5, 2) NOT NULL,
+    tax_amount NUMERIC(15, 2) NOT NULL,
+    bonus_points_used NUMERIC(15, 2) NOT NULL,
+    total_amount NUMERIC(15, 2) NOT NULL,
+    UNIQUE (branch_id, receipt_number)
+);
+
+CREATE INDEX idx_receipt_order_id ON moto_auto.receipt(order_id);
+
+CREATE TABLE moto_auto.receipt_line (
+    receipt_line_id SERIAL PRIMARY KEY,
+    receipt_id INTEGER NOT NULL REFERENCES moto_auto.receipt(receipt_id) ON DELETE CASCADE,
+    line_number INTEGER NOT NULL,
+    item_type VARCHAR(20) NOT NULL CHECK (item_type IN ('service', 'spare_part')),
+    name VARCHAR(100) NOT NULL,
+    quantity INTEGER NOT NULL,
+    unit_price NUMERIC(15, 2) NOT NULL,
+    discount_amount NUMERIC(15, 2) NOT NULL,
+    amount NUMERIC(15, 2) NOT NULL,
+    UNIQUE (receipt_id, line_number)
+);
+
+-- Строки заказа в порядке печати: услуга, затем запчасти к ней.
+CREATE OR REPLACE FUNCTION order_receipt_lines(target_order_id INTEGER)
+RETURNS TABLE (
+    line_number BIGINT,
+    item_type VARCHAR,
+    name VARCHAR,
+    quantity INTEGER,
+    unit_price NUMERIC,
+    discount_amount NUMERIC
+)
+STABLE
+AS $$
+    SELECT
+        ROW_NUMBER() OVER (ORDER BY line.order_service_id, line.part_line_id NULLS FIRST),
+        line.item_type, line.name, line.quantity, line.unit_price, line.discount_amount
+    FROM (
+        SELECT
+            os.order_service_id,
+            NULL::INTEGER AS part_line_id,
+            'service'::VARCHAR AS item_type,
+            os.service_name AS name,
+            1 AS quantity,
+            os.unit_price,
+            discount_of(os.unit_price, os.discount_type, os.discount_value) AS discount_amount
+        FROM moto_auto.order_service os
+        WHERE os.order_id = target_order_id
+        UNION ALL
+        SELECT
+            os.order_service_id,
+            osp.order_service_part_id,
+            'spare_part',
+            osp.part_name,
+            osp.quantity,
+            osp.unit_price,
+            discount_of(osp.unit_price * osp.quantity, osp.discount_type, osp.discount_value)
+        FROM moto_auto.order_service_part osp
+        INNER JOIN moto_auto.order_service os
+        ON osp.order_service_id = os.order_service_id
+        WHERE os.order_id = target_order_id
+    ) line;
+$$ LANGUAGE sql;
+
+-- Выписывает чек по заказу. Блокировка строки филиала делает нумерацию
+-- последовательной и без пропусков при одновременном закрытии заказов.
+CREATE OR REPLACE FUNCTION issue_receipt(target_order_id INTEGER)
+RETURNS moto_auto.receipt
+SECURITY DEFINER
+AS $$
+DECLARE
+    o moto_auto.orders;
+    b moto_auto.branch;
+    issued moto_auto.receipt;
+BEGIN
+    SELECT * INTO o FROM moto_auto.orders WHERE order_id = target_order_id;
+    SELECT * INTO b FROM moto_auto.branch WHERE branch_id = o.branch_id FOR UPDATE;
+
+    INSERT INTO moto_auto.receipt (
+        order_id, branch_id, receipt_number, client_name,
+        branch_city, branch_address, branch_postal_code, branch_phone_number,
+        subtotal, discount_amount, vat_rate, tax_amount, bonus_points_used, total_amount
+    )
+    SELECT
+        o.order_id, b.branch_id,
+        COALESCE((SELECT MAX(r.receipt_number) FROM moto_auto.receipt r WHERE r.branch_id = b.branch_id), 0) + 1,
+        c.name, b.city, b.address, b.postal_code, b.phone_number,
+        o.subtotal, o.discount_amount, o.vat_rate, o.tax_amount, o.bonus_points_used,
+        COALESCE(o.total_amount, 0)
+    FROM moto_auto.client c
+    WHERE c.client_id = o.client_id
+    RETURNING * INTO issued;
+
+    INSERT INTO moto_auto.receipt_line (
+        receipt_id, line_number, item_type, name, quantity, unit_price, discount_amount, amount
+    )
+    SELECT
+        issued.receipt_id, l.line_number, l.item_type, l.name, l.quantity, l.unit_price,
+        l.discount_amount, l.unit_price * l.quantity - l.discount_amount
+    FROM order_receipt_lines(target_order_id) l;
+
+    RETURN issued;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION issue_receipt_on_finish()
+RETURNS TRIGGER
+SECURITY DEFINER
+AS $$
+BEGIN
+    PERFORM issue_receipt(NEW.order_id);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_issue_receipt
+AFTER INSERT ON moto_auto.orders
+FOR EACH ROW
+WHEN (NEW.status = 'finished')
+EXECUTE FUNCTION issue_receipt_on_finish();
+
+CREATE TRIGGER trigger_issue_receipt_on_change
+AFTER UPDATE OF status ON moto_auto.orders
+FOR EACH ROW
+WHEN (NEW.status = 'finished' AND OLD.status <> 'finished')
+EXECUTE FUNCTION issue_receipt_on_finish();
+
+-- Чеки для уже выполненных заказов, пронумерованные по дате выполнения.
+INSERT INTO moto_auto.receipt (
+    order_id, branch_id, receipt_number, issued_at, client_name,
+    branch_city, branch_address, branch_postal_code, branch_phone_number,
+    subtotal, discount_amount, vat_rate, tax_amount, bonus_points_used, total_amount
+)
+SELECT
+    o.order_id, b.branch_id,
+    ROW_NUMBER() OVER (PARTITION BY b.branch_id ORDER BY COALESCE(o.completion_date, o.order_date), o.order_id),
+    COALESCE(o.completion_date, o.order_date),
+    c.name, b.city, b.address, b.postal_code, b.phone_number,
+    o.subtotal, o.discount_amount, o.vat_rate, o.tax_amount, o.bonus_points_used,
+    COALESCE(o.total_amount, 0)
+FROM moto_auto.orders o
+INNER JOIN moto_auto.branch b
+ON b.branch_id = o.branch_id
+INNER JOIN moto_auto.client c
+ON c.client_id = o.client_id
+WHERE o.status = 'finished';
+
+INSERT INTO moto_auto.receipt_line (
+    receipt_id, line_number, item_type, name, quantity, unit_price, discount_amount, amount
+)
+SELECT
+    r.receipt_id, l.line_number, l.item_type, l.name, l.quantity, l.unit_price,
+    l.discount_amount, l.unit_price * l.quantity - l.discount_amount
+FROM moto_auto.receipt r
+CROSS JOIN LATERAL order_receipt_lines(r.order_id) l;
+
+ALTER TABLE moto_auto.receipt ENABLE ROW LEVEL SECURITY;
+ALTER TABLE moto_auto.receipt_line ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY manager_receipt_policy ON moto_auto.receipt
+    FOR SELECT TO manager USING (
+        moto_auto.receipt.branch_id = current_app_branch_id()
+    );
+
+CREATE POLICY admin_receipt_policy ON moto_auto.receipt
+    FOR ALL TO admin USING (
+        moto_auto.receipt.branch_id = current_app_branch_id()
+    );
+
+CREATE POLICY analyst_receipt_policy ON moto_auto.receipt
+    FOR SELECT TO analyst USING (true);
+
+CREATE POLICY receipt_line_policy ON moto_auto.receipt_line
+    FOR SELECT TO manager, admin, analyst USING (
+        EXISTS (
+            SELECT 1
+            FROM moto_auto.receipt r
+            WHERE r.receipt_id = moto_auto.receipt_line.receipt_id
+        )
+    );
+
+GRANT SELECT ON moto_auto.receipt, moto_auto.receipt_line TO manager, analyst;
+
+COMMIT;
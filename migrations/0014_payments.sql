@@ -0,0 +1,147 @@
+BEGIN;
+
+-- Оплаты по заказам. Заказ можно оплатить частями и заранее, пока он в работе.
+-- cashier_id: пользователь, принявший оплату. Оплата баллами списывает их через журнал.
+CREATE TABLE moto_auto.payment (
+    payment_id SERIAL PRIMARY KEY,
+    order_id INTEGER NOT NULL REFERENCES moto_auto.orders(order_id) ON DELETE CASCADE,
+    amount NUMERIC(15, 2) NOT NULL CHECK (amount > 0),
+    method VARCHAR(20) NOT NULL CHECK (method IN ('cash', 'card', 'transfer', 'bonus')),
+    paid_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    cashier_id INTEGER NOT NULL
+        DEFAULT NULLIF(current_setting('moto_auto.actor', true), '')::INTEGER
+);
+
+CREATE INDEX idx_payment_order_id ON moto_auto.payment(order_id);
+
+-- Выдача заказа клиенту. Неоплаченный заказ выдаётся только с причиной,
+-- pickup_override_by: кто разрешил выдачу без полной оплаты.
+ALTER TABLE moto_auto.orders
+    ADD COLUMN picked_up_at TIMESTAMPTZ,
+    ADD COLUMN pickup_override_reason TEXT,
+    ADD COLUMN pickup_override_by INTEGER;
+
+-- Остаток к оплате по заказу. Переплата невозможна, поэтому остаток не бывает отрицательным.
+CREATE OR REPLACE FUNCTION order_outstanding(target_order_id INTEGER)
+RETURNS NUMERIC
+STABLE
+SECURITY DEFINER
+AS $$
+    SELECT COALESCE(o.total_amount, 0) - COALESCE(
+        (SELECT SUM(p.amount) FROM moto_auto.payment p WHERE p.order_id = o.order_id),
+        0
+    )
+    FROM moto_auto.orders o
+    WHERE o.order_id = target_order_id;
+$$ LANGUAGE sql;
+
+-- Долг клиента: остатки по его заказам в работе и выполненным.
+CREATE OR REPLACE FUNCTION client_outstanding(target_client_id INTEGER)
+RETURNS NUMERIC
+STABLE
+SECURITY DEFINER
+AS $$
+    SELECT COALESCE(SUM(order_outstanding(o.order_id)), 0)
+    FROM moto_auto.orders o
+    WHERE o.client_id = target_client_id
+    AND o.status IN ('processing', 'finished')
+    AND o.deleted_at IS NULL;
+$$ LANGUAGE sql;
+
+-- Оплата принимается по заказам в работе и выполненным и не больше остатка.
+CREATE OR REPLACE FUNCTION check_payment()
+RETURNS TRIGGER
+SECURITY DEFINER
+AS $$
+DECLARE
+    o moto_auto.orders;
+BEGIN
+    SELECT * INTO o FROM moto_auto.orders WHERE order_id = NEW.order_id FOR UPDATE;
+
+    IF o.status NOT IN ('processing', 'finished') OR o.deleted_at IS NOT NULL THEN
+        RAISE EXCEPTION 'Заказ % не принимает оплату', NEW.order_id;
+    END IF;
+    IF NEW.amount > order_outstanding(NEW.order_id) THEN
+        RAISE EXCEPTION 'Оплата превышает остаток по заказу %', NEW.order_id;
+    END IF;
+    IF NEW.method = 'bonus' THEN
+        PERFORM add_bonus_transaction(o.client_id, o.order_id, 'redemption', -NEW.amount, NULL);
+    END IF;
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_check_payment
+BEFORE INSERT ON moto_auto.payment
+FOR EACH ROW
+EXECUTE FUNCTION check_payment();
+
+-- Выдаётся только выполненный заказ. Без полной оплаты нужна причина.
+CREATE OR REPLACE FUNCTION check_order_pickup()
+RETURNS TRIGGER
+AS $$
+BEGIN
+    IF NEW.status <> 'finished' THEN
+        RAISE EXCEPTION 'Заказ % ещё не выполнен', NEW.order_id;
+    END IF;
+    IF order_outstanding(NEW.order_id) > 0 THEN
+        IF COALESCE(NEW.pickup_override_reason, '') = '' THEN
+            RAISE EXCEPTION 'Заказ % оплачен не полностью', NEW.order_id;
+        END IF;
+        NEW.pickup_override_by := NULLIF(current_setting('moto_auto.actor', true), '')::INTEGER;
+    ELSE
+        NEW.pickup_override_reason := NULL;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_check_order_pickup
+BEFORE UPDATE OF picked_up_at ON moto_auto.orders
+FOR EACH ROW
+WHEN (NEW.picked_up_at IS NOT NULL AND OLD.picked_up_at IS NULL)
+EXECUTE FUNCTION check_order_pickup();
+
+CREATE TRIGGER trigger_audit_payment
+AFTER INSERT OR UPDATE OR DELETE ON moto_auto.payment
+FOR EACH ROW EXECUTE FUNCTION audit_row_change('payment_id');
+
+-- Оплаты видят и принимают те, кто видит заказ.
+ALTER TABLE moto_auto.payment ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY manager_payment_policy ON moto_auto.payment
+    FOR ALL TO manager USING (
+        EXISTS (
+            SELECT 1
+            FROM moto_auto.orders o
+            WHERE o.order_id = moto_auto.payment.order_id
+        )
+    );
+
+CREATE POLICY master_payment_policy ON moto_auto.payment
+    FOR SELECT TO master USING (
+        EXISTS (
+            SELECT 1
+            FROM moto_auto.orders o
+            WHERE o.order_id = moto_auto.payment.order_id
+        )
+    );
+
+CREATE POLICY admin_payment_policy ON moto_auto.payment
+    FOR ALL TO admin USING (
+        EXISTS (
+            SELECT 1
+            FROM moto_auto.orders o
+            WHERE o.order_id = moto_auto.payment.order_id
+            AND o.branch_id = current_app_branch_id()
+        )
+    );
+
+CREATE POLICY analyst_payment_policy ON moto_auto.payment
+    FOR SELECT TO analyst USING (true);
+
+GRANT SELECT, INSERT ON moto_auto.payment TO manager;
+GRANT SELECT ON moto_auto.payment TO master;
+
+COMMIT;
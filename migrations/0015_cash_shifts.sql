@@ -0,0 +1,106 @@
+BEGIN;
+
+-- Кассовая смена филиала. В филиале открыта не больше одной смены.
+-- При закрытии сохраняются пересчитанная наличность, ожидаемая сумма и расхождение.
+CREATE TABLE moto_auto.cash_shift (
+    shift_id SERIAL PRIMARY KEY,
+    branch_id INTEGER NOT NULL REFERENCES moto_auto.branch(branch_id) ON DELETE RESTRICT,
+    opened_by INTEGER NOT NULL
+        DEFAULT NULLIF(current_setting('moto_auto.actor', true), '')::INTEGER,
+    opened_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    opening_cash NUMERIC(15, 2) NOT NULL DEFAULT 0 CHECK (opening_cash >= 0),
+    closed_by INTEGER,
+    closed_at TIMESTAMPTZ,
+    counted_cash NUMERIC(15, 2) CHECK (counted_cash >= 0),
+    expected_cash NUMERIC(15, 2),
+    discrepancy NUMERIC(15, 2),
+    CHECK (
+        (closed_at IS NULL AND counted_cash IS NULL AND expected_cash IS NULL AND discrepancy IS NULL)
+        OR (closed_at IS NOT NULL AND counted_cash IS NOT NULL AND expected_cash IS NOT NULL AND discrepancy IS NOT NULL)
+    )
+);
+
+CREATE UNIQUE INDEX idx_cash_shift_open ON moto_auto.cash_shift(branch_id) WHERE closed_at IS NULL;
+
+CREATE INDEX idx_payment_paid_at ON moto_auto.payment(paid_at);
+
+-- Оплаты по заказам филиала, принятые за время смены, по способам оплаты.
+-- Для открытой смены итоги считаются на текущий момент.
+CREATE OR REPLACE FUNCTION shift_payment_totals(target_shift_id INTEGER)
+RETURNS TABLE (
+    method VARCHAR,
+    amount NUMERIC,
+    payment_count BIGINT
+)
+STABLE
+SECURITY DEFINER
+AS $$
+    SELECT p.method, SUM(p.amount), COUNT(*)
+    FROM moto_auto.cash_shift s
+    INNER JOIN moto_auto.orders o
+    ON o.branch_id = s.branch_id
+    INNER JOIN moto_auto.payment p
+    ON p.order_id = o.order_id
+    WHERE s.shift_id = target_shift_id
+    AND p.paid_at >= s.opened_at
+    AND (s.closed_at IS NULL OR p.paid_at < s.closed_at)
+    GROUP BY p.method
+    ORDER BY p.method;
+$$ LANGUAGE sql;
+
+-- Закрывает смену: в кассе ожидается начальный остаток плюс оплаты наличными.
+CREATE OR REPLACE FUNCTION close_cash_shift(target_shift_id INTEGER, counted NUMERIC)
+RETURNS moto_auto.cash_shift
+SECURITY DEFINER
+AS $$
+DECLARE
+    shift moto_auto.cash_shift;
+    cash NUMERIC(15, 2);
+BEGIN
+    SELECT * INTO shift
+    FROM moto_auto.cash_shift
+    WHERE shift_id = target_shift_id AND closed_at IS NULL
+    FOR UPDATE;
+    IF NOT FOUND THEN
+        RAISE EXCEPTION 'Смена % не открыта', target_shift_id;
+    END IF;
+
+    SELECT COALESCE(SUM(t.amount), 0) INTO cash
+    FROM shift_payment_totals(target_shift_id) t
+    WHERE t.method = 'cash';
+
+    UPDATE moto_auto.cash_shift
+    SET closed_by = NULLIF(current_setting('moto_auto.actor', true), '')::INTEGER,
+        closed_at = NOW(),
+        counted_cash = counted,
+        expected_cash = shift.opening_cash + cash,
+        discrepancy = counted - (shift.opening_cash + cash)
+    WHERE shift_id = target_shift_id
+    RETURNING * INTO shift;
+
+    RETURN shift;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_audit_cash_shift
+AFTER INSERT OR UPDATE OR DELETE ON moto_auto.cash_shift
+FOR EACH ROW EXECUTE FUNCTION audit_row_change('shift_id');
+
+ALTER TABLE moto_auto.cash_shift ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY manager_cash_shift_policy ON moto_auto.cash_shift
+    FOR ALL TO manager USING (
+        moto_auto.cash_shift.branch_id = current_app_branch_id()
+    );
+
+CREATE POLICY admin_cash_shift_policy ON moto_auto.cash_shift
+    FOR ALL TO admin USING (
+        moto_auto.cash_shift.branch_id = current_app_branch_id()
+    );
+
+CREATE POLICY analyst_cash_shift_policy ON moto_auto.cash_shift
+    FOR SELECT TO analyst USING (true);
+
+GRANT SELECT, INSERT ON moto_auto.cash_shift TO manager;
+
+COMMIT;
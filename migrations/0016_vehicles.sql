@@ -0,0 +1,139 @@
+BEGIN;
+
+-- Транспорт клиентов. mileage: последний известный пробег, растёт вместе с пробегом при приёмке
+-- и назад не идёт. VIN уникален среди действующего транспорта: удалённый мотоцикл
+-- можно зарегистрировать снова, например у нового владельца.
+CREATE TABLE moto_auto.vehicle (
+    vehicle_id SERIAL PRIMARY KEY,
+    client_id INTEGER NOT NULL REFERENCES moto_auto.client(client_id) ON DELETE CASCADE,
+    make VARCHAR(50) NOT NULL,
+    model VARCHAR(50) NOT NULL,
+    year INTEGER CHECK (year >= 1885),
+    vin VARCHAR(17),
+    plate VARCHAR(20),
+    mileage INTEGER NOT NULL DEFAULT 0 CHECK (mileage >= 0),
+    deleted_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_vehicle_client_id ON moto_auto.vehicle(client_id);
+CREATE UNIQUE INDEX idx_vehicle_vin ON moto_auto.vehicle(vin) WHERE deleted_at IS NULL;
+
+CREATE OR REPLACE FUNCTION check_vehicle_mileage()
+RETURNS TRIGGER
+AS $$
+BEGIN
+    IF NEW.mileage < OLD.mileage THEN
+        RAISE EXCEPTION 'Пробег транспорта % не может уменьшиться', NEW.vehicle_id;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_check_vehicle_mileage
+BEFORE UPDATE OF mileage ON moto_auto.vehicle
+FOR EACH ROW
+EXECUTE FUNCTION check_vehicle_mileage();
+
+-- Заказ ссылается на обслуживаемый транспорт, intake_mileage: пробег при приёмке.
+ALTER TABLE moto_auto.orders
+    ADD COLUMN vehicle_id INTEGER REFERENCES moto_auto.vehicle(vehicle_id) ON DELETE SET NULL,
+    ADD COLUMN intake_mileage INTEGER CHECK (intake_mileage >= 0),
+    ADD CONSTRAINT orders_vehicle_mileage_check CHECK (intake_mileage IS NULL OR vehicle_id IS NOT NULL);
+
+CREATE INDEX idx_orders_vehicle_id ON moto_auto.orders(vehicle_id);
+
+-- Транспорт заказа принадлежит клиенту заказа. Пробег при приёмке обновляет пробег транспорта.
+CREATE OR REPLACE FUNCTION check_order_vehicle()
+RETURNS TRIGGER
+SECURITY DEFINER
+AS $$
+BEGIN
+    IF NEW.vehicle_id IS NULL THEN
+        RETURN NEW;
+    END IF;
+    IF NOT EXISTS (
+        SELECT 1
+        FROM moto_auto.vehicle v
+        WHERE v.vehicle_id = NEW.vehicle_id
+        AND v.client_id = NEW.client_id
+        AND v.deleted_at IS NULL
+    ) THEN
+        RAISE EXCEPTION 'Транспорт % не принадлежит клиенту %', NEW.vehicle_id, NEW.client_id;
+    END IF;
+
+    UPDATE moto_auto.vehicle
+    SET mileage = NEW.intake_mileage
+    WHERE vehicle_id = NEW.vehicle_id AND mileage < NEW.intake_mileage;
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_check_order_vehicle
+BEFORE INSERT OR UPDATE OF vehicle_id, intake_mileage, client_id ON moto_auto.orders
+FOR EACH ROW
+EXECUTE FUNCTION check_order_vehicle();
+
+-- История обслуживания транспорта: работы и запчасти по всем его заказам, новые сверху.
+-- Мастер видит её целиком, даже если прежние заказы выполняли другие мастера.
+CREATE OR REPLACE FUNCTION vehicle_service_history(target_vehicle_id INTEGER)
+RETURNS TABLE (
+    order_id INTEGER,
+    order_date TIMESTAMPTZ,
+    completion_date TIMESTAMPTZ,
+    status VARCHAR,
+    branch_id INTEGER,
+    master_id INTEGER,
+    intake_mileage INTEGER,
+    item_type VARCHAR,
+    name VARCHAR,
+    quantity INTEGER
+)
+STABLE
+SECURITY DEFINER
+AS $$
+    SELECT
+        o.order_id, o.order_date, o.completion_date, o.status, o.branch_id, o.master_id,
+        o.intake_mileage, l.item_type, l.name, l.quantity::INTEGER
+    FROM moto_auto.orders o
+    CROSS JOIN LATERAL order_receipt_lines(o.order_id) l
+    WHERE o.vehicle_id = target_vehicle_id
+    AND o.deleted_at IS NULL
+    ORDER BY o.order_date DESC, o.order_id DESC, l.line_number;
+$$ LANGUAGE sql;
+
+CREATE TRIGGER trigger_audit_vehicle
+AFTER INSERT OR UPDATE OR DELETE ON moto_auto.vehicle
+FOR EACH ROW EXECUTE FUNCTION audit_row_change('vehicle_id');
+
+-- Транспорт видят те, кто видит клиента.
+ALTER TABLE moto_auto.vehicle ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY manager_vehicle_policy ON moto_auto.vehicle
+    FOR ALL TO manager USING (
+        EXISTS (
+            SELECT 1
+            FROM moto_auto.client c
+            WHERE c.client_id = moto_auto.vehicle.client_id
+        )
+    );
+
+CREATE POLICY master_vehicle_policy ON moto_auto.vehicle
+    FOR SELECT TO master USING (
+        EXISTS (
+            SELECT 1
+            FROM moto_auto.client c
+            WHERE c.client_id = moto_auto.vehicle.client_id
+        )
+    );
+
+CREATE POLICY admin_vehicle_policy ON moto_auto.vehicle
+    FOR ALL TO admin USING (true);
+
+CREATE POLICY analyst_vehicle_policy ON moto_auto.vehicle
+    FOR SELECT TO analyst USING (true);
+
+GRANT SELECT, INSERT, UPDATE ON moto_auto.vehicle TO manager;
+GRANT SELECT ON moto_auto.vehicle TO master;
+
+COMMIT;
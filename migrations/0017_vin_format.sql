@@ -0,0 +1,29 @@
+BEGIN;
+
+-- VIN: 17 символов из цифр и заглавных латинских букв, кроме I, O и Q.
+-- Контрольную цифру проверяет приложение.
+-- Раньше VIN сохранялся как введён: сначала он приводится к заглавным без пробелов,
+-- а неверный или совпавший после этого с VIN другого действующего транспорта стирается.
+UPDATE moto_auto.vehicle
+SET vin = NULL
+WHERE UPPER(BTRIM(vin)) !~ '^[A-HJ-NPR-Z0-9]{17}$';
+
+UPDATE moto_auto.vehicle v
+SET vin = NULL
+WHERE EXISTS (
+    SELECT 1
+    FROM moto_auto.vehicle other
+    WHERE other.vehicle_id < v.vehicle_id
+    AND other.deleted_at IS NULL
+    AND UPPER(BTRIM(other.vin)) = UPPER(BTRIM(v.vin))
+)
+AND v.deleted_at IS NULL;
+
+UPDATE moto_auto.vehicle
+SET vin = UPPER(BTRIM(vin))
+WHERE vin <> UPPER(BTRIM(vin));
+
+ALTER TABLE moto_auto.vehicle
+    ADD CONSTRAINT vehicle_vin_format_check CHECK (vin ~ '^[A-HJ-NPR-Z0-9]{17}$');
+
+COMMIT;
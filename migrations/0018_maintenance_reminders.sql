@@ -0,0 +1,106 @@
+BEGIN;
+
+-- Рекомендуемая периодичность услуги: в месяцах и/или в километрах пробега.
+ALTER TABLE moto_auto.service
+    ADD COLUMN interval_months INTEGER CHECK (interval_months > 0),
+    ADD COLUMN interval_km INTEGER CHECK (interval_km > 0);
+
+-- Напоминание о плановом обслуживании. Отсчёт идёт от последнего выполненного заказа
+-- с этой услугой, поэтому на каждый такой заказ напоминание создаётся один раз.
+-- branch_id: филиал, где услуга выполнялась в последний раз.
+CREATE TABLE moto_auto.maintenance_reminder (
+    reminder_id SERIAL PRIMARY KEY,
+    client_id INTEGER NOT NULL REFERENCES moto_auto.client(client_id) ON DELETE CASCADE,
+    vehicle_id INTEGER NOT NULL REFERENCES moto_auto.vehicle(vehicle_id) ON DELETE CASCADE,
+    service_id INTEGER NOT NULL REFERENCES moto_auto.service(service_id) ON DELETE CASCADE,
+    last_order_id INTEGER NOT NULL REFERENCES moto_auto.orders(order_id) ON DELETE CASCADE,
+    branch_id INTEGER NOT NULL REFERENCES moto_auto.branch(branch_id) ON DELETE CASCADE,
+    due_date DATE,
+    due_mileage INTEGER,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    notified_at TIMESTAMPTZ,
+    dismissed_at TIMESTAMPTZ,
+    dismissed_by INTEGER,
+    UNIQUE (vehicle_id, service_id, last_order_id)
+);
+
+CREATE INDEX idx_maintenance_reminder_client_id ON moto_auto.maintenance_reminder(client_id);
+
+-- Создаёт напоминания по транспорту, которому подошёл срок или пробег для услуги,
+-- и возвращает созданные. Транспорт, уже стоящий в работе с этой услугой, пропускается.
+CREATE OR REPLACE FUNCTION create_maintenance_reminders()
+RETURNS SETOF moto_auto.maintenance_reminder
+SECURITY DEFINER
+AS $$
+    WITH last_service AS (
+        SELECT DISTINCT ON (o.vehicle_id, os.service_id)
+            o.vehicle_id, os.service_id, o.order_id, o.client_id, o.branch_id,
+            o.completion_date, o.intake_mileage
+        FROM moto_auto.orders o
+        INNER JOIN moto_auto.order_service os
+        ON os.order_id = o.order_id
+        WHERE o.status = 'finished'
+        AND o.deleted_at IS NULL
+        AND o.vehicle_id IS NOT NULL
+        ORDER BY o.vehicle_id, os.service_id, o.completion_date DESC, o.order_id DESC
+    ),
+    due AS (
+        SELECT
+            l.*,
+            (l.completion_date + make_interval(months => s.interval_months))::DATE AS due_date,
+            l.intake_mileage + s.interval_km AS due_mileage,
+            v.mileage
+        FROM last_service l
+        INNER JOIN moto_auto.service s
+        ON s.service_id = l.service_id
+        INNER JOIN moto_auto.vehicle v
+        ON v.vehicle_id = l.vehicle_id
+        WHERE s.deleted_at IS NULL
+        AND v.deleted_at IS NULL
+        AND v.client_id = l.client_id
+    )
+    INSERT INTO moto_auto.maintenance_reminder (
+        client_id, vehicle_id, service_id, last_order_id, branch_id, due_date, due_mileage
+    )
+    SELECT d.client_id, d.vehicle_id, d.service_id, d.order_id, d.branch_id, d.due_date, d.due_mileage
+    FROM due d
+    WHERE (d.due_date <= CURRENT_DATE OR d.mileage >= d.due_mileage)
+    AND NOT EXISTS (
+        SELECT 1
+        FROM moto_auto.orders o
+        INNER JOIN moto_auto.order_service os
+        ON os.order_id = o.order_id
+        WHERE o.vehicle_id = d.vehicle_id
+        AND os.service_id = d.service_id
+        AND o.status = 'processing'
+        AND o.deleted_at IS NULL
+    )
+    ON CONFLICT (vehicle_id, service_id, last_order_id) DO NOTHING
+    RETURNING *;
+$$ LANGUAGE sql;
+
+CREATE TRIGGER trigger_audit_maintenance_reminder
+AFTER INSERT OR UPDATE OR DELETE ON moto_auto.maintenance_reminder
+FOR EACH ROW EXECUTE FUNCTION audit_row_change('reminder_id');
+
+-- Напоминания видят те, кто видит клиента.
+ALTER TABLE moto_auto.maintenance_reminder ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY manager_maintenance_reminder_policy ON moto_auto.maintenance_reminder
+    FOR ALL TO manager USING (
+        EXISTS (
+            SELECT 1
+            FROM moto_auto.client c
+            WHERE c.client_id = moto_auto.maintenance_reminder.client_id
+        )
+    );
+
+CREATE POLICY admin_maintenance_reminder_policy ON moto_auto.maintenance_reminder
+    FOR ALL TO admin USING (true);
+
+CREATE POLICY analyst_maintenance_reminder_policy ON moto_auto.maintenance_reminder
+    FOR SELECT TO analyst USING (true);
+
+GRANT SELECT, UPDATE ON moto_auto.maintenance_reminder TO manager;
+
+COMMIT;
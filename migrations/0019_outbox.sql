@@ -0,0 +1,103 @@
+BEGIN;
+
+-- Очередь уведомлений клиентам. Строки добавляют триггеры по событиям, текст сообщения
+-- собирает и отправляет фоновый обработчик. Неудачная отправка повторяется с растущей
+-- паузой, после последней попытки сообщение помечается failed_at.
+CREATE TABLE moto_auto.outbox (
+    outbox_id SERIAL PRIMARY KEY,
+    kind VARCHAR(30) NOT NULL CHECK (kind IN ('booking_confirmed', 'order_status', 'maintenance_due')),
+    client_id INTEGER NOT NULL REFERENCES moto_auto.client(client_id) ON DELETE CASCADE,
+    order_id INTEGER REFERENCES moto_auto.orders(order_id) ON DELETE CASCADE,
+    schedule_id INTEGER REFERENCES moto_auto.schedule(schedule_id) ON DELETE CASCADE,
+    reminder_id INTEGER REFERENCES moto_auto.maintenance_reminder(reminder_id) ON DELETE CASCADE,
+    -- Статус заказа, о котором сообщаем: к отправке он может измениться снова.
+    order_status VARCHAR(20),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_error TEXT,
+    sent_at TIMESTAMPTZ,
+    failed_at TIMESTAMPTZ,
+    CHECK (
+        (kind = 'booking_confirmed' AND schedule_id IS NOT NULL)
+        OR (kind = 'order_status' AND order_id IS NOT NULL AND order_status IS NOT NULL)
+        OR (kind = 'maintenance_due' AND reminder_id IS NOT NULL)
+    )
+);
+
+CREATE INDEX idx_outbox_pending ON moto_auto.outbox(next_attempt_at)
+    WHERE sent_at IS NULL AND failed_at IS NULL;
+
+-- Клиенту сообщают о готовности, отмене и возврате заказа.
+CREATE OR REPLACE FUNCTION enqueue_order_notification()
+RETURNS TRIGGER
+SECURITY DEFINER
+AS $$
+BEGIN
+    INSERT INTO moto_auto.outbox (kind, client_id, order_id, order_status)
+    VALUES ('order_status', NEW.client_id, NEW.order_id, NEW.status);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_enqueue_order_notification
+AFTER UPDATE OF status ON moto_auto.orders
+FOR EACH ROW
+WHEN (OLD.status IS DISTINCT FROM NEW.status AND NEW.status IN ('finished', 'cancelled', 'refunded'))
+EXECUTE FUNCTION enqueue_order_notification();
+
+-- Клиенту подтверждают запись.
+CREATE OR REPLACE FUNCTION enqueue_booking_notification()
+RETURNS TRIGGER
+SECURITY DEFINER
+AS $$
+BEGIN
+    INSERT INTO moto_auto.outbox (kind, client_id, order_id, schedule_id)
+    VALUES ('booking_confirmed', NEW.client_id, NEW.order_id, NEW.schedule_id);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_enqueue_booking_notification_insert
+AFTER INSERT ON moto_auto.schedule
+FOR EACH ROW
+WHEN (NEW.status = 'confirmed')
+EXECUTE FUNCTION enqueue_booking_notification();
+
+CREATE TRIGGER trigger_enqueue_booking_notification_update
+AFTER UPDATE OF status ON moto_auto.schedule
+FOR EACH ROW
+WHEN (OLD.status IS DISTINCT FROM NEW.status AND NEW.status = 'confirmed')
+EXECUTE FUNCTION enqueue_booking_notification();
+
+-- Клиенту напоминают о плановом обслуживании.
+CREATE OR REPLACE FUNCTION enqueue_reminder_notification()
+RETURNS TRIGGER
+SECURITY DEFINER
+AS $$
+BEGIN
+    INSERT INTO moto_auto.outbox (kind, client_id, reminder_id)
+    VALUES ('maintenance_due', NEW.client_id, NEW.reminder_id);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_enqueue_reminder_notification
+AFTER INSERT ON moto_auto.maintenance_reminder
+FOR EACH ROW
+EXECUTE FUNCTION enqueue_reminder_notification();
+
+CREATE TRIGGER trigger_audit_outbox
+AFTER INSERT OR UPDATE OR DELETE ON moto_auto.outbox
+FOR EACH ROW EXECUTE FUNCTION audit_row_change('outbox_id');
+
+-- Очередь разбирает фоновый обработчик, пользователям она видна только для разбора сбоев.
+ALTER TABLE moto_auto.outbox ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY admin_outbox_policy ON moto_auto.outbox
+    FOR SELECT TO admin USING (true);
+
+CREATE POLICY analyst_outbox_policy ON moto_auto.outbox
+    FOR SELECT TO analyst USING (true);
+
+COMMIT;
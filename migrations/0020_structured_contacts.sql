@@ -0,0 +1,130 @@
+BEGIN;
+
+-- Контакты клиентов и сотрудников вместо одного текстового поля contact_info:
+-- телефоны в формате E.164, адрес почты в нижнем регистре, предпочитаемый канал и согласия.
+-- Номер без кода страны считается российским, как и в приложении.
+CREATE OR REPLACE FUNCTION normalize_phone(raw TEXT)
+RETURNS TEXT
+IMMUTABLE
+AS $$
+DECLARE
+    digits TEXT := regexp_replace(raw, '[\s().-]', '', 'g');
+BEGIN
+    IF digits ~ '^00' THEN
+        digits := '+' || substr(digits, 3);
+    ELSIF digits ~ '^8[0-9]{10}$' THEN
+        digits := '+7' || substr(digits, 2);
+    ELSIF digits ~ '^7[0-9]{10}$' THEN
+        digits := '+' || digits;
+    ELSIF digits ~ '^[0-9]{10}$' THEN
+        digits := '+7' || digits;
+    END IF;
+    IF digits ~ '^\+[1-9][0-9]{7,14}$' THEN
+        RETURN digits;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION normalize_email(raw TEXT)
+RETURNS TEXT
+IMMUTABLE
+AS $$
+    SELECT CASE
+        WHEN lower(btrim(raw)) ~ '^[^@\s]+@[^@\s.][^@\s]*\.[^@\s]*[^@\s.]$' THEN lower(btrim(raw))
+    END;
+$$ LANGUAGE sql;
+
+ALTER TABLE moto_auto.client
+    ADD COLUMN phones TEXT[] NOT NULL DEFAULT '{}'
+        CHECK (array_to_string(phones, ' ') ~ '^(\+[1-9][0-9]{7,14}( |$))*$'),
+    ADD COLUMN email VARCHAR(254) CHECK (email = normalize_email(email)),
+    ADD COLUMN preferred_channel VARCHAR(10) CHECK (preferred_channel IN ('email', 'sms')),
+    -- Сообщения о записи и заказах.
+    ADD COLUMN service_consent BOOLEAN NOT NULL DEFAULT TRUE,
+    -- Напоминания о плановом обслуживании и прочие предложения: только с явного согласия.
+    ADD COLUMN marketing_consent BOOLEAN NOT NULL DEFAULT FALSE,
+    -- То, что не удалось разобрать из старого contact_info.
+    ADD COLUMN contact_note TEXT;
+
+ALTER TABLE moto_auto.employee
+    ADD COLUMN phones TEXT[] NOT NULL DEFAULT '{}'
+        CHECK (array_to_string(phones, ' ') ~ '^(\+[1-9][0-9]{7,14}( |$))*$'),
+    ADD COLUMN email VARCHAR(254) CHECK (email = normalize_email(email)),
+    ADD COLUMN contact_note TEXT;
+
+-- Старые значения делятся по запятым и точкам с запятой: первый верный адрес идёт в email,
+-- номера в phones, остальное в contact_note.
+CREATE TEMPORARY TABLE parsed_contact ON COMMIT DROP AS
+SELECT
+    'client' AS owner,
+    client_id AS owner_id,
+    ordinality,
+    btrim(part) AS part,
+    normalize_email(part) AS email,
+    CASE WHEN part NOT LIKE '%@%' THEN normalize_phone(part) END AS phone
+FROM moto_auto.client,
+    regexp_split_to_table(contact_info, '[,;]') WITH ORDINALITY AS part
+UNION ALL
+SELECT
+    'employee',
+    employee_id,
+    ordinality,
+    btrim(part),
+    normalize_email(part),
+    CASE WHEN part NOT LIKE '%@%' THEN normalize_phone(part) END
+FROM moto_auto.employee,
+    regexp_split_to_table(contact_info, '[,;]') WITH ORDINALITY AS part;
+
+DELETE FROM parsed_contact WHERE part = '';
+
+-- Адрес, который не попал в email, остаётся в заметке.
+UPDATE parsed_contact p
+SET email = NULL
+WHERE email IS NOT NULL
+AND EXISTS (
+    SELECT 1
+    FROM parsed_contact earlier
+    WHERE earlier.owner = p.owner
+    AND earlier.owner_id = p.owner_id
+    AND earlier.email IS NOT NULL
+    AND earlier.ordinality < p.ordinality
+);
+
+-- Телефоны без повторов в порядке записи: первый остаётся основным.
+CREATE TEMPORARY TABLE merged_contact ON COMMIT DROP AS
+SELECT
+    p.owner,
+    p.owner_id,
+    COALESCE((
+        SELECT array_agg(phone ORDER BY first_seen)
+        FROM (
+            SELECT same.phone, min(same.ordinality) AS first_seen
+            FROM parsed_contact same
+            WHERE same.owner = p.owner
+            AND same.owner_id = p.owner_id
+            AND same.phone IS NOT NULL
+            GROUP BY same.phone
+        ) distinct_phone
+    ), '{}') AS phones,
+    max(p.email) AS email,
+    string_agg(p.part, ', ' ORDER BY p.ordinality) FILTER (WHERE p.email IS NULL AND p.phone IS NULL) AS note
+FROM parsed_contact p
+GROUP BY p.owner, p.owner_id;
+
+UPDATE moto_auto.client c
+SET phones = m.phones, email = m.email, contact_note = m.note
+FROM merged_contact m
+WHERE m.owner = 'client' AND m.owner_id = c.client_id;
+
+UPDATE moto_auto.employee e
+SET phones = m.phones, email = m.email, contact_note = m.note
+FROM merged_contact m
+WHERE m.owner = 'employee' AND m.owner_id = e.employee_id;
+
+ALTER TABLE moto_auto.client DROP COLUMN contact_info;
+ALTER TABLE moto_auto.employee DROP COLUMN contact_info;
+
+CREATE INDEX idx_client_email ON moto_auto.client(email);
+
+COMMIT;
@@ -0,0 +1,155 @@
+BEGIN;
+
+-- Поиск по клиентам, заказам, мотоциклам, запчастям и услугам: полнотекстовый по словам
+-- и нечёткий через pg_trgm по частям слов и опечаткам. Функция выполняется с правами
+-- вызывающего, так что каждая роль находит только то, что ей видно по политикам RLS.
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- array_to_string не IMMUTABLE, а для индекса по телефонам нужна неизменяемая функция.
+CREATE OR REPLACE FUNCTION phones_text(phones TEXT[])
+RETURNS TEXT
+IMMUTABLE
+AS $$
+    SELECT array_to_string(phones, ' ');
+$$ LANGUAGE sql;
+
+CREATE INDEX idx_client_name_trgm ON moto_auto.client USING GIN (name gin_trgm_ops);
+CREATE INDEX idx_client_email_trgm ON moto_auto.client USING GIN (email gin_trgm_ops);
+CREATE INDEX idx_client_phones_trgm ON moto_auto.client USING GIN (phones_text(phones) gin_trgm_ops);
+CREATE INDEX idx_client_search ON moto_auto.client
+    USING GIN (to_tsvector('simple', name || ' ' || COALESCE(email, '') || ' ' || COALESCE(contact_note, '')));
+
+CREATE INDEX idx_vehicle_name_trgm ON moto_auto.vehicle USING GIN ((make || ' ' || model) gin_trgm_ops);
+CREATE INDEX idx_vehicle_plate_trgm ON moto_auto.vehicle USING GIN (plate gin_trgm_ops);
+CREATE INDEX idx_vehicle_vin_trgm ON moto_auto.vehicle USING GIN (vin gin_trgm_ops);
+
+CREATE INDEX idx_spare_part_name_trgm ON moto_auto.spare_part USING GIN (part_name gin_trgm_ops);
+CREATE INDEX idx_spare_part_search ON moto_auto.spare_part
+    USING GIN (to_tsvector('simple', part_name || ' ' || description));
+
+CREATE INDEX idx_service_name_trgm ON moto_auto.service USING GIN (service_name gin_trgm_ops);
+CREATE INDEX idx_service_search ON moto_auto.service
+    USING GIN (to_tsvector('simple', service_name || ' ' || description));
+
+-- Ранг от 0 до 1: совпадение с началом названия выше всего, затем сходство по триграммам
+-- и полнотекстовый ранг. Номер заказа ищется, только если запрос состоит из цифр.
+CREATE OR REPLACE FUNCTION search(search_query TEXT, max_results INTEGER)
+RETURNS TABLE (kind TEXT, id INTEGER, title TEXT, subtitle TEXT, rank REAL)
+STABLE
+AS $$
+    WITH raw AS (
+        SELECT
+            btrim(search_query) AS text,
+            plainto_tsquery('simple', search_query) AS words,
+            regexp_replace(search_query, '\D', '', 'g') AS digits,
+            -- Знаки % и _ в запросе ищутся как есть.
+            regexp_replace(btrim(search_query), '([\\%_])', '\\\1', 'g') AS escaped
+    ), q AS (
+        SELECT raw.*, raw.escaped || '%' AS prefix, '%' || raw.escaped || '%' AS pattern
+        FROM raw
+    )
+    SELECT found.kind, found.id, found.title, found.subtitle, found.rank
+    FROM (
+        SELECT
+            'client' AS kind,
+            c.client_id AS id,
+            c.name::TEXT AS title,
+            NULLIF(concat_ws(', ', c.phones[1], c.email), '') AS subtitle,
+            GREATEST(
+                CASE WHEN c.name ILIKE q.prefix THEN 1 ELSE 0 END,
+                CASE WHEN length(q.digits) >= 3 AND phones_text(c.phones) LIKE '%' || q.digits || '%' THEN 0.9 ELSE 0 END,
+                CASE WHEN c.email ILIKE q.pattern THEN 0.8 ELSE 0 END,
+                word_similarity(q.text, c.name),
+                ts_rank(to_tsvector('simple', c.name || ' ' || COALESCE(c.email, '') || ' ' || COALESCE(c.contact_note, '')), q.words)
+            )::REAL AS rank
+        FROM moto_auto.client c, q
+        WHERE c.deleted_at IS NULL
+        AND (
+            to_tsvector('simple', c.name || ' ' || COALESCE(c.email, '') || ' ' || COALESCE(c.contact_note, '')) @@ q.words
+            OR q.text <% c.name
+            OR c.name ILIKE q.pattern
+            OR c.email ILIKE q.pattern
+            OR (length(q.digits) >= 3 AND phones_text(c.phones) LIKE '%' || q.digits || '%')
+        )
+
+        UNION ALL
+
+        SELECT
+            'order',
+            o.order_id,
+            'Order #' || o.order_id,
+            concat_ws(', ', c.name, o.status),
+            CASE WHEN o.order_id::TEXT = q.text THEN 1 ELSE 0.5 END::REAL
+        FROM moto_auto.orders o
+        INNER JOIN moto_auto.client c
+        ON c.client_id = o.client_id, q
+        WHERE o.deleted_at IS NULL
+        AND ltrim(q.text, '#') ~ '^[0-9]+$'
+        AND o.order_id::TEXT LIKE ltrim(q.text, '#') || '%'
+
+        UNION ALL
+
+        SELECT
+            'vehicle',
+            v.vehicle_id,
+            v.make || ' ' || v.model,
+            NULLIF(concat_ws(', ', v.plate, v.vin), ''),
+            GREATEST(
+                CASE WHEN lower(v.plate) = lower(q.text) OR v.vin = upper(q.text) THEN 1 ELSE 0 END,
+                CASE WHEN v.plate ILIKE q.pattern OR v.vin ILIKE q.pattern THEN 0.8 ELSE 0 END,
+                word_similarity(q.text, v.make || ' ' || v.model)
+            )::REAL
+        FROM moto_auto.vehicle v, q
+        WHERE v.deleted_at IS NULL
+        AND (
+            q.text <% (v.make || ' ' || v.model)
+            OR (v.make || ' ' || v.model) ILIKE q.pattern
+            OR v.plate ILIKE q.pattern
+            OR v.vin ILIKE q.pattern
+        )
+
+        UNION ALL
+
+        SELECT
+            'part',
+            p.part_id,
+            p.part_name::TEXT,
+            NULLIF(p.description, ''),
+            GREATEST(
+                CASE WHEN p.part_name ILIKE q.prefix THEN 1 ELSE 0 END,
+                word_similarity(q.text, p.part_name),
+                ts_rank(to_tsvector('simple', p.part_name || ' ' || p.description), q.words)
+            )::REAL
+        FROM moto_auto.spare_part p, q
+        WHERE p.deleted_at IS NULL
+        AND (
+            to_tsvector('simple', p.part_name || ' ' || p.description) @@ q.words
+            OR q.text <% p.part_name
+            OR p.part_name ILIKE q.pattern
+        )
+
+        UNION ALL
+
+        SELECT
+            'service',
+            s.service_id,
+            s.service_name::TEXT,
+            NULLIF(s.description, ''),
+            GREATEST(
+                CASE WHEN s.service_name ILIKE q.prefix THEN 1 ELSE 0 END,
+                word_similarity(q.text, s.service_name),
+                ts_rank(to_tsvector('simple', s.service_name || ' ' || s.description), q.words)
+            )::REAL
+        FROM moto_auto.service s, q
+        WHERE s.deleted_at IS NULL
+        AND (
+            to_tsvector('simple', s.service_name || ' ' || s.description) @@ q.words
+            OR q.text <% s.service_name
+            OR s.service_name ILIKE q.pattern
+        )
+    ) found
+    ORDER BY found.rank DESC, found.kind, found.id
+    LIMIT max_results;
+$$ LANGUAGE sql;
+
+COMMIT;
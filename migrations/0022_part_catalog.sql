@@ -0,0 +1,309 @@
+BEGIN;
+
+-- Каталог запчастей: артикул, номер по каталогу производителя мотоцикла (OEM),
+-- производитель запчасти и единица измерения.
+ALTER TABLE moto_auto.spare_part
+    ADD COLUMN sku VARCHAR(50) UNIQUE,
+    ADD COLUMN oem_number VARCHAR(50),
+    ADD COLUMN manufacturer VARCHAR(100),
+    ADD COLUMN unit VARCHAR(10) NOT NULL DEFAULT 'pcs'
+        CHECK (unit IN ('pcs', 'set', 'pair', 'l', 'ml', 'kg', 'g', 'm'));
+
+-- Номера пишут по-разному: 06455-MEL-D21, 06455MELD21, 06455 mel d21. Сравниваются
+-- только буквы и цифры без учёта регистра.
+CREATE OR REPLACE FUNCTION part_number_key(raw TEXT)
+RETURNS TEXT
+IMMUTABLE
+AS $$
+    SELECT upper(regexp_replace(raw, '[^[:alnum:]]', '', 'g'));
+$$ LANGUAGE sql;
+
+CREATE INDEX idx_spare_part_sku_trgm ON moto_auto.spare_part USING GIN (part_number_key(sku) gin_trgm_ops);
+CREATE INDEX idx_spare_part_oem_trgm ON moto_auto.spare_part USING GIN (part_number_key(oem_number) gin_trgm_ops);
+
+-- Применимость: марка, модель (NULL - любая модель марки) и годы выпуска (NULL - без ограничения).
+CREATE TABLE moto_auto.part_compatibility (
+    compatibility_id SERIAL PRIMARY KEY,
+    part_id INTEGER NOT NULL REFERENCES moto_auto.spare_part(part_id) ON DELETE CASCADE,
+    make VARCHAR(50) NOT NULL,
+    model VARCHAR(50),
+    year_from INTEGER,
+    year_to INTEGER,
+    CHECK (year_from <= year_to),
+    UNIQUE NULLS NOT DISTINCT (part_id, make, model, year_from, year_to)
+);
+
+CREATE INDEX idx_part_compatibility_part ON moto_auto.part_compatibility(part_id);
+CREATE INDEX idx_part_compatibility_make ON moto_auto.part_compatibility(lower(make), lower(model));
+
+-- Взаимозаменяемые запчасти. Пара хранится один раз, меньший номер первым.
+CREATE TABLE moto_auto.part_substitute (
+    part_id INTEGER NOT NULL REFERENCES moto_auto.spare_part(part_id) ON DELETE CASCADE,
+    substitute_id INTEGER NOT NULL REFERENCES moto_auto.spare_part(part_id) ON DELETE CASCADE,
+    PRIMARY KEY (part_id, substitute_id),
+    CHECK (part_id < substitute_id)
+);
+
+CREATE INDEX idx_part_substitute_substitute ON moto_auto.part_substitute(substitute_id);
+
+CREATE TRIGGER trigger_audit_part_compatibility
+AFTER INSERT OR UPDATE OR DELETE ON moto_auto.part_compatibility
+FOR EACH ROW EXECUTE FUNCTION audit_row_change('compatibility_id');
+
+CREATE TRIGGER trigger_audit_part_substitute
+AFTER INSERT OR UPDATE OR DELETE ON moto_auto.part_substitute
+FOR EACH ROW EXECUTE FUNCTION audit_row_change('part_id');
+
+-- Каталог общий для всех филиалов, его ведут администраторы.
+GRANT SELECT ON moto_auto.part_compatibility, moto_auto.part_substitute TO manager, master, analyst;
+
+-- Запчасти теперь находятся и по артикулу, и по номеру производителя, и по бренду.
+CREATE OR REPLACE FUNCTION search(search_query TEXT, max_results INTEGER)
+RETURNS TABLE (kind TEXT, id INTEGER, title TEXT, subtitle TEXT, rank REAL)
+STABLE
+AS $$
+    WITH raw AS (
+        SELECT
+            btrim(search_query) AS text,
+            plainto_tsquery('simple', search_query) AS words,
+            regexp_replace(search_query, '\D', '', 'g') AS digits,
+            -- Знаки % и _ в запросе ищутся как есть.
+            regexp_replace(btrim(search_query), '([\\%_])', '\\\1', 'g') AS escaped
+    ), q AS (
+        SELECT
+            raw.*,
+            raw.escaped || '%' AS prefix,
+            '%' || raw.escaped || '%' AS pattern,
+            part_number_key(raw.text) AS key
+        FROM raw
+    )
+    SELECT found.kind, found.id, found.title, found.subtitle, found.rank
+    FROM (
+        SELECT
+            'client' AS kind,
+            c.client_id AS id,
+            c.name::TEXT AS title,
+            NULLIF(concat_ws(', ', c.phones[1], c.email), '') AS subtitle,
+            GREATEST(
+                CASE WHEN c.name ILIKE q.prefix THEN 1 ELSE 0 END,
+                CASE WHEN length(q.digits) >= 3 AND phones_text(c.phones) LIKE '%' || q.digits || '%' THEN 0.9 ELSE 0 END,
+                CASE WHEN c.email ILIKE q.pattern THEN 0.8 ELSE 0 END,
+                word_similarity(q.text, c.name),
+                ts_rank(to_tsvector('simple', c.name || ' ' || COALESCE(c.email, '') || ' ' || COALESCE(c.contact_note, '')), q.words)
+            )::REAL AS rank
+        FROM moto_auto.client c, q
+        WHERE c.deleted_at IS NULL
+        AND (
+            to_tsvector('simple', c.name || ' ' || COALESCE(c.email, '') || ' ' || COALESCE(c.contact_note, '')) @@ q.words
+            OR q.text <% c.name
+            OR c.name ILIKE q.pattern
+            OR c.email ILIKE q.pattern
+            OR (length(q.digits) >= 3 AND phones_text(c.phones) LIKE '%' || q.digits || '%')
+        )
+
+        UNION ALL
+
+        SELECT
+            'order',
+            o.order_id,
+            'Order #' || o.order_id,
+            concat_ws(', ', c.name, o.status),
+            CASE WHEN o.order_id::TEXT = q.text THEN 1 ELSE 0.5 END::REAL
+        FROM moto_auto.orders o
+        INNER JOIN moto_auto.client c
+        ON c.client_id = o.client_id, q
+        WHERE o.deleted_at IS NULL
+        AND ltrim(q.text, '#') ~ '^[0-9]+$'
+        AND o.order_id::TEXT LIKE ltrim(q.text, '#') || '%'
+
+        UNION ALL
+
+        SELECT
+            'vehicle',
+            v.vehicle_id,
+            v.make || ' ' || v.model,
+            NULLIF(concat_ws(', ', v.plate, v.vin), ''),
+            GREATEST(
+                CASE WHEN lower(v.plate) = lower(q.text) OR v.vin = upper(q.text) THEN 1 ELSE 0 END,
+                CASE WHEN v.plate ILIKE q.pattern OR v.vin ILIKE q.pattern THEN 0.8 ELSE 0 END,
+                word_similarity(q.text, v.make || ' ' || v.model)
+            )::REAL
+        FROM moto_auto.vehicle v, q
+        WHERE v.deleted_at IS NULL
+        AND (
+            q.text <% (v.make || ' ' || v.model)
+            OR (v.make || ' ' || v.model) ILIKE q.pattern
+            OR v.plate ILIKE q.pattern
+            OR v.vin ILIKE q.pattern
+        )
+
+        UNION ALL
+
+        SELECT
+            'part',
+            p.part_id,
+            p.part_name::TEXT,
+            NULLIF(concat_ws(', ', p.sku, p.oem_number, p.manufacturer), ''),
+            GREATEST(
+                CASE WHEN length(q.key) >= 3 AND q.key IN (part_number_key(p.sku), part_number_key(p.oem_number)) THEN 1 ELSE 0 END,
+                CASE WHEN p.part_name ILIKE q.prefix THEN 1 ELSE 0 END,
+                CASE WHEN length(q.key) >= 3 AND (part_number_key(p.sku) LIKE '%' || q.key || '%'
+                    OR part_number_key(p.oem_number) LIKE '%' || q.key || '%') THEN 0.8 ELSE 0 END,
+                word_similarity(q.text, p.part_name),
+                ts_rank(to_tsvector('simple', p.part_name || ' ' || p.description || ' ' || COALESCE(p.manufacturer, '')), q.words)
+            )::REAL
+        FROM moto_auto.spare_part p, q
+        WHERE p.deleted_at IS NULL
+        AND (
+            to_tsvector('simple', p.part_name || ' ' || p.description || ' ' || COALESCE(p.manufacturer, '')) @@ q.words
+            OR q.text <% p.part_name
+            OR p.part_name ILIKE q.pattern
+            OR (length(q.key) >= 3 AND (part_number_key(p.sku) LIKE '%' || q.key || '%'
+                OR part_number_key(p.oem_number) LIKE '%' || q.key || '%'))
+        )
+
+        UNION ALL
+
+        SELECT
+            'service',
+            s.service_id,
+            s.service_name::TEXT,
+            NULLIF(s.description, ''),
+            GREATEST(
+                CASE WHEN s.service_name ILIKE q.prefix THEN 1 ELSE 0 END,
+                word_similarity(q.text, s.service_name),
+                ts_rank(to_tsvector('simple', s.service_name || ' ' || s.description), q.words)
+            )::REAL
+        FROM moto_auto.service s, q
+        WHERE s.deleted_at IS NULL
+        AND (
+            to_tsvector('simple', s.service_name || ' ' || s.description) @@ q.words
+            OR q.text <% s.service_name
+            OR s.service_name ILIKE q.pattern
+        )
+    ) found
+    ORDER BY found.rank DESC, found.kind, found.id
+    LIMIT max_results;
+$$ LANGUAGE sql;
+
+-- Подбор запчастей для заказа. Находит запчасти по названию, артикулу, OEM-номеру или
+-- производителю и добавляет их заменители. fits показывает, подходит ли запчасть к мотоциклу:
+-- NULL, если мотоцикл не указан или применимость запчасти не заполнена. Если год выпуска
+-- мотоцикла неизвестен, годы не проверяются. Подходящие запчасти идут первыми,
+-- цена и остаток берутся в указанном филиале.
+CREATE OR REPLACE FUNCTION find_parts(
+    search_query TEXT,
+    for_vehicle INTEGER,
+    for_branch INTEGER,
+    max_results INTEGER
+)
+RETURNS TABLE (
+    part_id INTEGER,
+    part_name TEXT,
+    sku TEXT,
+    oem_number TEXT,
+    manufacturer TEXT,
+    unit TEXT,
+    price NUMERIC,
+    stock_quantity INTEGER,
+    fits BOOLEAN,
+    substitute_for INTEGER,
+    rank REAL
+)
+STABLE
+AS $$
+    WITH raw AS (
+        SELECT
+            btrim(search_query) AS text,
+            plainto_tsquery('simple', search_query) AS words,
+            regexp_replace(btrim(search_query), '([\\%_])', '\\\1', 'g') AS escaped
+    ), q AS (
+        SELECT
+            raw.*,
+            raw.escaped || '%' AS prefix,
+            '%' || raw.escaped || '%' AS pattern,
+            part_number_key(raw.text) AS key
+        FROM raw
+    ), bike AS (
+        SELECT v.make, v.model, v.year
+        FROM moto_auto.vehicle v
+        WHERE v.vehicle_id = for_vehicle
+    ), matched AS (
+        SELECT
+            p.part_id,
+            GREATEST(
+                CASE WHEN length(q.key) >= 3 AND q.key IN (part_number_key(p.sku), part_number_key(p.oem_number)) THEN 1 ELSE 0 END,
+                CASE WHEN p.part_name ILIKE q.prefix THEN 0.9 ELSE 0 END,
+                CASE WHEN length(q.key) >= 3 AND (part_number_key(p.sku) LIKE '%' || q.key || '%'
+                    OR part_number_key(p.oem_number) LIKE '%' || q.key || '%') THEN 0.8 ELSE 0 END,
+                CASE WHEN p.manufacturer ILIKE q.prefix THEN 0.6 ELSE 0 END,
+                word_similarity(q.text, p.part_name),
+                ts_rank(to_tsvector('simple', p.part_name || ' ' || p.description || ' ' || COALESCE(p.manufacturer, '')), q.words)
+            )::REAL AS rank
+        FROM moto_auto.spare_part p, q
+        WHERE p.deleted_at IS NULL
+        AND (
+            to_tsvector('simple', p.part_name || ' ' || p.description || ' ' || COALESCE(p.manufacturer, '')) @@ q.words
+            OR q.text <% p.part_name
+            OR p.part_name ILIKE q.pattern
+            OR p.manufacturer ILIKE q.pattern
+            OR (length(q.key) >= 3 AND (part_number_key(p.sku) LIKE '%' || q.key || '%'
+                OR part_number_key(p.oem_number) LIKE '%' || q.key || '%'))
+        )
+    ), candidate AS (
+        SELECT m.part_id, NULL::INTEGER AS substitute_for, m.rank
+        FROM matched m
+        UNION ALL
+        -- Заменитель, который нашёлся и сам по себе, второй раз не выводится.
+        (
+            SELECT DISTINCT ON (other.part_id) other.part_id, m.part_id, (m.rank * 0.9)::REAL
+            FROM matched m
+            INNER JOIN moto_auto.part_substitute s
+            ON m.part_id IN (s.part_id, s.substitute_id)
+            CROSS JOIN LATERAL (
+                SELECT CASE WHEN s.part_id = m.part_id THEN s.substitute_id ELSE s.part_id END AS part_id
+            ) other
+            WHERE other.part_id NOT IN (SELECT matched.part_id FROM matched)
+            ORDER BY other.part_id, m.rank DESC
+        )
+    )
+    SELECT found.*
+    FROM (
+        SELECT
+            p.part_id,
+            p.part_name::TEXT,
+            p.sku::TEXT,
+            p.oem_number::TEXT,
+            p.manufacturer::TEXT,
+            p.unit::TEXT,
+            spb.price,
+            spb.stock_quantity,
+            CASE
+                WHEN NOT EXISTS (SELECT 1 FROM bike) THEN NULL
+                WHEN NOT EXISTS (
+                    SELECT 1 FROM moto_auto.part_compatibility pc WHERE pc.part_id = p.part_id
+                ) THEN NULL
+                ELSE EXISTS (
+                    SELECT 1
+                    FROM moto_auto.part_compatibility pc, bike b
+                    WHERE pc.part_id = p.part_id
+                    AND lower(pc.make) = lower(b.make)
+                    AND (pc.model IS NULL OR lower(pc.model) = lower(b.model))
+                    AND (b.year IS NULL OR b.year >= COALESCE(pc.year_from, b.year))
+                    AND (b.year IS NULL OR b.year <= COALESCE(pc.year_to, b.year))
+                )
+            END AS fits,
+            c.substitute_for,
+            c.rank
+        FROM candidate c
+        INNER JOIN moto_auto.spare_part p
+        ON p.part_id = c.part_id
+        LEFT JOIN moto_auto.spare_part_branch spb
+        ON spb.part_id = p.part_id AND spb.branch_id = for_branch
+        WHERE p.deleted_at IS NULL
+    ) found
+    ORDER BY found.fits IS TRUE DESC, found.fits IS NULL DESC, found.rank DESC, found.part_id
+    LIMIT max_results;
+$$ LANGUAGE sql;
+
+COMMIT;
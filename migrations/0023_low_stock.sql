@@ -0,0 +1,252 @@
+BEGIN;
+
+-- Точка заказа и размер заказа запчасти в филиале. Без точки заказа остаток не отслеживается.
+ALTER TABLE moto_auto.spare_part_branch
+    ADD COLUMN reorder_point INTEGER CHECK (reorder_point >= 0),
+    ADD COLUMN reorder_quantity INTEGER CHECK (reorder_quantity > 0),
+    ADD CONSTRAINT spare_part_branch_reorder_check
+        CHECK ((reorder_point IS NULL) = (reorder_quantity IS NULL));
+
+-- Остаток опустился ниже точки заказа. Открытое оповещение по позиции одно, оно закрывается,
+-- когда остаток восстановлен или точка заказа снята.
+CREATE TABLE moto_auto.low_stock_alert (
+    alert_id SERIAL PRIMARY KEY,
+    spare_part_branch_id INTEGER NOT NULL
+        REFERENCES moto_auto.spare_part_branch(spare_part_branch_id) ON DELETE CASCADE,
+    part_id INTEGER NOT NULL REFERENCES moto_auto.spare_part(part_id) ON DELETE CASCADE,
+    branch_id INTEGER NOT NULL REFERENCES moto_auto.branch(branch_id) ON DELETE CASCADE,
+    stock_quantity INTEGER NOT NULL,
+    reorder_point INTEGER NOT NULL,
+    detected_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    resolved_at TIMESTAMPTZ
+);
+
+CREATE UNIQUE INDEX idx_low_stock_alert_open ON moto_auto.low_stock_alert(spare_part_branch_id)
+    WHERE resolved_at IS NULL;
+CREATE INDEX idx_low_stock_alert_branch ON moto_auto.low_stock_alert(branch_id);
+
+-- Заказ поставщику. Черновик в филиале один, в него добавляются позиции с низким остатком;
+-- менеджер правит его и отправляет, а при получении количество добавляется к остатку.
+CREATE TABLE moto_auto.purchase_order (
+    purchase_order_id SERIAL PRIMARY KEY,
+    branch_id INTEGER NOT NULL REFERENCES moto_auto.branch(branch_id) ON DELETE RESTRICT,
+    status VARCHAR(20) NOT NULL DEFAULT 'draft'
+        CHECK (status IN ('draft', 'ordered', 'received', 'cancelled')),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    ordered_at TIMESTAMPTZ,
+    received_at TIMESTAMPTZ,
+    updated_by INTEGER
+);
+
+CREATE UNIQUE INDEX idx_purchase_order_draft ON moto_auto.purchase_order(branch_id)
+    WHERE status = 'draft';
+
+CREATE TABLE moto_auto.purchase_order_line (
+    purchase_order_line_id SERIAL PRIMARY KEY,
+    purchase_order_id INTEGER NOT NULL
+        REFERENCES moto_auto.purchase_order(purchase_order_id) ON DELETE CASCADE,
+    part_id INTEGER NOT NULL REFERENCES moto_auto.spare_part(part_id),
+    quantity INTEGER NOT NULL CHECK (quantity > 0),
+    UNIQUE (purchase_order_id, part_id)
+);
+
+-- Черновик можно отправить или отменить, отправленный заказ - получить или отменить.
+-- Полученный заказ пополняет остатки филиала.
+CREATE OR REPLACE FUNCTION purchase_order_status_change()
+RETURNS TRIGGER
+SECURITY DEFINER
+AS $$
+BEGIN
+    IF NEW.status = OLD.status THEN
+        RETURN NEW;
+    END IF;
+    IF NOT (
+        (OLD.status = 'draft' AND NEW.status IN ('ordered', 'cancelled'))
+        OR (OLD.status = 'ordered' AND NEW.status IN ('received', 'cancelled'))
+    ) THEN
+        RAISE EXCEPTION 'Заказ поставщику % нельзя перевести из % в %',
+            OLD.purchase_order_id, OLD.status, NEW.status;
+    END IF;
+    IF NEW.status = 'ordered' THEN
+        IF NOT EXISTS (
+            SELECT 1 FROM moto_auto.purchase_order_line
+            WHERE purchase_order_id = NEW.purchase_order_id
+        ) THEN
+            RAISE EXCEPTION 'В заказе поставщику % нет строк', NEW.purchase_order_id;
+        END IF;
+        NEW.ordered_at := NOW();
+    ELSIF NEW.status = 'received' THEN
+        NEW.received_at := NOW();
+        UPDATE moto_auto.spare_part_branch spb
+        SET stock_quantity = spb.stock_quantity + l.quantity
+        FROM moto_auto.purchase_order_line l
+        WHERE l.purchase_order_id = NEW.purchase_order_id
+        AND spb.part_id = l.part_id
+        AND spb.branch_id = NEW.branch_id;
+    END IF;
+    NEW.updated_by := NULLIF(current_setting('moto_auto.actor', true), '')::INTEGER;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_purchase_order_status_change
+BEFORE UPDATE OF status ON moto_auto.purchase_order
+FOR EACH ROW EXECUTE FUNCTION purchase_order_status_change();
+
+-- Позиции с остатком ниже точки заказа. Запчасти из архива не дозаказываются.
+CREATE OR REPLACE FUNCTION low_stock_parts()
+RETURNS SETOF moto_auto.spare_part_branch
+STABLE
+AS $$
+    SELECT spb.*
+    FROM moto_auto.spare_part_branch spb
+    INNER JOIN moto_auto.spare_part p
+    ON p.part_id = spb.part_id
+    WHERE spb.stock_quantity < spb.reorder_point
+    AND p.deleted_at IS NULL;
+$$ LANGUAGE sql;
+
+-- Закрывает оповещения по восстановленным остаткам, открывает новые и возвращает их.
+-- С draft_orders позиции с низким остатком, которых нет в черновике или отправленном заказе
+-- филиала, добавляются в черновик на размер заказа.
+CREATE OR REPLACE FUNCTION detect_low_stock(draft_orders BOOLEAN)
+RETURNS SETOF moto_auto.low_stock_alert
+SECURITY DEFINER
+AS $$
+BEGIN
+    UPDATE moto_auto.low_stock_alert a
+    SET resolved_at = NOW()
+    FROM moto_auto.spare_part_branch spb
+    WHERE spb.spare_part_branch_id = a.spare_part_branch_id
+    AND a.resolved_at IS NULL
+    AND (spb.reorder_point IS NULL OR spb.stock_quantity >= spb.reorder_point);
+
+    IF draft_orders THEN
+        INSERT INTO moto_auto.purchase_order (branch_id)
+        SELECT DISTINCT l.branch_id
+        FROM low_stock_parts() l
+        WHERE NOT EXISTS (
+            SELECT 1
+            FROM moto_auto.purchase_order po
+            INNER JOIN moto_auto.purchase_order_line pol
+            ON pol.purchase_order_id = po.purchase_order_id
+            WHERE po.branch_id = l.branch_id
+            AND pol.part_id = l.part_id
+            AND po.status IN ('draft', 'ordered')
+        )
+        ON CONFLICT (branch_id) WHERE status = 'draft' DO NOTHING;
+
+        INSERT INTO moto_auto.purchase_order_line (purchase_order_id, part_id, quantity)
+        SELECT draft.purchase_order_id, l.part_id, l.reorder_quantity
+        FROM low_stock_parts() l
+        INNER JOIN moto_auto.purchase_order draft
+        ON draft.branch_id = l.branch_id AND draft.status = 'draft'
+        WHERE NOT EXISTS (
+            SELECT 1
+            FROM moto_auto.purchase_order po
+            INNER JOIN moto_auto.purchase_order_line pol
+            ON pol.purchase_order_id = po.purchase_order_id
+            WHERE po.branch_id = l.branch_id
+            AND pol.part_id = l.part_id
+            AND po.status IN ('draft', 'ordered')
+        );
+    END IF;
+
+    RETURN QUERY
+    INSERT INTO moto_auto.low_stock_alert (
+        spare_part_branch_id, part_id, branch_id, stock_quantity, reorder_point
+    )
+    SELECT l.spare_part_branch_id, l.part_id, l.branch_id, l.stock_quantity, l.reorder_point
+    FROM low_stock_parts() l
+    ON CONFLICT (spare_part_branch_id) WHERE resolved_at IS NULL DO NOTHING
+    RETURNING *;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Точку заказа меняют только в своём филиале, и запчасть должна в нём продаваться.
+CREATE OR REPLACE FUNCTION set_reorder_level(
+    reorder_branch_id INTEGER,
+    reorder_part_id INTEGER,
+    new_reorder_point INTEGER,
+    new_reorder_quantity INTEGER
+)
+RETURNS SETOF moto_auto.spare_part_branch
+SECURITY DEFINER
+AS $$
+    UPDATE moto_auto.spare_part_branch
+    SET reorder_point = new_reorder_point, reorder_quantity = new_reorder_quantity
+    WHERE part_id = reorder_part_id
+    AND branch_id = reorder_branch_id
+    AND reorder_branch_id = current_app_branch_id()
+    RETURNING *;
+$$ LANGUAGE sql;
+
+CREATE TRIGGER trigger_audit_low_stock_alert
+AFTER INSERT OR UPDATE OR DELETE ON moto_auto.low_stock_alert
+FOR EACH ROW EXECUTE FUNCTION audit_row_change('alert_id');
+
+CREATE TRIGGER trigger_audit_purchase_order
+AFTER INSERT OR UPDATE OR DELETE ON moto_auto.purchase_order
+FOR EACH ROW EXECUTE FUNCTION audit_row_change('purchase_order_id');
+
+CREATE TRIGGER trigger_audit_purchase_order_line
+AFTER INSERT OR UPDATE OR DELETE ON moto_auto.purchase_order_line
+FOR EACH ROW EXECUTE FUNCTION audit_row_change('purchase_order_line_id');
+
+-- Остатки и заказы поставщикам видны в своём филиале.
+ALTER TABLE moto_auto.low_stock_alert ENABLE ROW LEVEL SECURITY;
+ALTER TABLE moto_auto.purchase_order ENABLE ROW LEVEL SECURITY;
+ALTER TABLE moto_auto.purchase_order_line ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY manager_low_stock_alert_policy ON moto_auto.low_stock_alert
+    FOR ALL TO manager USING (
+        moto_auto.low_stock_alert.branch_id = current_app_branch_id()
+    );
+
+CREATE POLICY admin_low_stock_alert_policy ON moto_auto.low_stock_alert
+    FOR ALL TO admin USING (
+        moto_auto.low_stock_alert.branch_id = current_app_branch_id()
+    );
+
+CREATE POLICY analyst_low_stock_alert_policy ON moto_auto.low_stock_alert
+    FOR SELECT TO analyst USING (true);
+
+CREATE POLICY manager_purchase_order_policy ON moto_auto.purchase_order
+    FOR ALL TO manager USING (
+        moto_auto.purchase_order.branch_id = current_app_branch_id()
+    );
+
+CREATE POLICY admin_purchase_order_policy ON moto_auto.purchase_order
+    FOR ALL TO admin USING (
+        moto_auto.purchase_order.branch_id = current_app_branch_id()
+    );
+
+CREATE POLICY analyst_purchase_order_policy ON moto_auto.purchase_order
+    FOR SELECT TO analyst USING (true);
+
+CREATE POLICY manager_purchase_order_line_policy ON moto_auto.purchase_order_line
+    FOR ALL TO manager USING (
+        EXISTS (
+            SELECT 1
+            FROM moto_auto.purchase_order po
+            WHERE po.purchase_order_id = moto_auto.purchase_order_line.purchase_order_id
+        )
+    );
+
+CREATE POLICY admin_purchase_order_line_policy ON moto_auto.purchase_order_line
+    FOR ALL TO admin USING (
+        EXISTS (
+            SELECT 1
+            FROM moto_auto.purchase_order po
+            WHERE po.purchase_order_id = moto_auto.purchase_order_line.purchase_order_id
+        )
+    );
+
+CREATE POLICY analyst_purchase_order_line_policy ON moto_auto.purchase_order_line
+    FOR SELECT TO analyst USING (true);
+
+GRANT SELECT ON moto_auto.low_stock_alert TO manager;
+GRANT SELECT, UPDATE ON moto_auto.purchase_order TO manager;
+GRANT SELECT, UPDATE, DELETE ON moto_auto.purchase_order_line TO manager;
+
+COMMIT;
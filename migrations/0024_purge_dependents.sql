@@ -0,0 +1,15 @@
+BEGIN;
+
+-- Очистка архива удаляет клиентов и запчасти. Доступ других филиалов к клиенту и строки
+-- заказов поставщикам без удалённой запчасти не нужны, поэтому удаляются вместе с ней.
+ALTER TABLE moto_auto.client_branch
+    DROP CONSTRAINT client_branch_client_id_fkey,
+    ADD CONSTRAINT client_branch_client_id_fkey
+        FOREIGN KEY (client_id) REFERENCES moto_auto.client(client_id) ON DELETE CASCADE;
+
+ALTER TABLE moto_auto.purchase_order_line
+    DROP CONSTRAINT purchase_order_line_part_id_fkey,
+    ADD CONSTRAINT purchase_order_line_part_id_fkey
+        FOREIGN KEY (part_id) REFERENCES moto_auto.spare_part(part_id) ON DELETE CASCADE;
+
+COMMIT;
@@ -0,0 +1,61 @@
+BEGIN;
+
+-- Оплаты входят в Z-отчёты закрытых смен, поэтому оплаченный заказ тоже нельзя удалить.
+ALTER TABLE moto_auto.payment
+    DROP CONSTRAINT payment_order_id_fkey,
+    ADD CONSTRAINT payment_order_id_fkey
+        FOREIGN KEY (order_id) REFERENCES moto_auto.orders(order_id) ON DELETE RESTRICT;
+
+CREATE OR REPLACE FUNCTION purge_archived_records(retention INTERVAL)
+RETURNS INTEGER AS $$
+DECLARE
+    purged INTEGER := 0;
+    affected INTEGER;
+BEGIN
+    CREATE TEMP TABLE purged_orders ON COMMIT DROP AS
+    SELECT o.order_id
+    FROM moto_auto.orders o
+    WHERE o.deleted_at < NOW() - retention
+      AND NOT EXISTS (SELECT 1 FROM moto_auto.receipt r WHERE r.order_id = o.order_id)
+      AND NOT EXISTS (SELECT 1 FROM moto_auto.payment p WHERE p.order_id = o.order_id);
+
+    DELETE FROM moto_auto.order_service_part
+    WHERE order_service_id IN (
+        SELECT order_service_id
+        FROM moto_auto.order_service
+        WHERE order_id IN (SELECT order_id FROM purged_orders)
+    );
+    DELETE FROM moto_auto.order_service
+    WHERE order_id IN (SELECT order_id FROM purged_orders);
+    DELETE FROM moto_auto.schedule
+    WHERE order_id IN (SELECT order_id FROM purged_orders);
+    DELETE FROM moto_auto.orders
+    WHERE order_id IN (SELECT order_id FROM purged_orders);
+    GET DIAGNOSTICS affected = ROW_COUNT;
+    purged := purged + affected;
+
+    DROP TABLE purged_orders;
+
+    DELETE FROM moto_auto.client c
+    WHERE c.deleted_at < NOW() - retention
+      AND NOT EXISTS (SELECT 1 FROM moto_auto.orders o WHERE o.client_id = c.client_id);
+    GET DIAGNOSTICS affected = ROW_COUNT;
+    purged := purged + affected;
+
+    DELETE FROM moto_auto.service s
+    WHERE s.deleted_at < NOW() - retention
+      AND NOT EXISTS (SELECT 1 FROM moto_auto.order_service os WHERE os.service_id = s.service_id);
+    GET DIAGNOSTICS affected = ROW_COUNT;
+    purged := purged + affected;
+
+    DELETE FROM moto_auto.spare_part sp
+    WHERE sp.deleted_at < NOW() - retention
+      AND NOT EXISTS (SELECT 1 FROM moto_auto.order_service_part osp WHERE osp.part_id = sp.part_id);
+    GET DIAGNOSTICS affected = ROW_COUNT;
+    purged := purged + affected;
+
+    RETURN purged;
+END;
+$$ LANGUAGE plpgsql;
+
+COMMIT;
@@ -0,0 +1,146 @@
+BEGIN;
+
+-- Возврат денег записывается оплатой с отрицательной суммой тем же способом,
+-- каким клиент платил. Такие строки добавляет только отмена или возврат заказа.
+ALTER TABLE moto_auto.payment
+    DROP CONSTRAINT payment_amount_check,
+    ADD CONSTRAINT payment_amount_check CHECK (amount <> 0);
+
+-- Остаток к оплате по заказу. Отменённый или возвращённый заказ ничего не должен.
+-- Переплата (например, после ручной правки суммы) не уходит в минус
+-- и не засчитывается в счёт других долгов клиента.
+CREATE OR REPLACE FUNCTION order_outstanding(target_order_id INTEGER)
+RETURNS NUMERIC
+STABLE
+SECURITY DEFINER
+AS $$
+    SELECT CASE
+        WHEN o.status IN ('cancelled', 'refunded') THEN 0
+        ELSE GREATEST(COALESCE(o.total_amount, 0) - COALESCE(
+            (SELECT SUM(p.amount) FROM moto_auto.payment p WHERE p.order_id = o.order_id),
+            0
+        ), 0)
+    END
+    FROM moto_auto.orders o
+    WHERE o.order_id = target_order_id;
+$$ LANGUAGE sql;
+
+-- Оплата принимается по заказам в работе и выполненным и не больше остатка.
+-- Возврат принимается по отменённым и возвращённым заказам и не больше оплаченного
+-- этим способом. Баллы за возврат оплаты баллами уже вернула отмена заказа.
+CREATE OR REPLACE FUNCTION check_payment()
+RETURNS TRIGGER
+SECURITY DEFINER
+AS $$
+DECLARE
+    o moto_auto.orders;
+BEGIN
+    SELECT * INTO o FROM moto_auto.orders WHERE order_id = NEW.order_id FOR UPDATE;
+
+    IF NEW.amount < 0 THEN
+        IF o.status NOT IN ('cancelled', 'refunded') THEN
+            RAISE EXCEPTION 'Заказ % не возвращён', NEW.order_id;
+        END IF;
+        IF -NEW.amount > (
+            SELECT COALESCE(SUM(p.amount), 0)
+            FROM moto_auto.payment p
+            WHERE p.order_id = NEW.order_id AND p.method = NEW.method
+        ) THEN
+            RAISE EXCEPTION 'Возврат превышает оплату по заказу %', NEW.order_id;
+        END IF;
+        RETURN NEW;
+    END IF;
+
+    IF o.status NOT IN ('processing', 'finished') OR o.deleted_at IS NOT NULL THEN
+        RAISE EXCEPTION 'Заказ % не принимает оплату', NEW.order_id;
+    END IF;
+    IF NEW.amount > order_outstanding(NEW.order_id) THEN
+        RAISE EXCEPTION 'Оплата превышает остаток по заказу %', NEW.order_id;
+    END IF;
+    IF NEW.method = 'bonus' THEN
+        PERFORM add_bonus_transaction(o.client_id, o.order_id, 'redemption', -NEW.amount, NULL);
+    END IF;
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+-- При отмене или возврате заказа клиенту возвращается всё, что он заплатил.
+CREATE OR REPLACE FUNCTION refund_order_payments()
+RETURNS TRIGGER
+SECURITY DEFINER
+AS $$
+BEGIN
+    INSERT INTO moto_auto.payment (order_id, amount, method)
+    SELECT p.order_id, -SUM(p.amount), p.method
+    FROM moto_auto.payment p
+    WHERE p.order_id = NEW.order_id
+    GROUP BY p.order_id, p.method
+    HAVING SUM(p.amount) > 0;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_refund_order_payments
+AFTER UPDATE OF status ON moto_auto.orders
+FOR EACH ROW
+WHEN (
+    NEW.status IN ('cancelled', 'refunded')
+    AND OLD.status NOT IN ('cancelled', 'refunded')
+)
+EXECUTE FUNCTION refund_order_payments();
+
+-- Оплаченный заказ нельзя переоценить: ни скидкой, ни баллами, ни строками.
+CREATE OR REPLACE FUNCTION lock_paid_order()
+RETURNS TRIGGER
+SECURITY DEFINER
+AS $$
+DECLARE
+    target_order_id INTEGER;
+    target_order_service_id INTEGER;
+BEGIN
+    IF TG_TABLE_NAME = 'orders' THEN
+        target_order_id := NEW.order_id;
+    ELSIF TG_TABLE_NAME = 'order_service' THEN
+        target_order_id := CASE WHEN TG_OP = 'DELETE' THEN OLD.order_id ELSE NEW.order_id END;
+    ELSE
+        target_order_service_id := CASE
+            WHEN TG_OP = 'DELETE' THEN OLD.order_service_id
+            ELSE NEW.order_service_id
+        END;
+        SELECT os.order_id INTO target_order_id
+        FROM moto_auto.order_service os
+        WHERE os.order_service_id = target_order_service_id;
+    END IF;
+
+    IF EXISTS (SELECT 1 FROM moto_auto.payment p WHERE p.order_id = target_order_id) THEN
+        RAISE EXCEPTION 'Заказ % уже оплачен и не может быть изменён', target_order_id;
+    END IF;
+
+    IF TG_OP = 'DELETE' THEN
+        RETURN OLD;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trigger_lock_paid_order
+BEFORE UPDATE ON moto_auto.orders
+FOR EACH ROW
+WHEN (
+    (OLD.discount_type, OLD.discount_value, OLD.bonus_points_used)
+    IS DISTINCT FROM (NEW.discount_type, NEW.discount_value, NEW.bonus_points_used)
+)
+EXECUTE FUNCTION lock_paid_order();
+
+CREATE TRIGGER trigger_lock_paid_order_service
+BEFORE INSERT OR UPDATE OR DELETE ON moto_auto.order_service
+FOR EACH ROW
+EXECUTE FUNCTION lock_paid_order();
+
+CREATE TRIGGER trigger_lock_paid_order_service_part
+BEFORE INSERT OR UPDATE OR DELETE ON moto_auto.order_service_part
+FOR EACH ROW
+EXECUTE FUNCTION lock_paid_order();
+
+COMMIT;
@@ -0,0 +1,87 @@
+BEGIN;
+
+-- Каждая оплата и каждый возврат проходят через кассовую смену филиала заказа.
+-- Итоги смены считаются по этой ссылке, а не по времени оплаты.
+ALTER TABLE moto_auto.payment
+    ADD COLUMN shift_id INTEGER REFERENCES moto_auto.cash_shift(shift_id);
+
+-- Оплаты, принятые до этого, относятся к смене, во время которой их приняли.
+-- Оплаты вне смен остаются без смены.
+UPDATE moto_auto.payment p
+SET shift_id = s.shift_id
+FROM moto_auto.orders o, moto_auto.cash_shift s
+WHERE o.order_id = p.order_id
+AND s.branch_id = o.branch_id
+AND p.paid_at >= s.opened_at
+AND (s.closed_at IS NULL OR p.paid_at < s.closed_at);
+
+CREATE INDEX idx_payment_shift_id ON moto_auto.payment(shift_id);
+
+-- Оплата принимается по заказам в работе и выполненным и не больше остатка.
+-- Возврат принимается по отменённым и возвращённым заказам и не больше оплаченного
+-- этим способом. Баллы за возврат оплаты баллами уже вернула отмена заказа.
+-- И то и другое записывается в открытую смену филиала заказа; без смены касса закрыта.
+CREATE OR REPLACE FUNCTION check_payment()
+RETURNS TRIGGER
+SECURITY DEFINER
+AS $$
+DECLARE
+    o moto_auto.orders;
+BEGIN
+    SELECT * INTO o FROM moto_auto.orders WHERE order_id = NEW.order_id FOR UPDATE;
+
+    SELECT s.shift_id INTO NEW.shift_id
+    FROM moto_auto.cash_shift s
+    WHERE s.branch_id = o.branch_id AND s.closed_at IS NULL
+    FOR SHARE;
+    IF NEW.shift_id IS NULL THEN
+        RAISE EXCEPTION 'В филиале % нет открытой смены', o.branch_id;
+    END IF;
+
+    IF NEW.amount < 0 THEN
+        IF o.status NOT IN ('cancelled', 'refunded') THEN
+            RAISE EXCEPTION 'Заказ % не возвращён', NEW.order_id;
+        END IF;
+        IF -NEW.amount > (
+            SELECT COALESCE(SUM(p.amount), 0)
+            FROM moto_auto.payment p
+            WHERE p.order_id = NEW.order_id AND p.method = NEW.method
+        ) THEN
+            RAISE EXCEPTION 'Возврат превышает оплату по заказу %', NEW.order_id;
+        END IF;
+        RETURN NEW;
+    END IF;
+
+    IF o.status NOT IN ('processing', 'finished') OR o.deleted_at IS NOT NULL THEN
+        RAISE EXCEPTION 'Заказ % не принимает оплату', NEW.order_id;
+    END IF;
+    IF NEW.amount > order_outstanding(NEW.order_id) THEN
+        RAISE EXCEPTION 'Оплата превышает остаток по заказу %', NEW.order_id;
+    END IF;
+    IF NEW.method = 'bonus' THEN
+        PERFORM add_bonus_transaction(o.client_id, o.order_id, 'redemption', -NEW.amount, NULL);
+    END IF;
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Оплаты и возвраты смены по способам оплаты. Возвраты уменьшают итог.
+-- Для открытой смены итоги считаются на текущий момент.
+CREATE OR REPLACE FUNCTION shift_payment_totals(target_shift_id INTEGER)
+RETURNS TABLE (
+    method VARCHAR,
+    amount NUMERIC,
+    payment_count BIGINT
+)
+STABLE
+SECURITY DEFINER
+AS $$
+    SELECT p.method, SUM(p.amount), COUNT(*)
+    FROM moto_auto.payment p
+    WHERE p.shift_id = target_shift_id
+    GROUP BY p.method
+    ORDER BY p.method;
+$$ LANGUAGE sql;
+
+COMMIT;
@@ -0,0 +1,75 @@
+//! Client and employee contacts. Phone numbers are kept in E.164 form (`+` and up to 15
+//! digits) and emails in lower case, the same way the `normalize_phone` and
+//! `normalize_email` database functions store them.
+
+/// Country code for numbers written without one.
+const DEFAULT_COUNTRY_CODE: &str = "7";
+
+#[derive(Debug, PartialEq)]
+pub enum ContactError {
+    Phone,
+    Email,
+}
+
+/// Brings a phone number to E.164. Spaces, dashes, dots and brackets are dropped, a
+/// leading `00` stands for `+`, and local numbers get the default country code: ten
+/// digits as they are, eleven starting with 8 or 7 without the trunk prefix.
+pub fn normalize_phone(phone: &str) -> Result<String, ContactError> {
+    let digits: String = phone
+        .chars()
+        .filter(|c| !c.is_whitespace() && !matches!(c, '(' | ')' | '-' | '.'))
+        .collect();
+    let number = if let Some(rest) = digits.strip_prefix("00") {
+        format!("+{}", rest)
+    } else if digits.starts_with('+') {
+        digits
+    } else if digits.len() == 11 && (digits.starts_with('8') || digits.starts_with('7')) {
+        format!("+{}{}", DEFAULT_COUNTRY_CODE, &digits[1..])
+    } else if digits.len() == 10 {
+        format!("+{}{}", DEFAULT_COUNTRY_CODE, digits)
+    } else {
+        return Err(ContactError::Phone);
+    };
+    let national = &number[1..];
+    if (8..=15).contains(&national.len())
+        && national.bytes().all(|b| b.is_ascii_digit())
+        && !national.starts_with('0')
+    {
+        Ok(number)
+    } else {
+        Err(ContactError::Phone)
+    }
+}
+
+/// Trims and lowercases an email, checking it has a local part and a dotted domain.
+pub fn normalize_email(email: &str) -> Result<String, ContactError> {
+    let email = email.trim().to_lowercase();
+    let (local, domain) = email.split_once('@').ok_or(ContactError::Email)?;
+    let valid = !local.is_empty()
+        && !domain.contains('@')
+        && domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && !email.chars().any(char::is_whitespace);
+    if valid {
+        Ok(email)
+    } else {
+        Err(ContactError::Email)
+    }
+}
+
+/// Normalises a list of phone numbers separated by commas, semicolons or new lines,
+/// dropping empty entries and repeats.
+pub fn normalize_phones(phones: &str) -> Result<Vec<String>, ContactError> {
+    let mut normalized: Vec<String> = Vec::new();
+    for phone in phones.split([',', ';', '\n']).map(str::trim) {
+        if phone.is_empty() {
+            continue;
+        }
+        let phone = normalize_phone(phone)?;
+        if !normalized.contains(&phone) {
+            normalized.push(phone);
+        }
+    }
+    Ok(normalized)
+}
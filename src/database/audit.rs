@@ -0,0 +1,32 @@
+use crate::database::{DbConn, DbError};
+use crate::models::AuditLog;
+
+pub async fn get_audit_log(
+    conn: &mut DbConn,
+    entity: Option<&str>,
+    entity_id: Option<i32>,
+    actor_user_id: Option<i32>,
+    operation: Option<&str>,
+    limit: i64,
+) -> Result<Vec<AuditLog>, DbError> {
+    sqlx::query_as!(
+        AuditLog,
+        r#"
+        SELECT * FROM moto_auto.audit_log
+        WHERE ($1::TEXT IS NULL OR entity = $1)
+          AND ($2::INTEGER IS NULL OR entity_id = $2)
+          AND ($3::INTEGER IS NULL OR actor_user_id = $3)
+          AND ($4::TEXT IS NULL OR operation = $4)
+        ORDER BY changed_at DESC, audit_id DESC
+        LIMIT $5
+        "#,
+        entity,
+        entity_id,
+        actor_user_id,
+        operation,
+        limit
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
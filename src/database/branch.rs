@@ -1,24 +1,34 @@
 use crate::{
-    database::{DbError, DbPool},
-    models::Branch,
+    database::{DbConn, DbError},
+    models::{Branch, BranchUpdate},
 };
 use log::error;
 
-pub async fn create_branch(pool: &DbPool, branch: Branch) -> Result<Branch, DbError> {
+pub async fn create_branch(
+    conn: &mut DbConn,
+    branch: Branch,
+    admin_username: &str,
+    admin_passwordhash: &str,
+) -> Result<Branch, DbError> {
     let branch = sqlx::query_as!(
         Branch,
         r#"
-        INSERT INTO moto_auto.branch (address, phone_number, postal_code, employee_count, city)
-        VALUES ($1, $2, $3, $4, $5)
-        RETURNING branch_id, address, phone_number, postal_code, employee_count, city
+        INSERT INTO moto_auto.branch (
+            address, phone_number, postal_code, employee_count, city, vat_rate, max_bonus_share
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING branch_id, address, phone_number, postal_code, employee_count, city, closed_at,
+            vat_rate, max_bonus_share
         "#,
         branch.address,
         branch.phone_number,
         branch.postal_code,
         branch.employee_count,
         branch.city,
+        branch.vat_rate,
+        branch.max_bonus_share,
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e));
     if let Err(_) = branch {
@@ -31,12 +41,12 @@ pub async fn create_branch(pool: &DbPool, branch: Branch) -> Result<Branch, DbEr
         INSERT INTO moto_auto.users (username, passwordhash, role, branch_id)
         VALUES ($1, $2, $3, $4)
         "#,
-        "admin",
-        "admin", // TODO: default passwordhash!
+        admin_username,
+        admin_passwordhash,
         "admin",
         branch.branch_id
     )
-    .execute(pool)
+    .execute(&mut *conn)
     .await
     {
         error!("Error creating default admin");
@@ -46,12 +56,9 @@ pub async fn create_branch(pool: &DbPool, branch: Branch) -> Result<Branch, DbEr
 }
 
 pub async fn update_branch(
-    pool: &DbPool,
+    conn: &mut DbConn,
     admin_branch_id: i32,
-    address: Option<&str>,
-    phone_number: Option<&str>,
-    postal_code: Option<&str>,
-    employee_count: Option<i32>,
+    update: BranchUpdate,
 ) -> Result<Branch, DbError> {
     sqlx::query_as!(
         Branch,
@@ -61,24 +68,78 @@ pub async fn update_branch(
             address = COALESCE($1, address),
             phone_number = COALESCE($2, phone_number),
             postal_code = COALESCE($3, postal_code),
-            employee_count = COALESCE($4, employee_count)
-        WHERE branch_id = $5
-        RETURNING branch_id, address, phone_number, postal_code, employee_count, city
+            employee_count = COALESCE($4, employee_count),
+            vat_rate = COALESCE($6, vat_rate),
+            max_bonus_share = COALESCE($7, max_bonus_share)
+        WHERE branch_id = $5 AND closed_at IS NULL
+        RETURNING branch_id, address, phone_number, postal_code, employee_count, city, closed_at,
+            vat_rate, max_bonus_share
         "#,
-        address,
-        phone_number,
-        postal_code,
-        employee_count,
-        admin_branch_id
+        update.address,
+        update.phone_number,
+        update.postal_code,
+        update.employee_count,
+        admin_branch_id,
+        update.vat_rate,
+        update.max_bonus_share
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
 }
 
+pub async fn close_branch(conn: &mut DbConn, branch_id: i32) -> Result<Branch, DbError> {
+    sqlx::query_as!(
+        Branch,
+        r#"
+        UPDATE moto_auto.branch
+        SET closed_at = NOW()
+        WHERE branch_id = $1 AND closed_at IS NULL
+        RETURNING branch_id, address, phone_number, postal_code, employee_count, city, closed_at,
+            vat_rate, max_bonus_share
+        "#,
+        branch_id
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+pub async fn reopen_branch(conn: &mut DbConn, branch_id: i32) -> Result<Branch, DbError> {
+    sqlx::query_as!(
+        Branch,
+        r#"
+        UPDATE moto_auto.branch
+        SET closed_at = NULL
+        WHERE branch_id = $1
+        RETURNING branch_id, address, phone_number, postal_code, employee_count, city, closed_at,
+            vat_rate, max_bonus_share
+        "#,
+        branch_id
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+pub async fn get_branch_by_id(conn: &mut DbConn, branch_id: i32) -> Result<Branch, DbError> {
+    sqlx::query_as!(
+        Branch,
+        r#"
+        SELECT * FROM moto_auto.branch
+        WHERE branch_id = $1
+        "#,
+        branch_id
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
 pub async fn get_branch(
-    pool: &DbPool,
+    conn: &mut DbConn,
     city: Option<&str>,
+    include_closed: bool,
 ) -> Result<Vec<Branch>, DbError> {
     match city {
         Some(city) => {
@@ -86,11 +147,13 @@ pub async fn get_branch(
                 Branch,
                 r#"
                 SELECT * FROM moto_auto.branch
-                WHERE city = $1
+                WHERE city = $1 AND ($2 OR closed_at IS NULL)
+                ORDER BY branch_id
                 "#,
-                city
+                city,
+                include_closed
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         },
@@ -99,9 +162,12 @@ pub async fn get_branch(
                 Branch,
                 r#"
                 SELECT * FROM moto_auto.branch
+                WHERE $1 OR closed_at IS NULL
+                ORDER BY branch_id
                 "#,
+                include_closed
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         }
@@ -2,10 +2,10 @@ use log::error;
 
 use crate::models::BranchEmployee;
 
-use crate::database::{DbError, DbPool};
+use crate::database::{DbConn, DbError};
 
 pub async fn delete_branch_employee(
-    pool: &DbPool,
+    conn: &mut DbConn,
     employee_id: i32,
     branch_id: i32,
 ) -> Result<(), DbError> {
@@ -17,14 +17,14 @@ pub async fn delete_branch_employee(
         employee_id,
         branch_id
     )
-    .execute(pool)
+    .execute(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
     .map(|_| {})
 }
 
 pub async fn create_branch_employee(
-    pool: &DbPool,
+    conn: &mut DbConn,
     employee_id: i32,
     branch_id: i32,
 ) -> Result<BranchEmployee, DbError> {
@@ -36,7 +36,7 @@ pub async fn create_branch_employee(
         "#,
         branch_id
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))?;
 
@@ -53,7 +53,7 @@ pub async fn create_branch_employee(
         employee_id,
         new_city
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))?;
 
@@ -74,13 +74,13 @@ pub async fn create_branch_employee(
         employee_id,
         branch_id
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
 }
 
 pub async fn get_branch_employee(
-    pool: &DbPool,
+    conn: &mut DbConn,
     branch_id: Option<i32>,
     employee_id: Option<i32>,
 ) -> Result<Vec<BranchEmployee>, DbError> {
@@ -94,7 +94,7 @@ pub async fn get_branch_employee(
                 "#,
                 id
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         },
@@ -107,7 +107,7 @@ pub async fn get_branch_employee(
                 "#,
                 id
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         },
@@ -121,7 +121,7 @@ pub async fn get_branch_employee(
                 branch_id,
                 employee_id
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         },
@@ -132,7 +132,7 @@ pub async fn get_branch_employee(
                 SELECT * FROM moto_auto.branch_employee
                 "#,
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         }
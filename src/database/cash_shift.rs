@@ -0,0 +1,94 @@
+use bigdecimal::BigDecimal;
+
+use crate::database::{DbConn, DbError};
+use crate::models::{CashShift, ShiftTotal};
+
+/// Opens a shift in the branch. Fails while another shift of the branch is open.
+pub async fn open_cash_shift(
+    conn: &mut DbConn,
+    branch_id: i32,
+    opening_cash: BigDecimal,
+) -> Result<CashShift, DbError> {
+    sqlx::query_as!(
+        CashShift,
+        r#"
+        INSERT INTO moto_auto.cash_shift (branch_id, opening_cash)
+        VALUES ($1, $2)
+        RETURNING *
+        "#,
+        branch_id,
+        opening_cash
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+/// Closes an open shift with the cash counted in the drawer. The database works out
+/// the expected cash and the discrepancy.
+pub async fn close_cash_shift(
+    conn: &mut DbConn,
+    shift_id: i32,
+    counted_cash: BigDecimal,
+) -> Result<CashShift, DbError> {
+    sqlx::query_as!(
+        CashShift,
+        r#"
+        SELECT
+            shift_id AS "shift_id!", branch_id AS "branch_id!", opened_by AS "opened_by!",
+            opened_at AS "opened_at!", opening_cash AS "opening_cash!", closed_by, closed_at,
+            counted_cash, expected_cash, discrepancy
+        FROM close_cash_shift($1, $2)
+        "#,
+        shift_id,
+        counted_cash
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+pub async fn get_cash_shift(conn: &mut DbConn, shift_id: i32) -> Result<CashShift, DbError> {
+    sqlx::query_as!(
+        CashShift,
+        r#"
+        SELECT * FROM moto_auto.cash_shift
+        WHERE shift_id = $1
+        "#,
+        shift_id
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+/// Shifts of a branch, the latest first.
+pub async fn get_cash_shifts(conn: &mut DbConn, branch_id: i32) -> Result<Vec<CashShift>, DbError> {
+    sqlx::query_as!(
+        CashShift,
+        r#"
+        SELECT * FROM moto_auto.cash_shift
+        WHERE branch_id = $1
+        ORDER BY opened_at DESC
+        "#,
+        branch_id
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+/// Payments taken during a shift by method, running totals while it is open.
+pub async fn get_shift_totals(conn: &mut DbConn, shift_id: i32) -> Result<Vec<ShiftTotal>, DbError> {
+    sqlx::query_as!(
+        ShiftTotal,
+        r#"
+        SELECT method AS "method!", amount AS "amount!", payment_count AS "payment_count!"
+        FROM shift_payment_totals($1)
+        "#,
+        shift_id
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
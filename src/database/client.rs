@@ -1,85 +1,215 @@
-use crate::database::{DbError, DbPool};
-use crate::models::Client;
+use crate::database::{DbConn, DbError};
+use crate::models::{Client, ClientBranch, ClientContacts};
 
-pub async fn create_client(pool: &DbPool, client: Client) -> Result<Client, DbError> {
+pub async fn create_client(conn: &mut DbConn, client: Client) -> Result<Client, DbError> {
     sqlx::query_as!(
         Client,
         r#"
-        INSERT INTO moto_auto.client (name, contact_info, status, bonus_points, total_spent)
-        VALUES ($1, $2, $3, $4, $5)
-        RETURNING client_id, name, contact_info, status, bonus_points, total_spent
+        INSERT INTO moto_auto.client (
+            name, status, bonus_points, total_spent, home_branch_id, phones, email,
+            preferred_channel, service_consent, marketing_consent, contact_note
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+        RETURNING client_id, name, status, bonus_points, total_spent, home_branch_id, deleted_at,
+            phones, email, preferred_channel, service_consent, marketing_consent, contact_note
         "#,
         client.name,
-        client.contact_info,
         client.status,
         client.bonus_points,
-        client.total_spent
+        client.total_spent,
+        client.home_branch_id,
+        &client.phones,
+        client.email,
+        client.preferred_channel,
+        client.service_consent,
+        client.marketing_consent,
+        client.contact_note
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
 }
 
 pub async fn update_client(
-    pool: &DbPool,
+    conn: &mut DbConn,
     name: Option<&str>,
-    contact_info: Option<&str>,
     client_id: i32,
 ) -> Result<Client, DbError> {
     sqlx::query_as!(
         Client,
         r#"
         UPDATE moto_auto.client
-        SET
-            name = COALESCE($1, name),
-            contact_info = COALESCE($2, contact_info)
-        WHERE client_id = $3
-        RETURNING client_id, name, contact_info, status, bonus_points, total_spent
+        SET name = COALESCE($1, name)
+        WHERE client_id = $2 AND deleted_at IS NULL
+        RETURNING client_id, name, status, bonus_points, total_spent, home_branch_id, deleted_at,
+            phones, email, preferred_channel, service_consent, marketing_consent, contact_note
         "#,
         name,
-        contact_info,
         client_id
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
 }
 
-pub async fn delete_client(pool: &DbPool, client_id: i32) -> Result<(), DbError> {
+/// Replaces the contact details of a client; they are expected to be normalised already.
+pub async fn update_client_contacts(
+    conn: &mut DbConn,
+    client_id: i32,
+    contacts: &ClientContacts,
+) -> Result<Client, DbError> {
+    sqlx::query_as!(
+        Client,
+        r#"
+        UPDATE moto_auto.client
+        SET
+            phones = $2,
+            email = $3,
+            preferred_channel = $4,
+            service_consent = $5,
+            marketing_consent = $6,
+            contact_note = $7
+        WHERE client_id = $1 AND deleted_at IS NULL
+        RETURNING client_id, name, status, bonus_points, total_spent, home_branch_id, deleted_at,
+            phones, email, preferred_channel, service_consent, marketing_consent, contact_note
+        "#,
+        client_id,
+        &contacts.phones,
+        contacts.email,
+        contacts.preferred_channel,
+        contacts.service_consent,
+        contacts.marketing_consent,
+        contacts.contact_note
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+pub async fn delete_client(conn: &mut DbConn, client_id: i32) -> Result<(), DbError> {
     sqlx::query!(
         r#"
-        DELETE FROM moto_auto.client
-        WHERE client_id = $1
+        UPDATE moto_auto.client
+        SET deleted_at = NOW()
+        WHERE client_id = $1 AND deleted_at IS NULL
+        RETURNING client_id
         "#,
         client_id
     )
-    .execute(pool)
+    .fetch_one(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
     .map(|_| {})
 }
 
+pub async fn restore_client(conn: &mut DbConn, client_id: i32) -> Result<Client, DbError> {
+    sqlx::query_as!(
+        Client,
+        r#"
+        UPDATE moto_auto.client
+        SET deleted_at = NULL
+        WHERE client_id = $1 AND deleted_at IS NOT NULL
+        RETURNING client_id, name, status, bonus_points, total_spent, home_branch_id, deleted_at,
+            phones, email, preferred_channel, service_consent, marketing_consent, contact_note
+        "#,
+        client_id
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+pub async fn get_archived_clients(conn: &mut DbConn) -> Result<Vec<Client>, DbError> {
+    sqlx::query_as!(
+        Client,
+        r#"
+        SELECT * FROM moto_auto.client
+        WHERE deleted_at IS NOT NULL
+        ORDER BY deleted_at DESC
+        "#,
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+pub async fn get_client_by_id(conn: &mut DbConn, client_id: i32) -> Result<Client, DbError> {
+    sqlx::query_as!(
+        Client,
+        r#"
+        SELECT * FROM moto_auto.client
+        WHERE client_id = $1
+        "#,
+        client_id
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+pub async fn share_client(
+    conn: &mut DbConn,
+    client_id: i32,
+    branch_id: i32,
+) -> Result<ClientBranch, DbError> {
+    sqlx::query_as!(
+        ClientBranch,
+        r#"
+        INSERT INTO moto_auto.client_branch (client_id, branch_id)
+        VALUES ($1, $2)
+        ON CONFLICT DO NOTHING
+        RETURNING client_id, branch_id
+        "#,
+        client_id,
+        branch_id
+    )
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+    .map(|_| ClientBranch { client_id, branch_id })
+}
+
+pub async fn unshare_client(
+    conn: &mut DbConn,
+    client_id: i32,
+    branch_id: i32,
+) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"
+        DELETE FROM moto_auto.client_branch
+        WHERE client_id = $1 AND branch_id = $2
+        "#,
+        client_id,
+        branch_id
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+    .map(|_| {})
+}
+
 pub async fn get_clients_by_master_id(
-    pool: &DbPool,
+    conn: &mut DbConn,
     master_id: i32,
 ) -> Result<Vec<Client>, DbError> {
     sqlx::query_as!(
         Client,
         r#"
-        SELECT DISTINCT c.client_id, c.name, c.contact_info, c.status, c.bonus_points, c.total_spent
+        SELECT DISTINCT c.client_id, c.name, c.status, c.bonus_points, c.total_spent, c.home_branch_id, c.deleted_at,
+                c.phones, c.email, c.preferred_channel, c.service_consent, c.marketing_consent, c.contact_note
         FROM moto_auto.client c
         INNER JOIN moto_auto.orders o ON c.client_id = o.client_id
-        WHERE o.master_id = $1
+        WHERE o.master_id = $1 AND c.deleted_at IS NULL
        "#,
         master_id
     )
-    .fetch_all(pool)
+    .fetch_all(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
 }
 
 pub async fn get_clients(
-    pool: &DbPool,
+    conn: &mut DbConn,
     master_id: Option<i32>,
     status: Option<String>
 ) -> Result<Vec<Client>, DbError> {
@@ -88,14 +218,15 @@ pub async fn get_clients(
             sqlx::query_as!(
                 Client,
                 r#"
-                SELECT DISTINCT c.client_id, c.name, c.contact_info, c.status, c.bonus_points, c.total_spent
+                SELECT DISTINCT c.client_id, c.name, c.status, c.bonus_points, c.total_spent, c.home_branch_id, c.deleted_at,
+                c.phones, c.email, c.preferred_channel, c.service_consent, c.marketing_consent, c.contact_note
                 FROM moto_auto.client c
                 INNER JOIN moto_auto.orders o ON c.client_id = o.client_id
-                WHERE o.master_id = $1
+                WHERE o.master_id = $1 AND c.deleted_at IS NULL
                "#,
                 id
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         },
@@ -103,15 +234,16 @@ pub async fn get_clients(
             sqlx::query_as!(
                 Client,
                 r#"
-                SELECT DISTINCT c.client_id, c.name, c.contact_info, c.status, c.bonus_points, c.total_spent
+                SELECT DISTINCT c.client_id, c.name, c.status, c.bonus_points, c.total_spent, c.home_branch_id, c.deleted_at,
+                c.phones, c.email, c.preferred_channel, c.service_consent, c.marketing_consent, c.contact_note
                 FROM moto_auto.client c
                 INNER JOIN moto_auto.orders o ON c.client_id = o.client_id
-                WHERE o.master_id = $1 AND c.status = $2
+                WHERE o.master_id = $1 AND c.status = $2 AND c.deleted_at IS NULL
                "#,
                 id,
                 status
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         },
@@ -120,11 +252,11 @@ pub async fn get_clients(
                 Client,
                 r#"
                 SELECT * FROM moto_auto.client
-                WHERE status = $1
+                WHERE status = $1 AND deleted_at IS NULL
                "#,
                 status
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         },
@@ -133,9 +265,10 @@ pub async fn get_clients(
                 Client,
                 r#"
                 SELECT * FROM moto_auto.client
+                WHERE deleted_at IS NULL
                "#
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         }
@@ -1,27 +1,28 @@
-use bigdecimal::BigDecimal;
-use crate::{database::{DbError, DbPool}, models::Employee};
+use crate::{database::{DbConn, DbError}, models::{Employee, EmployeeUpdate}};
 
-pub async fn create_employee(pool: &DbPool, employee: Employee) -> Result<Employee, DbError> {
+pub async fn create_employee(conn: &mut DbConn, employee: Employee) -> Result<Employee, DbError> {
     sqlx::query_as!(
         Employee, 
         r#"
-        INSERT INTO moto_auto.employee (name, age, position, contact_info, expirience_years, salary, description)
-        VALUES ($1, $2, $3, $4, $5, $6, $7)
-        RETURNING employee_id, name, age, position, contact_info, expirience_years, salary, description
+        INSERT INTO moto_auto.employee (name, age, position, phones, email, contact_note, expirience_years, salary, description)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        RETURNING employee_id, name, age, position, phones, email, contact_note, expirience_years, salary, description
         "#,
         employee.name,
         employee.age,
         employee.position,
-        employee.contact_info,
+        &employee.phones,
+        employee.email,
+        employee.contact_note,
         employee.expirience_years,
         employee.salary,
         employee.description
         )
-        .fetch_one(pool)
+        .fetch_one(&mut *conn)
         .await.map_err(|e| DbError::Sqlx(e))
 }
 
-pub async fn update_employee(pool: &DbPool, name: Option<&str>, age: Option<i32>, position: Option<&str>, contact_info: Option<&str>, expirience_years: Option<i32>, salary: Option<BigDecimal>, description: Option<&str>, employee_id: i32) -> Result<Employee, DbError> {
+pub async fn update_employee(conn: &mut DbConn, update: EmployeeUpdate, employee_id: i32) -> Result<Employee, DbError> {
     sqlx::query_as!(
         Employee,
         r#"
@@ -30,27 +31,29 @@ pub async fn update_employee(pool: &DbPool, name: Option<&str>, age: Option<i32>
             name = COALESCE($1, name),
             age = COALESCE($2, age),
             position = COALESCE($3, position),
-            contact_info = COALESCE($4, contact_info),
-            expirience_years = COALESCE($5, expirience_years),
-            salary = COALESCE($6, salary),
-            description = COALESCE($7, description)
-        WHERE employee_id = $8
-        RETURNING employee_id, name, age, position, contact_info, expirience_years, salary, description
+            phones = COALESCE($4, phones),
+            email = COALESCE($5, email),
+            expirience_years = COALESCE($6, expirience_years),
+            salary = COALESCE($7, salary),
+            description = COALESCE($8, description)
+        WHERE employee_id = $9
+        RETURNING employee_id, name, age, position, phones, email, contact_note, expirience_years, salary, description
         "#,
-        name,
-        age,
-        position,
-        contact_info,
-        expirience_years,
-        salary,
-        description,
+        update.name,
+        update.age,
+        update.position,
+        update.phones.as_deref(),
+        update.email,
+        update.expirience_years,
+        update.salary,
+        update.description,
         employee_id
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await.map_err(|e| DbError::Sqlx(e))
 }
 
-pub async fn delete_employee(pool: &DbPool, employee_id: i32) -> Result<(), DbError> {
+pub async fn delete_employee(conn: &mut DbConn, employee_id: i32) -> Result<(), DbError> {
     sqlx::query!(
         r#"
         DELETE FROM moto_auto.employee
@@ -58,13 +61,13 @@ pub async fn delete_employee(pool: &DbPool, employee_id: i32) -> Result<(), DbEr
         "#,
         employee_id
     )
-    .execute(pool)
+    .execute(&mut *conn)
     .await.map_err(|e| DbError::Sqlx(e))
     .map(|_|{})
 
 }
 
-pub async fn get_employees_by_branch(pool: &DbPool, branch_id: i32) -> Result<Vec<Employee>, DbError> {
+pub async fn get_employees_by_branch(conn: &mut DbConn, branch_id: i32) -> Result<Vec<Employee>, DbError> {
     sqlx::query_as!(
         Employee,
         r#"
@@ -76,13 +79,13 @@ pub async fn get_employees_by_branch(pool: &DbPool, branch_id: i32) -> Result<Ve
         "#,
         branch_id
     )
-    .fetch_all(pool)
+    .fetch_all(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
 }
 
 pub async fn get_employees(
-    pool: &DbPool,
+    conn: &mut DbConn,
     branch_id: Option<i32>,
 ) -> Result<Vec<Employee>, DbError> {
     match branch_id {
@@ -98,7 +101,7 @@ pub async fn get_employees(
                 "#,
                 branch_id
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
          },
@@ -109,7 +112,7 @@ pub async fn get_employees(
                 SELECT * FROM moto_auto.employee
                 "#,
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         }
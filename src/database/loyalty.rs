@@ -0,0 +1,134 @@
+use bigdecimal::BigDecimal;
+
+use crate::database::{DbConn, DbError};
+use crate::models::{BonusTransaction, LoyaltyTier};
+
+pub async fn get_loyalty_tiers(conn: &mut DbConn) -> Result<Vec<LoyaltyTier>, DbError> {
+    sqlx::query_as!(
+        LoyaltyTier,
+        r#"
+        SELECT * FROM moto_auto.loyalty_tier
+        ORDER BY spend_threshold
+        "#
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+pub async fn create_loyalty_tier(
+    conn: &mut DbConn,
+    tier: LoyaltyTier,
+) -> Result<LoyaltyTier, DbError> {
+    sqlx::query_as!(
+        LoyaltyTier,
+        r#"
+        INSERT INTO moto_auto.loyalty_tier (name, spend_threshold, accrual_rate, points_lifetime_days)
+        VALUES ($1, $2, $3, $4)
+        RETURNING *
+        "#,
+        tier.name,
+        tier.spend_threshold,
+        tier.accrual_rate,
+        tier.points_lifetime_days
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+pub async fn update_loyalty_tier(
+    conn: &mut DbConn,
+    tier_id: i32,
+    name: Option<&str>,
+    spend_threshold: Option<BigDecimal>,
+    accrual_rate: Option<BigDecimal>,
+    points_lifetime_days: Option<i32>,
+) -> Result<LoyaltyTier, DbError> {
+    sqlx::query_as!(
+        LoyaltyTier,
+        r#"
+        UPDATE moto_auto.loyalty_tier
+        SET
+            name = COALESCE($2, name),
+            spend_threshold = COALESCE($3, spend_threshold),
+            accrual_rate = COALESCE($4, accrual_rate),
+            points_lifetime_days = COALESCE($5, points_lifetime_days)
+        WHERE tier_id = $1
+        RETURNING *
+        "#,
+        tier_id,
+        name,
+        spend_threshold,
+        accrual_rate,
+        points_lifetime_days
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+/// Bonus point movements of a client, newest first.
+pub async fn get_bonus_statement(
+    conn: &mut DbConn,
+    client_id: i32,
+) -> Result<Vec<BonusTransaction>, DbError> {
+    sqlx::query_as!(
+        BonusTransaction,
+        r#"
+        SELECT * FROM moto_auto.bonus_transaction
+        WHERE client_id = $1
+        ORDER BY created_at DESC, transaction_id DESC
+        "#,
+        client_id
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+/// Manual correction of a client's balance. Negative `points` spend the accruals that
+/// expire first.
+pub async fn adjust_bonus_points(
+    conn: &mut DbConn,
+    client_id: i32,
+    points: BigDecimal,
+    reason: &str,
+) -> Result<BonusTransaction, DbError> {
+    sqlx::query_as!(
+        BonusTransaction,
+        r#"
+        SELECT
+            transaction_id AS "transaction_id!",
+            client_id AS "client_id!",
+            order_id,
+            kind AS "kind!",
+            points AS "points!",
+            remaining AS "remaining!",
+            expires_at,
+            created_at AS "created_at!",
+            actor_user_id,
+            reason
+        FROM adjust_bonus_points($1, $2, $3)
+        "#,
+        client_id,
+        points,
+        reason
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+/// Expires what is left of accruals past their expiry date and returns how many were expired.
+pub async fn expire_bonus_points(conn: &mut DbConn) -> Result<i32, DbError> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT expire_bonus_points()
+        "#
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+    .map(|expired| expired.unwrap_or_default())
+}
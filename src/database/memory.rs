@@ -0,0 +1,1274 @@
+//! In-memory implementation of the repository traits for handler tests. Triggers and
+//! row-level security are not modelled; the Postgres tests in `web::api::tests` cover those.
+//! Employees, payments, cash shifts, stock, part lookup and search live in the database and return
+//! [`DbError::Unsupported`] here.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+
+use crate::database::repo::{
+    AuditRepo, BranchRepo, CatalogRepo, ClientRepo, EmployeeRepo, LoyaltyRepo, OrderRepo, PaymentRepo, Repos,
+    SearchRepo, StockRepo, Store, VehicleRepo,
+    UserRepo,
+};
+use crate::database::spare_part::PART_UNITS;
+use crate::database::DbError;
+use crate::models::{
+    AuditLog, BonusTransaction, Branch, BranchUpdate, CashShift, Client, ClientBranch, ClientContacts, Employee, EmployeeUpdate, LoyaltyTier, MaintenanceReminder, Order, OrderService, OrderServicePart,
+    PartCompatibility, PartMatch, Payment, PriceHistory, PurchaseOrder, PurchaseOrderLine, Receipt, ReceiptLine, SearchResult, Service, ServiceBranch, ServiceHistoryEntry, ShiftTotal, SparePart,
+    SparePartBranch, StockLevel, User, Vehicle,
+};
+
+#[derive(Clone, Default)]
+pub struct MemoryData {
+    pub users: Vec<User>,
+    pub branches: Vec<Branch>,
+    pub orders: Vec<Order>,
+    pub order_services: Vec<OrderService>,
+    pub order_service_parts: Vec<OrderServicePart>,
+    pub clients: Vec<Client>,
+    pub client_branches: Vec<ClientBranch>,
+    pub services: Vec<Service>,
+    pub spare_parts: Vec<SparePart>,
+    pub part_compatibility: Vec<PartCompatibility>,
+    /// Pairs of interchangeable parts, the smaller id first.
+    pub part_substitutes: Vec<(i32, i32)>,
+    pub service_branches: Vec<ServiceBranch>,
+    pub spare_part_branches: Vec<SparePartBranch>,
+    pub price_history: Vec<PriceHistory>,
+    pub loyalty_tiers: Vec<LoyaltyTier>,
+    pub bonus_transactions: Vec<BonusTransaction>,
+    pub receipts: Vec<Receipt>,
+    pub receipt_lines: Vec<ReceiptLine>,
+    pub vehicles: Vec<Vehicle>,
+    pub maintenance_reminders: Vec<MaintenanceReminder>,
+    pub audit_log: Vec<AuditLog>,
+}
+
+#[derive(Clone, Default)]
+pub struct MemoryStore {
+    data: Arc<Mutex<MemoryData>>,
+}
+
+impl MemoryStore {
+    pub fn new(data: MemoryData) -> Self {
+        MemoryStore {
+            data: Arc::new(Mutex::new(data)),
+        }
+    }
+
+    pub fn data(&self) -> MutexGuard<'_, MemoryData> {
+        self.data.lock().unwrap()
+    }
+}
+
+#[async_trait]
+impl Store for MemoryStore {
+    async fn begin(&self) -> Result<Box<dyn Repos>, DbError> {
+        Ok(Box::new(MemoryRepos {
+            data: self.data().clone(),
+            store: self.data.clone(),
+        }))
+    }
+
+    async fn begin_as(&self, _user: &User) -> Result<Box<dyn Repos>, DbError> {
+        self.begin().await
+    }
+}
+
+/// Works on a snapshot of the store that replaces it on commit.
+pub struct MemoryRepos {
+    data: MemoryData,
+    store: Arc<Mutex<MemoryData>>,
+}
+
+fn not_found() -> DbError {
+    DbError::Sqlx(sqlx::Error::RowNotFound)
+}
+
+fn next_id(ids: impl Iterator<Item = Option<i32>>) -> Option<i32> {
+    Some(ids.flatten().max().unwrap_or(0) + 1)
+}
+
+/// Whether the order is in progress, so its pricing may change. Payments are not
+/// modelled, so no order here is paid.
+fn order_repriceable(data: &MemoryData, order_id: i32) -> bool {
+    data.orders.iter().any(|o| {
+        o.order_id == Some(order_id) && o.status == "processing" && o.deleted_at.is_none()
+    })
+}
+
+/// Whether the service line belongs to an order whose pricing may change.
+fn line_repriceable(data: &MemoryData, order_service_id: i32) -> bool {
+    data.order_services
+        .iter()
+        .any(|l| l.order_service_id == Some(order_service_id) && order_repriceable(data, l.order_id))
+}
+
+#[async_trait]
+impl Repos for MemoryRepos {
+    async fn commit(self: Box<Self>) -> Result<(), DbError> {
+        *self.store.lock().unwrap() = self.data;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl UserRepo for MemoryRepos {
+    async fn get_user(&mut self, username: &str) -> Result<User, DbError> {
+        self.data
+            .users
+            .iter()
+            .find(|u| u.username == username)
+            .cloned()
+            .ok_or_else(not_found)
+    }
+
+    async fn get_user_by_id(&mut self, user_id: i32) -> Result<User, DbError> {
+        self.data
+            .users
+            .iter()
+            .find(|u| u.user_id == Some(user_id))
+            .cloned()
+            .ok_or_else(not_found)
+    }
+
+    async fn get_users(&mut self, admin_branch_id: i32) -> Result<Vec<User>, DbError> {
+        Ok(self
+            .data
+            .users
+            .iter()
+            .filter(|u| u.branch_id == admin_branch_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn create_user(&mut self, _admin_branch_id: i32, user: &User) -> Result<User, DbError> {
+        if self.data.users.iter().any(|u| u.username == user.username) {
+            return Err(DbError::BadInput);
+        }
+        let mut user = user.clone();
+        user.user_id = next_id(self.data.users.iter().map(|u| u.user_id));
+        self.data.users.push(user.clone());
+        Ok(user)
+    }
+
+    async fn update_user(
+        &mut self,
+        admin_branch_id: i32,
+        username: &str,
+        new_passwordhash: Option<&str>,
+        role: Option<&str>,
+        branch_id: Option<i32>,
+    ) -> Result<User, DbError> {
+        let user = self
+            .data
+            .users
+            .iter_mut()
+            .find(|u| u.username == username)
+            .ok_or_else(not_found)?;
+        if user.branch_id != admin_branch_id {
+            return Err(DbError::NotPermitted);
+        }
+        if let Some(role) = role {
+            user.role = role.to_string();
+        }
+        user.branch_id = branch_id.unwrap_or(user.branch_id);
+        if let Some(passwordhash) = new_passwordhash {
+            user.passwordhash = passwordhash.to_string();
+        }
+        Ok(user.clone())
+    }
+}
+
+#[async_trait]
+impl EmployeeRepo for MemoryRepos {
+    async fn get_employees_by_branch(&mut self, _branch_id: i32) -> Result<Vec<Employee>, DbError> {
+        Err(DbError::Unsupported)
+    }
+
+    async fn update_employee(
+        &mut self,
+        _update: EmployeeUpdate,
+        _employee_id: i32,
+    ) -> Result<Employee, DbError> {
+        Err(DbError::Unsupported)
+    }
+}
+
+#[async_trait]
+impl BranchRepo for MemoryRepos {
+    async fn create_branch(
+        &mut self,
+        branch: Branch,
+        admin_username: &str,
+        admin_passwordhash: &str,
+    ) -> Result<Branch, DbError> {
+        let mut branch = branch;
+        branch.branch_id = next_id(self.data.branches.iter().map(|b| b.branch_id));
+        self.data.branches.push(branch.clone());
+        let admin = User {
+            user_id: None,
+            username: admin_username.to_string(),
+            passwordhash: admin_passwordhash.to_string(),
+            role: "admin".to_string(),
+            branch_id: branch.branch_id.unwrap(),
+        };
+        self.create_user(admin.branch_id, &admin).await?;
+        Ok(branch)
+    }
+
+    async fn update_branch(
+        &mut self,
+        branch_id: i32,
+        update: BranchUpdate,
+    ) -> Result<Branch, DbError> {
+        let branch = self
+            .data
+            .branches
+            .iter_mut()
+            .find(|b| b.branch_id == Some(branch_id) && b.closed_at.is_none())
+            .ok_or_else(not_found)?;
+        if let Some(address) = update.address {
+            branch.address = address;
+        }
+        if let Some(phone_number) = update.phone_number {
+            branch.phone_number = phone_number;
+        }
+        if let Some(postal_code) = update.postal_code {
+            branch.postal_code = postal_code;
+        }
+        branch.employee_count = update.employee_count.unwrap_or(branch.employee_count);
+        if let Some(vat_rate) = update.vat_rate {
+            branch.vat_rate = vat_rate;
+        }
+        if let Some(max_bonus_share) = update.max_bonus_share {
+            branch.max_bonus_share = max_bonus_share;
+        }
+        Ok(branch.clone())
+    }
+
+    async fn close_branch(&mut self, branch_id: i32) -> Result<Branch, DbError> {
+        let branch = self
+            .data
+            .branches
+            .iter_mut()
+            .find(|b| b.branch_id == Some(branch_id) && b.closed_at.is_none())
+            .ok_or_else(not_found)?;
+        branch.closed_at = Some(Utc::now());
+        Ok(branch.clone())
+    }
+
+    async fn reopen_branch(&mut self, branch_id: i32) -> Result<Branch, DbError> {
+        let branch = self
+            .data
+            .branches
+            .iter_mut()
+            .find(|b| b.branch_id == Some(branch_id))
+            .ok_or_else(not_found)?;
+        branch.closed_at = None;
+        Ok(branch.clone())
+    }
+
+    async fn get_branch_by_id(&mut self, branch_id: i32) -> Result<Branch, DbError> {
+        self.data
+            .branches
+            .iter()
+            .find(|b| b.branch_id == Some(branch_id))
+            .cloned()
+            .ok_or_else(not_found)
+    }
+
+    async fn get_branch(
+        &mut self,
+        city: Option<&str>,
+        include_closed: bool,
+    ) -> Result<Vec<Branch>, DbError> {
+        Ok(self
+            .data
+            .branches
+            .iter()
+            .filter(|b| city.is_none_or(|city| b.city == city))
+            .filter(|b| include_closed || b.closed_at.is_none())
+            .cloned()
+            .collect())
+    }
+}
+
+#[async_trait]
+impl OrderRepo for MemoryRepos {
+    async fn create_order(&mut self, order: Order) -> Result<Order, DbError> {
+        let mut order = order;
+        order.order_id = next_id(self.data.orders.iter().map(|o| o.order_id));
+        self.data.orders.push(order.clone());
+        Ok(order)
+    }
+
+    async fn update_order(
+        &mut self,
+        master_id: Option<i32>,
+        completion_date: Option<DateTime<Utc>>,
+        status: Option<String>,
+        order_id: i32,
+    ) -> Result<Order, DbError> {
+        let order = self
+            .data
+            .orders
+            .iter_mut()
+            .find(|o| o.order_id == Some(order_id) && o.deleted_at.is_none())
+            .ok_or_else(not_found)?;
+        order.master_id = master_id.unwrap_or(order.master_id);
+        order.completion_date = completion_date.or(order.completion_date);
+        order.status = status.unwrap_or(order.status.clone());
+        Ok(order.clone())
+    }
+
+    async fn get_orders(
+        &mut self,
+        branch_id: Option<i32>,
+        master_id: Option<i32>,
+        client_id: Option<i32>,
+    ) -> Result<Vec<Order>, DbError> {
+        Ok(self
+            .data
+            .orders
+            .iter()
+            .filter(|o| o.deleted_at.is_none())
+            .filter(|o| branch_id.is_none_or(|id| o.branch_id == id))
+            .filter(|o| master_id.is_none_or(|id| o.master_id == id))
+            .filter(|o| client_id.is_none_or(|id| o.client_id == id))
+            .cloned()
+            .collect())
+    }
+
+    async fn get_archived_orders(&mut self, branch_id: Option<i32>) -> Result<Vec<Order>, DbError> {
+        Ok(self
+            .data
+            .orders
+            .iter()
+            .filter(|o| o.deleted_at.is_some())
+            .filter(|o| branch_id.is_none_or(|id| o.branch_id == id))
+            .cloned()
+            .collect())
+    }
+
+    async fn delete_order(&mut self, order_id: i32) -> Result<(), DbError> {
+        let order = self
+            .data
+            .orders
+            .iter_mut()
+            .find(|o| o.order_id == Some(order_id) && o.deleted_at.is_none())
+            .ok_or_else(not_found)?;
+        order.deleted_at = Some(Utc::now());
+        Ok(())
+    }
+
+    async fn restore_order(&mut self, order_id: i32) -> Result<Order, DbError> {
+        let order = self
+            .data
+            .orders
+            .iter_mut()
+            .find(|o| o.order_id == Some(order_id) && o.deleted_at.is_some())
+            .ok_or_else(not_found)?;
+        order.deleted_at = None;
+        Ok(order.clone())
+    }
+
+    async fn refund_order(&mut self, _order_id: i32, _reason: &str) -> Result<Order, DbError> {
+        Err(DbError::Unsupported)
+    }
+
+    async fn close_order_for_pickup(
+        &mut self,
+        _order_id: i32,
+        _override_reason: Option<&str>,
+    ) -> Result<Order, DbError> {
+        Err(DbError::Unsupported)
+    }
+
+    async fn get_order_by_id(&mut self, order_id: i32) -> Result<Order, DbError> {
+        self.data
+            .orders
+            .iter()
+            .find(|o| o.order_id == Some(order_id))
+            .cloned()
+            .ok_or_else(not_found)
+    }
+
+    async fn update_order_pricing(
+        &mut self,
+        order_id: i32,
+        discount_type: Option<&str>,
+        discount_value: BigDecimal,
+        discount_reason: Option<&str>,
+        bonus_points_used: BigDecimal,
+    ) -> Result<Order, DbError> {
+        if !order_repriceable(&self.data, order_id) {
+            return Err(not_found());
+        }
+        let order = self
+            .data
+            .orders
+            .iter_mut()
+            .find(|o| o.order_id == Some(order_id))
+            .ok_or_else(not_found)?;
+        order.discount_type = discount_type.map(String::from);
+        order.discount_value = Some(discount_value);
+        order.discount_reason = discount_reason.map(String::from);
+        order.bonus_points_used = Some(bonus_points_used);
+        Ok(order.clone())
+    }
+
+    async fn discount_order_service(
+        &mut self,
+        order_service_id: i32,
+        discount_type: Option<&str>,
+        discount_value: BigDecimal,
+        discount_reason: Option<&str>,
+    ) -> Result<OrderService, DbError> {
+        if !line_repriceable(&self.data, order_service_id) {
+            return Err(not_found());
+        }
+        let line = self
+            .data
+            .order_services
+            .iter_mut()
+            .find(|l| l.order_service_id == Some(order_service_id))
+            .ok_or_else(not_found)?;
+        line.discount_type = discount_type.map(String::from);
+        line.discount_value = Some(discount_value);
+        line.discount_reason = discount_reason.map(String::from);
+        Ok(line.clone())
+    }
+
+    async fn discount_order_service_part(
+        &mut self,
+        order_service_part_id: i32,
+        discount_type: Option<&str>,
+        discount_value: BigDecimal,
+        discount_reason: Option<&str>,
+    ) -> Result<OrderServicePart, DbError> {
+        let repriceable = self
+            .data
+            .order_service_parts
+            .iter()
+            .find(|l| l.order_service_part_id == Some(order_service_part_id))
+            .is_some_and(|l| line_repriceable(&self.data, l.order_service_id));
+        if !repriceable {
+            return Err(not_found());
+        }
+        let line = self
+            .data
+            .order_service_parts
+            .iter_mut()
+            .find(|l| l.order_service_part_id == Some(order_service_part_id))
+            .ok_or_else(not_found)?;
+        line.discount_type = discount_type.map(String::from);
+        line.discount_value = Some(discount_value);
+        line.discount_reason = discount_reason.map(String::from);
+        Ok(line.clone())
+    }
+
+    async fn get_order_receipt(&mut self, order_id: i32) -> Result<Receipt, DbError> {
+        self.data
+            .receipts
+            .iter()
+            .filter(|r| r.order_id == order_id)
+            .max_by_key(|r| r.receipt_id)
+            .cloned()
+            .ok_or_else(not_found)
+    }
+
+    async fn get_receipt_lines(&mut self, receipt_id: i32) -> Result<Vec<ReceiptLine>, DbError> {
+        let mut lines: Vec<ReceiptLine> = self
+            .data
+            .receipt_lines
+            .iter()
+            .filter(|l| l.receipt_id == receipt_id)
+            .cloned()
+            .collect();
+        lines.sort_by_key(|l| l.line_number);
+        Ok(lines)
+    }
+}
+
+#[async_trait]
+impl ClientRepo for MemoryRepos {
+    async fn get_client_by_id(&mut self, client_id: i32) -> Result<Client, DbError> {
+        self.data
+            .clients
+            .iter()
+            .find(|c| c.client_id == Some(client_id))
+            .cloned()
+            .ok_or_else(not_found)
+    }
+
+    async fn get_archived_clients(&mut self) -> Result<Vec<Client>, DbError> {
+        Ok(self
+            .data
+            .clients
+            .iter()
+            .filter(|c| c.deleted_at.is_some())
+            .cloned()
+            .collect())
+    }
+
+    async fn delete_client(&mut self, client_id: i32) -> Result<(), DbError> {
+        let client = self
+            .data
+            .clients
+            .iter_mut()
+            .find(|c| c.client_id == Some(client_id) && c.deleted_at.is_none())
+            .ok_or_else(not_found)?;
+        client.deleted_at = Some(Utc::now());
+        Ok(())
+    }
+
+    async fn restore_client(&mut self, client_id: i32) -> Result<Client, DbError> {
+        let client = self
+            .data
+            .clients
+            .iter_mut()
+            .find(|c| c.client_id == Some(client_id) && c.deleted_at.is_some())
+            .ok_or_else(not_found)?;
+        client.deleted_at = None;
+        Ok(client.clone())
+    }
+
+    async fn share_client(
+        &mut self,
+        client_id: i32,
+        branch_id: i32,
+    ) -> Result<ClientBranch, DbError> {
+        let shared = ClientBranch {
+            client_id,
+            branch_id,
+        };
+        if !self
+            .data
+            .client_branches
+            .iter()
+            .any(|cb| cb.client_id == client_id && cb.branch_id == branch_id)
+        {
+            self.data.client_branches.push(shared.clone());
+        }
+        Ok(shared)
+    }
+
+    async fn unshare_client(&mut self, client_id: i32, branch_id: i32) -> Result<(), DbError> {
+        self.data
+            .client_branches
+            .retain(|cb| !(cb.client_id == client_id && cb.branch_id == branch_id));
+        Ok(())
+    }
+
+    async fn update_client_contacts(
+        &mut self,
+        client_id: i32,
+        contacts: &ClientContacts,
+    ) -> Result<Client, DbError> {
+        let client = self
+            .data
+            .clients
+            .iter_mut()
+            .find(|c| c.client_id == Some(client_id) && c.deleted_at.is_none())
+            .ok_or_else(not_found)?;
+        client.phones = contacts.phones.clone();
+        client.email = contacts.email.clone();
+        client.preferred_channel = contacts.preferred_channel.clone();
+        client.service_consent = contacts.service_consent;
+        client.marketing_consent = contacts.marketing_consent;
+        client.contact_note = contacts.contact_note.clone();
+        Ok(client.clone())
+    }
+}
+
+#[async_trait]
+impl CatalogRepo for MemoryRepos {
+    async fn get_services(&mut self) -> Result<Vec<Service>, DbError> {
+        Ok(self
+            .data
+            .services
+            .iter()
+            .filter(|s| s.deleted_at.is_none())
+            .cloned()
+            .collect())
+    }
+
+    async fn set_service_intervals(
+        &mut self,
+        service_id: i32,
+        interval_months: Option<i32>,
+        interval_km: Option<i32>,
+    ) -> Result<Service, DbError> {
+        if interval_months.is_some_and(|m| m <= 0) || interval_km.is_some_and(|km| km <= 0) {
+            return Err(DbError::BadInput);
+        }
+        let service = self
+            .data
+            .services
+            .iter_mut()
+            .find(|s| s.service_id == Some(service_id) && s.deleted_at.is_none())
+            .ok_or_else(not_found)?;
+        service.interval_months = interval_months;
+        service.interval_km = interval_km;
+        Ok(service.clone())
+    }
+
+    async fn get_archived_service(&mut self) -> Result<Vec<Service>, DbError> {
+        Ok(self
+            .data
+            .services
+            .iter()
+            .filter(|s| s.deleted_at.is_some())
+            .cloned()
+            .collect())
+    }
+
+    async fn delete_service(&mut self, service_id: i32) -> Result<(), DbError> {
+        let service = self
+            .data
+            .services
+            .iter_mut()
+            .find(|s| s.service_id == Some(service_id) && s.deleted_at.is_none())
+            .ok_or_else(not_found)?;
+        service.deleted_at = Some(Utc::now());
+        Ok(())
+    }
+
+    async fn restore_service(&mut self, service_id: i32) -> Result<Service, DbError> {
+        let service = self
+            .data
+            .services
+            .iter_mut()
+            .find(|s| s.service_id == Some(service_id) && s.deleted_at.is_some())
+            .ok_or_else(not_found)?;
+        service.deleted_at = None;
+        Ok(service.clone())
+    }
+
+    async fn get_archived_spare_part(&mut self) -> Result<Vec<SparePart>, DbError> {
+        Ok(self
+            .data
+            .spare_parts
+            .iter()
+            .filter(|p| p.deleted_at.is_some())
+            .cloned()
+            .collect())
+    }
+
+    async fn delete_spare_part(&mut self, part_id: i32) -> Result<(), DbError> {
+        let part = self
+            .data
+            .spare_parts
+            .iter_mut()
+            .find(|p| p.part_id == Some(part_id) && p.deleted_at.is_none())
+            .ok_or_else(not_found)?;
+        part.deleted_at = Some(Utc::now());
+        Ok(())
+    }
+
+    async fn restore_spare_part(&mut self, part_id: i32) -> Result<SparePart, DbError> {
+        let part = self
+            .data
+            .spare_parts
+            .iter_mut()
+            .find(|p| p.part_id == Some(part_id) && p.deleted_at.is_some())
+            .ok_or_else(not_found)?;
+        part.deleted_at = None;
+        Ok(part.clone())
+    }
+
+    async fn get_service_prices(&mut self, branch_id: i32) -> Result<Vec<ServiceBranch>, DbError> {
+        Ok(self
+            .data
+            .service_branches
+            .iter()
+            .filter(|p| p.branch_id == branch_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn get_part_prices(&mut self, branch_id: i32) -> Result<Vec<SparePartBranch>, DbError> {
+        Ok(self
+            .data
+            .spare_part_branches
+            .iter()
+            .filter(|p| p.branch_id == branch_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn get_price_history(
+        &mut self,
+        item_type: Option<&str>,
+        item_id: Option<i32>,
+        branch_id: Option<i32>,
+        limit: i64,
+    ) -> Result<Vec<PriceHistory>, DbError> {
+        Ok(self
+            .data
+            .price_history
+            .iter()
+            .rev()
+            .filter(|e| item_type.is_none_or(|v| e.item_type == v))
+            .filter(|e| item_id.is_none_or(|v| e.item_id == v))
+            .filter(|e| branch_id.is_none_or(|v| e.branch_id == v))
+            .take(limit as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn get_spare_parts(&mut self) -> Result<Vec<SparePart>, DbError> {
+        Ok(self
+            .data
+            .spare_parts
+            .iter()
+            .filter(|p| p.deleted_at.is_none())
+            .cloned()
+            .collect())
+    }
+
+    async fn update_part_details(
+        &mut self,
+        part_id: i32,
+        sku: Option<&str>,
+        oem_number: Option<&str>,
+        manufacturer: Option<&str>,
+        unit: &str,
+    ) -> Result<SparePart, DbError> {
+        if !PART_UNITS.contains(&unit) {
+            return Err(DbError::BadInput);
+        }
+        if sku.is_some()
+            && self
+                .data
+                .spare_parts
+                .iter()
+                .any(|p| p.part_id != Some(part_id) && p.sku.as_deref() == sku)
+        {
+            return Err(DbError::BadInput);
+        }
+        let part = self
+            .data
+            .spare_parts
+            .iter_mut()
+            .find(|p| p.part_id == Some(part_id) && p.deleted_at.is_none())
+            .ok_or_else(not_found)?;
+        part.sku = sku.map(String::from);
+        part.oem_number = oem_number.map(String::from);
+        part.manufacturer = manufacturer.map(String::from);
+        part.unit = unit.to_string();
+        Ok(part.clone())
+    }
+
+    async fn get_part_compatibility(
+        &mut self,
+        part_id: i32,
+    ) -> Result<Vec<PartCompatibility>, DbError> {
+        Ok(self
+            .data
+            .part_compatibility
+            .iter()
+            .filter(|c| c.part_id == part_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn add_part_compatibility(
+        &mut self,
+        mut compatibility: PartCompatibility,
+    ) -> Result<PartCompatibility, DbError> {
+        if let (Some(from), Some(to)) = (compatibility.year_from, compatibility.year_to) {
+            if from > to {
+                return Err(DbError::BadInput);
+            }
+        }
+        if !self
+            .data
+            .spare_parts
+            .iter()
+            .any(|p| p.part_id == Some(compatibility.part_id))
+        {
+            return Err(DbError::BadInput);
+        }
+        compatibility.compatibility_id =
+            next_id(self.data.part_compatibility.iter().map(|c| c.compatibility_id));
+        self.data.part_compatibility.push(compatibility.clone());
+        Ok(compatibility)
+    }
+
+    async fn remove_part_compatibility(&mut self, compatibility_id: i32) -> Result<(), DbError> {
+        self.data
+            .part_compatibility
+            .retain(|c| c.compatibility_id != Some(compatibility_id));
+        Ok(())
+    }
+
+    async fn get_part_substitutes(&mut self, part_id: i32) -> Result<Vec<SparePart>, DbError> {
+        let ids = substitutes_of(&self.data.part_substitutes, part_id);
+        Ok(self
+            .data
+            .spare_parts
+            .iter()
+            .filter(|p| p.deleted_at.is_none() && p.part_id.is_some_and(|id| ids.contains(&id)))
+            .cloned()
+            .collect())
+    }
+
+    async fn link_part_substitute(
+        &mut self,
+        part_id: i32,
+        substitute_id: i32,
+    ) -> Result<(), DbError> {
+        if part_id == substitute_id {
+            return Err(DbError::BadInput);
+        }
+        let pair = (part_id.min(substitute_id), part_id.max(substitute_id));
+        if !self.data.part_substitutes.contains(&pair) {
+            self.data.part_substitutes.push(pair);
+        }
+        Ok(())
+    }
+
+    async fn unlink_part_substitute(
+        &mut self,
+        part_id: i32,
+        substitute_id: i32,
+    ) -> Result<(), DbError> {
+        let pair = (part_id.min(substitute_id), part_id.max(substitute_id));
+        self.data.part_substitutes.retain(|p| *p != pair);
+        Ok(())
+    }
+
+    async fn find_parts(
+        &mut self,
+        _query: &str,
+        _vehicle_id: Option<i32>,
+        _branch_id: i32,
+        _limit: i32,
+    ) -> Result<Vec<PartMatch>, DbError> {
+        Err(DbError::Unsupported)
+    }
+}
+
+fn substitutes_of(pairs: &[(i32, i32)], part_id: i32) -> Vec<i32> {
+    pairs
+        .iter()
+        .filter_map(|&(a, b)| match part_id {
+            id if id == a => Some(b),
+            id if id == b => Some(a),
+            _ => None,
+        })
+        .collect()
+}
+
+#[async_trait]
+impl LoyaltyRepo for MemoryRepos {
+    async fn get_loyalty_tiers(&mut self) -> Result<Vec<LoyaltyTier>, DbError> {
+        let mut tiers = self.data.loyalty_tiers.clone();
+        tiers.sort_by(|a, b| a.spend_threshold.cmp(&b.spend_threshold));
+        Ok(tiers)
+    }
+
+    async fn create_loyalty_tier(&mut self, mut tier: LoyaltyTier) -> Result<LoyaltyTier, DbError> {
+        if self.data.loyalty_tiers.iter().any(|t| t.name == tier.name) {
+            return Err(DbError::BadInput);
+        }
+        tier.tier_id = next_id(self.data.loyalty_tiers.iter().map(|t| t.tier_id));
+        self.data.loyalty_tiers.push(tier.clone());
+        Ok(tier)
+    }
+
+    async fn update_loyalty_tier(
+        &mut self,
+        tier_id: i32,
+        name: Option<&str>,
+        spend_threshold: Option<BigDecimal>,
+        accrual_rate: Option<BigDecimal>,
+        points_lifetime_days: Option<i32>,
+    ) -> Result<LoyaltyTier, DbError> {
+        let tier = self
+            .data
+            .loyalty_tiers
+            .iter_mut()
+            .find(|t| t.tier_id == Some(tier_id))
+            .ok_or_else(not_found)?;
+        if let Some(name) = name {
+            tier.name = name.to_string();
+        }
+        if let Some(spend_threshold) = spend_threshold {
+            tier.spend_threshold = spend_threshold;
+        }
+        if let Some(accrual_rate) = accrual_rate {
+            tier.accrual_rate = accrual_rate;
+        }
+        if let Some(points_lifetime_days) = points_lifetime_days {
+            tier.points_lifetime_days = points_lifetime_days;
+        }
+        Ok(tier.clone())
+    }
+
+    async fn get_bonus_statement(
+        &mut self,
+        client_id: i32,
+    ) -> Result<Vec<BonusTransaction>, DbError> {
+        Ok(self
+            .data
+            .bonus_transactions
+            .iter()
+            .rev()
+            .filter(|t| t.client_id == client_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn adjust_bonus_points(
+        &mut self,
+        client_id: i32,
+        points: BigDecimal,
+        reason: &str,
+    ) -> Result<BonusTransaction, DbError> {
+        if reason.is_empty() {
+            return Err(DbError::BadInput);
+        }
+        let client = self
+            .data
+            .clients
+            .iter_mut()
+            .find(|c| c.client_id == Some(client_id))
+            .ok_or_else(not_found)?;
+        let balance = client.bonus_points.clone().unwrap_or_default() + &points;
+        if balance < BigDecimal::from(0) {
+            return Err(DbError::BadInput);
+        }
+        client.bonus_points = Some(balance);
+        let now = Utc::now();
+        let entry = BonusTransaction {
+            transaction_id: self.data.bonus_transactions.len() as i64 + 1,
+            client_id,
+            order_id: None,
+            kind: "adjustment".to_string(),
+            remaining: points.clone().max(BigDecimal::from(0)),
+            expires_at: (points > BigDecimal::from(0)).then(|| now + chrono::Duration::days(365)),
+            points,
+            created_at: now,
+            actor_user_id: None,
+            reason: Some(reason.to_string()),
+        };
+        self.data.bonus_transactions.push(entry.clone());
+        Ok(entry)
+    }
+}
+
+#[async_trait]
+impl PaymentRepo for MemoryRepos {
+    async fn create_payment(
+        &mut self,
+        _order_id: i32,
+        _amount: BigDecimal,
+        _method: &str,
+    ) -> Result<Payment, DbError> {
+        Err(DbError::Unsupported)
+    }
+
+    async fn get_order_payments(&mut self, _order_id: i32) -> Result<Vec<Payment>, DbError> {
+        Err(DbError::Unsupported)
+    }
+
+    async fn get_order_outstanding(&mut self, _order_id: i32) -> Result<BigDecimal, DbError> {
+        Err(DbError::Unsupported)
+    }
+
+    async fn get_client_outstanding(&mut self, _client_id: i32) -> Result<BigDecimal, DbError> {
+        Err(DbError::Unsupported)
+    }
+
+    async fn open_cash_shift(
+        &mut self,
+        _branch_id: i32,
+        _opening_cash: BigDecimal,
+    ) -> Result<CashShift, DbError> {
+        Err(DbError::Unsupported)
+    }
+
+    async fn close_cash_shift(
+        &mut self,
+        _shift_id: i32,
+        _counted_cash: BigDecimal,
+    ) -> Result<CashShift, DbError> {
+        Err(DbError::Unsupported)
+    }
+
+    async fn get_cash_shift(&mut self, _shift_id: i32) -> Result<CashShift, DbError> {
+        Err(DbError::Unsupported)
+    }
+
+    async fn get_cash_shifts(&mut self, _branch_id: i32) -> Result<Vec<CashShift>, DbError> {
+        Err(DbError::Unsupported)
+    }
+
+    async fn get_shift_totals(&mut self, _shift_id: i32) -> Result<Vec<ShiftTotal>, DbError> {
+        Err(DbError::Unsupported)
+    }
+}
+
+#[async_trait]
+impl VehicleRepo for MemoryRepos {
+    async fn create_vehicle(&mut self, mut vehicle: Vehicle) -> Result<Vehicle, DbError> {
+        if vehicle.vin.is_some()
+            && self
+                .data
+                .vehicles
+                .iter()
+                .any(|v| v.vin == vehicle.vin && v.deleted_at.is_none())
+        {
+            return Err(DbError::BadInput);
+        }
+        vehicle.vehicle_id = next_id(self.data.vehicles.iter().map(|v| v.vehicle_id));
+        self.data.vehicles.push(vehicle.clone());
+        Ok(vehicle)
+    }
+
+    async fn update_vehicle(&mut self, vehicle: Vehicle) -> Result<Vehicle, DbError> {
+        let stored = self
+            .data
+            .vehicles
+            .iter_mut()
+            .find(|v| v.vehicle_id == vehicle.vehicle_id && v.deleted_at.is_none())
+            .ok_or_else(not_found)?;
+        if vehicle.mileage < stored.mileage {
+            return Err(DbError::BadInput);
+        }
+        *stored = Vehicle {
+            client_id: stored.client_id,
+            ..vehicle
+        };
+        Ok(stored.clone())
+    }
+
+    async fn delete_vehicle(&mut self, vehicle_id: i32) -> Result<(), DbError> {
+        if let Some(vehicle) = self
+            .data
+            .vehicles
+            .iter_mut()
+            .find(|v| v.vehicle_id == Some(vehicle_id) && v.deleted_at.is_none())
+        {
+            vehicle.deleted_at = Some(Utc::now());
+        }
+        Ok(())
+    }
+
+    async fn get_vehicle_by_id(&mut self, vehicle_id: i32) -> Result<Vehicle, DbError> {
+        self.data
+            .vehicles
+            .iter()
+            .find(|v| v.vehicle_id == Some(vehicle_id))
+            .cloned()
+            .ok_or_else(not_found)
+    }
+
+    async fn get_client_vehicles(&mut self, client_id: i32) -> Result<Vec<Vehicle>, DbError> {
+        Ok(self
+            .data
+            .vehicles
+            .iter()
+            .filter(|v| v.client_id == client_id && v.deleted_at.is_none())
+            .cloned()
+            .collect())
+    }
+
+    async fn get_vehicle_history(
+        &mut self,
+        vehicle_id: i32,
+    ) -> Result<Vec<ServiceHistoryEntry>, DbError> {
+        let mut orders: Vec<&Order> = self
+            .data
+            .orders
+            .iter()
+            .filter(|o| o.vehicle_id == Some(vehicle_id) && o.deleted_at.is_none())
+            .collect();
+        orders.sort_by_key(|o| std::cmp::Reverse((o.order_date, o.order_id)));
+        let mut entries = Vec::new();
+        for order in orders {
+            let entry = |item_type: &str, name: &str, quantity: i32| ServiceHistoryEntry {
+                order_id: order.order_id.unwrap_or_default(),
+                order_date: order.order_date,
+                completion_date: order.completion_date,
+                status: order.status.clone(),
+                branch_id: order.branch_id,
+                master_id: order.master_id,
+                intake_mileage: order.intake_mileage,
+                item_type: item_type.to_string(),
+                name: name.to_string(),
+                quantity,
+            };
+            for service in self
+                .data
+                .order_services
+                .iter()
+                .filter(|s| Some(s.order_id) == order.order_id)
+            {
+                entries.push(entry("service", service.service_name.as_deref().unwrap_or_default(), 1));
+                for part in self
+                    .data
+                    .order_service_parts
+                    .iter()
+                    .filter(|p| Some(p.order_service_id) == service.order_service_id)
+                {
+                    entries.push(entry(
+                        "spare_part",
+                        part.part_name.as_deref().unwrap_or_default(),
+                        part.quantity,
+                    ));
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    async fn assign_order_vehicle(
+        &mut self,
+        order_id: i32,
+        vehicle_id: i32,
+        intake_mileage: Option<i32>,
+    ) -> Result<Order, DbError> {
+        let order = self
+            .data
+            .orders
+            .iter_mut()
+            .find(|o| o.order_id == Some(order_id) && o.deleted_at.is_none())
+            .ok_or_else(not_found)?;
+        let vehicle = self
+            .data
+            .vehicles
+            .iter_mut()
+            .find(|v| {
+                v.vehicle_id == Some(vehicle_id)
+                    && v.client_id == order.client_id
+                    && v.deleted_at.is_none()
+            })
+            .ok_or(DbError::BadInput)?;
+        if let Some(mileage) = intake_mileage {
+            vehicle.mileage = vehicle.mileage.max(mileage);
+        }
+        order.vehicle_id = Some(vehicle_id);
+        order.intake_mileage = intake_mileage;
+        Ok(order.clone())
+    }
+
+    async fn get_maintenance_reminders(&mut self) -> Result<Vec<MaintenanceReminder>, DbError> {
+        Ok(self.data.maintenance_reminders.clone())
+    }
+
+    async fn dismiss_maintenance_reminder(&mut self, reminder_id: i32) -> Result<(), DbError> {
+        let index = self
+            .data
+            .maintenance_reminders
+            .iter()
+            .position(|r| r.reminder_id == reminder_id)
+            .ok_or_else(not_found)?;
+        self.data.maintenance_reminders.remove(index);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AuditRepo for MemoryRepos {
+    async fn get_audit_log(
+        &mut self,
+        entity: Option<&str>,
+        entity_id: Option<i32>,
+        actor_user_id: Option<i32>,
+        operation: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<AuditLog>, DbError> {
+        Ok(self
+            .data
+            .audit_log
+            .iter()
+            .rev()
+            .filter(|a| entity.is_none_or(|v| a.entity == v))
+            .filter(|a| entity_id.is_none_or(|v| a.entity_id == v))
+            .filter(|a| actor_user_id.is_none_or(|v| a.actor_user_id == Some(v)))
+            .filter(|a| operation.is_none_or(|v| a.operation == v))
+            .take(limit as usize)
+            .cloned()
+            .collect())
+    }
+}
+
+#[async_trait]
+impl SearchRepo for MemoryRepos {
+    async fn search(&mut self, _query: &str, _limit: i32) -> Result<Vec<SearchResult>, DbError> {
+        Err(DbError::Unsupported)
+    }
+}
+
+#[async_trait]
+impl StockRepo for MemoryRepos {
+    async fn get_stock_levels(
+        &mut self,
+        _branch_id: i32,
+        _low_only: bool,
+    ) -> Result<Vec<StockLevel>, DbError> {
+        Err(DbError::Unsupported)
+    }
+
+    async fn set_reorder_level(
+        &mut self,
+        _branch_id: i32,
+        _part_id: i32,
+        _reorder_point: Option<i32>,
+        _reorder_quantity: Option<i32>,
+    ) -> Result<SparePartBranch, DbError> {
+        Err(DbError::Unsupported)
+    }
+
+    async fn get_open_purchase_orders(
+        &mut self,
+        _branch_id: i32,
+    ) -> Result<Vec<PurchaseOrder>, DbError> {
+        Err(DbError::Unsupported)
+    }
+
+    async fn get_purchase_order(
+        &mut self,
+        _purchase_order_id: i32,
+    ) -> Result<PurchaseOrder, DbError> {
+        Err(DbError::Unsupported)
+    }
+
+    async fn get_purchase_order_lines(
+        &mut self,
+        _purchase_order_id: i32,
+    ) -> Result<Vec<PurchaseOrderLine>, DbError> {
+        Err(DbError::Unsupported)
+    }
+
+    async fn update_purchase_order_line(
+        &mut self,
+        _purchase_order_line_id: i32,
+        _quantity: i32,
+    ) -> Result<PurchaseOrderLine, DbError> {
+        Err(DbError::Unsupported)
+    }
+
+    async fn remove_purchase_order_line(
+        &mut self,
+        _purchase_order_line_id: i32,
+    ) -> Result<(), DbError> {
+        Err(DbError::Unsupported)
+    }
+
+    async fn set_purchase_order_status(
+        &mut self,
+        _purchase_order_id: i32,
+        _status: &str,
+    ) -> Result<PurchaseOrder, DbError> {
+        Err(DbError::Unsupported)
+    }
+}
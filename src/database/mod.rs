@@ -1,24 +1,86 @@
+pub mod audit;
 pub mod branch;
 pub mod branch_employee;
+pub mod cash_shift;
 pub mod client;
 pub mod employee;
+pub mod loyalty;
+#[cfg(test)]
+pub mod memory;
 pub mod order_service;
 pub mod order_service_part;
 pub mod orders;
+pub mod outbox;
+pub mod part_compatibility;
+pub mod payment;
+pub mod price_history;
+pub mod receipt;
+pub mod reminder;
+pub mod repo;
+pub mod retention;
 pub mod schedule;
+pub mod search;
 pub mod service;
 pub mod service_branch;
 pub mod spare_part;
 pub mod spare_part_branch;
+pub mod stock;
 pub mod user;
+pub mod vehicle;
 
-use sqlx::{Pool, Postgres};
+use sqlx::{PgConnection, Pool, Postgres, Transaction};
+
+use crate::models::User;
 
 pub type DbPool = Pool<Postgres>;
+pub type DbConn = PgConnection;
+pub type DbTransaction = Transaction<'static, Postgres>;
 
 #[derive(Debug)]
 pub enum DbError {
     Sqlx(sqlx::Error),
     NotPermitted,
     BadInput,
+    /// The in-memory test store leaves the operation to the database.
+    #[cfg(test)]
+    Unsupported,
+}
+
+fn db_role(app_role: &str) -> Option<&'static str> {
+    match app_role {
+        "superadmin" => Some("superadmin_role"),
+        "admin" => Some("admin"),
+        "analyst" => Some("analyst"),
+        "master" => Some("master"),
+        "manager" => Some("manager"),
+        _ => None,
+    }
+}
+
+/// Starts a transaction on behalf of an application user, see [`act_as`].
+pub async fn begin_as(pool: &DbPool, user: &User) -> Result<DbTransaction, DbError> {
+    let mut tx = pool.begin().await.map_err(DbError::Sqlx)?;
+    act_as(&mut tx, user).await?;
+    Ok(tx)
+}
+
+/// Switches the current transaction to the database role matching the user's
+/// role and sets `moto_auto.actor`, which row-level security policies and
+/// audit triggers read until the transaction ends.
+pub async fn act_as(tx: &mut DbConn, user: &User) -> Result<(), DbError> {
+    let role = db_role(&user.role).ok_or(DbError::NotPermitted)?;
+    sqlx::query(&format!("SET LOCAL ROLE {}", role))
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::Sqlx)?;
+    sqlx::query!(
+        r#"
+        SELECT set_config('moto_auto.actor', $1, true)
+        "#,
+        user.user_id.unwrap_or_default().to_string()
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(DbError::Sqlx)
+    .map(|_| {})
 }
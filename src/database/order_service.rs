@@ -1,23 +1,59 @@
-use crate::database::{DbError, DbPool};
+use bigdecimal::BigDecimal;
+
+use crate::database::{DbConn, DbError};
 use crate::models::OrderService;
 
-pub async fn create_order_service(pool: &DbPool, order_service: OrderService) -> Result<OrderService, DbError> {
+pub async fn create_order_service(conn: &mut DbConn, order_service: OrderService) -> Result<OrderService, DbError> {
     sqlx::query_as!(
         OrderService,
         r#"
         INSERT INTO moto_auto.order_service (order_id, service_id)
         VALUES ($1, $2)
-        RETURNING order_service_id, order_id, service_id
+        RETURNING order_service_id, order_id, service_id, unit_price, service_name,
+            discount_type, discount_value, discount_reason
         "#,
         order_service.order_id,
         order_service.service_id
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
 }
 
-pub async fn delete_order_service(pool: &DbPool, order_id: i32, service_id: i32) -> Result<(), DbError> {
+pub async fn discount_order_service(
+    conn: &mut DbConn,
+    order_service_id: i32,
+    discount_type: Option<&str>,
+    discount_value: BigDecimal,
+    discount_reason: Option<&str>,
+) -> Result<OrderService, DbError> {
+    sqlx::query_as!(
+        OrderService,
+        r#"
+        UPDATE moto_auto.order_service
+        SET
+            discount_type = $2,
+            discount_value = $3,
+            discount_reason = $4
+        WHERE order_service_id = $1
+        AND order_id IN (
+            SELECT order_id FROM moto_auto.orders
+            WHERE status = 'processing' AND deleted_at IS NULL
+        )
+        RETURNING order_service_id, order_id, service_id, unit_price, service_name,
+            discount_type, discount_value, discount_reason
+        "#,
+        order_service_id,
+        discount_type,
+        discount_value,
+        discount_reason
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+pub async fn delete_order_service(conn: &mut DbConn, order_id: i32, service_id: i32) -> Result<(), DbError> {
     sqlx::query!(
         r#"
         DELETE FROM moto_auto.order_service
@@ -26,14 +62,14 @@ pub async fn delete_order_service(pool: &DbPool, order_id: i32, service_id: i32)
         order_id,
         service_id
     )
-    .execute(pool)
+    .execute(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
     .map(|_| {})
 }
 
 pub async fn get_order_service(
-    pool: &DbPool,
+    conn: &mut DbConn,
     order_id: Option<i32>,
     service_id: Option<i32>,
 ) -> Result<Vec<OrderService>, DbError> {
@@ -47,7 +83,7 @@ pub async fn get_order_service(
                 "#,
                 id
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         },
@@ -60,7 +96,7 @@ pub async fn get_order_service(
                 "#,
                 id
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         },
@@ -71,7 +107,7 @@ pub async fn get_order_service(
                 SELECT * FROM moto_auto.order_service
                 "#,
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         },
@@ -85,7 +121,7 @@ pub async fn get_order_service(
                 order_id,
                 service_id
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         }
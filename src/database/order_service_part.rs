@@ -1,12 +1,12 @@
 use bigdecimal::BigDecimal;
 
-use crate::database::{DbError, DbPool};
+use crate::database::{DbConn, DbError};
 use crate::models::OrderServicePart;
 
 use super::order_service;
 
 pub async fn create_order_service_part(
-    pool: &DbPool,
+    conn: &mut DbConn,
     order_service_part: OrderServicePart,
 ) -> Result<OrderServicePart, DbError> {
     sqlx::query_as!(
@@ -14,19 +14,20 @@ pub async fn create_order_service_part(
         r#"
         INSERT INTO moto_auto.order_service_part (part_id, order_service_id, quantity)
         VALUES ($1, $2, $3)
-        RETURNING order_service_part_id, part_id, order_service_id, quantity
+        RETURNING order_service_part_id, part_id, order_service_id, quantity, unit_price, part_name,
+            discount_type, discount_value, discount_reason
         "#,
         order_service_part.part_id,
         order_service_part.order_service_id,
         order_service_part.quantity
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
 }
 
 pub async fn update_order_service_part(
-    pool: &DbPool,
+    conn: &mut DbConn,
     quantity: Option<i32>,
     order_service_part_id: i32,
 ) -> Result<OrderServicePart, DbError> {
@@ -37,18 +38,55 @@ pub async fn update_order_service_part(
         SET
             quantity = COALESCE($1, quantity)
         WHERE order_service_part_id = $2
-        RETURNING order_service_part_id, part_id, order_service_id, quantity
+        RETURNING order_service_part_id, part_id, order_service_id, quantity, unit_price, part_name,
+            discount_type, discount_value, discount_reason
         "#,
         quantity,
         order_service_part_id
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
 }
 
+pub async fn discount_order_service_part(
+    conn: &mut DbConn,
+    order_service_part_id: i32,
+    discount_type: Option<&str>,
+    discount_value: BigDecimal,
+    discount_reason: Option<&str>,
+) -> Result<OrderServicePart, DbError> {
+    sqlx::query_as!(
+        OrderServicePart,
+        r#"
+        UPDATE moto_auto.order_service_part
+        SET
+            discount_type = $2,
+            discount_value = $3,
+            discount_reason = $4
+        WHERE order_service_part_id = $1
+        AND order_service_id IN (
+            SELECT os.order_service_id
+            FROM moto_auto.order_service os
+            INNER JOIN moto_auto.orders o
+            ON o.order_id = os.order_id
+            WHERE o.status = 'processing' AND o.deleted_at IS NULL
+        )
+        RETURNING order_service_part_id, part_id, order_service_id, quantity, unit_price, part_name,
+            discount_type, discount_value, discount_reason
+        "#,
+        order_service_part_id,
+        discount_type,
+        discount_value,
+        discount_reason
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
 pub async fn delete_order_service_part(
-    pool: &DbPool,
+    conn: &mut DbConn,
     order_service_part_id: i32,
 ) -> Result<(), DbError> {
     sqlx::query!(
@@ -58,14 +96,14 @@ pub async fn delete_order_service_part(
         "#,
         order_service_part_id
     )
-    .execute(pool)
+    .execute(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
     .map(|_| {})
 }
 
 pub async fn get_order_service_part(
-    pool: &DbPool,
+    conn: &mut DbConn,
     part_id: Option<i32>,
     order_service_id: Option<i32>,
 ) -> Result<Vec<OrderServicePart>, DbError> {
@@ -79,7 +117,7 @@ pub async fn get_order_service_part(
                 "#,
                 id
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         },
@@ -92,7 +130,7 @@ pub async fn get_order_service_part(
                 "#,
                 id
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         },
@@ -106,7 +144,7 @@ pub async fn get_order_service_part(
                 part_id,
                 order_service_id
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         },
@@ -117,7 +155,7 @@ pub async fn get_order_service_part(
                 SELECT * FROM moto_auto.order_service_part
                 "#,
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         }
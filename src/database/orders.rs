@@ -1,15 +1,21 @@
+use bigdecimal::BigDecimal;
 use chrono;
 
-use crate::database::{DbError, DbPool};
+use crate::database::{DbConn, DbError};
 use crate::models::Order;
 
-pub async fn create_order(pool: &DbPool, order: Order) -> Result<Order, DbError> {
+pub async fn create_order(conn: &mut DbConn, order: Order) -> Result<Order, DbError> {
     sqlx::query_as!(
         Order,
         r#"
-        INSERT INTO moto_auto.orders (client_id, branch_id, master_id, order_date, completion_date, total_amount, status)
-        VALUES ($1, $2, $3, $4, $5, $6, $7)
-        RETURNING order_id, client_id, branch_id, master_id, order_date, completion_date, total_amount, status
+        INSERT INTO moto_auto.orders (
+            client_id, branch_id, master_id, order_date, completion_date, total_amount, status, vehicle_id, intake_mileage
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        RETURNING order_id, client_id, branch_id, master_id, order_date, completion_date, total_amount, status, deleted_at,
+            discount_type, discount_value, discount_reason, subtotal, discount_amount, vat_rate, tax_amount, bonus_points_used,
+            refunded_at, refund_reason, picked_up_at, pickup_override_reason, pickup_override_by, vehicle_id,
+            intake_mileage
         "#,
         order.client_id,
         order.branch_id,
@@ -17,15 +23,17 @@ pub async fn create_order(pool: &DbPool, order: Order) -> Result<Order, DbError>
         order.order_date,
         order.completion_date,
         order.total_amount,
-        order.status
+        order.status,
+        order.vehicle_id,
+        order.intake_mileage
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
 }
 
 pub async fn update_order(
-    pool: &DbPool,
+    conn: &mut DbConn,
     master_id: Option<i32>,
     completion_date: Option<chrono::DateTime<chrono::Utc>>,
     status: Option<String>,
@@ -39,35 +47,202 @@ pub async fn update_order(
             master_id = COALESCE($1, master_id),
             completion_date = COALESCE($2, completion_date),
             status = COALESCE($3, status)
-        WHERE order_id = $4
-        RETURNING order_id, client_id, branch_id, master_id, order_date, completion_date, total_amount, status
+        WHERE order_id = $4 AND deleted_at IS NULL
+        RETURNING order_id, client_id, branch_id, master_id, order_date, completion_date, total_amount, status, deleted_at,
+            discount_type, discount_value, discount_reason, subtotal, discount_amount, vat_rate, tax_amount, bonus_points_used,
+            refunded_at, refund_reason, picked_up_at, pickup_override_reason, pickup_override_by, vehicle_id,
+            intake_mileage
         "#,
         master_id,
         completion_date,
         status,
         order_id
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
 }
 
-pub async fn delete_order(pool: &DbPool, order_id: i32) -> Result<(), DbError> {
+/// Sets the order discount and the bonus points to redeem on an order in progress. The
+/// database recomputes the breakdown and caps the points at the branch's share of the order.
+pub async fn update_order_pricing(
+    conn: &mut DbConn,
+    order_id: i32,
+    discount_type: Option<&str>,
+    discount_value: BigDecimal,
+    discount_reason: Option<&str>,
+    bonus_points_used: BigDecimal,
+) -> Result<Order, DbError> {
+    sqlx::query_as!(
+        Order,
+        r#"
+        UPDATE moto_auto.orders
+        SET
+            discount_type = $2,
+            discount_value = $3,
+            discount_reason = $4,
+            bonus_points_used = $5
+        WHERE order_id = $1 AND status = 'processing' AND deleted_at IS NULL
+        RETURNING order_id, client_id, branch_id, master_id, order_date, completion_date, total_amount, status, deleted_at,
+            discount_type, discount_value, discount_reason, subtotal, discount_amount, vat_rate, tax_amount, bonus_points_used,
+            refunded_at, refund_reason, picked_up_at, pickup_override_reason, pickup_override_by, vehicle_id,
+            intake_mileage
+        "#,
+        order_id,
+        discount_type,
+        discount_value,
+        discount_reason,
+        bonus_points_used
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+pub async fn delete_order(conn: &mut DbConn, order_id: i32) -> Result<(), DbError> {
     sqlx::query!(
         r#"
-        DELETE FROM moto_auto.orders
-        WHERE order_id = $1
+        UPDATE moto_auto.orders
+        SET deleted_at = NOW()
+        WHERE order_id = $1 AND deleted_at IS NULL
+        RETURNING order_id
         "#,
         order_id
     )
-    .execute(pool)
+    .fetch_one(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
     .map(|_| {})
 }
 
+/// Refunds a finished order. The database takes back the bonus points accrued for it,
+/// returns the points redeemed on it and recalculates the client's spend and tier.
+pub async fn refund_order(conn: &mut DbConn, order_id: i32, reason: &str) -> Result<Order, DbError> {
+    sqlx::query_as!(
+        Order,
+        r#"
+        UPDATE moto_auto.orders
+        SET status = 'refunded', refunded_at = NOW(), refund_reason = $2
+        WHERE order_id = $1 AND status = 'finished' AND deleted_at IS NULL
+        RETURNING order_id, client_id, branch_id, master_id, order_date, completion_date, total_amount, status, deleted_at,
+            discount_type, discount_value, discount_reason, subtotal, discount_amount, vat_rate, tax_amount, bonus_points_used,
+            refunded_at, refund_reason, picked_up_at, pickup_override_reason, pickup_override_by, vehicle_id,
+            intake_mileage
+        "#,
+        order_id,
+        reason
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+/// Hands a finished order over to the client. An order that is not fully paid needs
+/// an override reason; the database records who allowed it.
+pub async fn close_order_for_pickup(
+    conn: &mut DbConn,
+    order_id: i32,
+    override_reason: Option<&str>,
+) -> Result<Order, DbError> {
+    sqlx::query_as!(
+        Order,
+        r#"
+        UPDATE moto_auto.orders
+        SET picked_up_at = NOW(), pickup_override_reason = $2
+        WHERE order_id = $1 AND picked_up_at IS NULL AND deleted_at IS NULL
+        RETURNING order_id, client_id, branch_id, master_id, order_date, completion_date, total_amount, status, deleted_at,
+            discount_type, discount_value, discount_reason, subtotal, discount_amount, vat_rate, tax_amount, bonus_points_used,
+            refunded_at, refund_reason, picked_up_at, pickup_override_reason, pickup_override_by, vehicle_id,
+            intake_mileage
+        "#,
+        order_id,
+        override_reason
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+/// Links an order to the serviced vehicle of its client with the odometer reading at intake.
+pub async fn assign_order_vehicle(
+    conn: &mut DbConn,
+    order_id: i32,
+    vehicle_id: i32,
+    intake_mileage: Option<i32>,
+) -> Result<Order, DbError> {
+    sqlx::query_as!(
+        Order,
+        r#"
+        UPDATE moto_auto.orders
+        SET vehicle_id = $2, intake_mileage = $3
+        WHERE order_id = $1 AND deleted_at IS NULL
+        RETURNING order_id, client_id, branch_id, master_id, order_date, completion_date, total_amount, status, deleted_at,
+            discount_type, discount_value, discount_reason, subtotal, discount_amount, vat_rate, tax_amount, bonus_points_used,
+            refunded_at, refund_reason, picked_up_at, pickup_override_reason, pickup_override_by, vehicle_id,
+            intake_mileage
+        "#,
+        order_id,
+        vehicle_id,
+        intake_mileage
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+pub async fn restore_order(conn: &mut DbConn, order_id: i32) -> Result<Order, DbError> {
+    sqlx::query_as!(
+        Order,
+        r#"
+        UPDATE moto_auto.orders
+        SET deleted_at = NULL
+        WHERE order_id = $1 AND deleted_at IS NOT NULL
+        RETURNING order_id, client_id, branch_id, master_id, order_date, completion_date, total_amount, status, deleted_at,
+            discount_type, discount_value, discount_reason, subtotal, discount_amount, vat_rate, tax_amount, bonus_points_used,
+            refunded_at, refund_reason, picked_up_at, pickup_override_reason, pickup_override_by, vehicle_id,
+            intake_mileage
+        "#,
+        order_id
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+pub async fn get_archived_orders(
+    conn: &mut DbConn,
+    branch_id: Option<i32>,
+) -> Result<Vec<Order>, DbError> {
+    sqlx::query_as!(
+        Order,
+        r#"
+        SELECT * FROM moto_auto.orders
+        WHERE deleted_at IS NOT NULL AND ($1::INTEGER IS NULL OR branch_id = $1)
+        ORDER BY deleted_at DESC
+        "#,
+        branch_id
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+pub async fn get_order_by_id(conn: &mut DbConn, order_id: i32) -> Result<Order, DbError> {
+    sqlx::query_as!(
+        Order,
+        r#"
+        SELECT * FROM moto_auto.orders
+        WHERE order_id = $1
+        "#,
+        order_id
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
 pub async fn get_orders(
-    pool: &DbPool,
+    conn: &mut DbConn,
     branch_id: Option<i32>,
     master_id: Option<i32>,
     client_id: Option<i32>,
@@ -78,11 +253,11 @@ pub async fn get_orders(
                 Order,
                 r#"
                 SELECT * FROM moto_auto.orders
-                WHERE branch_id = $1
+                WHERE branch_id = $1 AND deleted_at IS NULL
                 "#,
                 id
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         },
@@ -91,11 +266,11 @@ pub async fn get_orders(
                 Order,
                 r#"
                 SELECT * FROM moto_auto.orders
-                WHERE master_id = $1
+                WHERE master_id = $1 AND deleted_at IS NULL
                 "#,
                 id
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         },
@@ -104,11 +279,11 @@ pub async fn get_orders(
                 Order,
                 r#"
                 SELECT * FROM moto_auto.orders
-                WHERE client_id = $1
+                WHERE client_id = $1 AND deleted_at IS NULL
                 "#,
                 id
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         },
@@ -117,12 +292,12 @@ pub async fn get_orders(
                 Order,
                 r#"
                 SELECT * FROM moto_auto.orders
-                WHERE branch_id = $1 AND master_id = $2
+                WHERE branch_id = $1 AND master_id = $2 AND deleted_at IS NULL
                 "#,
                 branch_id,
                 master_id
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         },
@@ -131,12 +306,12 @@ pub async fn get_orders(
                 Order,
                 r#"
                 SELECT * FROM moto_auto.orders
-                WHERE branch_id = $1 AND client_id = $2
+                WHERE branch_id = $1 AND client_id = $2 AND deleted_at IS NULL
                 "#,
                 branch_id,
                 client_id
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         },
@@ -145,12 +320,12 @@ pub async fn get_orders(
                 Order,
                 r#"
                 SELECT * FROM moto_auto.orders
-                WHERE master_id = $1 AND client_id = $2
+                WHERE master_id = $1 AND client_id = $2 AND deleted_at IS NULL
                 "#,
                 master_id,
                 client_id
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         },
@@ -159,13 +334,13 @@ pub async fn get_orders(
                 Order,
                 r#"
                 SELECT * FROM moto_auto.orders
-                WHERE branch_id = $1 AND master_id = $2 AND client_id = $3
+                WHERE branch_id = $1 AND master_id = $2 AND client_id = $3 AND deleted_at IS NULL
                 "#,
                 branch_id,
                 master_id,
                 client_id
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         },
@@ -174,9 +349,10 @@ pub async fn get_orders(
                 Order,
                 r#"
                 SELECT * FROM moto_auto.orders
+                WHERE deleted_at IS NULL
                 "#,
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         }
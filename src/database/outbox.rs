@@ -0,0 +1,90 @@
+use crate::database::{DbConn, DbError};
+use crate::models::OutboxMessage;
+
+/// Locks up to `limit` notifications that are due to be sent, oldest first. Rows locked by
+/// another worker are skipped.
+pub async fn claim_outbox(conn: &mut DbConn, limit: i64) -> Result<Vec<OutboxMessage>, DbError> {
+    sqlx::query_as!(
+        OutboxMessage,
+        r#"
+        SELECT
+            ob.outbox_id, ob.kind, ob.attempts, c.name AS client_name, c.phones[1] AS "phone?",
+            c.email, c.preferred_channel, c.service_consent, c.marketing_consent,
+            ob.order_id, ob.order_status, o.total_amount AS "total_amount?",
+            s.scheduled_datetime AS "scheduled_datetime?", ob.reminder_id,
+            r.due_date AS "due_date?", r.due_mileage AS "due_mileage?",
+            sv.service_name AS "service_name?", v.make AS "make?", v.model AS "model?",
+            b.address AS "branch_address?", b.phone_number AS "branch_phone?"
+        FROM moto_auto.outbox ob
+        INNER JOIN moto_auto.client c
+        ON c.client_id = ob.client_id
+        LEFT JOIN moto_auto.orders o
+        ON o.order_id = ob.order_id
+        LEFT JOIN moto_auto.schedule s
+        ON s.schedule_id = ob.schedule_id
+        LEFT JOIN moto_auto.maintenance_reminder r
+        ON r.reminder_id = ob.reminder_id
+        LEFT JOIN moto_auto.service sv
+        ON sv.service_id = r.service_id
+        LEFT JOIN moto_auto.vehicle v
+        ON v.vehicle_id = COALESCE(r.vehicle_id, o.vehicle_id)
+        LEFT JOIN moto_auto.branch b
+        ON b.branch_id = COALESCE(s.branch_id, o.branch_id, r.branch_id)
+        WHERE ob.sent_at IS NULL
+        AND ob.failed_at IS NULL
+        AND ob.next_attempt_at <= NOW()
+        ORDER BY ob.outbox_id
+        LIMIT $1
+        FOR UPDATE OF ob SKIP LOCKED
+        "#,
+        limit
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+pub async fn mark_outbox_sent(conn: &mut DbConn, outbox_id: i32) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"
+        UPDATE moto_auto.outbox
+        SET sent_at = NOW(), attempts = attempts + 1, last_error = NULL
+        WHERE outbox_id = $1
+        "#,
+        outbox_id
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+    .map(|_| {})
+}
+
+/// Records a failed attempt. The next one waits twice as long as the previous pause,
+/// starting from a minute; after `max_attempts`, or straight away with `give_up`, the
+/// notification is not tried again.
+pub async fn mark_outbox_failed(
+    conn: &mut DbConn,
+    outbox_id: i32,
+    error: &str,
+    max_attempts: i32,
+    give_up: bool,
+) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"
+        UPDATE moto_auto.outbox
+        SET attempts = attempts + 1,
+            last_error = $2,
+            next_attempt_at = NOW() + make_interval(mins => power(2, attempts)::INTEGER),
+            failed_at = CASE WHEN $4 OR attempts + 1 >= $3 THEN NOW() END
+        WHERE outbox_id = $1
+        "#,
+        outbox_id,
+        error,
+        max_attempts,
+        give_up
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+    .map(|_| {})
+}
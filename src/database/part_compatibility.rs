@@ -0,0 +1,59 @@
+use crate::database::{DbConn, DbError};
+use crate::models::PartCompatibility;
+
+pub async fn get_part_compatibility(
+    conn: &mut DbConn,
+    part_id: i32,
+) -> Result<Vec<PartCompatibility>, DbError> {
+    sqlx::query_as!(
+        PartCompatibility,
+        r#"
+        SELECT * FROM moto_auto.part_compatibility
+        WHERE part_id = $1
+        ORDER BY make, model NULLS FIRST, year_from NULLS FIRST
+        "#,
+        part_id
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+pub async fn add_part_compatibility(
+    conn: &mut DbConn,
+    compatibility: PartCompatibility,
+) -> Result<PartCompatibility, DbError> {
+    sqlx::query_as!(
+        PartCompatibility,
+        r#"
+        INSERT INTO moto_auto.part_compatibility (part_id, make, model, year_from, year_to)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING *
+        "#,
+        compatibility.part_id,
+        compatibility.make,
+        compatibility.model,
+        compatibility.year_from,
+        compatibility.year_to
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+pub async fn remove_part_compatibility(
+    conn: &mut DbConn,
+    compatibility_id: i32,
+) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"
+        DELETE FROM moto_auto.part_compatibility
+        WHERE compatibility_id = $1
+        "#,
+        compatibility_id
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+    .map(|_| {})
+}
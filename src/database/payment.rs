@@ -0,0 +1,64 @@
+use bigdecimal::BigDecimal;
+
+use crate::database::{DbConn, DbError};
+use crate::models::Payment;
+
+/// Takes a payment for an order. The database refuses payments above the outstanding
+/// balance and takes bonus payments from the client's points.
+pub async fn create_payment(
+    conn: &mut DbConn,
+    order_id: i32,
+    amount: BigDecimal,
+    method: &str,
+) -> Result<Payment, DbError> {
+    sqlx::query_as!(
+        Payment,
+        r#"
+        INSERT INTO moto_auto.payment (order_id, amount, method)
+        VALUES ($1, $2, $3)
+        RETURNING *
+        "#,
+        order_id,
+        amount,
+        method
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+pub async fn get_order_payments(conn: &mut DbConn, order_id: i32) -> Result<Vec<Payment>, DbError> {
+    sqlx::query_as!(
+        Payment,
+        r#"
+        SELECT * FROM moto_auto.payment
+        WHERE order_id = $1
+        ORDER BY paid_at, payment_id
+        "#,
+        order_id
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+pub async fn get_order_outstanding(conn: &mut DbConn, order_id: i32) -> Result<BigDecimal, DbError> {
+    sqlx::query_scalar!(
+        r#"SELECT order_outstanding($1) AS "outstanding!""#,
+        order_id
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+/// What a client still owes on orders in progress and finished orders.
+pub async fn get_client_outstanding(conn: &mut DbConn, client_id: i32) -> Result<BigDecimal, DbError> {
+    sqlx::query_scalar!(
+        r#"SELECT client_outstanding($1) AS "outstanding!""#,
+        client_id
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
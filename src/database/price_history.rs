@@ -0,0 +1,29 @@
+use crate::database::{DbConn, DbError};
+use crate::models::PriceHistory;
+
+pub async fn get_price_history(
+    conn: &mut DbConn,
+    item_type: Option<&str>,
+    item_id: Option<i32>,
+    branch_id: Option<i32>,
+    limit: i64,
+) -> Result<Vec<PriceHistory>, DbError> {
+    sqlx::query_as!(
+        PriceHistory,
+        r#"
+        SELECT * FROM moto_auto.price_history
+        WHERE ($1::TEXT IS NULL OR item_type = $1)
+          AND ($2::INTEGER IS NULL OR item_id = $2)
+          AND ($3::INTEGER IS NULL OR branch_id = $3)
+        ORDER BY changed_at DESC, price_history_id DESC
+        LIMIT $4
+        "#,
+        item_type,
+        item_id,
+        branch_id,
+        limit
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
@@ -0,0 +1,38 @@
+use crate::database::{DbConn, DbError};
+use crate::models::{Receipt, ReceiptLine};
+
+/// The latest receipt issued for an order. An order finished again after a
+/// cancellation gets a new receipt, the old one stays in the sequence.
+pub async fn get_order_receipt(conn: &mut DbConn, order_id: i32) -> Result<Receipt, DbError> {
+    sqlx::query_as!(
+        Receipt,
+        r#"
+        SELECT * FROM moto_auto.receipt
+        WHERE order_id = $1
+        ORDER BY receipt_id DESC
+        LIMIT 1
+        "#,
+        order_id
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+pub async fn get_receipt_lines(
+    conn: &mut DbConn,
+    receipt_id: i32,
+) -> Result<Vec<ReceiptLine>, DbError> {
+    sqlx::query_as!(
+        ReceiptLine,
+        r#"
+        SELECT * FROM moto_auto.receipt_line
+        WHERE receipt_id = $1
+        ORDER BY line_number
+        "#,
+        receipt_id
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
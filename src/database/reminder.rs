@@ -0,0 +1,94 @@
+use crate::database::{DbConn, DbError};
+use crate::models::MaintenanceReminder;
+
+/// Creates reminders for vehicles due for a recurring service and returns the new ones.
+pub async fn create_maintenance_reminders(
+    conn: &mut DbConn,
+) -> Result<Vec<MaintenanceReminder>, DbError> {
+    sqlx::query_as!(
+        MaintenanceReminder,
+        r#"
+        SELECT
+            r.reminder_id AS "reminder_id!", r.client_id AS "client_id!", c.name AS client_name,
+            c.phones[1] AS "phone?", c.email, r.vehicle_id AS "vehicle_id!", v.make, v.model, v.plate, v.mileage,
+            r.service_id AS "service_id!", s.service_name, r.last_order_id AS "last_order_id!",
+            r.branch_id AS "branch_id!", r.due_date, r.due_mileage, r.created_at AS "created_at!",
+            r.notified_at
+        FROM create_maintenance_reminders() r
+        INNER JOIN moto_auto.client c
+        ON c.client_id = r.client_id
+        INNER JOIN moto_auto.vehicle v
+        ON v.vehicle_id = r.vehicle_id
+        INNER JOIN moto_auto.service s
+        ON s.service_id = r.service_id
+        ORDER BY r.reminder_id
+        "#
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+/// Reminders nobody has dismissed yet, the earliest due first.
+pub async fn get_maintenance_reminders(
+    conn: &mut DbConn,
+) -> Result<Vec<MaintenanceReminder>, DbError> {
+    sqlx::query_as!(
+        MaintenanceReminder,
+        r#"
+        SELECT
+            r.reminder_id, r.client_id, c.name AS client_name, c.phones[1] AS "phone?", c.email,
+            r.vehicle_id,
+            v.make, v.model, v.plate, v.mileage, r.service_id, s.service_name, r.last_order_id,
+            r.branch_id, r.due_date, r.due_mileage, r.created_at, r.notified_at
+        FROM moto_auto.maintenance_reminder r
+        INNER JOIN moto_auto.client c
+        ON c.client_id = r.client_id
+        INNER JOIN moto_auto.vehicle v
+        ON v.vehicle_id = r.vehicle_id
+        INNER JOIN moto_auto.service s
+        ON s.service_id = r.service_id
+        WHERE r.dismissed_at IS NULL
+        ORDER BY r.due_date NULLS LAST, r.reminder_id
+        "#
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+/// Takes a reminder off the list once the client has been contacted or booked.
+pub async fn dismiss_maintenance_reminder(
+    conn: &mut DbConn,
+    reminder_id: i32,
+) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"
+        UPDATE moto_auto.maintenance_reminder
+        SET dismissed_at = NOW(),
+            dismissed_by = NULLIF(current_setting('moto_auto.actor', true), '')::INTEGER
+        WHERE reminder_id = $1 AND dismissed_at IS NULL
+        RETURNING reminder_id
+        "#,
+        reminder_id
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+    .map(|_| {})
+}
+
+pub async fn mark_reminder_notified(conn: &mut DbConn, reminder_id: i32) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"
+        UPDATE moto_auto.maintenance_reminder
+        SET notified_at = NOW()
+        WHERE reminder_id = $1
+        "#,
+        reminder_id
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+    .map(|_| {})
+}
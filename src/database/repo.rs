@@ -0,0 +1,937 @@
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+
+use crate::database::{
+    audit, begin_as, branch, cash_shift, client, employee, loyalty, order_service, order_service_part, orders, part_compatibility, payment,
+    price_history, receipt, reminder, search, service, service_branch, spare_part, spare_part_branch, stock, user, vehicle, DbError, DbPool,
+    DbTransaction,
+};
+use crate::models::{
+    AuditLog, BonusTransaction, Branch, BranchUpdate, CashShift, Client, ClientBranch, ClientContacts, Employee, EmployeeUpdate, LoyaltyTier, MaintenanceReminder, Order, OrderService, OrderServicePart,
+    PartCompatibility, PartMatch, Payment, PriceHistory, PurchaseOrder, PurchaseOrderLine, Receipt, ReceiptLine, SearchResult, Service, ServiceBranch, ServiceHistoryEntry, ShiftTotal, SparePart,
+    SparePartBranch, StockLevel, User, Vehicle,
+};
+
+#[async_trait]
+pub trait UserRepo {
+    async fn get_user(&mut self, username: &str) -> Result<User, DbError>;
+    async fn get_user_by_id(&mut self, user_id: i32) -> Result<User, DbError>;
+    async fn get_users(&mut self, admin_branch_id: i32) -> Result<Vec<User>, DbError>;
+    async fn create_user(&mut self, admin_branch_id: i32, user: &User) -> Result<User, DbError>;
+    async fn update_user(
+        &mut self,
+        admin_branch_id: i32,
+        username: &str,
+        new_passwordhash: Option<&str>,
+        role: Option<&str>,
+        branch_id: Option<i32>,
+    ) -> Result<User, DbError>;
+}
+
+#[async_trait]
+pub trait EmployeeRepo {
+    async fn get_employees_by_branch(&mut self, branch_id: i32) -> Result<Vec<Employee>, DbError>;
+    async fn update_employee(
+        &mut self,
+        update: EmployeeUpdate,
+        employee_id: i32,
+    ) -> Result<Employee, DbError>;
+}
+
+#[async_trait]
+pub trait BranchRepo {
+    async fn create_branch(
+        &mut self,
+        branch: Branch,
+        admin_username: &str,
+        admin_passwordhash: &str,
+    ) -> Result<Branch, DbError>;
+    async fn update_branch(
+        &mut self,
+        branch_id: i32,
+        update: BranchUpdate,
+    ) -> Result<Branch, DbError>;
+    async fn close_branch(&mut self, branch_id: i32) -> Result<Branch, DbError>;
+    async fn reopen_branch(&mut self, branch_id: i32) -> Result<Branch, DbError>;
+    async fn get_branch_by_id(&mut self, branch_id: i32) -> Result<Branch, DbError>;
+    async fn get_branch(
+        &mut self,
+        city: Option<&str>,
+        include_closed: bool,
+    ) -> Result<Vec<Branch>, DbError>;
+}
+
+#[async_trait]
+pub trait OrderRepo {
+    async fn create_order(&mut self, order: Order) -> Result<Order, DbError>;
+    async fn update_order(
+        &mut self,
+        master_id: Option<i32>,
+        completion_date: Option<DateTime<Utc>>,
+        status: Option<String>,
+        order_id: i32,
+    ) -> Result<Order, DbError>;
+    async fn get_orders(
+        &mut self,
+        branch_id: Option<i32>,
+        master_id: Option<i32>,
+        client_id: Option<i32>,
+    ) -> Result<Vec<Order>, DbError>;
+    async fn get_archived_orders(&mut self, branch_id: Option<i32>) -> Result<Vec<Order>, DbError>;
+    async fn delete_order(&mut self, order_id: i32) -> Result<(), DbError>;
+    async fn restore_order(&mut self, order_id: i32) -> Result<Order, DbError>;
+    async fn refund_order(&mut self, order_id: i32, reason: &str) -> Result<Order, DbError>;
+    async fn close_order_for_pickup(
+        &mut self,
+        order_id: i32,
+        override_reason: Option<&str>,
+    ) -> Result<Order, DbError>;
+    async fn get_order_by_id(&mut self, order_id: i32) -> Result<Order, DbError>;
+    async fn update_order_pricing(
+        &mut self,
+        order_id: i32,
+        discount_type: Option<&str>,
+        discount_value: BigDecimal,
+        discount_reason: Option<&str>,
+        bonus_points_used: BigDecimal,
+    ) -> Result<Order, DbError>;
+    async fn discount_order_service(
+        &mut self,
+        order_service_id: i32,
+        discount_type: Option<&str>,
+        discount_value: BigDecimal,
+        discount_reason: Option<&str>,
+    ) -> Result<OrderService, DbError>;
+    async fn discount_order_service_part(
+        &mut self,
+        order_service_part_id: i32,
+        discount_type: Option<&str>,
+        discount_value: BigDecimal,
+        discount_reason: Option<&str>,
+    ) -> Result<OrderServicePart, DbError>;
+    async fn get_order_receipt(&mut self, order_id: i32) -> Result<Receipt, DbError>;
+    async fn get_receipt_lines(&mut self, receipt_id: i32) -> Result<Vec<ReceiptLine>, DbError>;
+}
+
+#[async_trait]
+pub trait ClientRepo {
+    async fn get_client_by_id(&mut self, client_id: i32) -> Result<Client, DbError>;
+    async fn get_archived_clients(&mut self) -> Result<Vec<Client>, DbError>;
+    async fn delete_client(&mut self, client_id: i32) -> Result<(), DbError>;
+    async fn restore_client(&mut self, client_id: i32) -> Result<Client, DbError>;
+    async fn share_client(
+        &mut self,
+        client_id: i32,
+        branch_id: i32,
+    ) -> Result<ClientBranch, DbError>;
+    async fn unshare_client(&mut self, client_id: i32, branch_id: i32) -> Result<(), DbError>;
+    async fn update_client_contacts(
+        &mut self,
+        client_id: i32,
+        contacts: &ClientContacts,
+    ) -> Result<Client, DbError>;
+}
+
+#[async_trait]
+pub trait CatalogRepo {
+    async fn get_services(&mut self) -> Result<Vec<Service>, DbError>;
+    async fn set_service_intervals(
+        &mut self,
+        service_id: i32,
+        interval_months: Option<i32>,
+        interval_km: Option<i32>,
+    ) -> Result<Service, DbError>;
+    async fn get_archived_service(&mut self) -> Result<Vec<Service>, DbError>;
+    async fn delete_service(&mut self, service_id: i32) -> Result<(), DbError>;
+    async fn restore_service(&mut self, service_id: i32) -> Result<Service, DbError>;
+    async fn get_archived_spare_part(&mut self) -> Result<Vec<SparePart>, DbError>;
+    async fn delete_spare_part(&mut self, part_id: i32) -> Result<(), DbError>;
+    async fn restore_spare_part(&mut self, part_id: i32) -> Result<SparePart, DbError>;
+    async fn get_service_prices(&mut self, branch_id: i32) -> Result<Vec<ServiceBranch>, DbError>;
+    async fn get_part_prices(&mut self, branch_id: i32) -> Result<Vec<SparePartBranch>, DbError>;
+    async fn get_price_history(
+        &mut self,
+        item_type: Option<&str>,
+        item_id: Option<i32>,
+        branch_id: Option<i32>,
+        limit: i64,
+    ) -> Result<Vec<PriceHistory>, DbError>;
+    async fn get_spare_parts(&mut self) -> Result<Vec<SparePart>, DbError>;
+    async fn update_part_details(
+        &mut self,
+        part_id: i32,
+        sku: Option<&str>,
+        oem_number: Option<&str>,
+        manufacturer: Option<&str>,
+        unit: &str,
+    ) -> Result<SparePart, DbError>;
+    async fn get_part_compatibility(&mut self, part_id: i32)
+        -> Result<Vec<PartCompatibility>, DbError>;
+    async fn add_part_compatibility(
+        &mut self,
+        compatibility: PartCompatibility,
+    ) -> Result<PartCompatibility, DbError>;
+    async fn remove_part_compatibility(&mut self, compatibility_id: i32) -> Result<(), DbError>;
+    async fn get_part_substitutes(&mut self, part_id: i32) -> Result<Vec<SparePart>, DbError>;
+    async fn link_part_substitute(&mut self, part_id: i32, substitute_id: i32)
+        -> Result<(), DbError>;
+    async fn unlink_part_substitute(
+        &mut self,
+        part_id: i32,
+        substitute_id: i32,
+    ) -> Result<(), DbError>;
+    async fn find_parts(
+        &mut self,
+        query: &str,
+        vehicle_id: Option<i32>,
+        branch_id: i32,
+        limit: i32,
+    ) -> Result<Vec<PartMatch>, DbError>;
+}
+
+#[async_trait]
+pub trait LoyaltyRepo {
+    async fn get_loyalty_tiers(&mut self) -> Result<Vec<LoyaltyTier>, DbError>;
+    async fn create_loyalty_tier(&mut self, tier: LoyaltyTier) -> Result<LoyaltyTier, DbError>;
+    async fn update_loyalty_tier(
+        &mut self,
+        tier_id: i32,
+        name: Option<&str>,
+        spend_threshold: Option<BigDecimal>,
+        accrual_rate: Option<BigDecimal>,
+        points_lifetime_days: Option<i32>,
+    ) -> Result<LoyaltyTier, DbError>;
+    async fn get_bonus_statement(&mut self, client_id: i32)
+        -> Result<Vec<BonusTransaction>, DbError>;
+    async fn adjust_bonus_points(
+        &mut self,
+        client_id: i32,
+        points: BigDecimal,
+        reason: &str,
+    ) -> Result<BonusTransaction, DbError>;
+}
+
+#[async_trait]
+pub trait PaymentRepo {
+    async fn create_payment(
+        &mut self,
+        order_id: i32,
+        amount: BigDecimal,
+        method: &str,
+    ) -> Result<Payment, DbError>;
+    async fn get_order_payments(&mut self, order_id: i32) -> Result<Vec<Payment>, DbError>;
+    async fn get_order_outstanding(&mut self, order_id: i32) -> Result<BigDecimal, DbError>;
+    async fn get_client_outstanding(&mut self, client_id: i32) -> Result<BigDecimal, DbError>;
+    async fn open_cash_shift(
+        &mut self,
+        branch_id: i32,
+        opening_cash: BigDecimal,
+    ) -> Result<CashShift, DbError>;
+    async fn close_cash_shift(
+        &mut self,
+        shift_id: i32,
+        counted_cash: BigDecimal,
+    ) -> Result<CashShift, DbError>;
+    async fn get_cash_shift(&mut self, shift_id: i32) -> Result<CashShift, DbError>;
+    async fn get_cash_shifts(&mut self, branch_id: i32) -> Result<Vec<CashShift>, DbError>;
+    async fn get_shift_totals(&mut self, shift_id: i32) -> Result<Vec<ShiftTotal>, DbError>;
+}
+
+#[async_trait]
+pub trait VehicleRepo {
+    async fn create_vehicle(&mut self, vehicle: Vehicle) -> Result<Vehicle, DbError>;
+    async fn update_vehicle(&mut self, vehicle: Vehicle) -> Result<Vehicle, DbError>;
+    async fn delete_vehicle(&mut self, vehicle_id: i32) -> Result<(), DbError>;
+    async fn get_vehicle_by_id(&mut self, vehicle_id: i32) -> Result<Vehicle, DbError>;
+    async fn get_client_vehicles(&mut self, client_id: i32) -> Result<Vec<Vehicle>, DbError>;
+    async fn get_vehicle_history(
+        &mut self,
+        vehicle_id: i32,
+    ) -> Result<Vec<ServiceHistoryEntry>, DbError>;
+    async fn assign_order_vehicle(
+        &mut self,
+        order_id: i32,
+        vehicle_id: i32,
+        intake_mileage: Option<i32>,
+    ) -> Result<Order, DbError>;
+    async fn get_maintenance_reminders(&mut self) -> Result<Vec<MaintenanceReminder>, DbError>;
+    async fn dismiss_maintenance_reminder(&mut self, reminder_id: i32) -> Result<(), DbError>;
+}
+
+#[async_trait]
+pub trait AuditRepo {
+    async fn get_audit_log(
+        &mut self,
+        entity: Option<&str>,
+        entity_id: Option<i32>,
+        actor_user_id: Option<i32>,
+        operation: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<AuditLog>, DbError>;
+}
+
+#[async_trait]
+pub trait SearchRepo {
+    async fn search(&mut self, query: &str, limit: i32) -> Result<Vec<SearchResult>, DbError>;
+}
+
+#[async_trait]
+pub trait StockRepo {
+    async fn get_stock_levels(&mut self, branch_id: i32, low_only: bool) -> Result<Vec<StockLevel>, DbError>;
+    async fn set_reorder_level(
+        &mut self,
+        branch_id: i32,
+        part_id: i32,
+        reorder_point: Option<i32>,
+        reorder_quantity: Option<i32>,
+    ) -> Result<SparePartBranch, DbError>;
+    async fn get_open_purchase_orders(&mut self, branch_id: i32) -> Result<Vec<PurchaseOrder>, DbError>;
+    async fn get_purchase_order(&mut self, purchase_order_id: i32) -> Result<PurchaseOrder, DbError>;
+    async fn get_purchase_order_lines(&mut self, purchase_order_id: i32) -> Result<Vec<PurchaseOrderLine>, DbError>;
+    async fn update_purchase_order_line(
+        &mut self,
+        purchase_order_line_id: i32,
+        quantity: i32,
+    ) -> Result<PurchaseOrderLine, DbError>;
+    async fn remove_purchase_order_line(&mut self, purchase_order_line_id: i32) -> Result<(), DbError>;
+    async fn set_purchase_order_status(&mut self, purchase_order_id: i32, status: &str) -> Result<PurchaseOrder, DbError>;
+}
+
+/// One unit of work. Nothing is persisted until [`Repos::commit`]; dropping it rolls back.
+#[async_trait]
+pub trait Repos:
+    UserRepo
+    + EmployeeRepo
+    + BranchRepo
+    + OrderRepo
+    + ClientRepo
+    + CatalogRepo
+    + LoyaltyRepo
+    + PaymentRepo
+    + VehicleRepo
+    + AuditRepo
+    + SearchRepo
+    + StockRepo
+    + Send
+{
+    async fn commit(self: Box<Self>) -> Result<(), DbError>;
+}
+
+#[async_trait]
+pub trait Store: Send + Sync {
+    /// Unit of work without an acting user, for login and session lookups.
+    async fn begin(&self) -> Result<Box<dyn Repos>, DbError>;
+    /// Unit of work on behalf of `user`, see [`begin_as`].
+    async fn begin_as(&self, user: &User) -> Result<Box<dyn Repos>, DbError>;
+}
+
+pub struct PgStore {
+    pool: DbPool,
+}
+
+impl PgStore {
+    pub fn new(pool: DbPool) -> Self {
+        PgStore { pool }
+    }
+}
+
+#[async_trait]
+impl Store for PgStore {
+    async fn begin(&self) -> Result<Box<dyn Repos>, DbError> {
+        let tx = self.pool.begin().await.map_err(DbError::Sqlx)?;
+        Ok(Box::new(tx))
+    }
+
+    async fn begin_as(&self, user: &User) -> Result<Box<dyn Repos>, DbError> {
+        Ok(Box::new(begin_as(&self.pool, user).await?))
+    }
+}
+
+#[async_trait]
+impl Repos for DbTransaction {
+    async fn commit(self: Box<Self>) -> Result<(), DbError> {
+        DbTransaction::commit(*self)
+            .await
+            .map_err(DbError::Sqlx)
+    }
+}
+
+#[async_trait]
+impl UserRepo for DbTransaction {
+    async fn get_user(&mut self, username: &str) -> Result<User, DbError> {
+        user::get_user(self, username).await
+    }
+
+    async fn get_user_by_id(&mut self, user_id: i32) -> Result<User, DbError> {
+        user::get_user_by_id(self, user_id).await
+    }
+
+    async fn get_users(&mut self, admin_branch_id: i32) -> Result<Vec<User>, DbError> {
+        user::get_users(self, admin_branch_id).await
+    }
+
+    async fn create_user(&mut self, admin_branch_id: i32, user: &User) -> Result<User, DbError> {
+        user::create_user(self, admin_branch_id, user).await
+    }
+
+    async fn update_user(
+        &mut self,
+        admin_branch_id: i32,
+        username: &str,
+        new_passwordhash: Option<&str>,
+        role: Option<&str>,
+        branch_id: Option<i32>,
+    ) -> Result<User, DbError> {
+        user::update_user(
+            self,
+            admin_branch_id,
+            username,
+            new_passwordhash,
+            role,
+            branch_id,
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl EmployeeRepo for DbTransaction {
+    async fn get_employees_by_branch(&mut self, branch_id: i32) -> Result<Vec<Employee>, DbError> {
+        employee::get_employees_by_branch(self, branch_id).await
+    }
+
+    async fn update_employee(
+        &mut self,
+        update: EmployeeUpdate,
+        employee_id: i32,
+    ) -> Result<Employee, DbError> {
+        employee::update_employee(self, update, employee_id).await
+    }
+}
+
+#[async_trait]
+impl BranchRepo for DbTransaction {
+    async fn create_branch(
+        &mut self,
+        branch: Branch,
+        admin_username: &str,
+        admin_passwordhash: &str,
+    ) -> Result<Branch, DbError> {
+        branch::create_branch(self, branch, admin_username, admin_passwordhash).await
+    }
+
+    async fn update_branch(
+        &mut self,
+        branch_id: i32,
+        update: BranchUpdate,
+    ) -> Result<Branch, DbError> {
+        branch::update_branch(self, branch_id, update).await
+    }
+
+    async fn close_branch(&mut self, branch_id: i32) -> Result<Branch, DbError> {
+        branch::close_branch(self, branch_id).await
+    }
+
+    async fn reopen_branch(&mut self, branch_id: i32) -> Result<Branch, DbError> {
+        branch::reopen_branch(self, branch_id).await
+    }
+
+    async fn get_branch_by_id(&mut self, branch_id: i32) -> Result<Branch, DbError> {
+        branch::get_branch_by_id(self, branch_id).await
+    }
+
+    async fn get_branch(
+        &mut self,
+        city: Option<&str>,
+        include_closed: bool,
+    ) -> Result<Vec<Branch>, DbError> {
+        branch::get_branch(self, city, include_closed).await
+    }
+}
+
+#[async_trait]
+impl OrderRepo for DbTransaction {
+    async fn create_order(&mut self, order: Order) -> Result<Order, DbError> {
+        orders::create_order(self, order).await
+    }
+
+    async fn update_order(
+        &mut self,
+        master_id: Option<i32>,
+        completion_date: Option<DateTime<Utc>>,
+        status: Option<String>,
+        order_id: i32,
+    ) -> Result<Order, DbError> {
+        orders::update_order(self, master_id, completion_date, status, order_id).await
+    }
+
+    async fn get_orders(
+        &mut self,
+        branch_id: Option<i32>,
+        master_id: Option<i32>,
+        client_id: Option<i32>,
+    ) -> Result<Vec<Order>, DbError> {
+        orders::get_orders(self, branch_id, master_id, client_id).await
+    }
+
+    async fn get_archived_orders(&mut self, branch_id: Option<i32>) -> Result<Vec<Order>, DbError> {
+        orders::get_archived_orders(self, branch_id).await
+    }
+
+    async fn delete_order(&mut self, order_id: i32) -> Result<(), DbError> {
+        orders::delete_order(self, order_id).await
+    }
+
+    async fn restore_order(&mut self, order_id: i32) -> Result<Order, DbError> {
+        orders::restore_order(self, order_id).await
+    }
+
+    async fn refund_order(&mut self, order_id: i32, reason: &str) -> Result<Order, DbError> {
+        orders::refund_order(self, order_id, reason).await
+    }
+
+    async fn close_order_for_pickup(
+        &mut self,
+        order_id: i32,
+        override_reason: Option<&str>,
+    ) -> Result<Order, DbError> {
+        orders::close_order_for_pickup(self, order_id, override_reason).await
+    }
+
+    async fn get_order_by_id(&mut self, order_id: i32) -> Result<Order, DbError> {
+        orders::get_order_by_id(self, order_id).await
+    }
+
+    async fn update_order_pricing(
+        &mut self,
+        order_id: i32,
+        discount_type: Option<&str>,
+        discount_value: BigDecimal,
+        discount_reason: Option<&str>,
+        bonus_points_used: BigDecimal,
+    ) -> Result<Order, DbError> {
+        orders::update_order_pricing(
+            self,
+            order_id,
+            discount_type,
+            discount_value,
+            discount_reason,
+            bonus_points_used,
+        )
+        .await
+    }
+
+    async fn discount_order_service(
+        &mut self,
+        order_service_id: i32,
+        discount_type: Option<&str>,
+        discount_value: BigDecimal,
+        discount_reason: Option<&str>,
+    ) -> Result<OrderService, DbError> {
+        order_service::discount_order_service(
+            self,
+            order_service_id,
+            discount_type,
+            discount_value,
+            discount_reason,
+        )
+        .await
+    }
+
+    async fn discount_order_service_part(
+        &mut self,
+        order_service_part_id: i32,
+        discount_type: Option<&str>,
+        discount_value: BigDecimal,
+        discount_reason: Option<&str>,
+    ) -> Result<OrderServicePart, DbError> {
+        order_service_part::discount_order_service_part(
+            self,
+            order_service_part_id,
+            discount_type,
+            discount_value,
+            discount_reason,
+        )
+        .await
+    }
+
+    async fn get_order_receipt(&mut self, order_id: i32) -> Result<Receipt, DbError> {
+        receipt::get_order_receipt(self, order_id).await
+    }
+
+    async fn get_receipt_lines(&mut self, receipt_id: i32) -> Result<Vec<ReceiptLine>, DbError> {
+        receipt::get_receipt_lines(self, receipt_id).await
+    }
+}
+
+#[async_trait]
+impl ClientRepo for DbTransaction {
+    async fn get_client_by_id(&mut self, client_id: i32) -> Result<Client, DbError> {
+        client::get_client_by_id(self, client_id).await
+    }
+
+    async fn get_archived_clients(&mut self) -> Result<Vec<Client>, DbError> {
+        client::get_archived_clients(self).await
+    }
+
+    async fn delete_client(&mut self, client_id: i32) -> Result<(), DbError> {
+        client::delete_client(self, client_id).await
+    }
+
+    async fn restore_client(&mut self, client_id: i32) -> Result<Client, DbError> {
+        client::restore_client(self, client_id).await
+    }
+
+    async fn share_client(
+        &mut self,
+        client_id: i32,
+        branch_id: i32,
+    ) -> Result<ClientBranch, DbError> {
+        client::share_client(self, client_id, branch_id).await
+    }
+
+    async fn unshare_client(&mut self, client_id: i32, branch_id: i32) -> Result<(), DbError> {
+        client::unshare_client(self, client_id, branch_id).await
+    }
+
+    async fn update_client_contacts(
+        &mut self,
+        client_id: i32,
+        contacts: &ClientContacts,
+    ) -> Result<Client, DbError> {
+        client::update_client_contacts(self, client_id, contacts).await
+    }
+}
+
+#[async_trait]
+impl CatalogRepo for DbTransaction {
+    async fn get_services(&mut self) -> Result<Vec<Service>, DbError> {
+        service::get_service(self).await
+    }
+
+    async fn set_service_intervals(
+        &mut self,
+        service_id: i32,
+        interval_months: Option<i32>,
+        interval_km: Option<i32>,
+    ) -> Result<Service, DbError> {
+        service::set_service_intervals(self, service_id, interval_months, interval_km).await
+    }
+
+    async fn get_archived_service(&mut self) -> Result<Vec<Service>, DbError> {
+        service::get_archived_service(self).await
+    }
+
+    async fn delete_service(&mut self, service_id: i32) -> Result<(), DbError> {
+        service::delete_service(self, service_id).await
+    }
+
+    async fn restore_service(&mut self, service_id: i32) -> Result<Service, DbError> {
+        service::restore_service(self, service_id).await
+    }
+
+    async fn get_archived_spare_part(&mut self) -> Result<Vec<SparePart>, DbError> {
+        spare_part::get_archived_spare_part(self).await
+    }
+
+    async fn delete_spare_part(&mut self, part_id: i32) -> Result<(), DbError> {
+        spare_part::delete_spare_part(self, part_id).await
+    }
+
+    async fn restore_spare_part(&mut self, part_id: i32) -> Result<SparePart, DbError> {
+        spare_part::restore_spare_part(self, part_id).await
+    }
+
+    async fn get_service_prices(&mut self, branch_id: i32) -> Result<Vec<ServiceBranch>, DbError> {
+        service_branch::get_service_branch(self, Some(branch_id), None).await
+    }
+
+    async fn get_part_prices(&mut self, branch_id: i32) -> Result<Vec<SparePartBranch>, DbError> {
+        spare_part_branch::get_spare_part_branch(self, Some(branch_id), None).await
+    }
+
+    async fn get_price_history(
+        &mut self,
+        item_type: Option<&str>,
+        item_id: Option<i32>,
+        branch_id: Option<i32>,
+        limit: i64,
+    ) -> Result<Vec<PriceHistory>, DbError> {
+        price_history::get_price_history(self, item_type, item_id, branch_id, limit).await
+    }
+
+    async fn get_spare_parts(&mut self) -> Result<Vec<SparePart>, DbError> {
+        spare_part::get_spare_part(self).await
+    }
+
+    async fn update_part_details(
+        &mut self,
+        part_id: i32,
+        sku: Option<&str>,
+        oem_number: Option<&str>,
+        manufacturer: Option<&str>,
+        unit: &str,
+    ) -> Result<SparePart, DbError> {
+        spare_part::update_part_details(self, part_id, sku, oem_number, manufacturer, unit).await
+    }
+
+    async fn get_part_compatibility(
+        &mut self,
+        part_id: i32,
+    ) -> Result<Vec<PartCompatibility>, DbError> {
+        part_compatibility::get_part_compatibility(self, part_id).await
+    }
+
+    async fn add_part_compatibility(
+        &mut self,
+        compatibility: PartCompatibility,
+    ) -> Result<PartCompatibility, DbError> {
+        part_compatibility::add_part_compatibility(self, compatibility).await
+    }
+
+    async fn remove_part_compatibility(&mut self, compatibility_id: i32) -> Result<(), DbError> {
+        part_compatibility::remove_part_compatibility(self, compatibility_id).await
+    }
+
+    async fn get_part_substitutes(&mut self, part_id: i32) -> Result<Vec<SparePart>, DbError> {
+        spare_part::get_part_substitutes(self, part_id).await
+    }
+
+    async fn link_part_substitute(
+        &mut self,
+        part_id: i32,
+        substitute_id: i32,
+    ) -> Result<(), DbError> {
+        spare_part::link_part_substitute(self, part_id, substitute_id).await
+    }
+
+    async fn unlink_part_substitute(
+        &mut self,
+        part_id: i32,
+        substitute_id: i32,
+    ) -> Result<(), DbError> {
+        spare_part::unlink_part_substitute(self, part_id, substitute_id).await
+    }
+
+    async fn find_parts(
+        &mut self,
+        query: &str,
+        vehicle_id: Option<i32>,
+        branch_id: i32,
+        limit: i32,
+    ) -> Result<Vec<PartMatch>, DbError> {
+        spare_part::find_parts(self, query, vehicle_id, branch_id, limit).await
+    }
+}
+
+#[async_trait]
+impl LoyaltyRepo for DbTransaction {
+    async fn get_loyalty_tiers(&mut self) -> Result<Vec<LoyaltyTier>, DbError> {
+        loyalty::get_loyalty_tiers(self).await
+    }
+
+    async fn create_loyalty_tier(&mut self, tier: LoyaltyTier) -> Result<LoyaltyTier, DbError> {
+        loyalty::create_loyalty_tier(self, tier).await
+    }
+
+    async fn update_loyalty_tier(
+        &mut self,
+        tier_id: i32,
+        name: Option<&str>,
+        spend_threshold: Option<BigDecimal>,
+        accrual_rate: Option<BigDecimal>,
+        points_lifetime_days: Option<i32>,
+    ) -> Result<LoyaltyTier, DbError> {
+        loyalty::update_loyalty_tier(
+            self,
+            tier_id,
+            name,
+            spend_threshold,
+            accrual_rate,
+            points_lifetime_days,
+        )
+        .await
+    }
+
+    async fn get_bonus_statement(
+        &mut self,
+        client_id: i32,
+    ) -> Result<Vec<BonusTransaction>, DbError> {
+        loyalty::get_bonus_statement(self, client_id).await
+    }
+
+    async fn adjust_bonus_points(
+        &mut self,
+        client_id: i32,
+        points: BigDecimal,
+        reason: &str,
+    ) -> Result<BonusTransaction, DbError> {
+        loyalty::adjust_bonus_points(self, client_id, points, reason).await
+    }
+}
+
+#[async_trait]
+impl PaymentRepo for DbTransaction {
+    async fn create_payment(
+        &mut self,
+        order_id: i32,
+        amount: BigDecimal,
+        method: &str,
+    ) -> Result<Payment, DbError> {
+        payment::create_payment(self, order_id, amount, method).await
+    }
+
+    async fn get_order_payments(&mut self, order_id: i32) -> Result<Vec<Payment>, DbError> {
+        payment::get_order_payments(self, order_id).await
+    }
+
+    async fn get_order_outstanding(&mut self, order_id: i32) -> Result<BigDecimal, DbError> {
+        payment::get_order_outstanding(self, order_id).await
+    }
+
+    async fn get_client_outstanding(&mut self, client_id: i32) -> Result<BigDecimal, DbError> {
+        payment::get_client_outstanding(self, client_id).await
+    }
+
+    async fn open_cash_shift(
+        &mut self,
+        branch_id: i32,
+        opening_cash: BigDecimal,
+    ) -> Result<CashShift, DbError> {
+        cash_shift::open_cash_shift(self, branch_id, opening_cash).await
+    }
+
+    async fn close_cash_shift(
+        &mut self,
+        shift_id: i32,
+        counted_cash: BigDecimal,
+    ) -> Result<CashShift, DbError> {
+        cash_shift::close_cash_shift(self, shift_id, counted_cash).await
+    }
+
+    async fn get_cash_shift(&mut self, shift_id: i32) -> Result<CashShift, DbError> {
+        cash_shift::get_cash_shift(self, shift_id).await
+    }
+
+    async fn get_cash_shifts(&mut self, branch_id: i32) -> Result<Vec<CashShift>, DbError> {
+        cash_shift::get_cash_shifts(self, branch_id).await
+    }
+
+    async fn get_shift_totals(&mut self, shift_id: i32) -> Result<Vec<ShiftTotal>, DbError> {
+        cash_shift::get_shift_totals(self, shift_id).await
+    }
+}
+
+#[async_trait]
+impl VehicleRepo for DbTransaction {
+    async fn create_vehicle(&mut self, vehicle: Vehicle) -> Result<Vehicle, DbError> {
+        vehicle::create_vehicle(self, vehicle).await
+    }
+
+    async fn update_vehicle(&mut self, vehicle: Vehicle) -> Result<Vehicle, DbError> {
+        vehicle::update_vehicle(self, vehicle).await
+    }
+
+    async fn delete_vehicle(&mut self, vehicle_id: i32) -> Result<(), DbError> {
+        vehicle::delete_vehicle(self, vehicle_id).await
+    }
+
+    async fn get_vehicle_by_id(&mut self, vehicle_id: i32) -> Result<Vehicle, DbError> {
+        vehicle::get_vehicle_by_id(self, vehicle_id).await
+    }
+
+    async fn get_client_vehicles(&mut self, client_id: i32) -> Result<Vec<Vehicle>, DbError> {
+        vehicle::get_client_vehicles(self, client_id).await
+    }
+
+    async fn get_vehicle_history(
+        &mut self,
+        vehicle_id: i32,
+    ) -> Result<Vec<ServiceHistoryEntry>, DbError> {
+        vehicle::get_vehicle_history(self, vehicle_id).await
+    }
+
+    async fn assign_order_vehicle(
+        &mut self,
+        order_id: i32,
+        vehicle_id: i32,
+        intake_mileage: Option<i32>,
+    ) -> Result<Order, DbError> {
+        orders::assign_order_vehicle(self, order_id, vehicle_id, intake_mileage).await
+    }
+
+    async fn get_maintenance_reminders(&mut self) -> Result<Vec<MaintenanceReminder>, DbError> {
+        reminder::get_maintenance_reminders(self).await
+    }
+
+    async fn dismiss_maintenance_reminder(&mut self, reminder_id: i32) -> Result<(), DbError> {
+        reminder::dismiss_maintenance_reminder(self, reminder_id).await
+    }
+}
+
+#[async_trait]
+impl AuditRepo for DbTransaction {
+    async fn get_audit_log(
+        &mut self,
+        entity: Option<&str>,
+        entity_id: Option<i32>,
+        actor_user_id: Option<i32>,
+        operation: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<AuditLog>, DbError> {
+        audit::get_audit_log(self, entity, entity_id, actor_user_id, operation, limit).await
+    }
+}
+
+#[async_trait]
+impl SearchRepo for DbTransaction {
+    async fn search(&mut self, query: &str, limit: i32) -> Result<Vec<SearchResult>, DbError> {
+        search::search(self, query, limit).await
+    }
+}
+
+#[async_trait]
+impl StockRepo for DbTransaction {
+    async fn get_stock_levels(&mut self, branch_id: i32, low_only: bool) -> Result<Vec<StockLevel>, DbError> {
+        stock::get_stock_levels(self, branch_id, low_only).await
+    }
+
+    async fn set_reorder_level(
+        &mut self,
+        branch_id: i32,
+        part_id: i32,
+        reorder_point: Option<i32>,
+        reorder_quantity: Option<i32>,
+    ) -> Result<SparePartBranch, DbError> {
+        stock::set_reorder_level(self, branch_id, part_id, reorder_point, reorder_quantity).await
+    }
+
+    async fn get_open_purchase_orders(&mut self, branch_id: i32) -> Result<Vec<PurchaseOrder>, DbError> {
+        stock::get_open_purchase_orders(self, branch_id).await
+    }
+
+    async fn get_purchase_order(&mut self, purchase_order_id: i32) -> Result<PurchaseOrder, DbError> {
+        stock::get_purchase_order(self, purchase_order_id).await
+    }
+
+    async fn get_purchase_order_lines(&mut self, purchase_order_id: i32) -> Result<Vec<PurchaseOrderLine>, DbError> {
+        stock::get_purchase_order_lines(self, purchase_order_id).await
+    }
+
+    async fn update_purchase_order_line(
+        &mut self,
+        purchase_order_line_id: i32,
+        quantity: i32,
+    ) -> Result<PurchaseOrderLine, DbError> {
+        stock::update_purchase_order_line(self, purchase_order_line_id, quantity).await
+    }
+
+    async fn remove_purchase_order_line(&mut self, purchase_order_line_id: i32) -> Result<(), DbError> {
+        stock::remove_purchase_order_line(self, purchase_order_line_id).await
+    }
+
+    async fn set_purchase_order_status(&mut self, purchase_order_id: i32, status: &str) -> Result<PurchaseOrder, DbError> {
+        stock::set_purchase_order_status(self, purchase_order_id, status).await
+    }
+}
@@ -0,0 +1,14 @@
+use crate::database::{DbConn, DbError};
+
+pub async fn purge_archived(conn: &mut DbConn, retention_days: i32) -> Result<i32, DbError> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT purge_archived_records(make_interval(days => $1))
+        "#,
+        retention_days
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+    .map(|purged| purged.unwrap_or_default())
+}
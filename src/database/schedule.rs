@@ -1,7 +1,7 @@
-use crate::database::{DbError, DbPool};
+use crate::database::{DbConn, DbError};
 use crate::models::Schedule;
 
-pub async fn create_schedule(pool: &DbPool, schedule: Schedule) -> Result<Schedule, DbError> {
+pub async fn create_schedule(conn: &mut DbConn, schedule: Schedule) -> Result<Schedule, DbError> {
     sqlx::query_as!(
         Schedule,
         r#"
@@ -15,13 +15,13 @@ pub async fn create_schedule(pool: &DbPool, schedule: Schedule) -> Result<Schedu
         schedule.scheduled_datetime,
         schedule.status
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
 }
 
 pub async fn update_schedule(
-    pool: &DbPool,
+    conn: &mut DbConn,
     scheduled_datetime: Option<chrono::DateTime<chrono::Utc>>,
     status: Option<&str>,
     schedule_id: i32,
@@ -40,12 +40,12 @@ pub async fn update_schedule(
         status,
         schedule_id
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
 }
 
-pub async fn delete_schedule(pool: &DbPool, schedule_id: i32) -> Result<(), DbError> {
+pub async fn delete_schedule(conn: &mut DbConn, schedule_id: i32) -> Result<(), DbError> {
     sqlx::query!(
         r#"
         DELETE FROM moto_auto.schedule
@@ -53,14 +53,14 @@ pub async fn delete_schedule(pool: &DbPool, schedule_id: i32) -> Result<(), DbEr
         "#,
         schedule_id
     )
-    .execute(pool)
+    .execute(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
     .map(|_| {})
 }
 
 pub async fn get_schedule(
-    pool: &DbPool,
+    conn: &mut DbConn,
     branch_id: Option<i32>,
     client_id: Option<i32>,
     status: Option<&str>,
@@ -75,7 +75,7 @@ pub async fn get_schedule(
                 "#,
                 id
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         },
@@ -88,7 +88,7 @@ pub async fn get_schedule(
                 "#,
                 id
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         },
@@ -101,7 +101,7 @@ pub async fn get_schedule(
                 "#,
                 status
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         },
@@ -115,7 +115,7 @@ pub async fn get_schedule(
                 branch_id,
                 client_id
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         },
@@ -129,7 +129,7 @@ pub async fn get_schedule(
                 branch_id,
                 status
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         },
@@ -143,7 +143,7 @@ pub async fn get_schedule(
                 client_id,
                 status
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         },
@@ -158,7 +158,7 @@ pub async fn get_schedule(
                 client_id,
                 status
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         },
@@ -169,7 +169,7 @@ pub async fn get_schedule(
                 SELECT * FROM moto_auto.schedule
                 "#,
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         }
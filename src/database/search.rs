@@ -0,0 +1,26 @@
+use crate::database::{DbConn, DbError};
+use crate::models::SearchResult;
+
+/// Shorter queries match too much to be useful.
+pub const MIN_QUERY_LENGTH: usize = 2;
+
+/// Clients, orders, vehicles, parts and services matching `query`, the best first. Only
+/// rows the current role can see are searched.
+pub async fn search(
+    conn: &mut DbConn,
+    query: &str,
+    limit: i32,
+) -> Result<Vec<SearchResult>, DbError> {
+    sqlx::query_as!(
+        SearchResult,
+        r#"
+        SELECT kind AS "kind!", id AS "id!", title AS "title!", subtitle, rank AS "rank!"
+        FROM search($1, $2)
+        "#,
+        query,
+        limit
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
@@ -1,24 +1,24 @@
-use crate::database::{DbError, DbPool};
+use crate::database::{DbConn, DbError};
 use crate::models::Service;
 
-pub async fn create_service(pool: &DbPool, service: Service) -> Result<Service, DbError> {
+pub async fn create_service(conn: &mut DbConn, service: Service) -> Result<Service, DbError> {
     sqlx::query_as!(
         Service,
         r#"
         INSERT INTO moto_auto.service (service_name, description)
         VALUES ($1, $2)
-        RETURNING service_id, service_name, description
+        RETURNING *
         "#,
         service.service_name,
         service.description
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
 }
 
 pub async fn update_service(
-    pool: &DbPool,
+    conn: &mut DbConn,
     service_name: Option<&str>,
     description: Option<&str>,
     service_id: i32,
@@ -30,40 +30,97 @@ pub async fn update_service(
         SET
             service_name = COALESCE($1, service_name),
             description = COALESCE($2, description)
-        WHERE service_id = $3
-        RETURNING service_id, service_name, description
+        WHERE service_id = $3 AND deleted_at IS NULL
+        RETURNING *
         "#,
         service_name,
         description,
         service_id
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
 }
 
-pub async fn delete_service(pool: &DbPool, service_id: i32) -> Result<(), DbError> {
+pub async fn delete_service(conn: &mut DbConn, service_id: i32) -> Result<(), DbError> {
     sqlx::query!(
         r#"
-        DELETE FROM moto_auto.service
-        WHERE service_id = $1
+        UPDATE moto_auto.service
+        SET deleted_at = NOW()
+        WHERE service_id = $1 AND deleted_at IS NULL
+        RETURNING service_id
         "#,
         service_id
     )
-    .execute(pool)
+    .fetch_one(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
     .map(|_| {})
 }
 
-pub async fn get_service(pool: &DbPool) -> Result<Vec<Service>, DbError> {
+pub async fn get_service(conn: &mut DbConn) -> Result<Vec<Service>, DbError> {
     sqlx::query_as!(
         Service,
         r#"
         SELECT * FROM moto_auto.service
+        WHERE deleted_at IS NULL
        "#,
     )
-    .fetch_all(pool)
+    .fetch_all(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
 }
+
+pub async fn restore_service(conn: &mut DbConn, service_id: i32) -> Result<Service, DbError> {
+    sqlx::query_as!(
+        Service,
+        r#"
+        UPDATE moto_auto.service
+        SET deleted_at = NULL
+        WHERE service_id = $1 AND deleted_at IS NOT NULL
+        RETURNING *
+        "#,
+        service_id
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+pub async fn get_archived_service(conn: &mut DbConn) -> Result<Vec<Service>, DbError> {
+    sqlx::query_as!(
+        Service,
+        r#"
+        SELECT * FROM moto_auto.service
+        WHERE deleted_at IS NOT NULL
+        ORDER BY deleted_at DESC
+        "#,
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+/// Sets how often the service is recommended; `None` clears an interval.
+pub async fn set_service_intervals(
+    conn: &mut DbConn,
+    service_id: i32,
+    interval_months: Option<i32>,
+    interval_km: Option<i32>,
+) -> Result<Service, DbError> {
+    sqlx::query_as!(
+        Service,
+        r#"
+        UPDATE moto_auto.service
+        SET interval_months = $2, interval_km = $3
+        WHERE service_id = $1 AND deleted_at IS NULL
+        RETURNING *
+        "#,
+        service_id,
+        interval_months,
+        interval_km
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
@@ -1,12 +1,12 @@
 use bigdecimal::BigDecimal;
 
-use crate::database::{DbError, DbPool};
+use crate::database::{DbConn, DbError};
 use crate::models::ServiceBranch;
 
 use super::service;
 
 pub async fn create_service_branch(
-    pool: &DbPool,
+    conn: &mut DbConn,
     service_branch: ServiceBranch,
 ) -> Result<ServiceBranch, DbError> {
     sqlx::query_as!(
@@ -20,13 +20,13 @@ pub async fn create_service_branch(
         service_branch.branch_id,
         service_branch.service_id
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
 }
 
 pub async fn update_service_branch(
-    pool: &DbPool,
+    conn: &mut DbConn,
     price: Option<BigDecimal>,
     service_branch_id: i32,
 ) -> Result<ServiceBranch, DbError> {
@@ -42,13 +42,13 @@ pub async fn update_service_branch(
         price,
         service_branch_id
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
 }
 
 pub async fn delete_service_branch(
-    pool: &DbPool,
+    conn: &mut DbConn,
     service_id: i32,
     branch_id: i32,
 ) -> Result<(), DbError> {
@@ -60,14 +60,14 @@ pub async fn delete_service_branch(
         service_id,
         branch_id
     )
-    .execute(pool)
+    .execute(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
     .map(|_| {})
 }
 
 pub async fn get_service_branch(
-    pool: &DbPool,
+    conn: &mut DbConn,
     branch_id: Option<i32>,
     service_id: Option<i32>,
 ) -> Result<Vec<ServiceBranch>, DbError> {
@@ -81,7 +81,7 @@ pub async fn get_service_branch(
                 "#,
                 id
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         },
@@ -94,7 +94,7 @@ pub async fn get_service_branch(
                 "#,
                 id
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         },
@@ -108,7 +108,7 @@ pub async fn get_service_branch(
                 branch_id,
                 service_id
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         },
@@ -119,7 +119,7 @@ pub async fn get_service_branch(
                 SELECT * FROM moto_auto.service_branch
                 "#,
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         }
@@ -1,24 +1,31 @@
-use crate::database::{DbError, DbPool};
-use crate::models::SparePart;
+use crate::database::{DbConn, DbError};
+use crate::models::{PartMatch, SparePart};
 
-pub async fn create_spare_part(pool: &DbPool, spare_part: SparePart) -> Result<SparePart, DbError> {
+/// Units a part is counted in; the `spare_part_unit_check` constraint holds the same list.
+pub const PART_UNITS: [&str; 8] = ["pcs", "set", "pair", "l", "ml", "kg", "g", "m"];
+
+pub async fn create_spare_part(conn: &mut DbConn, spare_part: SparePart) -> Result<SparePart, DbError> {
     sqlx::query_as!(
         SparePart,
         r#"
-        INSERT INTO moto_auto.spare_part (part_name, description)
-        VALUES ($1, $2)
-        RETURNING part_id, part_name, description
+        INSERT INTO moto_auto.spare_part (part_name, description, sku, oem_number, manufacturer, unit)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING part_id, part_name, description, sku, oem_number, manufacturer, unit, deleted_at
         "#,
         spare_part.part_name,
-        spare_part.description
+        spare_part.description,
+        spare_part.sku,
+        spare_part.oem_number,
+        spare_part.manufacturer,
+        spare_part.unit
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
 }
 
 pub async fn update_spare_part(
-    pool: &DbPool,
+    conn: &mut DbConn,
     part_name: Option<&str>,
     description: Option<&str>,
     part_id: i32,
@@ -30,40 +37,192 @@ pub async fn update_spare_part(
         SET
             part_name = COALESCE($1, part_name),
             description = COALESCE($2, description)
-        WHERE part_id = $3
-        RETURNING part_id, part_name, description
+        WHERE part_id = $3 AND deleted_at IS NULL
+        RETURNING part_id, part_name, description, sku, oem_number, manufacturer, unit, deleted_at
         "#,
         part_name,
         description,
         part_id
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
 }
 
-pub async fn delete_spare_part(pool: &DbPool, part_id: i32) -> Result<(), DbError> {
+pub async fn delete_spare_part(conn: &mut DbConn, part_id: i32) -> Result<(), DbError> {
     sqlx::query!(
         r#"
-        DELETE FROM moto_auto.spare_part
-        WHERE part_id = $1
+        UPDATE moto_auto.spare_part
+        SET deleted_at = NOW()
+        WHERE part_id = $1 AND deleted_at IS NULL
+        RETURNING part_id
         "#,
         part_id
     )
-    .execute(pool)
+    .fetch_one(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
     .map(|_| {})
 }
 
-pub async fn get_spare_part(pool: &DbPool) -> Result<Vec<SparePart>, DbError> {
+pub async fn get_spare_part(conn: &mut DbConn) -> Result<Vec<SparePart>, DbError> {
     sqlx::query_as!(
         SparePart,
         r#"
         SELECT * FROM moto_auto.spare_part
+        WHERE deleted_at IS NULL
        "#,
     )
-    .fetch_all(pool)
+    .fetch_all(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
 }
+
+pub async fn restore_spare_part(conn: &mut DbConn, part_id: i32) -> Result<SparePart, DbError> {
+    sqlx::query_as!(
+        SparePart,
+        r#"
+        UPDATE moto_auto.spare_part
+        SET deleted_at = NULL
+        WHERE part_id = $1 AND deleted_at IS NOT NULL
+        RETURNING part_id, part_name, description, sku, oem_number, manufacturer, unit, deleted_at
+        "#,
+        part_id
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+pub async fn get_archived_spare_part(conn: &mut DbConn) -> Result<Vec<SparePart>, DbError> {
+    sqlx::query_as!(
+        SparePart,
+        r#"
+        SELECT * FROM moto_auto.spare_part
+        WHERE deleted_at IS NOT NULL
+        ORDER BY deleted_at DESC
+        "#,
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+/// Replaces the catalogue details of a part; empty values clear them.
+pub async fn update_part_details(
+    conn: &mut DbConn,
+    part_id: i32,
+    sku: Option<&str>,
+    oem_number: Option<&str>,
+    manufacturer: Option<&str>,
+    unit: &str,
+) -> Result<SparePart, DbError> {
+    sqlx::query_as!(
+        SparePart,
+        r#"
+        UPDATE moto_auto.spare_part
+        SET sku = $2, oem_number = $3, manufacturer = $4, unit = $5
+        WHERE part_id = $1 AND deleted_at IS NULL
+        RETURNING part_id, part_name, description, sku, oem_number, manufacturer, unit, deleted_at
+        "#,
+        part_id,
+        sku,
+        oem_number,
+        manufacturer,
+        unit
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+/// Parts that can replace `part_id`, whichever way round the pair was recorded.
+pub async fn get_part_substitutes(conn: &mut DbConn, part_id: i32) -> Result<Vec<SparePart>, DbError> {
+    sqlx::query_as!(
+        SparePart,
+        r#"
+        SELECT p.part_id, p.part_name, p.description, p.sku, p.oem_number, p.manufacturer, p.unit, p.deleted_at
+        FROM moto_auto.part_substitute s
+        INNER JOIN moto_auto.spare_part p
+        ON p.part_id = CASE WHEN s.part_id = $1 THEN s.substitute_id ELSE s.part_id END
+        WHERE $1 IN (s.part_id, s.substitute_id)
+        AND p.deleted_at IS NULL
+        ORDER BY p.part_name
+        "#,
+        part_id
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+/// Records that two parts replace each other.
+pub async fn link_part_substitute(conn: &mut DbConn, part_id: i32, substitute_id: i32) -> Result<(), DbError> {
+    if part_id == substitute_id {
+        return Err(DbError::BadInput);
+    }
+    sqlx::query!(
+        r#"
+        INSERT INTO moto_auto.part_substitute (part_id, substitute_id)
+        VALUES (LEAST($1::INTEGER, $2::INTEGER), GREATEST($1::INTEGER, $2::INTEGER))
+        ON CONFLICT DO NOTHING
+        "#,
+        part_id,
+        substitute_id
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+    .map(|_| {})
+}
+
+pub async fn unlink_part_substitute(conn: &mut DbConn, part_id: i32, substitute_id: i32) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"
+        DELETE FROM moto_auto.part_substitute
+        WHERE part_id = LEAST($1::INTEGER, $2::INTEGER) AND substitute_id = GREATEST($1::INTEGER, $2::INTEGER)
+        "#,
+        part_id,
+        substitute_id
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+    .map(|_| {})
+}
+
+/// Parts matching `query` by name, SKU, OEM number or manufacturer together with their
+/// substitutes, the ones that fit `vehicle_id` first. Prices and stock are `branch_id`'s.
+pub async fn find_parts(
+    conn: &mut DbConn,
+    query: &str,
+    vehicle_id: Option<i32>,
+    branch_id: i32,
+    limit: i32,
+) -> Result<Vec<PartMatch>, DbError> {
+    sqlx::query_as!(
+        PartMatch,
+        r#"
+        SELECT
+            part_id AS "part_id!",
+            part_name AS "part_name!",
+            sku,
+            oem_number,
+            manufacturer,
+            unit AS "unit!",
+            price,
+            stock_quantity,
+            fits,
+            substitute_for,
+            rank AS "rank!"
+        FROM find_parts($1, $2, $3, $4)
+        "#,
+        query,
+        vehicle_id,
+        branch_id,
+        limit
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
@@ -1,28 +1,32 @@
 use bigdecimal::BigDecimal;
 
-use crate::database::{DbError, DbPool};
+use crate::database::{DbConn, DbError};
 use crate::models::SparePartBranch;
 
-pub async fn create_spare_part_branch(pool: &DbPool, spare_part_branch: SparePartBranch) -> Result<SparePartBranch, DbError> {
+pub async fn create_spare_part_branch(conn: &mut DbConn, spare_part_branch: SparePartBranch) -> Result<SparePartBranch, DbError> {
     sqlx::query_as!(
         SparePartBranch,
         r#"
-        INSERT INTO moto_auto.spare_part_branch (part_id, branch_id, stock_quantity, price)
-        VALUES ($1, $2, $3, $4)
-        RETURNING spare_part_branch_id, part_id, branch_id, stock_quantity, price
+        INSERT INTO moto_auto.spare_part_branch (
+            part_id, branch_id, stock_quantity, price, reorder_point, reorder_quantity
+        )
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING spare_part_branch_id, part_id, branch_id, stock_quantity, price, reorder_point, reorder_quantity
         "#,
         spare_part_branch.part_id,
         spare_part_branch.branch_id,
         spare_part_branch.stock_quantity,
-        spare_part_branch.price
+        spare_part_branch.price,
+        spare_part_branch.reorder_point,
+        spare_part_branch.reorder_quantity
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
 }
 
 pub async fn update_spare_part_branch(
-    pool: &DbPool,
+    conn: &mut DbConn,
     stock_quantity: Option<i32>,
     price: Option<BigDecimal>,
     spare_part_branch_id: i32,
@@ -35,18 +39,18 @@ pub async fn update_spare_part_branch(
             stock_quantity = COALESCE($1, stock_quantity),
             price = COALESCE($2, price)
         WHERE spare_part_branch_id = $3
-        RETURNING spare_part_branch_id, part_id, branch_id, stock_quantity, price
+        RETURNING spare_part_branch_id, part_id, branch_id, stock_quantity, price, reorder_point, reorder_quantity
         "#,
         stock_quantity,
         price,
         spare_part_branch_id
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
 }
 
-pub async fn delete_spare_part_branch(pool: &DbPool, part_id: i32, branch_id: i32) -> Result<(), DbError> {
+pub async fn delete_spare_part_branch(conn: &mut DbConn, part_id: i32, branch_id: i32) -> Result<(), DbError> {
     sqlx::query!(
         r#"
         DELETE FROM moto_auto.spare_part_branch
@@ -55,14 +59,14 @@ pub async fn delete_spare_part_branch(pool: &DbPool, part_id: i32, branch_id: i3
         part_id,
         branch_id
     )
-    .execute(pool)
+    .execute(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
     .map(|_| {})
 }
 
 pub async fn get_spare_part_branch(
-    pool: &DbPool,
+    conn: &mut DbConn,
     branch_id: Option<i32>,
     part_id: Option<i32>,
 ) -> Result<Vec<SparePartBranch>, DbError> {
@@ -76,7 +80,7 @@ pub async fn get_spare_part_branch(
                 "#,
                 id
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         },
@@ -89,7 +93,7 @@ pub async fn get_spare_part_branch(
                 "#,
                 id
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         },
@@ -103,7 +107,7 @@ pub async fn get_spare_part_branch(
                 branch_id,
                 part_id
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         },
@@ -114,7 +118,7 @@ pub async fn get_spare_part_branch(
                 SELECT * FROM moto_auto.spare_part_branch
                 "#,
             )
-            .fetch_all(pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Sqlx(e))
         }
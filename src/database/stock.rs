@@ -0,0 +1,234 @@
+use crate::database::{DbConn, DbError};
+use crate::models::{LowStockAlert, PurchaseOrder, PurchaseOrderLine, SparePartBranch, StockLevel};
+
+/// Resolves alerts for stock that is back above the reorder point and raises alerts for
+/// stock below it, returning the new ones. With `draft_orders` the low parts are also
+/// added to each branch's draft purchase order unless they are already on order.
+pub async fn detect_low_stock(
+    conn: &mut DbConn,
+    draft_orders: bool,
+) -> Result<Vec<LowStockAlert>, DbError> {
+    sqlx::query_as!(
+        LowStockAlert,
+        r#"
+        SELECT
+            alert_id AS "alert_id!", spare_part_branch_id AS "spare_part_branch_id!",
+            part_id AS "part_id!", branch_id AS "branch_id!",
+            stock_quantity AS "stock_quantity!", reorder_point AS "reorder_point!",
+            detected_at AS "detected_at!", resolved_at
+        FROM detect_low_stock($1)
+        ORDER BY branch_id, alert_id
+        "#,
+        draft_orders
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+/// Parts sold in the branch with their reorder levels, low ones first. With `low_only`
+/// only parts below their reorder point are listed.
+pub async fn get_stock_levels(
+    conn: &mut DbConn,
+    branch_id: i32,
+    low_only: bool,
+) -> Result<Vec<StockLevel>, DbError> {
+    sqlx::query_as!(
+        StockLevel,
+        r#"
+        SELECT
+            spb.spare_part_branch_id AS "spare_part_branch_id!", spb.part_id, p.part_name, p.sku,
+            p.unit, spb.stock_quantity, spb.reorder_point, spb.reorder_quantity,
+            COALESCE((
+                SELECT SUM(l.quantity)
+                FROM moto_auto.purchase_order_line l
+                INNER JOIN moto_auto.purchase_order po
+                ON po.purchase_order_id = l.purchase_order_id
+                WHERE po.branch_id = spb.branch_id
+                AND l.part_id = spb.part_id
+                AND po.status IN ('draft', 'ordered')
+            ), 0)::BIGINT AS "on_order!",
+            (
+                SELECT a.detected_at
+                FROM moto_auto.low_stock_alert a
+                WHERE a.spare_part_branch_id = spb.spare_part_branch_id
+                AND a.resolved_at IS NULL
+            ) AS low_since
+        FROM moto_auto.spare_part_branch spb
+        INNER JOIN moto_auto.spare_part p
+        ON p.part_id = spb.part_id
+        WHERE spb.branch_id = $1
+        AND p.deleted_at IS NULL
+        AND (NOT $2 OR spb.stock_quantity < spb.reorder_point)
+        ORDER BY spb.stock_quantity < spb.reorder_point IS TRUE DESC, p.part_name, spb.part_id
+        "#,
+        branch_id,
+        low_only
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+/// Sets the reorder point and quantity of a part in the branch, which must be the acting
+/// user's. Both are set or both cleared.
+pub async fn set_reorder_level(
+    conn: &mut DbConn,
+    branch_id: i32,
+    part_id: i32,
+    reorder_point: Option<i32>,
+    reorder_quantity: Option<i32>,
+) -> Result<SparePartBranch, DbError> {
+    sqlx::query_as!(
+        SparePartBranch,
+        r#"
+        SELECT
+            spare_part_branch_id, part_id AS "part_id!", branch_id AS "branch_id!",
+            stock_quantity AS "stock_quantity!", price AS "price!", reorder_point,
+            reorder_quantity
+        FROM set_reorder_level($1, $2, $3, $4)
+        "#,
+        branch_id,
+        part_id,
+        reorder_point,
+        reorder_quantity
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+/// Draft and ordered purchase orders of the branch, the oldest first.
+pub async fn get_open_purchase_orders(
+    conn: &mut DbConn,
+    branch_id: i32,
+) -> Result<Vec<PurchaseOrder>, DbError> {
+    sqlx::query_as!(
+        PurchaseOrder,
+        r#"
+        SELECT * FROM moto_auto.purchase_order
+        WHERE branch_id = $1 AND status IN ('draft', 'ordered')
+        ORDER BY created_at, purchase_order_id
+        "#,
+        branch_id
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+pub async fn get_purchase_order(
+    conn: &mut DbConn,
+    purchase_order_id: i32,
+) -> Result<PurchaseOrder, DbError> {
+    sqlx::query_as!(
+        PurchaseOrder,
+        r#"
+        SELECT * FROM moto_auto.purchase_order
+        WHERE purchase_order_id = $1
+        "#,
+        purchase_order_id
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+pub async fn get_purchase_order_lines(
+    conn: &mut DbConn,
+    purchase_order_id: i32,
+) -> Result<Vec<PurchaseOrderLine>, DbError> {
+    sqlx::query_as!(
+        PurchaseOrderLine,
+        r#"
+        SELECT l.purchase_order_line_id, l.purchase_order_id, l.part_id, p.part_name, p.sku, p.unit,
+            l.quantity
+        FROM moto_auto.purchase_order_line l
+        INNER JOIN moto_auto.spare_part p
+        ON p.part_id = l.part_id
+        WHERE l.purchase_order_id = $1
+        ORDER BY p.part_name, l.part_id
+        "#,
+        purchase_order_id
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+/// Changes the quantity on a line of a draft purchase order.
+pub async fn update_purchase_order_line(
+    conn: &mut DbConn,
+    purchase_order_line_id: i32,
+    quantity: i32,
+) -> Result<PurchaseOrderLine, DbError> {
+    sqlx::query_as!(
+        PurchaseOrderLine,
+        r#"
+        WITH updated AS (
+            UPDATE moto_auto.purchase_order_line l
+            SET quantity = $2
+            FROM moto_auto.purchase_order po
+            WHERE po.purchase_order_id = l.purchase_order_id
+            AND po.status = 'draft'
+            AND l.purchase_order_line_id = $1
+            RETURNING l.*
+        )
+        SELECT u.purchase_order_line_id, u.purchase_order_id, u.part_id, p.part_name, p.sku, p.unit,
+            u.quantity
+        FROM updated u
+        INNER JOIN moto_auto.spare_part p
+        ON p.part_id = u.part_id
+        "#,
+        purchase_order_line_id,
+        quantity
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+/// Takes a line off a draft purchase order.
+pub async fn remove_purchase_order_line(
+    conn: &mut DbConn,
+    purchase_order_line_id: i32,
+) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"
+        DELETE FROM moto_auto.purchase_order_line l
+        USING moto_auto.purchase_order po
+        WHERE po.purchase_order_id = l.purchase_order_id
+        AND po.status = 'draft'
+        AND l.purchase_order_line_id = $1
+        RETURNING l.purchase_order_line_id
+        "#,
+        purchase_order_line_id
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+    .map(|_| {})
+}
+
+/// Moves a purchase order to `ordered`, `received` or `cancelled`. The database checks
+/// the transition and adds the quantities to the branch's stock once received.
+pub async fn set_purchase_order_status(
+    conn: &mut DbConn,
+    purchase_order_id: i32,
+    status: &str,
+) -> Result<PurchaseOrder, DbError> {
+    sqlx::query_as!(
+        PurchaseOrder,
+        r#"
+        UPDATE moto_auto.purchase_order
+        SET status = $2
+        WHERE purchase_order_id = $1
+        RETURNING *
+        "#,
+        purchase_order_id,
+        status
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
@@ -1,10 +1,10 @@
 use log::error;
 
-use crate::database::{DbError, DbPool};
+use crate::database::{DbConn, DbError};
 use crate::models::User;
 
 async fn admin_branch_check(
-    pool: &DbPool,
+    conn: &mut DbConn,
     admin_branch_id: i32,
     username: &str,
 ) -> Result<(), DbError> {
@@ -15,7 +15,7 @@ async fn admin_branch_check(
         "#,
         username
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await;
     if branch_id.is_err() {
         error!("Error fetching branch_id with username: {}", username);
@@ -29,7 +29,7 @@ async fn admin_branch_check(
 }
 
 pub async fn create_user(
-    pool: &DbPool,
+    conn: &mut DbConn,
     admin_branch_id: i32,
     user: &User,
 ) -> Result<User, DbError> {
@@ -45,22 +45,20 @@ pub async fn create_user(
         user.role,
         user.branch_id
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
 }
 
 pub async fn update_user(
-    pool: &DbPool,
+    conn: &mut DbConn,
     admin_branch_id: i32,
     username: &str,
     new_passwordhash: Option<&str>,
     role: Option<&str>,
     branch_id: Option<i32>,
 ) -> Result<User, DbError> {
-    if let Err(err) = admin_branch_check(pool, admin_branch_id, username).await {
-        return Err(err);
-    }
+    admin_branch_check(&mut *conn, admin_branch_id, username).await?;
 
     sqlx::query_as!(
         User,
@@ -78,19 +76,17 @@ pub async fn update_user(
         branch_id,
         new_passwordhash
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
 }
 
 pub async fn delete_user(
-    pool: &DbPool,
+    conn: &mut DbConn,
     username: &str,
     admin_branch_id: i32,
 ) -> Result<(), DbError> {
-    if let Err(err) = admin_branch_check(pool, admin_branch_id, username).await {
-        return Err(err);
-    }
+    admin_branch_check(&mut *conn, admin_branch_id, username).await?;
 
     sqlx::query!(
         r#"
@@ -99,13 +95,13 @@ pub async fn delete_user(
         "#,
         username
     )
-    .execute(pool)
+    .execute(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
     .map(|_| {})
 }
 
-pub async fn get_user(pool: &DbPool, username: &str) -> Result<User, DbError> {
+pub async fn get_user(conn: &mut DbConn, username: &str) -> Result<User, DbError> {
     sqlx::query_as!(
         User,
         r#"
@@ -114,12 +110,12 @@ pub async fn get_user(pool: &DbPool, username: &str) -> Result<User, DbError> {
         "#,
         username,
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
 }
 
-pub async fn get_users(pool: &DbPool, admin_branch_id: i32) -> Result<Vec<User>, DbError> {
+pub async fn get_users(conn: &mut DbConn, admin_branch_id: i32) -> Result<Vec<User>, DbError> {
     sqlx::query_as!(
         User,
         r#"
@@ -128,7 +124,21 @@ pub async fn get_users(pool: &DbPool, admin_branch_id: i32) -> Result<Vec<User>,
        "#,
         admin_branch_id
     )
-    .fetch_all(pool)
+    .fetch_all(&mut *conn)
     .await
     .map_err(|e| DbError::Sqlx(e))
 }
+
+pub async fn get_user_by_id(conn: &mut DbConn, user_id: i32) -> Result<User, DbError> {
+    sqlx::query_as!(
+        User,
+        r#"
+        SELECT * FROM moto_auto.users
+        WHERE user_id = $1
+        "#,
+        user_id,
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
@@ -0,0 +1,111 @@
+use crate::database::{DbConn, DbError};
+use crate::models::{ServiceHistoryEntry, Vehicle};
+
+pub async fn create_vehicle(conn: &mut DbConn, vehicle: Vehicle) -> Result<Vehicle, DbError> {
+    sqlx::query_as!(
+        Vehicle,
+        r#"
+        INSERT INTO moto_auto.vehicle (client_id, make, model, year, vin, plate, mileage)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING *
+        "#,
+        vehicle.client_id,
+        vehicle.make,
+        vehicle.model,
+        vehicle.year,
+        vehicle.vin,
+        vehicle.plate,
+        vehicle.mileage
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+/// Replaces the vehicle details. The owner stays the same.
+pub async fn update_vehicle(conn: &mut DbConn, vehicle: Vehicle) -> Result<Vehicle, DbError> {
+    sqlx::query_as!(
+        Vehicle,
+        r#"
+        UPDATE moto_auto.vehicle
+        SET make = $2, model = $3, year = $4, vin = $5, plate = $6, mileage = $7
+        WHERE vehicle_id = $1 AND deleted_at IS NULL
+        RETURNING *
+        "#,
+        vehicle.vehicle_id,
+        vehicle.make,
+        vehicle.model,
+        vehicle.year,
+        vehicle.vin,
+        vehicle.plate,
+        vehicle.mileage
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+pub async fn delete_vehicle(conn: &mut DbConn, vehicle_id: i32) -> Result<(), DbError> {
+    sqlx::query!(
+        r#"
+        UPDATE moto_auto.vehicle
+        SET deleted_at = NOW()
+        WHERE vehicle_id = $1 AND deleted_at IS NULL
+        "#,
+        vehicle_id
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+    .map(|_| {})
+}
+
+pub async fn get_vehicle_by_id(conn: &mut DbConn, vehicle_id: i32) -> Result<Vehicle, DbError> {
+    sqlx::query_as!(
+        Vehicle,
+        r#"
+        SELECT * FROM moto_auto.vehicle
+        WHERE vehicle_id = $1
+        "#,
+        vehicle_id
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+pub async fn get_client_vehicles(conn: &mut DbConn, client_id: i32) -> Result<Vec<Vehicle>, DbError> {
+    sqlx::query_as!(
+        Vehicle,
+        r#"
+        SELECT * FROM moto_auto.vehicle
+        WHERE client_id = $1 AND deleted_at IS NULL
+        ORDER BY vehicle_id
+        "#,
+        client_id
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
+
+/// Work done on a vehicle, the latest order first. Covers orders of every master and branch.
+pub async fn get_vehicle_history(
+    conn: &mut DbConn,
+    vehicle_id: i32,
+) -> Result<Vec<ServiceHistoryEntry>, DbError> {
+    sqlx::query_as!(
+        ServiceHistoryEntry,
+        r#"
+        SELECT
+            order_id AS "order_id!", order_date AS "order_date!", completion_date, status AS "status!",
+            branch_id AS "branch_id!", master_id AS "master_id!", intake_mileage, item_type AS "item_type!",
+            name AS "name!", quantity AS "quantity!"
+        FROM vehicle_service_history($1)
+        "#,
+        vehicle_id
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(DbError::Sqlx)
+}
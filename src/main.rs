@@ -1,17 +1,25 @@
+mod config;
+mod contact;
 mod database;
 mod models;
+mod notify;
+mod pricing;
+mod receipt;
+mod vin;
 mod web;
+use config::Config;
 use sqlx::PgPool;
 use web::serve;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::from_env();
     let pool: PgPool = sqlx::postgres::PgPoolOptions::new()
         .max_connections(10)
-        .connect("postgres://localhost:5432/moto_auto?user=superadmin&password=superadmin")
+        .connect(&config.database_url)
         .await?;
 
-    let _ = serve(pool, "127.0.0.1:8080").await;
+    let _ = serve(pool, config).await;
 
     Ok(())
 }
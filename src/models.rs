@@ -11,7 +11,7 @@ pub struct User {
     pub branch_id: i32,
 }
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
 pub struct Branch {
     pub branch_id: Option<i32>,
     pub address: String,
@@ -19,6 +19,20 @@ pub struct Branch {
     pub postal_code: String,
     pub employee_count: i32,
     pub city: String,
+    pub closed_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub vat_rate: BigDecimal,
+    pub max_bonus_share: BigDecimal,
+}
+
+/// Branch details to change; fields left as `None` keep their value.
+#[derive(Debug, Default, Clone)]
+pub struct BranchUpdate {
+    pub address: Option<String>,
+    pub phone_number: Option<String>,
+    pub postal_code: Option<String>,
+    pub employee_count: Option<i32>,
+    pub vat_rate: Option<BigDecimal>,
+    pub max_bonus_share: Option<BigDecimal>,
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
@@ -27,12 +41,27 @@ pub struct Employee {
     pub name: String,
     pub age: i32,
     pub position: String,
-    pub contact_info: String,
+    pub phones: Vec<String>,
+    pub email: Option<String>,
+    pub contact_note: Option<String>,
     pub expirience_years: i32,
     pub salary: BigDecimal,
     pub description: String,
 }
 
+/// Employee details to change; fields left as `None` keep their value.
+#[derive(Debug, Default, Clone)]
+pub struct EmployeeUpdate {
+    pub name: Option<String>,
+    pub age: Option<i32>,
+    pub position: Option<String>,
+    pub phones: Option<Vec<String>>,
+    pub email: Option<String>,
+    pub expirience_years: Option<i32>,
+    pub salary: Option<BigDecimal>,
+    pub description: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct BranchEmployee {
     pub branch_employee_id: Option<i32>,
@@ -40,17 +69,45 @@ pub struct BranchEmployee {
     pub branch_id: i32,
 }
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
 pub struct Client {
     pub client_id: Option<i32>,
     pub name: String,
-    pub contact_info: String,
     pub status: String,
     pub bonus_points: Option<BigDecimal>,
     pub total_spent: BigDecimal,
+    pub home_branch_id: i32,
+    pub deleted_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Phone numbers in E.164 form, the first one is the main.
+    pub phones: Vec<String>,
+    pub email: Option<String>,
+    /// `email` or `sms`; without it emails go first.
+    pub preferred_channel: Option<String>,
+    /// Whether the client gets messages about bookings and orders.
+    pub service_consent: bool,
+    /// Whether the client gets maintenance reminders and offers.
+    pub marketing_consent: bool,
+    pub contact_note: Option<String>,
+}
+
+/// Contact details of a client as a manager edits them.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ClientContacts {
+    pub phones: Vec<String>,
+    pub email: Option<String>,
+    pub preferred_channel: Option<String>,
+    pub service_consent: bool,
+    pub marketing_consent: bool,
+    pub contact_note: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct ClientBranch {
+    pub client_id: i32,
+    pub branch_id: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone, Default)]
 pub struct Order {
     pub order_id: Option<i32>,
     pub client_id: i32,
@@ -60,16 +117,35 @@ pub struct Order {
     pub completion_date: Option<chrono::DateTime<chrono::Utc>>,
     pub total_amount: Option<BigDecimal>,
     pub status: String,
+    pub deleted_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub discount_type: Option<String>,
+    pub discount_value: Option<BigDecimal>,
+    pub discount_reason: Option<String>,
+    pub subtotal: Option<BigDecimal>,
+    pub discount_amount: Option<BigDecimal>,
+    pub vat_rate: Option<BigDecimal>,
+    pub tax_amount: Option<BigDecimal>,
+    pub bonus_points_used: Option<BigDecimal>,
+    pub refunded_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub refund_reason: Option<String>,
+    pub picked_up_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub pickup_override_reason: Option<String>,
+    pub pickup_override_by: Option<i32>,
+    pub vehicle_id: Option<i32>,
+    pub intake_mileage: Option<i32>,
 }
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
 pub struct Service {
     pub service_id: Option<i32>,
     pub service_name: String,
     pub description: String,
+    pub deleted_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub interval_months: Option<i32>,
+    pub interval_km: Option<i32>,
 }
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
 pub struct ServiceBranch {
     pub service_branch_id: Option<i32>,
     pub price: BigDecimal,
@@ -77,35 +153,86 @@ pub struct ServiceBranch {
     pub service_id: i32,
 }
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
 pub struct SparePart {
     pub part_id: Option<i32>,
     pub part_name: String,
     pub description: String,
+    pub sku: Option<String>,
+    /// Number in the motorcycle maker's parts catalogue.
+    pub oem_number: Option<String>,
+    pub manufacturer: Option<String>,
+    /// One of [`crate::database::spare_part::PART_UNITS`].
+    pub unit: String,
+    pub deleted_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+/// Motorcycles a part fits: any model of `make` when `model` is empty, and any year
+/// when the bounds are.
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct PartCompatibility {
+    pub compatibility_id: Option<i32>,
+    pub part_id: i32,
+    pub make: String,
+    pub model: Option<String>,
+    pub year_from: Option<i32>,
+    pub year_to: Option<i32>,
+}
+
+/// A part found for an order. `fits` is unknown when there is no vehicle or the part
+/// has no compatibility entries; `substitute_for` is set for parts listed because they
+/// replace a matched one. Price and stock are the branch's.
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct PartMatch {
+    pub part_id: i32,
+    pub part_name: String,
+    pub sku: Option<String>,
+    pub oem_number: Option<String>,
+    pub manufacturer: Option<String>,
+    pub unit: String,
+    pub price: Option<BigDecimal>,
+    pub stock_quantity: Option<i32>,
+    pub fits: Option<bool>,
+    pub substitute_for: Option<i32>,
+    pub rank: f32,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
 pub struct SparePartBranch {
     pub spare_part_branch_id: Option<i32>,
     pub part_id: i32,
     pub branch_id: i32,
     pub stock_quantity: i32,
     pub price: BigDecimal,
+    /// Stock below this is low; stock is not watched without it.
+    pub reorder_point: Option<i32>,
+    /// How much to order when stock is low, set together with the reorder point.
+    pub reorder_quantity: Option<i32>,
 }
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone, Default)]
 pub struct OrderService {
     pub order_service_id: Option<i32>,
     pub order_id: i32,
     pub service_id: i32,
+    pub unit_price: Option<BigDecimal>,
+    pub service_name: Option<String>,
+    pub discount_type: Option<String>,
+    pub discount_value: Option<BigDecimal>,
+    pub discount_reason: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone, Default)]
 pub struct OrderServicePart {
     pub order_service_part_id: Option<i32>,
     pub part_id: i32,
     pub order_service_id: i32,
     pub quantity: i32,
+    pub unit_price: Option<BigDecimal>,
+    pub part_name: Option<String>,
+    pub discount_type: Option<String>,
+    pub discount_value: Option<BigDecimal>,
+    pub discount_reason: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
@@ -117,3 +244,257 @@ pub struct Schedule {
     pub scheduled_datetime: chrono::DateTime<chrono::Utc>,
     pub status: String,
 }
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct AuditLog {
+    pub audit_id: i64,
+    pub actor_user_id: Option<i32>,
+    pub entity: String,
+    pub entity_id: i32,
+    pub operation: String,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+    pub changed_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct PriceHistory {
+    pub price_history_id: i64,
+    pub item_type: String,
+    pub item_id: i32,
+    pub branch_id: i32,
+    pub old_price: Option<BigDecimal>,
+    pub new_price: Option<BigDecimal>,
+    pub actor_user_id: Option<i32>,
+    pub changed_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct LoyaltyTier {
+    pub tier_id: Option<i32>,
+    pub name: String,
+    pub spend_threshold: BigDecimal,
+    pub accrual_rate: BigDecimal,
+    pub points_lifetime_days: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct BonusTransaction {
+    pub transaction_id: i64,
+    pub client_id: i32,
+    pub order_id: Option<i32>,
+    pub kind: String,
+    pub points: BigDecimal,
+    pub remaining: BigDecimal,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub actor_user_id: Option<i32>,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct Receipt {
+    pub receipt_id: i32,
+    pub order_id: i32,
+    pub branch_id: i32,
+    pub receipt_number: i32,
+    pub issued_at: chrono::DateTime<chrono::Utc>,
+    pub client_name: String,
+    pub branch_city: String,
+    pub branch_address: String,
+    pub branch_postal_code: String,
+    pub branch_phone_number: String,
+    pub subtotal: BigDecimal,
+    pub discount_amount: BigDecimal,
+    pub vat_rate: BigDecimal,
+    pub tax_amount: BigDecimal,
+    pub bonus_points_used: BigDecimal,
+    pub total_amount: BigDecimal,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct ReceiptLine {
+    pub receipt_line_id: i32,
+    pub receipt_id: i32,
+    pub line_number: i32,
+    pub item_type: String,
+    pub name: String,
+    pub quantity: i32,
+    pub unit_price: BigDecimal,
+    pub discount_amount: BigDecimal,
+    pub amount: BigDecimal,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct Payment {
+    pub payment_id: i32,
+    pub order_id: i32,
+    pub amount: BigDecimal,
+    pub method: String,
+    pub paid_at: chrono::DateTime<chrono::Utc>,
+    pub cashier_id: i32,
+    pub shift_id: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct CashShift {
+    pub shift_id: i32,
+    pub branch_id: i32,
+    pub opened_by: i32,
+    pub opened_at: chrono::DateTime<chrono::Utc>,
+    pub opening_cash: BigDecimal,
+    pub closed_by: Option<i32>,
+    pub closed_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub counted_cash: Option<BigDecimal>,
+    pub expected_cash: Option<BigDecimal>,
+    pub discrepancy: Option<BigDecimal>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct ShiftTotal {
+    pub method: String,
+    pub amount: BigDecimal,
+    pub payment_count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct Vehicle {
+    pub vehicle_id: Option<i32>,
+    pub client_id: i32,
+    pub make: String,
+    pub model: String,
+    pub year: Option<i32>,
+    pub vin: Option<String>,
+    pub plate: Option<String>,
+    pub mileage: i32,
+    pub deleted_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct ServiceHistoryEntry {
+    pub order_id: i32,
+    pub order_date: chrono::DateTime<chrono::Utc>,
+    pub completion_date: Option<chrono::DateTime<chrono::Utc>>,
+    pub status: String,
+    pub branch_id: i32,
+    pub master_id: i32,
+    pub intake_mileage: Option<i32>,
+    pub item_type: String,
+    pub name: String,
+    pub quantity: i32,
+}
+
+/// A due maintenance reminder with the client, vehicle and service it is about.
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct MaintenanceReminder {
+    pub reminder_id: i32,
+    pub client_id: i32,
+    pub client_name: String,
+    pub phone: Option<String>,
+    pub email: Option<String>,
+    pub vehicle_id: i32,
+    pub make: String,
+    pub model: String,
+    pub plate: Option<String>,
+    pub mileage: i32,
+    pub service_id: i32,
+    pub service_name: String,
+    pub last_order_id: i32,
+    pub branch_id: i32,
+    pub due_date: Option<chrono::NaiveDate>,
+    pub due_mileage: Option<i32>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub notified_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// A queued notification with what its message needs about the client, order, booking
+/// or reminder it is about.
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct OutboxMessage {
+    pub outbox_id: i32,
+    pub kind: String,
+    pub attempts: i32,
+    pub client_name: String,
+    pub phone: Option<String>,
+    pub email: Option<String>,
+    pub preferred_channel: Option<String>,
+    pub service_consent: bool,
+    pub marketing_consent: bool,
+    pub order_id: Option<i32>,
+    pub order_status: Option<String>,
+    pub total_amount: Option<BigDecimal>,
+    pub scheduled_datetime: Option<chrono::DateTime<chrono::Utc>>,
+    pub reminder_id: Option<i32>,
+    pub due_date: Option<chrono::NaiveDate>,
+    pub due_mileage: Option<i32>,
+    pub service_name: Option<String>,
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub branch_address: Option<String>,
+    pub branch_phone: Option<String>,
+}
+
+/// A match of the global search: `kind` is `client`, `order`, `vehicle`, `part` or
+/// `service` and `id` the key in its table. Better matches rank closer to 1.
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct SearchResult {
+    pub kind: String,
+    pub id: i32,
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub rank: f32,
+}
+
+/// Raised by the low-stock job when a part's stock in a branch falls below its reorder
+/// point, and resolved once the stock is back or the point is cleared.
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct LowStockAlert {
+    pub alert_id: i32,
+    pub spare_part_branch_id: i32,
+    pub part_id: i32,
+    pub branch_id: i32,
+    pub stock_quantity: i32,
+    pub reorder_point: i32,
+    pub detected_at: chrono::DateTime<chrono::Utc>,
+    pub resolved_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// A part's stock in a branch. `on_order` counts draft and ordered purchase order lines,
+/// `low_since` is when the open low-stock alert was raised.
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct StockLevel {
+    pub spare_part_branch_id: i32,
+    pub part_id: i32,
+    pub part_name: String,
+    pub sku: Option<String>,
+    pub unit: String,
+    pub stock_quantity: i32,
+    pub reorder_point: Option<i32>,
+    pub reorder_quantity: Option<i32>,
+    pub on_order: i64,
+    pub low_since: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Order to a supplier: `draft`, `ordered`, `received` or `cancelled`. Receiving it adds
+/// the quantities to the branch's stock.
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct PurchaseOrder {
+    pub purchase_order_id: i32,
+    pub branch_id: i32,
+    pub status: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub ordered_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub received_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub updated_by: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct PurchaseOrderLine {
+    pub purchase_order_line_id: i32,
+    pub purchase_order_id: i32,
+    pub part_id: i32,
+    pub part_name: String,
+    pub sku: Option<String>,
+    pub unit: String,
+    pub quantity: i32,
+}
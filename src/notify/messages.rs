@@ -0,0 +1,161 @@
+//! Message bodies, rendered from the plain text templates in `templates/notifications`.
+
+use askama::Template;
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::models::OutboxMessage;
+
+use super::{Channel, Notification, NotifyError};
+
+#[derive(Template)]
+#[template(path = "notifications/booking_confirmed.txt")]
+struct BookingConfirmed<'a> {
+    client_name: &'a str,
+    scheduled_at: DateTime<Utc>,
+    branch_address: Option<&'a str>,
+    branch_phone: Option<&'a str>,
+}
+
+#[derive(Template)]
+#[template(path = "notifications/order_finished.txt")]
+struct OrderFinished<'a> {
+    client_name: &'a str,
+    order_id: i32,
+    vehicle: Option<String>,
+    total_amount: Option<&'a BigDecimal>,
+    branch_address: Option<&'a str>,
+}
+
+#[derive(Template)]
+#[template(path = "notifications/order_cancelled.txt")]
+struct OrderCancelled<'a> {
+    client_name: &'a str,
+    order_id: i32,
+    branch_phone: Option<&'a str>,
+}
+
+#[derive(Template)]
+#[template(path = "notifications/order_refunded.txt")]
+struct OrderRefunded<'a> {
+    client_name: &'a str,
+    order_id: i32,
+    total_amount: Option<&'a BigDecimal>,
+}
+
+#[derive(Template)]
+#[template(path = "notifications/maintenance_due.txt")]
+struct MaintenanceDue<'a> {
+    client_name: &'a str,
+    vehicle: Option<String>,
+    service_name: &'a str,
+    due_date: Option<NaiveDate>,
+    due_mileage: Option<i32>,
+    branch_phone: Option<&'a str>,
+}
+
+fn vehicle(message: &OutboxMessage) -> Option<String> {
+    match (&message.make, &message.model) {
+        (Some(make), Some(model)) => Some(format!("{} {}", make, model)),
+        _ => None,
+    }
+}
+
+/// The client's preferred channel when they have a contact for it, otherwise email
+/// before a text message.
+fn recipient(message: &OutboxMessage) -> Result<(Channel, &str), NotifyError> {
+    let email = message.email.as_deref().map(|email| (Channel::Email, email));
+    let sms = message.phone.as_deref().map(|phone| (Channel::Sms, phone));
+    let recipient = match message.preferred_channel.as_deref() {
+        Some("sms") => sms.or(email),
+        _ => email.or(sms),
+    };
+    recipient.ok_or(NotifyError::NoContact)
+}
+
+/// Maintenance reminders need the client's marketing consent, messages about bookings
+/// and orders the consent to service messages.
+fn consented(message: &OutboxMessage) -> bool {
+    match message.kind.as_str() {
+        "maintenance_due" => message.marketing_consent,
+        _ => message.service_consent,
+    }
+}
+
+/// Builds the notification for a queued message.
+pub fn render(message: &OutboxMessage) -> Result<Notification, NotifyError> {
+    if !consented(message) {
+        return Err(NotifyError::NoConsent);
+    }
+    let (channel, recipient) = recipient(message)?;
+    let client_name = message.client_name.as_str();
+    let branch_address = message.branch_address.as_deref();
+    let branch_phone = message.branch_phone.as_deref();
+    let order_id = message.order_id.unwrap_or_default();
+    let (subject, body) = match (message.kind.as_str(), message.order_status.as_deref()) {
+        ("booking_confirmed", _) => (
+            "Your visit is booked".to_string(),
+            BookingConfirmed {
+                client_name,
+                scheduled_at: message.scheduled_datetime.ok_or(NotifyError::Outdated)?,
+                branch_address,
+                branch_phone,
+            }
+            .render(),
+        ),
+        ("order_status", Some("finished")) => (
+            "Your order is ready".to_string(),
+            OrderFinished {
+                client_name,
+                order_id,
+                vehicle: vehicle(message),
+                total_amount: message.total_amount.as_ref(),
+                branch_address,
+            }
+            .render(),
+        ),
+        ("order_status", Some("cancelled")) => (
+            format!("Order {} cancelled", order_id),
+            OrderCancelled {
+                client_name,
+                order_id,
+                branch_phone,
+            }
+            .render(),
+        ),
+        ("order_status", Some("refunded")) => (
+            format!("Order {} refunded", order_id),
+            OrderRefunded {
+                client_name,
+                order_id,
+                total_amount: message.total_amount.as_ref(),
+            }
+            .render(),
+        ),
+        ("maintenance_due", _) => {
+            let service_name = message
+                .service_name
+                .as_deref()
+                .ok_or(NotifyError::Outdated)?;
+            (
+                format!("{} is due", service_name),
+                MaintenanceDue {
+                    client_name,
+                    vehicle: vehicle(message),
+                    service_name,
+                    due_date: message.due_date,
+                    due_mileage: message.due_mileage,
+                    branch_phone,
+                }
+                .render(),
+            )
+        }
+        _ => return Err(NotifyError::Outdated),
+    };
+    Ok(Notification {
+        channel,
+        recipient: recipient.to_string(),
+        subject,
+        body: body.map_err(|e| NotifyError::Send(e.to_string()))?,
+    })
+}
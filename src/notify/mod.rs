@@ -0,0 +1,220 @@
+//! Messages to clients. Events queue notifications in the outbox table and
+//! [`deliver_outbox`] sends them through a [`Notifier`]: email over SMTP, text messages
+//! through an SMS gateway, or a file or the log when no real channel is set up.
+
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use sqlx::Connection;
+use tokio::io::AsyncWriteExt;
+
+use crate::config::Config;
+use crate::database::outbox::{claim_outbox, mark_outbox_failed, mark_outbox_sent};
+use crate::database::reminder::mark_reminder_notified;
+use crate::database::{DbConn, DbError};
+
+mod messages;
+mod sms;
+mod smtp;
+
+pub use messages::render;
+pub use sms::SmsGatewayNotifier;
+pub use smtp::SmtpNotifier;
+
+/// Attempts at sending a notification before it is left for good.
+pub const MAX_ATTEMPTS: i32 = 5;
+
+/// Notifications sent in one run at most.
+const BATCH_SIZE: usize = 50;
+
+/// How long a channel may take over one notification before the attempt counts as failed.
+const SEND_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Channel {
+    Email,
+    Sms,
+}
+
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub channel: Channel,
+    /// An email address or a phone number, depending on the channel.
+    pub recipient: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(Debug)]
+pub enum NotifyError {
+    Send(String),
+    BadRecipient,
+    /// The client has no contact to send to.
+    NoContact,
+    /// The client has not agreed to this kind of message.
+    NoConsent,
+    /// What the notification is about no longer exists.
+    Outdated,
+}
+
+impl NotifyError {
+    /// Errors that will not go away by trying again.
+    fn is_permanent(&self) -> bool {
+        !matches!(self, NotifyError::Send(_))
+    }
+}
+
+impl fmt::Display for NotifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotifyError::Send(e) => write!(f, "{}", e),
+            NotifyError::BadRecipient => write!(f, "bad recipient"),
+            NotifyError::NoContact => write!(f, "no contact"),
+            NotifyError::NoConsent => write!(f, "no consent"),
+            NotifyError::Outdated => write!(f, "outdated"),
+        }
+    }
+}
+
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    async fn send(&self, notification: &Notification) -> Result<(), NotifyError>;
+}
+
+/// Writes notifications to the log instead of delivering them.
+pub struct LogNotifier;
+
+#[async_trait]
+impl Notifier for LogNotifier {
+    async fn send(&self, notification: &Notification) -> Result<(), NotifyError> {
+        tracing::info!(
+            channel = ?notification.channel,
+            recipient = %notification.recipient,
+            subject = %notification.subject,
+            "{}",
+            notification.body
+        );
+        Ok(())
+    }
+}
+
+/// Appends notifications to a file, for trying things out locally.
+pub struct FileNotifier {
+    path: PathBuf,
+}
+
+impl FileNotifier {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileNotifier { path: path.into() }
+    }
+}
+
+#[async_trait]
+impl Notifier for FileNotifier {
+    async fn send(&self, notification: &Notification) -> Result<(), NotifyError> {
+        let entry = format!(
+            "{} {:?} to {}: {}\n{}\n\n",
+            chrono::Utc::now().format("%Y-%m-%d %H:%M:%S"),
+            notification.channel,
+            notification.recipient,
+            notification.subject,
+            notification.body.trim_end()
+        );
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .map_err(|e| NotifyError::Send(e.to_string()))?;
+        file.write_all(entry.as_bytes())
+            .await
+            .map_err(|e| NotifyError::Send(e.to_string()))
+    }
+}
+
+/// Hands each notification to the sender of its channel.
+pub struct ChannelNotifier {
+    pub email: Arc<dyn Notifier>,
+    pub sms: Arc<dyn Notifier>,
+}
+
+#[async_trait]
+impl Notifier for ChannelNotifier {
+    async fn send(&self, notification: &Notification) -> Result<(), NotifyError> {
+        match notification.channel {
+            Channel::Email => self.email.send(notification).await,
+            Channel::Sms => self.sms.send(notification).await,
+        }
+    }
+}
+
+/// Senders set up in the config. A channel without settings falls back to the
+/// notification file if there is one, otherwise to the log.
+pub fn notifier_from_config(config: &Config) -> Arc<dyn Notifier> {
+    let fallback: Arc<dyn Notifier> = match &config.notification_file {
+        Some(path) => Arc::new(FileNotifier::new(path)),
+        None => Arc::new(LogNotifier),
+    };
+    let email: Arc<dyn Notifier> = match &config.smtp_url {
+        Some(url) => match SmtpNotifier::new(url, &config.smtp_from) {
+            Ok(smtp) => Arc::new(smtp),
+            Err(e) => {
+                eprintln!("Error setting up SMTP, emails go to the fallback: {}", e);
+                fallback.clone()
+            }
+        },
+        None => fallback.clone(),
+    };
+    let sms: Arc<dyn Notifier> = match &config.sms_gateway_url {
+        Some(url) => Arc::new(SmsGatewayNotifier::new(
+            url,
+            config.sms_gateway_token.as_deref(),
+        )),
+        None => fallback,
+    };
+    Arc::new(ChannelNotifier { email, sms })
+}
+
+/// Sends the notifications that are due from the outbox and returns how many went out.
+/// Failed ones are tried again later, see [`mark_outbox_failed`]. Each notification is
+/// claimed, sent and marked in a transaction of its own, so an error recording one does
+/// not undo the others and get them sent twice.
+pub async fn deliver_outbox(conn: &mut DbConn, notifier: &dyn Notifier) -> Result<usize, DbError> {
+    let mut sent = 0;
+    for _ in 0..BATCH_SIZE {
+        let mut tx = conn.begin().await.map_err(DbError::Sqlx)?;
+        let Some(message) = claim_outbox(&mut tx, 1).await?.pop() else {
+            break;
+        };
+        let result = match render(&message) {
+            Ok(notification) => tokio::time::timeout(SEND_TIMEOUT, notifier.send(&notification))
+                .await
+                .unwrap_or_else(|_| Err(NotifyError::Send("timed out".to_string()))),
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => {
+                mark_outbox_sent(&mut tx, message.outbox_id).await?;
+                if let Some(reminder_id) = message.reminder_id {
+                    mark_reminder_notified(&mut tx, reminder_id).await?;
+                }
+                sent += 1;
+            }
+            Err(e) => {
+                mark_outbox_failed(
+                    &mut tx,
+                    message.outbox_id,
+                    &e.to_string(),
+                    MAX_ATTEMPTS,
+                    e.is_permanent(),
+                )
+                .await?
+            }
+        }
+        tx.commit().await.map_err(DbError::Sqlx)?;
+    }
+    Ok(sent)
+}
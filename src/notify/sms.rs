@@ -0,0 +1,47 @@
+use async_trait::async_trait;
+use serde::Serialize;
+
+use super::{Notification, Notifier, NotifyError};
+
+/// Sends text messages through an HTTP gateway that takes a JSON body with the phone
+/// number and the text.
+pub struct SmsGatewayNotifier {
+    client: reqwest::Client,
+    url: String,
+    token: Option<String>,
+}
+
+#[derive(Serialize)]
+struct SmsRequest<'a> {
+    to: &'a str,
+    text: &'a str,
+}
+
+impl SmsGatewayNotifier {
+    pub fn new(url: &str, token: Option<&str>) -> Self {
+        SmsGatewayNotifier {
+            client: reqwest::Client::new(),
+            url: url.to_string(),
+            token: token.map(str::to_string),
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for SmsGatewayNotifier {
+    async fn send(&self, notification: &Notification) -> Result<(), NotifyError> {
+        let mut request = self.client.post(&self.url).json(&SmsRequest {
+            to: &notification.recipient,
+            text: &notification.body,
+        });
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| NotifyError::Send(e.to_string()))
+            .map(|_| {})
+    }
+}
@@ -0,0 +1,141 @@
+//! Order totals on the application side, used for previews before an order is saved.
+//! Mirrors the price snapshots taken by the order line triggers and `price_order` in the
+//! database, and must stay in step with them.
+
+use bigdecimal::{BigDecimal, RoundingMode};
+use serde::{Deserialize, Serialize};
+
+use crate::models::{ServiceBranch, SparePartBranch};
+
+#[derive(Debug, Deserialize)]
+pub struct PartLine {
+    pub part_id: i32,
+    pub quantity: i32,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct OrderLines {
+    #[serde(default)]
+    pub service_ids: Vec<i32>,
+    #[serde(default)]
+    pub parts: Vec<PartLine>,
+    pub discount: Option<Discount>,
+    pub bonus_points: Option<BigDecimal>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiscountKind {
+    Percent,
+    Fixed,
+}
+
+impl DiscountKind {
+    pub fn parse(value: &str) -> Option<DiscountKind> {
+        match value {
+            "percent" => Some(DiscountKind::Percent),
+            "fixed" => Some(DiscountKind::Fixed),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DiscountKind::Percent => "percent",
+            DiscountKind::Fixed => "fixed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Discount {
+    pub kind: DiscountKind,
+    pub value: BigDecimal,
+}
+
+impl Discount {
+    /// Amount taken off `base`, as `discount_of` computes it. A fixed discount never
+    /// exceeds the base.
+    pub fn of(&self, base: &BigDecimal) -> BigDecimal {
+        match self.kind {
+            DiscountKind::Percent => round(base * &self.value / BigDecimal::from(100)),
+            DiscountKind::Fixed => self.value.clone().min(base.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct OrderBreakdown {
+    pub subtotal: BigDecimal,
+    pub discount_amount: BigDecimal,
+    pub tax_amount: BigDecimal,
+    pub bonus_points_used: BigDecimal,
+    pub total_amount: BigDecimal,
+}
+
+/// Rounds to kopecks half away from zero, like `ROUND(x, 2)` on numerics.
+fn round(value: BigDecimal) -> BigDecimal {
+    value.with_scale_round(2, RoundingMode::HalfUp)
+}
+
+/// Breakdown of an order with `subtotal` and `line_discounts` already summed over its lines.
+/// The order discount applies after line discounts, VAT after all discounts, and bonus points
+/// are capped at `max_bonus_share` percent of the amount with tax.
+pub fn price_order(
+    subtotal: BigDecimal,
+    line_discounts: BigDecimal,
+    discount: Option<&Discount>,
+    vat_rate: &BigDecimal,
+    max_bonus_share: &BigDecimal,
+    bonus_points: &BigDecimal,
+) -> OrderBreakdown {
+    let order_discount = discount
+        .map(|d| d.of(&(&subtotal - &line_discounts)))
+        .unwrap_or_default();
+    let discount_amount = line_discounts + order_discount;
+    let taxable = &subtotal - &discount_amount;
+    let tax_amount = round(&taxable * vat_rate / BigDecimal::from(100));
+    let gross = taxable + &tax_amount;
+    let bonus_cap = round(&gross * max_bonus_share / BigDecimal::from(100));
+    let bonus_points_used = bonus_points.clone().min(bonus_cap);
+    OrderBreakdown {
+        total_amount: &gross - &bonus_points_used,
+        subtotal,
+        discount_amount,
+        tax_amount,
+        bonus_points_used,
+    }
+}
+
+/// Sums the lines of `lines` at the prices of `branch_id`, taking the price an order line would snapshot
+/// when added: the first price row of the branch, or nothing if the branch has none.
+pub fn compute_order_total(
+    branch_id: i32,
+    lines: &OrderLines,
+    service_prices: &[ServiceBranch],
+    part_prices: &[SparePartBranch],
+) -> BigDecimal {
+    let services: BigDecimal = lines
+        .service_ids
+        .iter()
+        .filter_map(|service_id| {
+            service_prices
+                .iter()
+                .filter(|p| p.branch_id == branch_id && p.service_id == *service_id)
+                .min_by_key(|p| p.service_branch_id)
+        })
+        .map(|p| p.price.clone())
+        .sum();
+    let parts: BigDecimal = lines
+        .parts
+        .iter()
+        .filter_map(|line| {
+            part_prices
+                .iter()
+                .filter(|p| p.branch_id == branch_id && p.part_id == line.part_id)
+                .min_by_key(|p| p.spare_part_branch_id)
+                .map(|p| &p.price * BigDecimal::from(line.quantity))
+        })
+        .sum();
+    services + parts
+}
@@ -0,0 +1,176 @@
+//! Printable receipts. The PDF uses the standard Helvetica font, which every
+//! viewer has, so nothing is embedded; text outside Latin-1 prints as `?`.
+
+use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, Str};
+
+use crate::models::{Receipt, ReceiptLine};
+
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 50.0;
+const LINE_HEIGHT: f32 = 16.0;
+const FONT: Name = Name(b"F1");
+const BOLD_FONT: Name = Name(b"F2");
+
+/// Column offsets from the left margin: name, quantity, price, discount, amount.
+const COLUMNS: [f32; 5] = [0.0, 250.0, 300.0, 375.0, 440.0];
+
+/// Text drawn at a fixed position of a page.
+struct Text {
+    x: f32,
+    y: f32,
+    bold: bool,
+    text: String,
+}
+
+/// Lays rows out top to bottom and starts a new page when one is full.
+struct Layout {
+    pages: Vec<Vec<Text>>,
+    y: f32,
+}
+
+impl Layout {
+    fn new() -> Self {
+        Layout {
+            pages: vec![Vec::new()],
+            y: PAGE_HEIGHT - MARGIN,
+        }
+    }
+
+    fn row(&mut self, cells: &[(f32, &str)], bold: bool) {
+        if self.y < MARGIN {
+            self.pages.push(Vec::new());
+            self.y = PAGE_HEIGHT - MARGIN;
+        }
+        let page = self.pages.last_mut().unwrap();
+        for (x, text) in cells {
+            page.push(Text {
+                x: MARGIN + x,
+                y: self.y,
+                bold,
+                text: text.to_string(),
+            });
+        }
+        self.y -= LINE_HEIGHT;
+    }
+
+    fn gap(&mut self) {
+        self.y -= LINE_HEIGHT / 2.0;
+    }
+}
+
+/// Encodes text for the WinAnsi font encoding.
+fn win_ansi(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c as u32 {
+            0x20..=0x7e | 0xa0..=0xff => c as u8,
+            _ => b'?',
+        })
+        .collect()
+}
+
+pub fn render_pdf(receipt: &Receipt, lines: &[ReceiptLine]) -> Vec<u8> {
+    let mut layout = Layout::new();
+    layout.row(&[(0.0, &format!("Receipt No. {}", receipt.receipt_number))], true);
+    layout.row(&[(0.0, &format!("Order #{}", receipt.order_id))], false);
+    layout.row(&[(0.0, &receipt.issued_at.format("%Y-%m-%d %H:%M").to_string())], false);
+    layout.gap();
+    layout.row(
+        &[(
+            0.0,
+            &format!(
+                "{}, {}, {}",
+                receipt.branch_address, receipt.branch_city, receipt.branch_postal_code
+            ),
+        )],
+        false,
+    );
+    layout.row(&[(0.0, &format!("Phone: {}", receipt.branch_phone_number))], false);
+    layout.row(&[(0.0, &format!("Client: {}", receipt.client_name))], false);
+    layout.gap();
+    layout.row(
+        &[
+            (COLUMNS[0], "Item"),
+            (COLUMNS[1], "Qty"),
+            (COLUMNS[2], "Price"),
+            (COLUMNS[3], "Discount"),
+            (COLUMNS[4], "Amount"),
+        ],
+        true,
+    );
+    for line in lines {
+        layout.row(
+            &[
+                (COLUMNS[0], &line.name),
+                (COLUMNS[1], &line.quantity.to_string()),
+                (COLUMNS[2], &line.unit_price.to_string()),
+                (COLUMNS[3], &line.discount_amount.to_string()),
+                (COLUMNS[4], &line.amount.to_string()),
+            ],
+            false,
+        );
+    }
+    layout.gap();
+    let totals = [
+        ("Subtotal", receipt.subtotal.to_string()),
+        ("Discount", receipt.discount_amount.to_string()),
+        (
+            &*format!("VAT {}%", receipt.vat_rate),
+            receipt.tax_amount.to_string(),
+        ),
+        ("Bonus points", receipt.bonus_points_used.to_string()),
+    ];
+    for (label, value) in &totals {
+        layout.row(&[(COLUMNS[3], label), (COLUMNS[4], value)], false);
+    }
+    layout.row(
+        &[(COLUMNS[3], "Total"), (COLUMNS[4], &receipt.total_amount.to_string())],
+        true,
+    );
+
+    let mut pdf = Pdf::new();
+    let catalog_id = Ref::new(1);
+    let page_tree_id = Ref::new(2);
+    let font_id = Ref::new(3);
+    let bold_font_id = Ref::new(4);
+    let page_ids: Vec<Ref> = (0..layout.pages.len())
+        .map(|i| Ref::new(5 + 2 * i as i32))
+        .collect();
+
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.pages(page_tree_id)
+        .kids(page_ids.iter().copied())
+        .count(page_ids.len() as i32);
+    pdf.type1_font(font_id)
+        .base_font(Name(b"Helvetica"))
+        .encoding_predefined(Name(b"WinAnsiEncoding"));
+    pdf.type1_font(bold_font_id)
+        .base_font(Name(b"Helvetica-Bold"))
+        .encoding_predefined(Name(b"WinAnsiEncoding"));
+
+    for (page_id, texts) in page_ids.iter().zip(&layout.pages) {
+        let content_id = Ref::new(page_id.get() + 1);
+        let mut page = pdf.page(*page_id);
+        page.media_box(Rect::new(0.0, 0.0, PAGE_WIDTH, PAGE_HEIGHT))
+            .parent(page_tree_id)
+            .contents(content_id);
+        page.resources()
+            .fonts()
+            .pair(FONT, font_id)
+            .pair(BOLD_FONT, bold_font_id);
+        page.finish();
+
+        let mut content = Content::new();
+        content.begin_text();
+        for text in texts {
+            content
+                .set_font(if text.bold { BOLD_FONT } else { FONT }, 10.0)
+                .set_text_matrix([1.0, 0.0, 0.0, 1.0, text.x, text.y])
+                .show(Str(&win_ansi(&text.text)));
+        }
+        content.end_text();
+        pdf.stream(content_id, &content.finish());
+    }
+
+    pdf.finish()
+}
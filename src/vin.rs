@@ -0,0 +1,163 @@
+//! Vehicle identification numbers (ISO 3779). A VIN is 17 characters from the digits and
+//! capital letters without I, O and Q. North American VINs carry a check digit in the ninth
+//! position; elsewhere it is optional and often a plain letter or digit. Decoding works
+//! offline from the table of world manufacturer identifiers below.
+
+use serde::Serialize;
+
+const VIN_LENGTH: usize = 17;
+
+/// Weight of each position in the check digit sum; the check digit itself weighs nothing.
+const WEIGHTS: [u32; VIN_LENGTH] = [8, 7, 6, 5, 4, 3, 2, 10, 0, 9, 8, 7, 6, 5, 4, 3, 2];
+
+/// World manufacturer identifiers: the first three characters of a VIN.
+const MANUFACTURERS: &[(&str, &str)] = &[
+    ("1HD", "Harley-Davidson"),
+    ("1HF", "Honda"),
+    ("1HG", "Honda"),
+    ("56K", "Indian Motorcycle"),
+    ("5HD", "Harley-Davidson"),
+    ("5VP", "Victory"),
+    ("932", "Harley-Davidson"),
+    ("JH2", "Honda"),
+    ("JH3", "Honda"),
+    ("JKA", "Kawasaki"),
+    ("JKB", "Kawasaki"),
+    ("JS1", "Suzuki"),
+    ("JSA", "Suzuki"),
+    ("JTD", "Toyota"),
+    ("JYA", "Yamaha"),
+    ("JYE", "Yamaha"),
+    ("MBL", "Hero MotoCorp"),
+    ("MD2", "Bajaj"),
+    ("ME4", "Honda"),
+    ("ML5", "Kawasaki"),
+    ("SMT", "Triumph"),
+    ("VBK", "KTM"),
+    ("WBA", "BMW"),
+    ("WB1", "BMW Motorrad"),
+    ("WDB", "Mercedes-Benz"),
+    ("WVW", "Volkswagen"),
+    ("XTA", "Lada"),
+    ("ZAP", "Piaggio"),
+    ("ZCG", "MV Agusta"),
+    ("ZD4", "Aprilia"),
+    ("ZDC", "Honda"),
+    ("ZDM", "Ducati"),
+    ("ZGU", "Moto Guzzi"),
+];
+
+/// Model year codes of the tenth position, with the first year each stands for.
+/// The codes repeat every 30 years.
+const MODEL_YEARS: &[(u8, i32)] = &[
+    (b'A', 1980),
+    (b'B', 1981),
+    (b'C', 1982),
+    (b'D', 1983),
+    (b'E', 1984),
+    (b'F', 1985),
+    (b'G', 1986),
+    (b'H', 1987),
+    (b'J', 1988),
+    (b'K', 1989),
+    (b'L', 1990),
+    (b'M', 1991),
+    (b'N', 1992),
+    (b'P', 1993),
+    (b'R', 1994),
+    (b'S', 1995),
+    (b'T', 1996),
+    (b'V', 1997),
+    (b'W', 1998),
+    (b'X', 1999),
+    (b'Y', 2000),
+    (b'1', 2001),
+    (b'2', 2002),
+    (b'3', 2003),
+    (b'4', 2004),
+    (b'5', 2005),
+    (b'6', 2006),
+    (b'7', 2007),
+    (b'8', 2008),
+    (b'9', 2009),
+];
+
+#[derive(Debug, PartialEq)]
+pub enum VinError {
+    Length,
+    Character,
+    CheckDigit,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DecodedVin {
+    pub vin: String,
+    pub wmi: String,
+    pub manufacturer: Option<String>,
+    pub model_year: Option<i32>,
+    pub serial_number: String,
+}
+
+/// Value of a character in the check digit sum. Letters count by their place in the
+/// alphabet, restarting at J and again at S.
+fn transliterate(c: u8) -> Option<u32> {
+    match c {
+        b'0'..=b'9' => Some((c - b'0') as u32),
+        b'A'..=b'H' => Some((c - b'A') as u32 + 1),
+        b'J'..=b'N' => Some((c - b'J') as u32 + 1),
+        b'P' => Some(7),
+        b'R' => Some(9),
+        b'S'..=b'Z' => Some((c - b'S') as u32 + 2),
+        _ => None,
+    }
+}
+
+/// Normalises a VIN to capitals without surrounding spaces and checks its length,
+/// characters and, for North American manufacturers, the check digit.
+pub fn validate(vin: &str) -> Result<String, VinError> {
+    let vin = vin.trim().to_ascii_uppercase();
+    if vin.len() != VIN_LENGTH {
+        return Err(VinError::Length);
+    }
+    let mut sum = 0;
+    for (c, weight) in vin.bytes().zip(WEIGHTS) {
+        sum += transliterate(c).ok_or(VinError::Character)? * weight;
+    }
+    let check = match sum % 11 {
+        10 => b'X',
+        digit => b'0' + digit as u8,
+    };
+    // Only WMIs starting with 1 to 5 are assigned in North America, where the check digit
+    // is mandatory.
+    if matches!(vin.as_bytes()[0], b'1'..=b'5') && vin.as_bytes()[8] != check {
+        return Err(VinError::CheckDigit);
+    }
+    Ok(vin)
+}
+
+/// The latest year the model year code can stand for, no later than the models of the
+/// year after `current_year`.
+fn model_year(code: u8, current_year: i32) -> Option<i32> {
+    let (_, first) = MODEL_YEARS.iter().find(|(c, _)| *c == code)?;
+    let latest = current_year + 1;
+    let cycles = (latest - first).max(0) / 30;
+    Some(first + cycles * 30)
+}
+
+/// Validates a VIN and reads the manufacturer and model year out of it. The year code
+/// repeats every 30 years, so it is read as the latest year up to `current_year` + 1.
+/// Unknown manufacturers and year codes decode to nothing rather than failing.
+pub fn decode(vin: &str, current_year: i32) -> Result<DecodedVin, VinError> {
+    let vin = validate(vin)?;
+    let wmi = &vin[..3];
+    Ok(DecodedVin {
+        wmi: wmi.to_string(),
+        manufacturer: MANUFACTURERS
+            .iter()
+            .find(|(code, _)| *code == wmi)
+            .map(|(_, name)| name.to_string()),
+        model_year: model_year(vin.as_bytes()[9], current_year),
+        serial_number: vin[11..].to_string(),
+        vin,
+    })
+}
@@ -1,9 +1,11 @@
-use axum::Extension;
+use axum::{async_trait, extract::FromRequestParts, http::request::Parts, http::StatusCode};
 use tower_sessions::Session;
 
+use crate::models::User;
 use crate::web::session::{ApiKey, Cache, API_KEY};
+use crate::web::state::AppState;
 
-pub async fn get_user_id(cache: Extension<Cache>, session: Session) -> Result<Option<i32>, ()> {
+pub async fn get_user_id(cache: &Cache, session: &Session) -> Result<Option<i32>, ()> {
     if let Ok(Some(key)) = session.get::<ApiKey>(API_KEY).await {
         if let Ok(Some(user_id)) = cache.read().map(|hm| hm.get(&key.0).map(|v| v.clone())) {
             i32::from_str_radix(&user_id, 10)
@@ -16,3 +18,37 @@ pub async fn get_user_id(cache: Extension<Cache>, session: Session) -> Result<Op
         Err(())
     }
 }
+
+/// The logged-in user of the request. Requests without a live session, from a removed
+/// user or from a user of a closed branch are rejected with `401 Unauthorized`.
+pub struct CurrentUser(pub User);
+
+#[async_trait]
+impl FromRequestParts<AppState> for CurrentUser {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, StatusCode> {
+        let session = Session::from_request_parts(parts, state)
+            .await
+            .map_err(|_| StatusCode::UNAUTHORIZED)?;
+        let Ok(Some(user_id)) = get_user_id(&state.sessions, &session).await else {
+            return Err(StatusCode::UNAUTHORIZED);
+        };
+        let mut tx = state
+            .store
+            .begin()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let user = tx
+            .get_user_by_id(user_id)
+            .await
+            .map_err(|_| StatusCode::UNAUTHORIZED)?;
+        if user.role != "superadmin" {
+            match tx.get_branch_by_id(user.branch_id).await {
+                Ok(branch) if branch.closed_at.is_none() => {}
+                _ => return Err(StatusCode::UNAUTHORIZED),
+            }
+        }
+        Ok(CurrentUser(user))
+    }
+}
@@ -1,19 +1,34 @@
-use axum::{http::StatusCode, response::Redirect, Extension, Form, Json};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::Redirect,
+    Form, Json,
+};
+use bigdecimal::BigDecimal;
+use chrono::Datelike;
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
 use tower_sessions::Session;
 use uuid::Uuid;
 
 use crate::{
-    database::{
-        orders::{create_order, update_order},
-        user::{create_user, get_user, update_user},
+    contact,
+    database::{repo::Repos, search::MIN_QUERY_LENGTH, spare_part::PART_UNITS},
+    models::{
+        BonusTransaction, Branch, BranchUpdate, CashShift, Client, ClientBranch, ClientContacts, Employee,
+        EmployeeUpdate, LoyaltyTier,
+        MaintenanceReminder,
+        Order, OrderService, OrderServicePart, PartCompatibility, PartMatch, Payment,
+        PurchaseOrder, PurchaseOrderLine, SearchResult, Service, ServiceHistoryEntry, ShiftTotal,
+        SparePart, SparePartBranch, StockLevel, User,
+        Vehicle,
     },
-    models::{Order, User},
-    web::session::{ApiKey, Cache, API_KEY},
+    pricing::{compute_order_total, price_order, DiscountKind, OrderBreakdown, OrderLines},
+    vin::{self, DecodedVin},
+    web::session::{ApiKey, API_KEY},
+    web::state::AppState,
 };
 
-use super::common::get_user_id;
+use super::common::CurrentUser;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct LoginForm {
@@ -22,20 +37,30 @@ pub struct LoginForm {
 }
 
 pub async fn login(
-    db: Extension<PgPool>,
+    State(state): State<AppState>,
     session: Session,
-    cache: Extension<Cache>,
     Form(login): Form<LoginForm>,
 ) -> Redirect {
-    if let Ok(user) = get_user(&db, &login.login).await {
+    let Ok(mut tx) = state.store.begin().await else {
+        return Redirect::to("/login");
+    };
+    if let Ok(user) = tx.get_user(&login.login).await {
         if sha256::digest(&login.password) == user.passwordhash {
+            if user.role != "superadmin" {
+                match tx.get_branch_by_id(user.branch_id).await {
+                    Ok(branch) if branch.closed_at.is_none() => {}
+                    _ => return Redirect::to("/login"),
+                }
+            }
             let apikey = ApiKey(Uuid::new_v4().to_string());
             session.insert(API_KEY, &apikey).await.unwrap();
-            cache
+            state
+                .sessions
                 .write()
                 .unwrap()
                 .insert(apikey.0, user.user_id.unwrap().to_string());
             match user.role.as_ref() {
+                "superadmin" => return Redirect::to("/superadmin"),
                 "admin" => return Redirect::to("/admin"),
                 "master" => return Redirect::to("/master"),
                 "analyst" => return Redirect::to("/analyst"),
@@ -48,38 +73,48 @@ pub async fn login(
 }
 
 pub async fn admin_update_user(
-    db: Extension<PgPool>,
-    session: Session,
-    cache: Extension<Cache>,
+    State(state): State<AppState>,
+    CurrentUser(admin): CurrentUser,
     Form(user): Form<User>,
 ) -> Result<Json<User>, StatusCode> {
+    if admin.role != "superadmin" && admin.role != "admin" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    // Superadmins run outside row-level security, so only a superadmin may make one or move
+    // users between branches.
+    if admin.role != "superadmin" && (user.role == "superadmin" || user.branch_id != admin.branch_id) {
+        return Err(StatusCode::FORBIDDEN);
+    }
     let mut new_passwordhash: Option<String> = None;
     if !user.passwordhash.is_empty() {
         new_passwordhash = Some(sha256::digest(&user.passwordhash));
     }
     let mut new_user = user.clone();
     new_user.passwordhash = new_passwordhash.clone().unwrap_or_default();
-    if let Ok(Some(user_id)) = get_user_id(cache, session).await {
-        if let Ok(created_user) = create_user(&db, user_id, &user).await {
-            return Ok(Json(created_user));
-        } else {
-            if let Ok(updated_user) = update_user(
-                &db,
-                user_id,
-                &user.username,
-                new_passwordhash.as_deref(),
-                Some(&user.role),
-                Some(user.branch_id),
-            )
-            .await
-            {
-                return Ok(Json(updated_user));
-            } else {
-                return Err(StatusCode::BAD_REQUEST);
-            }
-        }
+    let mut tx = state
+        .store
+        .begin_as(&admin)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let saved_user = if user.user_id.unwrap_or(0) == 0 {
+        tx.create_user(admin.branch_id, &new_user).await
+    } else {
+        tx.update_user(
+            admin.branch_id,
+            &user.username,
+            new_passwordhash.as_deref(),
+            Some(&user.role),
+            Some(user.branch_id),
+        )
+        .await
+    };
+    if let Ok(saved_user) = saved_user {
+        tx.commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        return Ok(Json(saved_user));
     }
-    Err(StatusCode::UNAUTHORIZED)
+    Err(StatusCode::BAD_REQUEST)
 }
 
 #[derive(Deserialize)]
@@ -88,37 +123,1785 @@ pub struct OrderCompleteForm {
 }
 
 pub async fn master_complete_order(
-    db: Extension<PgPool>,
-    cache: Extension<Cache>,
-    session: Session,
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
     Form(form): Form<OrderCompleteForm>,
 ) -> Result<(), StatusCode> {
-    if let Ok(Some(user_id)) = get_user_id(cache, session).await {
-        if let Ok(_) = update_order(
-            &db,
-            Some(user_id),
-            Some(chrono::offset::Utc::now()),
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if tx
+        .update_order(
+            user.user_id,
+            Some(state.clock.now()),
             Some("finished".to_string()),
             form.order_id,
         )
         .await
-        {
-            return Ok(());
-        } else {
-            return Err(StatusCode::BAD_REQUEST);
-        }
+        .is_ok()
+    {
+        tx.commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        return Ok(());
     }
-    return Err(StatusCode::UNAUTHORIZED);
+    Err(StatusCode::BAD_REQUEST)
 }
 
-pub async fn manager_edit_order(db: Extension<PgPool>, cache: Extension<Cache>, session: Session, Form(order): Form<Order>) -> Result<(), StatusCode>{
+pub async fn manager_edit_order(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(order): Form<Order>,
+) -> Result<(), StatusCode> {
     let mut fixed_order = order.clone();
     fixed_order.completion_date = None;
-    if let Ok(Some(_)) = get_user_id(cache, session).await {
-        if let Ok(_) = create_order(&db, fixed_order).await {
-            return Ok(());
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if tx.create_order(fixed_order).await.is_ok() {
+        tx.commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        return Ok(());
+    }
+    Err(StatusCode::BAD_REQUEST)
+}
+
+pub async fn manager_preview_total(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Json(lines): Json<OrderLines>,
+) -> Result<Json<OrderBreakdown>, StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let (Ok(branch), Ok(service_prices), Ok(part_prices)) = (
+        tx.get_branch_by_id(user.branch_id).await,
+        tx.get_service_prices(user.branch_id).await,
+        tx.get_part_prices(user.branch_id).await,
+    ) {
+        let subtotal = compute_order_total(user.branch_id, &lines, &service_prices, &part_prices);
+        return Ok(Json(price_order(
+            subtotal,
+            BigDecimal::from(0),
+            lines.discount.as_ref(),
+            &branch.vat_rate,
+            &branch.max_bonus_share,
+            &lines.bonus_points.unwrap_or_default(),
+        )));
+    }
+    Err(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// A validated discount: kind, value and reason as stored in the database.
+type DiscountFields<'a> = (Option<&'static str>, BigDecimal, Option<&'a str>);
+
+/// Checks discount form fields. No kind means no discount; a discount needs a reason and
+/// a percentage cannot exceed 100.
+fn discount_fields<'a>(
+    discount_type: &Option<String>,
+    discount_value: &Option<String>,
+    discount_reason: &'a Option<String>,
+) -> Result<DiscountFields<'a>, StatusCode> {
+    let kind = match discount_type.as_deref().filter(|v| !v.is_empty()) {
+        Some(kind) => Some(DiscountKind::parse(kind).ok_or(StatusCode::BAD_REQUEST)?),
+        None => None,
+    };
+    let value = decimal_field(discount_value)?.unwrap_or_default();
+    let reason = discount_reason.as_deref().filter(|v| !v.is_empty());
+    let zero = BigDecimal::from(0);
+    match kind {
+        None if value == zero => Ok((None, zero, None)),
+        None => Err(StatusCode::BAD_REQUEST),
+        Some(_) if value < zero || reason.is_none() => Err(StatusCode::BAD_REQUEST),
+        Some(DiscountKind::Percent) if value > BigDecimal::from(100) => Err(StatusCode::BAD_REQUEST),
+        Some(kind) => Ok((Some(kind.as_str()), value, reason)),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct OrderPricingForm {
+    pub order_id: i32,
+    pub discount_type: Option<String>,
+    pub discount_value: Option<String>,
+    pub discount_reason: Option<String>,
+    pub bonus_points_used: Option<String>,
+}
+
+pub async fn manager_price_order(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<OrderPricingForm>,
+) -> Result<Json<Order>, StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let (discount_type, discount_value, discount_reason) = discount_fields(
+        &form.discount_type,
+        &form.discount_value,
+        &form.discount_reason,
+    )?;
+    let bonus_points_used = decimal_field(&form.bonus_points_used)?.unwrap_or_default();
+    if bonus_points_used < BigDecimal::from(0) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(order) = tx
+        .update_order_pricing(
+            form.order_id,
+            discount_type,
+            discount_value,
+            discount_reason,
+            bonus_points_used,
+        )
+        .await
+    {
+        tx.commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        return Ok(Json(order));
+    }
+    Err(StatusCode::BAD_REQUEST)
+}
+
+#[derive(Deserialize)]
+pub struct RefundForm {
+    pub order_id: i32,
+    pub reason: String,
+}
+
+pub async fn manager_refund_order(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<RefundForm>,
+) -> Result<Json<Order>, StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    if form.reason.trim().is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(order) = tx.refund_order(form.order_id, form.reason.trim()).await {
+        tx.commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        return Ok(Json(order));
+    }
+    Err(StatusCode::BAD_REQUEST)
+}
+
+#[derive(Deserialize)]
+pub struct PaymentForm {
+    pub order_id: i32,
+    pub amount: String,
+    pub method: String,
+}
+
+pub async fn manager_add_payment(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<PaymentForm>,
+) -> Result<Json<Payment>, StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let amount: BigDecimal = form.amount.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+    if !matches!(form.method.as_str(), "cash" | "card" | "transfer" | "bonus") {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(payment) = tx.create_payment(form.order_id, amount, &form.method).await {
+        tx.commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        return Ok(Json(payment));
+    }
+    Err(StatusCode::BAD_REQUEST)
+}
+
+#[derive(Deserialize)]
+pub struct OrderPaymentsQuery {
+    pub order_id: i32,
+}
+
+#[derive(Serialize)]
+pub struct OrderPayments {
+    pub order: Order,
+    pub payments: Vec<Payment>,
+    pub outstanding: BigDecimal,
+    pub client_outstanding: BigDecimal,
+}
+
+pub async fn manager_order_payments(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Query(query): Query<OrderPaymentsQuery>,
+) -> Result<Json<OrderPayments>, StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    // The balance functions bypass row-level security, so check the order is visible first.
+    let order = tx
+        .get_order_by_id(query.order_id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    if let (Ok(payments), Ok(outstanding), Ok(client_outstanding)) = (
+        tx.get_order_payments(query.order_id).await,
+        tx.get_order_outstanding(query.order_id).await,
+        tx.get_client_outstanding(order.client_id).await,
+    ) {
+        return Ok(Json(OrderPayments {
+            order,
+            payments,
+            outstanding,
+            client_outstanding,
+        }));
+    }
+    Err(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[derive(Deserialize)]
+pub struct PickupForm {
+    pub order_id: i32,
+    pub override_reason: Option<String>,
+}
+
+/// Hands a finished order over to the client. Masters can only hand over paid orders,
+/// managers can let an unpaid order go by giving a reason.
+pub async fn close_order_for_pickup(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<PickupForm>,
+) -> Result<Json<Order>, StatusCode> {
+    let override_reason = form
+        .override_reason
+        .as_deref()
+        .map(str::trim)
+        .filter(|r| !r.is_empty());
+    match user.role.as_ref() {
+        "manager" => {}
+        "master" if override_reason.is_none() => {}
+        _ => return Err(StatusCode::FORBIDDEN),
+    }
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(order) = tx.close_order_for_pickup(form.order_id, override_reason).await {
+        tx.commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        return Ok(Json(order));
+    }
+    Err(StatusCode::BAD_REQUEST)
+}
+
+#[derive(Deserialize)]
+pub struct ShiftOpenForm {
+    pub opening_cash: Option<String>,
+}
+
+pub async fn manager_open_shift(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<ShiftOpenForm>,
+) -> Result<Json<CashShift>, StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let opening_cash = decimal_field(&form.opening_cash)?.unwrap_or_default();
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(shift) = tx.open_cash_shift(user.branch_id, opening_cash).await {
+        tx.commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        return Ok(Json(shift));
+    }
+    Err(StatusCode::BAD_REQUEST)
+}
+
+#[derive(Deserialize)]
+pub struct ShiftCloseForm {
+    pub shift_id: i32,
+    pub counted_cash: String,
+}
+
+pub async fn manager_close_shift(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<ShiftCloseForm>,
+) -> Result<Json<CashShift>, StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let counted_cash: BigDecimal = form
+        .counted_cash
+        .parse()
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    // Closing bypasses row-level security, so check the shift is visible first.
+    tx.get_cash_shift(form.shift_id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    if let Ok(shift) = tx.close_cash_shift(form.shift_id, counted_cash).await {
+        tx.commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        return Ok(Json(shift));
+    }
+    Err(StatusCode::BAD_REQUEST)
+}
+
+#[derive(Deserialize)]
+pub struct ShiftReportQuery {
+    pub shift_id: i32,
+}
+
+#[derive(Serialize)]
+pub struct ShiftReport {
+    pub shift: CashShift,
+    pub totals: Vec<ShiftTotal>,
+}
+
+pub async fn manager_shift_report(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Query(query): Query<ShiftReportQuery>,
+) -> Result<Json<ShiftReport>, StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let shift = tx
+        .get_cash_shift(query.shift_id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    if let Ok(totals) = tx.get_shift_totals(query.shift_id).await {
+        return Ok(Json(ShiftReport { shift, totals }));
+    }
+    Err(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[derive(Deserialize)]
+pub struct LineDiscountForm {
+    pub order_service_id: Option<i32>,
+    pub order_service_part_id: Option<i32>,
+    pub discount_type: Option<String>,
+    pub discount_value: Option<String>,
+    pub discount_reason: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum DiscountedLine {
+    Service(OrderService),
+    Part(OrderServicePart),
+}
+
+pub async fn manager_discount_line(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<LineDiscountForm>,
+) -> Result<Json<DiscountedLine>, StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let (discount_type, discount_value, discount_reason) = discount_fields(
+        &form.discount_type,
+        &form.discount_value,
+        &form.discount_reason,
+    )?;
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let line = match (form.order_service_id, form.order_service_part_id) {
+        (Some(id), None) => tx
+            .discount_order_service(id, discount_type, discount_value, discount_reason)
+            .await
+            .map(DiscountedLine::Service),
+        (None, Some(id)) => tx
+            .discount_order_service_part(id, discount_type, discount_value, discount_reason)
+            .await
+            .map(DiscountedLine::Part),
+        _ => return Err(StatusCode::BAD_REQUEST),
+    };
+    if let Ok(line) = line {
+        tx.commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        return Ok(Json(line));
+    }
+    Err(StatusCode::BAD_REQUEST)
+}
+
+#[derive(Deserialize)]
+pub struct BranchCreateForm {
+    pub address: String,
+    pub phone_number: String,
+    pub postal_code: String,
+    pub city: String,
+    pub vat_rate: Option<String>,
+    pub max_bonus_share: Option<String>,
+    pub admin_username: String,
+    pub admin_password: String,
+}
+
+pub async fn superadmin_create_branch(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<BranchCreateForm>,
+) -> Result<Json<Branch>, StatusCode> {
+    if user.role != "superadmin" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    if form.admin_username.is_empty() || form.admin_password.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let branch = Branch {
+        branch_id: None,
+        address: form.address,
+        phone_number: form.phone_number,
+        postal_code: form.postal_code,
+        employee_count: 0,
+        city: form.city,
+        closed_at: None,
+        vat_rate: decimal_field(&form.vat_rate)?.unwrap_or_default(),
+        max_bonus_share: decimal_field(&form.max_bonus_share)?.unwrap_or(BigDecimal::from(30)),
+    };
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(created_branch) = tx
+        .create_branch(
+            branch,
+            &form.admin_username,
+            &sha256::digest(&form.admin_password),
+        )
+        .await
+    {
+        tx.commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        return Ok(Json(created_branch));
+    }
+    Err(StatusCode::BAD_REQUEST)
+}
+
+#[derive(Deserialize)]
+pub struct BranchIdForm {
+    pub branch_id: i32,
+}
+
+pub async fn superadmin_close_branch(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<BranchIdForm>,
+) -> Result<Json<Branch>, StatusCode> {
+    if user.role != "superadmin" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(branch) = tx.close_branch(form.branch_id).await {
+        tx.commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        return Ok(Json(branch));
+    }
+    Err(StatusCode::BAD_REQUEST)
+}
+
+pub async fn superadmin_reopen_branch(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<BranchIdForm>,
+) -> Result<Json<Branch>, StatusCode> {
+    if user.role != "superadmin" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(branch) = tx.reopen_branch(form.branch_id).await {
+        tx.commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        return Ok(Json(branch));
+    }
+    Err(StatusCode::BAD_REQUEST)
+}
+
+#[derive(Deserialize)]
+pub struct BranchUpdateForm {
+    pub branch_id: i32,
+    pub address: Option<String>,
+    pub phone_number: Option<String>,
+    pub postal_code: Option<String>,
+    pub vat_rate: Option<String>,
+    pub max_bonus_share: Option<String>,
+}
+
+/// Parses an optional decimal form field, treating an empty field as absent.
+fn decimal_field(value: &Option<String>) -> Result<Option<BigDecimal>, StatusCode> {
+    value
+        .as_deref()
+        .filter(|v| !v.is_empty())
+        .map(|v| v.parse().map_err(|_| StatusCode::BAD_REQUEST))
+        .transpose()
+}
+
+pub async fn admin_update_branch(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<BranchUpdateForm>,
+) -> Result<Json<Branch>, StatusCode> {
+    match user.role.as_ref() {
+        "superadmin" => {}
+        "admin" if user.branch_id == form.branch_id => {}
+        _ => return Err(StatusCode::FORBIDDEN),
+    }
+    let vat_rate = decimal_field(&form.vat_rate)?;
+    let max_bonus_share = decimal_field(&form.max_bonus_share)?;
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(branch) = tx
+        .update_branch(
+            form.branch_id,
+            BranchUpdate {
+                address: form.address.filter(|v| !v.is_empty()),
+                phone_number: form.phone_number.filter(|v| !v.is_empty()),
+                postal_code: form.postal_code.filter(|v| !v.is_empty()),
+                employee_count: None,
+                vat_rate,
+                max_bonus_share,
+            },
+        )
+        .await
+    {
+        tx.commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        return Ok(Json(branch));
+    }
+    Err(StatusCode::BAD_REQUEST)
+}
+
+#[derive(Deserialize)]
+pub struct ArchiveForm {
+    pub entity: String,
+    pub id: i32,
+}
+
+/// Admins archive and restore orders and clients of their own branch. Services and spare
+/// parts are shared by every branch, so only a superadmin archives or restores them.
+async fn check_archive_scope(
+    tx: &mut Box<dyn Repos>,
+    user: &User,
+    form: &ArchiveForm,
+) -> Result<(), StatusCode> {
+    if !matches!(form.entity.as_ref(), "order" | "client" | "service" | "spare_part") {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    match user.role.as_ref() {
+        "superadmin" => return Ok(()),
+        "admin" => {}
+        _ => return Err(StatusCode::FORBIDDEN),
+    }
+    let branch_id = match form.entity.as_ref() {
+        "order" => tx.get_order_by_id(form.id).await.map(|o| o.branch_id),
+        "client" => tx.get_client_by_id(form.id).await.map(|c| c.home_branch_id),
+        _ => return Err(StatusCode::FORBIDDEN),
+    }
+    .map_err(|_| StatusCode::NOT_FOUND)?;
+    if branch_id != user.branch_id {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    Ok(())
+}
+
+pub async fn admin_archive(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<ArchiveForm>,
+) -> Result<(), StatusCode> {
+    if user.role != "superadmin" && user.role != "admin" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    check_archive_scope(&mut tx, &user, &form).await?;
+    let archived = match form.entity.as_ref() {
+        "order" => tx.delete_order(form.id).await,
+        "client" => tx.delete_client(form.id).await,
+        "service" => tx.delete_service(form.id).await,
+        _ => tx.delete_spare_part(form.id).await,
+    };
+    if archived.is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    tx.commit()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+pub async fn admin_restore(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<ArchiveForm>,
+) -> Result<(), StatusCode> {
+    if user.role != "superadmin" && user.role != "admin" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    check_archive_scope(&mut tx, &user, &form).await?;
+    let restored = match form.entity.as_ref() {
+        "order" => tx.restore_order(form.id).await.map(|_| ()),
+        "client" => tx.restore_client(form.id).await.map(|_| ()),
+        "service" => tx.restore_service(form.id).await.map(|_| ()),
+        _ => tx.restore_spare_part(form.id).await.map(|_| ()),
+    };
+    if restored.is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    tx.commit()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[derive(Deserialize)]
+pub struct LoyaltyTierForm {
+    pub tier_id: Option<i32>,
+    pub name: Option<String>,
+    pub spend_threshold: Option<String>,
+    pub accrual_rate: Option<String>,
+    pub points_lifetime_days: Option<String>,
+}
+
+/// Parses an optional integer form field, treating an empty field as absent.
+fn integer_field(value: &Option<String>) -> Result<Option<i32>, StatusCode> {
+    value
+        .as_deref()
+        .filter(|v| !v.is_empty())
+        .map(|v| v.parse().map_err(|_| StatusCode::BAD_REQUEST))
+        .transpose()
+}
+
+pub async fn admin_create_loyalty_tier(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<LoyaltyTierForm>,
+) -> Result<Json<LoyaltyTier>, StatusCode> {
+    if user.role != "superadmin" && user.role != "admin" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let (Some(name), Some(spend_threshold), Some(accrual_rate)) = (
+        form.name.filter(|v| !v.is_empty()),
+        decimal_field(&form.spend_threshold)?,
+        decimal_field(&form.accrual_rate)?,
+    ) else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+    let tier = LoyaltyTier {
+        tier_id: None,
+        name,
+        spend_threshold,
+        accrual_rate,
+        points_lifetime_days: integer_field(&form.points_lifetime_days)?.unwrap_or(365),
+    };
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(tier) = tx.create_loyalty_tier(tier).await {
+        tx.commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        return Ok(Json(tier));
+    }
+    Err(StatusCode::BAD_REQUEST)
+}
+
+pub async fn admin_update_loyalty_tier(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<LoyaltyTierForm>,
+) -> Result<Json<LoyaltyTier>, StatusCode> {
+    if user.role != "superadmin" && user.role != "admin" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let tier_id = form.tier_id.ok_or(StatusCode::BAD_REQUEST)?;
+    let spend_threshold = decimal_field(&form.spend_threshold)?;
+    let accrual_rate = decimal_field(&form.accrual_rate)?;
+    let points_lifetime_days = integer_field(&form.points_lifetime_days)?;
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(tier) = tx
+        .update_loyalty_tier(
+            tier_id,
+            form.name.as_deref().filter(|v| !v.is_empty()),
+            spend_threshold,
+            accrual_rate,
+            points_lifetime_days,
+        )
+        .await
+    {
+        tx.commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        return Ok(Json(tier));
+    }
+    Err(StatusCode::BAD_REQUEST)
+}
+
+#[derive(Deserialize)]
+pub struct BonusStatementQuery {
+    pub client_id: i32,
+}
+
+#[derive(Serialize)]
+pub struct BonusStatement {
+    pub client: Client,
+    pub entries: Vec<BonusTransaction>,
+}
+
+pub async fn manager_bonus_statement(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Query(query): Query<BonusStatementQuery>,
+) -> Result<Json<BonusStatement>, StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let client = tx
+        .get_client_by_id(query.client_id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    if let Ok(entries) = tx.get_bonus_statement(query.client_id).await {
+        return Ok(Json(BonusStatement { client, entries }));
+    }
+    Err(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[derive(Deserialize)]
+pub struct BonusAdjustmentForm {
+    pub client_id: i32,
+    pub points: String,
+    pub reason: String,
+}
+
+pub async fn manager_adjust_bonus_points(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<BonusAdjustmentForm>,
+) -> Result<Json<BonusTransaction>, StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let points: BigDecimal = form.points.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+    if points == BigDecimal::from(0) || form.reason.trim().is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    // The ledger functions bypass row-level security, so check the client is visible first.
+    tx.get_client_by_id(form.client_id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    if let Ok(entry) = tx
+        .adjust_bonus_points(form.client_id, points, form.reason.trim())
+        .await
+    {
+        tx.commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        return Ok(Json(entry));
+    }
+    Err(StatusCode::BAD_REQUEST)
+}
+
+#[derive(Deserialize)]
+pub struct ClientShareForm {
+    pub client_id: i32,
+    pub branch_id: i32,
+}
+
+pub async fn manager_share_client(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<ClientShareForm>,
+) -> Result<Json<ClientBranch>, StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(client_branch) = tx.share_client(form.client_id, form.branch_id).await {
+        tx.commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        return Ok(Json(client_branch));
+    }
+    Err(StatusCode::BAD_REQUEST)
+}
+
+pub async fn manager_unshare_client(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<ClientShareForm>,
+) -> Result<(), StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if tx
+        .unshare_client(form.client_id, form.branch_id)
+        .await
+        .is_ok()
+    {
+        return tx
+            .commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    Err(StatusCode::BAD_REQUEST)
+}
+
+/// Phones come as one field separated by commas or new lines; the consents are
+/// checkboxes, sent only when ticked.
+#[derive(Deserialize)]
+pub struct ClientContactsForm {
+    pub client_id: i32,
+    pub phones: Option<String>,
+    pub email: Option<String>,
+    pub preferred_channel: Option<String>,
+    pub service_consent: Option<String>,
+    pub marketing_consent: Option<String>,
+    pub contact_note: Option<String>,
+}
+
+impl ClientContactsForm {
+    fn to_contacts(&self) -> Result<ClientContacts, StatusCode> {
+        let phones = contact::normalize_phones(self.phones.as_deref().unwrap_or_default())
+            .map_err(|_| StatusCode::BAD_REQUEST)?;
+        let email = text_field(&self.email)
+            .map(|email| contact::normalize_email(&email))
+            .transpose()
+            .map_err(|_| StatusCode::BAD_REQUEST)?;
+        let preferred_channel = text_field(&self.preferred_channel);
+        let reachable = match preferred_channel.as_deref() {
+            None => true,
+            Some("email") => email.is_some(),
+            Some("sms") => !phones.is_empty(),
+            Some(_) => false,
+        };
+        if !reachable {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        Ok(ClientContacts {
+            phones,
+            email,
+            preferred_channel,
+            service_consent: self.service_consent.is_some(),
+            marketing_consent: self.marketing_consent.is_some(),
+            contact_note: text_field(&self.contact_note),
+        })
+    }
+}
+
+pub async fn manager_update_client_contacts(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<ClientContactsForm>,
+) -> Result<Json<Client>, StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let contacts = form.to_contacts()?;
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    tx.get_client_by_id(form.client_id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    if let Ok(client) = tx.update_client_contacts(form.client_id, &contacts).await {
+        tx.commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        return Ok(Json(client));
+    }
+    Err(StatusCode::BAD_REQUEST)
+}
+
+/// Phones come as one field separated by commas or new lines, as for clients. A blank
+/// email keeps the one on record.
+#[derive(Deserialize)]
+pub struct EmployeeContactsForm {
+    pub employee_id: i32,
+    pub phones: Option<String>,
+    pub email: Option<String>,
+}
+
+pub async fn admin_update_employee_contacts(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<EmployeeContactsForm>,
+) -> Result<Json<Employee>, StatusCode> {
+    if user.role != "admin" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let update = EmployeeUpdate {
+        phones: Some(
+            contact::normalize_phones(form.phones.as_deref().unwrap_or_default())
+                .map_err(|_| StatusCode::BAD_REQUEST)?,
+        ),
+        email: text_field(&form.email)
+            .map(|email| contact::normalize_email(&email))
+            .transpose()
+            .map_err(|_| StatusCode::BAD_REQUEST)?,
+        ..Default::default()
+    };
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    // Employees have no row-level security, so keep admins to their own branch.
+    let employees = tx
+        .get_employees_by_branch(user.branch_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !employees
+        .iter()
+        .any(|e| e.employee_id == Some(form.employee_id))
+    {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    if let Ok(employee) = tx.update_employee(update, form.employee_id).await {
+        tx.commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        return Ok(Json(employee));
+    }
+    Err(StatusCode::BAD_REQUEST)
+}
+
+#[derive(Deserialize)]
+pub struct VehicleForm {
+    pub vehicle_id: Option<String>,
+    pub client_id: i32,
+    pub make: String,
+    pub model: String,
+    pub year: Option<String>,
+    pub vin: Option<String>,
+    pub plate: Option<String>,
+    pub mileage: Option<String>,
+}
+
+fn text_field(value: &Option<String>) -> Option<String> {
+    value
+        .as_deref()
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+}
+
+impl VehicleForm {
+    fn to_vehicle(&self) -> Result<Vehicle, StatusCode> {
+        let make = self.make.trim();
+        let model = self.model.trim();
+        if make.is_empty() || model.is_empty() {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        Ok(Vehicle {
+            vehicle_id: integer_field(&self.vehicle_id)?,
+            client_id: self.client_id,
+            make: make.to_string(),
+            model: model.to_string(),
+            year: integer_field(&self.year)?,
+            vin: text_field(&self.vin)
+                .map(|v| vin::validate(&v))
+                .transpose()
+                .map_err(|_| StatusCode::BAD_REQUEST)?,
+            plate: text_field(&self.plate),
+            mileage: integer_field(&self.mileage)?.unwrap_or_default(),
+            deleted_at: None,
+        })
+    }
+}
+
+/// Creates a vehicle when the form has no vehicle_id, otherwise replaces its details.
+pub async fn manager_edit_vehicle(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<VehicleForm>,
+) -> Result<Json<Vehicle>, StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut vehicle = form.to_vehicle()?;
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    tx.get_client_by_id(vehicle.client_id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    let saved = match vehicle.vehicle_id {
+        None => tx.create_vehicle(vehicle).await,
+        Some(vehicle_id) => {
+            let stored = tx
+                .get_vehicle_by_id(vehicle_id)
+                .await
+                .map_err(|_| StatusCode::NOT_FOUND)?;
+            if stored.client_id != vehicle.client_id {
+                return Err(StatusCode::BAD_REQUEST);
+            }
+            // A blank mileage keeps the odometer reading on record.
+            if text_field(&form.mileage).is_none() {
+                vehicle.mileage = stored.mileage;
+            }
+            tx.update_vehicle(vehicle).await
         }
+    };
+    if let Ok(vehicle) = saved {
+        tx.commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        return Ok(Json(vehicle));
+    }
+    Err(StatusCode::BAD_REQUEST)
+}
+
+#[derive(Deserialize)]
+pub struct VehicleIdForm {
+    pub vehicle_id: i32,
+}
+
+pub async fn manager_delete_vehicle(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<VehicleIdForm>,
+) -> Result<(), StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    tx.get_vehicle_by_id(form.vehicle_id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    if tx.delete_vehicle(form.vehicle_id).await.is_ok() {
+        return tx
+            .commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    Err(StatusCode::BAD_REQUEST)
+}
+
+#[derive(Deserialize)]
+pub struct ClientVehiclesQuery {
+    pub client_id: i32,
+}
+
+pub async fn manager_client_vehicles(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Query(query): Query<ClientVehiclesQuery>,
+) -> Result<Json<Vec<Vehicle>>, StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    tx.get_client_by_id(query.client_id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    if let Ok(vehicles) = tx.get_client_vehicles(query.client_id).await {
+        return Ok(Json(vehicles));
+    }
+    Err(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[derive(Deserialize)]
+pub struct AssignVehicleForm {
+    pub order_id: i32,
+    pub vehicle_id: i32,
+    pub intake_mileage: Option<String>,
+}
+
+pub async fn manager_assign_vehicle(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<AssignVehicleForm>,
+) -> Result<Json<Order>, StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let intake_mileage = integer_field(&form.intake_mileage)?;
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(order) = tx
+        .assign_order_vehicle(form.order_id, form.vehicle_id, intake_mileage)
+        .await
+    {
+        tx.commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        return Ok(Json(order));
+    }
+    Err(StatusCode::BAD_REQUEST)
+}
+
+#[derive(Deserialize)]
+pub struct VehicleHistoryQuery {
+    pub vehicle_id: i32,
+}
+
+#[derive(Serialize)]
+pub struct VehicleHistory {
+    pub vehicle: Vehicle,
+    pub entries: Vec<ServiceHistoryEntry>,
+}
+
+/// Everything done on a vehicle, including orders other masters and branches worked on.
+pub async fn vehicle_history(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Query(query): Query<VehicleHistoryQuery>,
+) -> Result<Json<VehicleHistory>, StatusCode> {
+    if !matches!(user.role.as_ref(), "manager" | "master") {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    // The history function bypasses row-level security, so check the vehicle is visible first.
+    let vehicle = tx
+        .get_vehicle_by_id(query.vehicle_id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    if let Ok(entries) = tx.get_vehicle_history(query.vehicle_id).await {
+        return Ok(Json(VehicleHistory { vehicle, entries }));
+    }
+    Err(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[derive(Deserialize)]
+pub struct DecodeVinQuery {
+    pub vin: String,
+}
+
+pub async fn decode_vin(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Query(query): Query<DecodeVinQuery>,
+) -> Result<Json<DecodedVin>, StatusCode> {
+    if !matches!(user.role.as_ref(), "manager" | "master") {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    vin::decode(&query.vin, state.clock.now().year())
+        .map(Json)
+        .map_err(|_| StatusCode::BAD_REQUEST)
+}
+
+#[derive(Deserialize)]
+pub struct ServiceIntervalsForm {
+    pub service_id: i32,
+    pub interval_months: Option<String>,
+    pub interval_km: Option<String>,
+}
+
+pub async fn admin_update_service_intervals(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<ServiceIntervalsForm>,
+) -> Result<Json<Service>, StatusCode> {
+    if user.role != "superadmin" && user.role != "admin" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let interval_months = integer_field(&form.interval_months)?;
+    let interval_km = integer_field(&form.interval_km)?;
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(service) = tx
+        .set_service_intervals(form.service_id, interval_months, interval_km)
+        .await
+    {
+        tx.commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        return Ok(Json(service));
+    }
+    Err(StatusCode::BAD_REQUEST)
+}
+
+pub async fn manager_maintenance_reminders(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+) -> Result<Json<Vec<MaintenanceReminder>>, StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(reminders) = tx.get_maintenance_reminders().await {
+        return Ok(Json(reminders));
+    }
+    Err(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[derive(Deserialize)]
+pub struct ReminderForm {
+    pub reminder_id: i32,
+}
+
+pub async fn manager_dismiss_reminder(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<ReminderForm>,
+) -> Result<(), StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if tx.dismiss_maintenance_reminder(form.reminder_id).await.is_ok() {
+        return tx
+            .commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    Err(StatusCode::NOT_FOUND)
+}
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    pub q: String,
+    pub limit: Option<i32>,
+}
+
+/// Most results one search returns.
+const MAX_SEARCH_RESULTS: i32 = 50;
+
+/// Search for any role; what it finds is limited to what the role can see.
+pub async fn search(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Query(query): Query<SearchQuery>,
+) -> Result<Json<Vec<SearchResult>>, StatusCode> {
+    let q = query.q.trim();
+    if q.chars().count() < MIN_QUERY_LENGTH {
+        return Ok(Json(Vec::new()));
+    }
+    let limit = query.limit.unwrap_or(20).clamp(1, MAX_SEARCH_RESULTS);
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::FORBIDDEN)?;
+    if let Ok(results) = tx.search(q, limit).await {
+        return Ok(Json(results));
+    }
+    Err(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[derive(Deserialize)]
+pub struct FindPartsQuery {
+    pub q: String,
+    pub order_id: i32,
+}
+
+/// Most parts one lookup returns.
+const FIND_PARTS_RESULTS: i32 = 20;
+
+/// Parts for an order's line items: those fitting the order's vehicle come first, with
+/// substitutes and the branch's price and stock.
+pub async fn find_parts(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Query(query): Query<FindPartsQuery>,
+) -> Result<Json<Vec<PartMatch>>, StatusCode> {
+    if !matches!(user.role.as_ref(), "manager" | "master") {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let order = tx
+        .get_order_by_id(query.order_id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    let q = query.q.trim();
+    if q.chars().count() < MIN_QUERY_LENGTH {
+        return Ok(Json(Vec::new()));
+    }
+    if let Ok(parts) = tx
+        .find_parts(q, order.vehicle_id, order.branch_id, FIND_PARTS_RESULTS)
+        .await
+    {
+        return Ok(Json(parts));
+    }
+    Err(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[derive(Deserialize)]
+pub struct PartDetailsForm {
+    pub part_id: i32,
+    pub sku: Option<String>,
+    pub oem_number: Option<String>,
+    pub manufacturer: Option<String>,
+    pub unit: String,
+}
+
+pub async fn admin_update_part_details(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<PartDetailsForm>,
+) -> Result<Json<SparePart>, StatusCode> {
+    if user.role != "superadmin" && user.role != "admin" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    if !PART_UNITS.contains(&form.unit.as_str()) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let sku = text_field(&form.sku);
+    let oem_number = text_field(&form.oem_number);
+    let manufacturer = text_field(&form.manufacturer);
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(part) = tx
+        .update_part_details(
+            form.part_id,
+            sku.as_deref(),
+            oem_number.as_deref(),
+            manufacturer.as_deref(),
+            &form.unit,
+        )
+        .await
+    {
+        tx.commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        return Ok(Json(part));
+    }
+    Err(StatusCode::BAD_REQUEST)
+}
+
+#[derive(Deserialize)]
+pub struct PartCompatibilityForm {
+    pub part_id: i32,
+    pub make: String,
+    pub model: Option<String>,
+    pub year_from: Option<String>,
+    pub year_to: Option<String>,
+}
+
+pub async fn admin_add_part_compatibility(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<PartCompatibilityForm>,
+) -> Result<Json<PartCompatibility>, StatusCode> {
+    if user.role != "superadmin" && user.role != "admin" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let make = form.make.trim();
+    if make.is_empty() {
         return Err(StatusCode::BAD_REQUEST);
     }
-    return Err(StatusCode::BAD_REQUEST);
+    let compatibility = PartCompatibility {
+        compatibility_id: None,
+        part_id: form.part_id,
+        make: make.to_string(),
+        model: text_field(&form.model),
+        year_from: integer_field(&form.year_from)?,
+        year_to: integer_field(&form.year_to)?,
+    };
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(compatibility) = tx.add_part_compatibility(compatibility).await {
+        tx.commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        return Ok(Json(compatibility));
+    }
+    Err(StatusCode::BAD_REQUEST)
+}
+
+#[derive(Deserialize)]
+pub struct CompatibilityForm {
+    pub compatibility_id: i32,
+}
+
+pub async fn admin_remove_part_compatibility(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<CompatibilityForm>,
+) -> Result<(), StatusCode> {
+    if user.role != "superadmin" && user.role != "admin" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if tx.remove_part_compatibility(form.compatibility_id).await.is_ok() {
+        return tx
+            .commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    Err(StatusCode::BAD_REQUEST)
+}
+
+#[derive(Deserialize)]
+pub struct PartSubstituteForm {
+    pub part_id: i32,
+    pub substitute_id: i32,
+}
+
+pub async fn admin_link_part_substitute(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<PartSubstituteForm>,
+) -> Result<(), StatusCode> {
+    if user.role != "superadmin" && user.role != "admin" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if tx
+        .link_part_substitute(form.part_id, form.substitute_id)
+        .await
+        .is_ok()
+    {
+        return tx
+            .commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    Err(StatusCode::BAD_REQUEST)
+}
+
+pub async fn admin_unlink_part_substitute(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<PartSubstituteForm>,
+) -> Result<(), StatusCode> {
+    if user.role != "superadmin" && user.role != "admin" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if tx
+        .unlink_part_substitute(form.part_id, form.substitute_id)
+        .await
+        .is_ok()
+    {
+        return tx
+            .commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    Err(StatusCode::BAD_REQUEST)
+}
+
+#[derive(Deserialize)]
+pub struct StockLevelsQuery {
+    pub low_only: Option<bool>,
+}
+
+/// Stock of the manager's branch with reorder levels, low parts first.
+pub async fn manager_stock_levels(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Query(query): Query<StockLevelsQuery>,
+) -> Result<Json<Vec<StockLevel>>, StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(levels) = tx
+        .get_stock_levels(user.branch_id, query.low_only.unwrap_or(false))
+        .await
+    {
+        return Ok(Json(levels));
+    }
+    Err(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[derive(Deserialize)]
+pub struct ReorderLevelForm {
+    pub part_id: i32,
+    pub reorder_point: Option<String>,
+    pub reorder_quantity: Option<String>,
+}
+
+/// Sets when a part of the branch counts as low and how much to order then. Leaving both
+/// empty stops watching the part.
+pub async fn manager_set_reorder_level(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<ReorderLevelForm>,
+) -> Result<Json<SparePartBranch>, StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let reorder_point = integer_field(&form.reorder_point)?;
+    let reorder_quantity = integer_field(&form.reorder_quantity)?;
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(level) = tx
+        .set_reorder_level(user.branch_id, form.part_id, reorder_point, reorder_quantity)
+        .await
+    {
+        tx.commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        return Ok(Json(level));
+    }
+    Err(StatusCode::BAD_REQUEST)
+}
+
+#[derive(Serialize)]
+pub struct PurchaseOrderDetails {
+    pub purchase_order: PurchaseOrder,
+    pub lines: Vec<PurchaseOrderLine>,
+}
+
+/// Draft and ordered purchase orders of the manager's branch.
+pub async fn manager_purchase_orders(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+) -> Result<Json<Vec<PurchaseOrderDetails>>, StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let orders = tx
+        .get_open_purchase_orders(user.branch_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let mut details = Vec::new();
+    for purchase_order in orders {
+        let lines = tx
+            .get_purchase_order_lines(purchase_order.purchase_order_id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        details.push(PurchaseOrderDetails {
+            purchase_order,
+            lines,
+        });
+    }
+    Ok(Json(details))
+}
+
+#[derive(Deserialize)]
+pub struct PurchaseOrderLineForm {
+    pub purchase_order_line_id: i32,
+    pub quantity: i32,
+}
+
+/// Changes a line of a draft purchase order; a quantity of 0 takes it off the order.
+pub async fn manager_update_purchase_order_line(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<PurchaseOrderLineForm>,
+) -> Result<(), StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    if form.quantity < 0 {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let updated = if form.quantity == 0 {
+        tx.remove_purchase_order_line(form.purchase_order_line_id)
+            .await
+    } else {
+        tx.update_purchase_order_line(form.purchase_order_line_id, form.quantity)
+            .await
+            .map(|_| {})
+    };
+    if updated.is_ok() {
+        return tx
+            .commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    Err(StatusCode::BAD_REQUEST)
+}
+
+#[derive(Deserialize)]
+pub struct PurchaseOrderStatusForm {
+    pub purchase_order_id: i32,
+    pub status: String,
+}
+
+/// Sends a draft to the supplier, books an ordered purchase order in as received, or
+/// cancels either.
+pub async fn manager_set_purchase_order_status(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Form(form): Form<PurchaseOrderStatusForm>,
+) -> Result<Json<PurchaseOrder>, StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    if !matches!(form.status.as_str(), "ordered" | "received" | "cancelled") {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let mut tx = state
+        .store
+        .begin_as(&user)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let purchase_order = tx
+        .get_purchase_order(form.purchase_order_id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    if purchase_order.branch_id != user.branch_id {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    if let Ok(purchase_order) = tx
+        .set_purchase_order_status(form.purchase_order_id, &form.status)
+        .await
+    {
+        tx.commit()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        return Ok(Json(purchase_order));
+    }
+    Err(StatusCode::BAD_REQUEST)
 }
@@ -1,5 +1,25 @@
-use axum::{routing::post, Router};
-use handlers::{admin_update_user, login, manager_edit_order, master_complete_order};
+use axum::{
+    routing::{get, post},
+    Router,
+};
+use handlers::{
+    admin_add_part_compatibility, admin_archive, admin_create_loyalty_tier, admin_link_part_substitute,
+    admin_remove_part_compatibility, admin_restore, admin_unlink_part_substitute,
+    admin_update_branch, admin_update_employee_contacts, admin_update_loyalty_tier, admin_update_part_details,
+    admin_update_service_intervals, admin_update_user, close_order_for_pickup, decode_vin,
+    find_parts, login, manager_add_payment, manager_adjust_bonus_points, manager_assign_vehicle,
+    manager_bonus_statement, manager_client_vehicles, manager_close_shift, manager_delete_vehicle,
+    manager_discount_line, manager_dismiss_reminder, manager_edit_order, manager_edit_vehicle,
+    manager_maintenance_reminders, manager_open_shift, manager_order_payments,
+    manager_preview_total, manager_price_order, manager_purchase_orders, manager_refund_order,
+    manager_set_purchase_order_status, manager_set_reorder_level, manager_share_client,
+    manager_shift_report, manager_stock_levels, manager_unshare_client,
+    manager_update_client_contacts, manager_update_purchase_order_line,
+    master_complete_order, search, superadmin_close_branch, superadmin_create_branch,
+    superadmin_reopen_branch, vehicle_history,
+};
+
+use crate::web::state::AppState;
 
 pub mod common;
 mod handlers;
@@ -7,13 +27,68 @@ mod handlers;
 #[cfg(test)]
 mod tests;
 
-pub fn new_api_router() -> Router {
-    let admin_router = Router::new().route("/update_user", post(admin_update_user));
-    let master_router = Router::new().route("/complete_order", post(master_complete_order));
-    let manager_router = Router::new().route("/edit_order", post(manager_edit_order));
-    let default_router = Router::new().route("/login", post(login));
+pub fn new_api_router() -> Router<AppState> {
+    let superadmin_router = Router::new()
+        .route("/create_branch", post(superadmin_create_branch))
+        .route("/close_branch", post(superadmin_close_branch))
+        .route("/reopen_branch", post(superadmin_reopen_branch));
+    let admin_router = Router::new()
+        .route("/update_user", post(admin_update_user))
+        .route("/update_branch", post(admin_update_branch))
+        .route("/update_employee_contacts", post(admin_update_employee_contacts))
+        .route("/archive", post(admin_archive))
+        .route("/restore", post(admin_restore))
+        .route("/create_loyalty_tier", post(admin_create_loyalty_tier))
+        .route("/update_loyalty_tier", post(admin_update_loyalty_tier))
+        .route("/update_service_intervals", post(admin_update_service_intervals))
+        .route("/update_part_details", post(admin_update_part_details))
+        .route("/add_part_compatibility", post(admin_add_part_compatibility))
+        .route("/remove_part_compatibility", post(admin_remove_part_compatibility))
+        .route("/link_part_substitute", post(admin_link_part_substitute))
+        .route("/unlink_part_substitute", post(admin_unlink_part_substitute));
+    let master_router = Router::new()
+        .route("/complete_order", post(master_complete_order))
+        .route("/close_order", post(close_order_for_pickup))
+        .route("/vehicle_history", get(vehicle_history))
+        .route("/find_parts", get(find_parts))
+        .route("/decode_vin", get(decode_vin));
+    let manager_router = Router::new()
+        .route("/edit_order", post(manager_edit_order))
+        .route("/preview_total", post(manager_preview_total))
+        .route("/price_order", post(manager_price_order))
+        .route("/discount_line", post(manager_discount_line))
+        .route("/refund_order", post(manager_refund_order))
+        .route("/add_payment", post(manager_add_payment))
+        .route("/order_payments", get(manager_order_payments))
+        .route("/close_order", post(close_order_for_pickup))
+        .route("/open_shift", post(manager_open_shift))
+        .route("/close_shift", post(manager_close_shift))
+        .route("/shift_report", get(manager_shift_report))
+        .route("/bonus_statement", get(manager_bonus_statement))
+        .route("/adjust_bonus_points", post(manager_adjust_bonus_points))
+        .route("/share_client", post(manager_share_client))
+        .route("/unshare_client", post(manager_unshare_client))
+        .route("/update_client_contacts", post(manager_update_client_contacts))
+        .route("/edit_vehicle", post(manager_edit_vehicle))
+        .route("/delete_vehicle", post(manager_delete_vehicle))
+        .route("/client_vehicles", get(manager_client_vehicles))
+        .route("/assign_vehicle", post(manager_assign_vehicle))
+        .route("/maintenance_reminders", get(manager_maintenance_reminders))
+        .route("/dismiss_reminder", post(manager_dismiss_reminder))
+        .route("/stock_levels", get(manager_stock_levels))
+        .route("/set_reorder_level", post(manager_set_reorder_level))
+        .route("/purchase_orders", get(manager_purchase_orders))
+        .route("/update_purchase_order_line", post(manager_update_purchase_order_line))
+        .route("/set_purchase_order_status", post(manager_set_purchase_order_status))
+        .route("/vehicle_history", get(vehicle_history))
+        .route("/find_parts", get(find_parts))
+        .route("/decode_vin", get(decode_vin));
+    let default_router = Router::new()
+        .route("/login", post(login))
+        .route("/search", get(search));
     Router::new()
         .nest("/", default_router)
+        .nest("/superadmin", superadmin_router)
         .nest("/admin", admin_router)
         .nest("/master", master_router)
         .nest("/manager", manager_router)
@@ -0,0 +1,221 @@
+use axum::http::StatusCode;
+use bigdecimal::BigDecimal;
+
+use crate::contact::{normalize_email, normalize_phone, ContactError};
+use crate::database::{
+    act_as,
+    client::{create_client, get_client_by_id, get_clients, share_client},
+    DbError,
+};
+use crate::models::Client;
+
+use super::harness::{assert_status, json_body, TestApp};
+
+pub fn client(name: &str, status: &str, home_branch_id: i32) -> Client {
+    Client {
+        client_id: None,
+        name: name.to_string(),
+        status: status.to_string(),
+        bonus_points: Some(BigDecimal::from(0)),
+        total_spent: BigDecimal::from(0),
+        home_branch_id,
+        deleted_at: None,
+        phones: vec!["+70000000000".to_string()],
+        email: None,
+        preferred_channel: None,
+        service_consent: true,
+        marketing_consent: false,
+        contact_note: None,
+    }
+}
+
+#[tokio::test]
+async fn manager_sees_only_home_and_shared_clients() {
+    let app = TestApp::spawn().await;
+    let manager = app.user("manager_scope", "manager", 1).await;
+    let mut tx = app.db.begin().await.unwrap();
+    let own = create_client(&mut tx, client("Own branch", "casual", 1))
+        .await
+        .unwrap();
+    let foreign = create_client(&mut tx, client("Other branch", "casual", 2))
+        .await
+        .unwrap();
+    let shared = create_client(&mut tx, client("Shared", "casual", 2))
+        .await
+        .unwrap();
+    share_client(&mut tx, shared.client_id.unwrap(), 1)
+        .await
+        .unwrap();
+
+    act_as(&mut tx, &manager).await.unwrap();
+    let visible: Vec<Option<i32>> = get_clients(&mut tx, None, None)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|c| c.client_id)
+        .collect();
+
+    assert!(visible.contains(&own.client_id));
+    assert!(visible.contains(&shared.client_id));
+    assert!(!visible.contains(&foreign.client_id));
+}
+
+#[tokio::test]
+async fn manager_cannot_create_client_for_other_branch() {
+    let app = TestApp::spawn().await;
+    let manager = app.user("manager_scope", "manager", 1).await;
+    let mut tx = app.db.begin().await.unwrap();
+    act_as(&mut tx, &manager).await.unwrap();
+
+    match create_client(&mut tx, client("Foreign", "casual", 2)).await {
+        Err(DbError::Sqlx(sqlx::Error::Database(e))) => {
+            assert_eq!(e.code().as_deref(), Some("42501"))
+        }
+        other => panic!("expected row-level security violation, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn contacts_are_normalised() {
+    assert_eq!(
+        normalize_phone("8 (900) 123-45-67").unwrap(),
+        "+79001234567"
+    );
+    assert_eq!(normalize_phone("9001234567").unwrap(), "+79001234567");
+    assert_eq!(
+        normalize_phone("0044 20 7946 0958").unwrap(),
+        "+442079460958"
+    );
+    assert_eq!(normalize_phone("+1 212.555.0100").unwrap(), "+12125550100");
+    assert_eq!(normalize_phone("12345"), Err(ContactError::Phone));
+    assert_eq!(normalize_phone("+7 900 CALL ME"), Err(ContactError::Phone));
+    assert_eq!(
+        normalize_email(" Rider@Example.COM ").unwrap(),
+        "rider@example.com"
+    );
+    assert_eq!(normalize_email("rider@localhost"), Err(ContactError::Email));
+    assert_eq!(
+        normalize_email("rider example@mail.com"),
+        Err(ContactError::Email)
+    );
+
+    // The seed clients had their emails in the old free-text field.
+    let app = TestApp::spawn().await;
+    let mut conn = app.conn().await;
+    let seeded = get_client_by_id(&mut conn, 1).await.unwrap();
+    assert_eq!(seeded.email.as_deref(), Some("alice.cooper@example.com"));
+    assert!(seeded.phones.is_empty());
+    assert!(seeded.contact_note.is_none());
+}
+
+#[tokio::test]
+async fn manager_edits_client_contacts() {
+    let app = TestApp::spawn().await;
+    app.user("manager_contacts", "manager", 1).await;
+    let mut conn = app.conn().await;
+    let own = create_client(&mut conn, client("Own contacts", "casual", 1))
+        .await
+        .unwrap();
+    let foreign = create_client(&mut conn, client("Foreign contacts", "casual", 2))
+        .await
+        .unwrap();
+    let cookie = app
+        .login("manager_contacts", "manager_contacts")
+        .await
+        .unwrap();
+    let own_id = own.client_id.unwrap().to_string();
+
+    let response = app
+        .post_form(
+            "/api/v1/manager/update_client_contacts",
+            Some(&cookie),
+            &[
+                ("client_id", &own_id),
+                (
+                    "phones",
+                    "8 (900) 123-45-67, +7 900 123 45 67\n+44 20 7946 0958",
+                ),
+                ("email", " Rider@Example.COM "),
+                ("preferred_channel", "sms"),
+                ("marketing_consent", "on"),
+            ],
+        )
+        .await;
+    assert_status(&response, StatusCode::OK);
+    let updated = json_body(response).await;
+    assert_eq!(
+        updated["phones"],
+        serde_json::json!(["+79001234567", "+442079460958"])
+    );
+    assert_eq!(updated["email"], "rider@example.com");
+    assert_eq!(updated["preferred_channel"], "sms");
+    assert_eq!(updated["service_consent"], false);
+    assert_eq!(updated["marketing_consent"], true);
+
+    for invalid in [
+        vec![("phones", "12345")],
+        vec![("email", "not an email")],
+        vec![("preferred_channel", "email")],
+        vec![("preferred_channel", "pigeon"), ("phones", "+79001234567")],
+    ] {
+        let mut fields = vec![("client_id", own_id.as_str())];
+        fields.extend(invalid);
+        let response = app
+            .post_form(
+                "/api/v1/manager/update_client_contacts",
+                Some(&cookie),
+                &fields,
+            )
+            .await;
+        assert_status(&response, StatusCode::BAD_REQUEST);
+    }
+
+    let response = app
+        .post_form(
+            "/api/v1/manager/update_client_contacts",
+            Some(&cookie),
+            &[
+                ("client_id", &foreign.client_id.unwrap().to_string()),
+                ("email", "foreign@example.com"),
+            ],
+        )
+        .await;
+    assert_status(&response, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn only_managers_share_clients() {
+    let app = TestApp::spawn().await;
+    app.user("master_share", "master", 1).await;
+    app.user("admin_share", "admin", 1).await;
+    app.user("manager_share", "manager", 1).await;
+    let own = create_client(&mut app.conn().await, client("Shared by hand", "casual", 1))
+        .await
+        .unwrap();
+    let client_id = own.client_id.unwrap().to_string();
+    let form = [("client_id", client_id.as_str()), ("branch_id", "2")];
+
+    for name in ["master_share", "admin_share"] {
+        let cookie = app.login(name, name).await.unwrap();
+        for uri in [
+            "/api/v1/manager/share_client",
+            "/api/v1/manager/unshare_client",
+        ] {
+            assert_status(
+                &app.post_form(uri, Some(&cookie), &form).await,
+                StatusCode::FORBIDDEN,
+            );
+        }
+    }
+
+    let cookie = app.login("manager_share", "manager_share").await.unwrap();
+    for uri in [
+        "/api/v1/manager/share_client",
+        "/api/v1/manager/unshare_client",
+    ] {
+        assert_status(
+            &app.post_form(uri, Some(&cookie), &form).await,
+            StatusCode::OK,
+        );
+    }
+}
@@ -0,0 +1,53 @@
+use axum::http::StatusCode;
+
+use super::harness::{assert_status, json_body, TestApp};
+
+#[tokio::test]
+async fn admin_keeps_employee_contacts_of_their_branch() {
+    let app = TestApp::spawn().await;
+    app.user("admin_staff", "admin", 1).await;
+    app.user("manager_staff", "manager", 1).await;
+    let cookie = app.login("admin_staff", "admin_staff").await.unwrap();
+    let uri = "/api/v1/admin/update_employee_contacts";
+
+    let response = app
+        .post_form(
+            uri,
+            Some(&cookie),
+            &[
+                ("employee_id", "1"),
+                ("phones", "8 (900) 123-45-67, 9001234568"),
+                ("email", " John.Doe@Example.com "),
+            ],
+        )
+        .await;
+    assert_status(&response, StatusCode::OK);
+    let employee = json_body(response).await;
+    assert_eq!(
+        employee["phones"],
+        serde_json::json!(["+79001234567", "+79001234568"])
+    );
+    assert_eq!(employee["email"], "john.doe@example.com");
+
+    assert_status(
+        &app.post_form(
+            uri,
+            Some(&cookie),
+            &[("employee_id", "1"), ("phones", "12345")],
+        )
+        .await,
+        StatusCode::BAD_REQUEST,
+    );
+    // Employee 3 works in another branch.
+    assert_status(
+        &app.post_form(uri, Some(&cookie), &[("employee_id", "3")])
+            .await,
+        StatusCode::NOT_FOUND,
+    );
+    let manager = app.login("manager_staff", "manager_staff").await.unwrap();
+    assert_status(
+        &app.post_form(uri, Some(&manager), &[("employee_id", "1")])
+            .await,
+        StatusCode::FORBIDDEN,
+    );
+}
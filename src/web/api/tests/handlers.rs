@@ -0,0 +1,375 @@
+use axum::http::StatusCode;
+use bigdecimal::BigDecimal;
+
+use crate::config::Config;
+use crate::database::memory::{MemoryData, MemoryStore};
+use crate::models::{Branch, Order, SparePart, User};
+use crate::web::state::{AppState, Clock};
+
+use super::harness::{assert_status, json_body, location, TestRouter};
+
+fn branch(branch_id: i32) -> Branch {
+    Branch {
+        branch_id: Some(branch_id),
+        address: "Lenina 1".to_string(),
+        phone_number: "+70000000000".to_string(),
+        postal_code: "100000".to_string(),
+        employee_count: 0,
+        city: "Moscow".to_string(),
+        closed_at: None,
+        vat_rate: BigDecimal::from(0),
+        max_bonus_share: BigDecimal::from(30),
+    }
+}
+
+fn user(user_id: i32, role: &str, branch_id: i32) -> User {
+    let username = format!("{}{}", role, user_id);
+    User {
+        user_id: Some(user_id),
+        passwordhash: sha256::digest(&username),
+        username,
+        role: role.to_string(),
+        branch_id,
+    }
+}
+
+struct FixedClock(chrono::DateTime<chrono::Utc>);
+
+impl Clock for FixedClock {
+    fn now(&self) -> chrono::DateTime<chrono::Utc> {
+        self.0
+    }
+}
+
+fn archived_order(order_id: i32, branch_id: i32) -> Order {
+    Order {
+        order_id: Some(order_id),
+        client_id: 1,
+        branch_id,
+        master_id: 4,
+        order_date: chrono::Utc::now(),
+        completion_date: None,
+        total_amount: None,
+        status: "processing".to_string(),
+        deleted_at: Some(chrono::Utc::now()),
+        ..Default::default()
+    }
+}
+
+fn store() -> MemoryStore {
+    MemoryStore::new(MemoryData {
+        branches: vec![branch(1), branch(2)],
+        users: vec![
+            user(1, "superadmin", 1),
+            user(2, "admin", 1),
+            user(3, "manager", 1),
+            user(4, "master", 1),
+        ],
+        orders: vec![archived_order(1, 1), archived_order(2, 2)],
+        ..Default::default()
+    })
+}
+
+async fn logged_in(store: &MemoryStore, username: &str) -> (TestRouter, String) {
+    let router = TestRouter::new(AppState::new(store.clone(), Config::default()));
+    let cookie = router.login(username, username).await.unwrap();
+    (router, cookie)
+}
+
+#[tokio::test]
+async fn login_redirects_by_role() {
+    let store = store();
+    let router = TestRouter::new(AppState::new(store.clone(), Config::default()));
+
+    for (username, home) in [
+        ("superadmin1", "/superadmin"),
+        ("admin2", "/admin"),
+        ("master4", "/master"),
+    ] {
+        let response = router
+            .post_form(
+                "/api/v1/login",
+                None,
+                &[("login", username), ("password", username)],
+            )
+            .await;
+        assert_eq!(location(&response), Some(home));
+    }
+}
+
+#[tokio::test]
+async fn only_superadmin_closes_branches() {
+    let store = store();
+
+    let (router, cookie) = logged_in(&store, "admin2").await;
+    let response = router
+        .post_form(
+            "/api/v1/superadmin/close_branch",
+            Some(&cookie),
+            &[("branch_id", "2")],
+        )
+        .await;
+    assert_status(&response, StatusCode::FORBIDDEN);
+    assert!(store.data().branches[1].closed_at.is_none());
+
+    let (router, cookie) = logged_in(&store, "superadmin1").await;
+    let response = router
+        .post_form(
+            "/api/v1/superadmin/close_branch",
+            Some(&cookie),
+            &[("branch_id", "2")],
+        )
+        .await;
+    assert_status(&response, StatusCode::OK);
+    assert!(store.data().branches[1].closed_at.is_some());
+}
+
+#[tokio::test]
+async fn admin_updates_only_own_branch() {
+    let store = store();
+    let (router, cookie) = logged_in(&store, "admin2").await;
+
+    let response = router
+        .post_form(
+            "/api/v1/admin/update_branch",
+            Some(&cookie),
+            &[("branch_id", "2"), ("address", "Mira 5")],
+        )
+        .await;
+    assert_status(&response, StatusCode::FORBIDDEN);
+
+    let response = router
+        .post_form(
+            "/api/v1/admin/update_branch",
+            Some(&cookie),
+            &[("branch_id", "1"), ("address", "Mira 5")],
+        )
+        .await;
+    assert_status(&response, StatusCode::OK);
+    assert_eq!(store.data().branches[0].address, "Mira 5");
+}
+
+#[tokio::test]
+async fn only_superadmin_grants_superadmin_or_moves_users() {
+    let store = store();
+    let form = |role, branch_id| {
+        [
+            ("user_id", "4"),
+            ("username", "master4"),
+            ("passwordhash", ""),
+            ("role", role),
+            ("branch_id", branch_id),
+        ]
+    };
+
+    let (router, cookie) = logged_in(&store, "manager3").await;
+    let response = router
+        .post_form(
+            "/api/v1/admin/update_user",
+            Some(&cookie),
+            &form("manager", "1"),
+        )
+        .await;
+    assert_status(&response, StatusCode::FORBIDDEN);
+
+    let (router, cookie) = logged_in(&store, "admin2").await;
+    for (role, branch_id) in [("superadmin", "1"), ("master", "2")] {
+        let response = router
+            .post_form(
+                "/api/v1/admin/update_user",
+                Some(&cookie),
+                &form(role, branch_id),
+            )
+            .await;
+        assert_status(&response, StatusCode::FORBIDDEN);
+    }
+    assert_eq!(store.data().users[3].role, "master");
+    assert_eq!(store.data().users[3].branch_id, 1);
+
+    let (router, cookie) = logged_in(&store, "superadmin1").await;
+    let response = router
+        .post_form(
+            "/api/v1/admin/update_user",
+            Some(&cookie),
+            &form("master", "2"),
+        )
+        .await;
+    assert_status(&response, StatusCode::OK);
+    assert_eq!(store.data().users[3].branch_id, 2);
+}
+
+#[tokio::test]
+async fn admin_restores_only_own_branch_orders() {
+    let store = store();
+    let (router, cookie) = logged_in(&store, "admin2").await;
+
+    let response = router
+        .post_form(
+            "/api/v1/admin/restore",
+            Some(&cookie),
+            &[("entity", "order"), ("id", "2")],
+        )
+        .await;
+    assert_status(&response, StatusCode::FORBIDDEN);
+
+    let response = router
+        .post_form(
+            "/api/v1/admin/restore",
+            Some(&cookie),
+            &[("entity", "order"), ("id", "1")],
+        )
+        .await;
+    assert_status(&response, StatusCode::OK);
+    assert!(store.data().orders[0].deleted_at.is_none());
+    assert!(store.data().orders[1].deleted_at.is_some());
+}
+
+#[tokio::test]
+async fn admin_archives_only_own_branch_orders_and_not_the_catalog() {
+    let store = store();
+    for (order_id, branch_id) in [(3, 1), (4, 2)] {
+        let mut order = archived_order(order_id, branch_id);
+        order.deleted_at = None;
+        store.data().orders.push(order);
+    }
+    store.data().spare_parts.push(SparePart {
+        part_id: Some(1),
+        part_name: "Chain".to_string(),
+        description: String::new(),
+        sku: None,
+        oem_number: None,
+        manufacturer: None,
+        unit: "pcs".to_string(),
+        deleted_at: None,
+    });
+    let archive = |entity, id| [("entity", entity), ("id", id)];
+
+    let (router, cookie) = logged_in(&store, "admin2").await;
+    for (uri, entity, id, status) in [
+        ("/api/v1/admin/archive", "order", "3", StatusCode::OK),
+        ("/api/v1/admin/archive", "order", "4", StatusCode::FORBIDDEN),
+        (
+            "/api/v1/admin/archive",
+            "spare_part",
+            "1",
+            StatusCode::FORBIDDEN,
+        ),
+        (
+            "/api/v1/admin/restore",
+            "spare_part",
+            "1",
+            StatusCode::FORBIDDEN,
+        ),
+        (
+            "/api/v1/admin/archive",
+            "branch",
+            "1",
+            StatusCode::BAD_REQUEST,
+        ),
+    ] {
+        let response = router
+            .post_form(uri, Some(&cookie), &archive(entity, id))
+            .await;
+        assert_status(&response, status);
+    }
+    assert!(store.data().orders[2].deleted_at.is_some());
+    assert!(store.data().orders[3].deleted_at.is_none());
+
+    let (router, cookie) = logged_in(&store, "superadmin1").await;
+    let response = router
+        .post_form(
+            "/api/v1/admin/archive",
+            Some(&cookie),
+            &archive("spare_part", "1"),
+        )
+        .await;
+    assert_status(&response, StatusCode::OK);
+    assert!(store.data().spare_parts[0].deleted_at.is_some());
+}
+
+#[tokio::test]
+async fn managers_cannot_restore() {
+    let store = store();
+    let (router, cookie) = logged_in(&store, "manager3").await;
+
+    let response = router
+        .post_form(
+            "/api/v1/admin/restore",
+            Some(&cookie),
+            &[("entity", "order"), ("id", "1")],
+        )
+        .await;
+
+    assert_status(&response, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn completion_date_comes_from_the_clock() {
+    let store = store();
+    let mut order = archived_order(3, 1);
+    order.deleted_at = None;
+    store.data().orders.push(order);
+    let completed_at = chrono::DateTime::parse_from_rfc3339("2025-03-01T10:00:00Z")
+        .unwrap()
+        .to_utc();
+    let router = TestRouter::new(
+        AppState::new(store.clone(), Config::default()).with_clock(FixedClock(completed_at)),
+    );
+    let cookie = router.login("master4", "master4").await.unwrap();
+
+    let response = router
+        .post_form(
+            "/api/v1/master/complete_order",
+            Some(&cookie),
+            &[("order_id", "3")],
+        )
+        .await;
+
+    assert_status(&response, StatusCode::OK);
+    assert_eq!(store.data().orders[2].completion_date, Some(completed_at));
+    assert_eq!(store.data().orders[2].status, "finished");
+}
+
+#[tokio::test]
+async fn sessions_of_removed_users_are_rejected() {
+    let store = store();
+    let (router, cookie) = logged_in(&store, "admin2").await;
+    store.data().users.retain(|u| u.username != "admin2");
+
+    let response = router
+        .post_form(
+            "/api/v1/admin/update_branch",
+            Some(&cookie),
+            &[("branch_id", "1"), ("address", "Mira 5")],
+        )
+        .await;
+
+    assert_status(&response, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn vin_model_years_come_from_the_clock() {
+    let store = store();
+    for (today, year) in [
+        ("2026-06-01T00:00:00Z", 2018),
+        ("2000-06-01T00:00:00Z", 1988),
+    ] {
+        let now = chrono::DateTime::parse_from_rfc3339(today)
+            .unwrap()
+            .to_utc();
+        let router = TestRouter::new(
+            AppState::new(store.clone(), Config::default()).with_clock(FixedClock(now)),
+        );
+        let cookie = router.login("master4", "master4").await.unwrap();
+
+        let response = router
+            .get(
+                "/api/v1/master/decode_vin?vin=ZDM1XBEW0JB012345",
+                Some(&cookie),
+            )
+            .await;
+
+        assert_status(&response, StatusCode::OK);
+        assert_eq!(json_body(response).await["model_year"], year);
+    }
+}
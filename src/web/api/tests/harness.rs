@@ -0,0 +1,350 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    path::Path,
+    str::FromStr,
+};
+
+use bigdecimal::BigDecimal;
+
+use axum::{
+    body::Body,
+    http::{header, Request, Response, StatusCode},
+    Router,
+};
+use sqlx::{
+    postgres::{PgConnectOptions, PgPoolOptions},
+    ConnectOptions, Connection, PgConnection, PgPool,
+};
+use tokio::sync::OnceCell;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::database::{repo::PgStore, user::create_user};
+use crate::models::User;
+use crate::web::{new_app, state::AppState};
+
+const TEST_DATABASE_PREFIX: &str = "moto_auto_test_";
+
+static TEMPLATE: OnceCell<String> = OnceCell::const_new();
+
+fn server_options() -> PgConnectOptions {
+    PgConnectOptions::from_str(&Config::from_env().database_url)
+        .expect("DATABASE_URL is not a valid Postgres URL")
+}
+
+fn migration_files() -> Vec<std::path::PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("migrations");
+    let mut files: Vec<_> = std::fs::read_dir(dir)
+        .expect("migrations directory is missing")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "sql"))
+        .collect();
+    files.sort();
+    files
+}
+
+/// Name of the template database for the current set of migrations. Changing any
+/// migration changes the name, so stale templates are never reused.
+fn template_name(files: &[std::path::PathBuf]) -> String {
+    let mut hasher = DefaultHasher::new();
+    for file in files {
+        std::fs::read_to_string(file).unwrap().hash(&mut hasher);
+    }
+    format!("{}tpl_{:016x}", TEST_DATABASE_PREFIX, hasher.finish())
+}
+
+/// Builds the migrated template database once per test run. Databases left over by
+/// earlier runs are dropped on the way, skipping any that are still in use.
+async fn template() -> &'static str {
+    TEMPLATE
+        .get_or_init(|| async {
+            let files = migration_files();
+            let name = template_name(&files);
+            let mut server = server_options().connect().await.unwrap();
+
+            let leftovers: Vec<String> = sqlx::query_scalar(
+                "SELECT datname FROM pg_database WHERE datname LIKE $1 AND datname <> $2",
+            )
+            .bind(format!("{}%", TEST_DATABASE_PREFIX))
+            .bind(&name)
+            .fetch_all(&mut server)
+            .await
+            .unwrap();
+            for leftover in leftovers {
+                let _ = sqlx::query(&format!("DROP DATABASE \"{}\"", leftover))
+                    .execute(&mut server)
+                    .await;
+            }
+
+            let exists: bool =
+                sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM pg_database WHERE datname = $1)")
+                    .bind(&name)
+                    .fetch_one(&mut server)
+                    .await
+                    .unwrap();
+            if !exists {
+                let building = format!("{}build_{}", TEST_DATABASE_PREFIX, Uuid::new_v4().simple());
+                sqlx::query(&format!("CREATE DATABASE \"{}\"", building))
+                    .execute(&mut server)
+                    .await
+                    .unwrap();
+                let mut conn = server_options()
+                    .database(&building)
+                    .connect()
+                    .await
+                    .unwrap();
+                for file in &files {
+                    let sql = std::fs::read_to_string(file).unwrap();
+                    if let Err(e) = sqlx::raw_sql(&sql).execute(&mut conn).await {
+                        panic!("Error applying {}: {}", file.display(), e);
+                    }
+                }
+                conn.close().await.unwrap();
+                // Another test binary may have finished the same template first.
+                if sqlx::query(&format!(
+                    "ALTER DATABASE \"{}\" RENAME TO \"{}\"",
+                    building, name
+                ))
+                .execute(&mut server)
+                .await
+                .is_err()
+                {
+                    let _ = sqlx::query(&format!("DROP DATABASE \"{}\"", building))
+                        .execute(&mut server)
+                        .await;
+                }
+            }
+            name
+        })
+        .await
+}
+
+/// A throwaway copy of the migrated database together with the application router.
+pub struct TestApp {
+    pub db: PgPool,
+    name: String,
+    router: TestRouter,
+}
+
+impl Drop for TestApp {
+    /// Drops the test's database along with any connections the test left open. `drop`
+    /// cannot await, so this runs on a runtime of its own.
+    fn drop(&mut self) {
+        let name = std::mem::take(&mut self.name);
+        let _ = std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            runtime.block_on(async {
+                let mut server = server_options().connect().await?;
+                sqlx::query(&format!(
+                    "DROP DATABASE IF EXISTS \"{}\" WITH (FORCE)",
+                    name
+                ))
+                .execute(&mut server)
+                .await
+            })
+        })
+        .join();
+    }
+}
+
+impl std::ops::Deref for TestApp {
+    type Target = TestRouter;
+
+    fn deref(&self) -> &TestRouter {
+        &self.router
+    }
+}
+
+/// The application router driven in-process, without a listening socket.
+pub struct TestRouter {
+    router: Router,
+}
+
+impl TestApp {
+    pub async fn spawn() -> TestApp {
+        let template = template().await;
+        let name = format!("{}db_{}", TEST_DATABASE_PREFIX, Uuid::new_v4().simple());
+        let mut server = server_options().connect().await.unwrap();
+        sqlx::query(&format!(
+            "CREATE DATABASE \"{}\" TEMPLATE \"{}\"",
+            name, template
+        ))
+        .execute(&mut server)
+        .await
+        .unwrap();
+        let db = PgPoolOptions::new()
+            .max_connections(4)
+            .connect_with(server_options().database(&name))
+            .await
+            .unwrap();
+        let router = TestRouter::new(AppState::new(PgStore::new(db.clone()), Config::default()));
+        TestApp { db, name, router }
+    }
+
+    pub async fn conn(&self) -> PgConnection {
+        self.db.acquire().await.unwrap().detach()
+    }
+
+    /// Creates a user whose password equals the username.
+    pub async fn user(&self, username: &str, role: &str, branch_id: i32) -> User {
+        let user = User {
+            user_id: None,
+            username: username.to_string(),
+            passwordhash: sha256::digest(username),
+            role: role.to_string(),
+            branch_id,
+        };
+        create_user(&mut self.conn().await, branch_id, &user)
+            .await
+            .unwrap()
+    }
+}
+
+impl TestRouter {
+    pub fn new(state: AppState) -> TestRouter {
+        TestRouter {
+            router: new_app(state),
+        }
+    }
+
+    pub async fn send(&self, request: Request<Body>) -> Response<Body> {
+        self.router.clone().oneshot(request).await.unwrap()
+    }
+
+    /// Logs in through the API and returns the session cookie.
+    pub async fn login(&self, username: &str, password: &str) -> Option<String> {
+        let response = self
+            .post_form(
+                "/api/v1/login",
+                None,
+                &[("login", username), ("password", password)],
+            )
+            .await;
+        response.headers().get(header::SET_COOKIE).map(|cookie| {
+            cookie
+                .to_str()
+                .unwrap()
+                .split(';')
+                .next()
+                .unwrap()
+                .to_string()
+        })
+    }
+
+    pub async fn get(&self, uri: &str, cookie: Option<&str>) -> Response<Body> {
+        let mut request = Request::get(uri);
+        if let Some(cookie) = cookie {
+            request = request.header(header::COOKIE, cookie);
+        }
+        self.send(request.body(Body::empty()).unwrap()).await
+    }
+
+    pub async fn post_form(
+        &self,
+        uri: &str,
+        cookie: Option<&str>,
+        form: &[(&str, &str)],
+    ) -> Response<Body> {
+        let body = form
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, urlencode(value)))
+            .collect::<Vec<_>>()
+            .join("&");
+        let mut request =
+            Request::post(uri).header(header::CONTENT_TYPE, "application/x-www-form-urlencoded");
+        if let Some(cookie) = cookie {
+            request = request.header(header::COOKIE, cookie);
+        }
+        self.send(request.body(Body::from(body)).unwrap()).await
+    }
+
+    pub async fn post_json(
+        &self,
+        uri: &str,
+        cookie: Option<&str>,
+        json: &serde_json::Value,
+    ) -> Response<Body> {
+        let mut request = Request::post(uri).header(header::CONTENT_TYPE, "application/json");
+        if let Some(cookie) = cookie {
+            request = request.header(header::COOKIE, cookie);
+        }
+        self.send(request.body(Body::from(json.to_string())).unwrap())
+            .await
+    }
+
+    /// Opens a cash shift in the manager's branch with an empty drawer.
+    pub async fn open_shift(&self, cookie: &str) -> StatusCode {
+        self.post_form("/api/v1/manager/open_shift", Some(cookie), &[])
+            .await
+            .status()
+    }
+
+    /// Takes a payment through the API. The branch needs an open shift.
+    pub async fn pay(&self, cookie: &str, order_id: i32, amount: &str, method: &str) -> StatusCode {
+        self.post_form(
+            "/api/v1/manager/add_payment",
+            Some(cookie),
+            &[
+                ("order_id", &order_id.to_string()),
+                ("amount", amount),
+                ("method", method),
+            ],
+        )
+        .await
+        .status()
+    }
+}
+
+pub async fn body_string(response: Response<Body>) -> String {
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    String::from_utf8(bytes.to_vec()).unwrap()
+}
+
+pub async fn json_body(response: Response<Body>) -> serde_json::Value {
+    serde_json::from_str(&body_string(response).await).unwrap()
+}
+
+/// Posts a form with the session cookie and returns only the status.
+pub async fn post(app: &TestApp, cookie: &str, uri: &str, form: &[(&str, &str)]) -> StatusCode {
+    app.post_form(uri, Some(cookie), form).await.status()
+}
+
+/// Parses a decimal serialized as a JSON string.
+pub fn decimal(value: &serde_json::Value) -> BigDecimal {
+    value.as_str().unwrap().parse().unwrap()
+}
+
+pub fn location(response: &Response<Body>) -> Option<&str> {
+    response
+        .headers()
+        .get(header::LOCATION)
+        .map(|value| value.to_str().unwrap())
+}
+
+pub fn assert_status(response: &Response<Body>, status: StatusCode) {
+    assert_eq!(
+        response.status(),
+        status,
+        "unexpected response {:?}",
+        response
+    );
+}
+
+fn urlencode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
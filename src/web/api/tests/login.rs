@@ -0,0 +1,76 @@
+use axum::http::StatusCode;
+
+use crate::database::branch::close_branch;
+
+use super::harness::{assert_status, location, TestApp};
+
+#[tokio::test]
+async fn login_redirects_to_role_home() {
+    let app = TestApp::spawn().await;
+    app.user("master_login", "master", 1).await;
+
+    let cookie = app.login("master_login", "master_login").await.unwrap();
+    let response = app.get("/master", Some(&cookie)).await;
+
+    assert_status(&response, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn login_with_wrong_password_is_rejected() {
+    let app = TestApp::spawn().await;
+    app.user("master_login", "master", 1).await;
+
+    let response = app
+        .post_form(
+            "/api/v1/login",
+            None,
+            &[("login", "master_login"), ("password", "wrong")],
+        )
+        .await;
+
+    assert_status(&response, StatusCode::SEE_OTHER);
+    assert_eq!(location(&response), Some("/login"));
+}
+
+#[tokio::test]
+async fn pages_require_a_session() {
+    let app = TestApp::spawn().await;
+
+    let response = app.get("/master", None).await;
+
+    assert_status(&response, StatusCode::SEE_OTHER);
+    assert_eq!(location(&response), Some("/login"));
+}
+
+#[tokio::test]
+async fn closed_branch_users_cannot_log_in() {
+    let app = TestApp::spawn().await;
+    app.user("master_closed", "master", 2).await;
+    close_branch(&mut app.conn().await, 2).await.unwrap();
+
+    let response = app
+        .post_form(
+            "/api/v1/login",
+            None,
+            &[("login", "master_closed"), ("password", "master_closed")],
+        )
+        .await;
+
+    assert_eq!(location(&response), Some("/login"));
+}
+
+#[tokio::test]
+async fn closing_a_branch_ends_its_sessions() {
+    let app = TestApp::spawn().await;
+    app.user("manager_closing", "manager", 2).await;
+    let cookie = app
+        .login("manager_closing", "manager_closing")
+        .await
+        .unwrap();
+    let uri = "/api/v1/manager/stock_levels";
+    assert_status(&app.get(uri, Some(&cookie)).await, StatusCode::OK);
+
+    close_branch(&mut app.conn().await, 2).await.unwrap();
+
+    assert_status(&app.get(uri, Some(&cookie)).await, StatusCode::UNAUTHORIZED);
+}
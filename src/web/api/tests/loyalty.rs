@@ -0,0 +1,246 @@
+use bigdecimal::BigDecimal;
+use chrono::{Duration, Utc};
+
+use crate::database::{client::get_client_by_id, loyalty::expire_bonus_points};
+use axum::http::StatusCode;
+
+use super::harness::{assert_status, json_body, TestApp};
+use super::orders::{complete, give_bonus_points, order_with_lines};
+
+/// Accruals of a client as (points, remaining, expires_at), oldest first.
+async fn accruals(
+    app: &TestApp,
+    client_id: i32,
+) -> Vec<(BigDecimal, BigDecimal, Option<chrono::DateTime<Utc>>)> {
+    sqlx::query_as(
+        "SELECT points, remaining, expires_at FROM moto_auto.bonus_transaction
+         WHERE client_id = $1 AND kind = 'accrual' ORDER BY transaction_id",
+    )
+    .bind(client_id)
+    .fetch_all(&app.db)
+    .await
+    .unwrap()
+}
+
+async fn tier_id(app: &TestApp, name: &str) -> i32 {
+    sqlx::query_scalar("SELECT tier_id FROM moto_auto.loyalty_tier WHERE name = $1")
+        .bind(name)
+        .fetch_one(&app.db)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn accrual_uses_tier_rate_and_lifetime() {
+    let app = TestApp::spawn().await;
+    app.user("admin_tiers", "admin", 1).await;
+    let master = app.user("master_tiers", "master", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let cookie = app.login("admin_tiers", "admin_tiers").await.unwrap();
+    let casual = tier_id(&app, "casual").await.to_string();
+
+    let response = app
+        .post_form(
+            "/api/v1/admin/update_loyalty_tier",
+            Some(&cookie),
+            &[
+                ("tier_id", &casual),
+                ("accrual_rate", "5"),
+                ("points_lifetime_days", "30"),
+            ],
+        )
+        .await;
+    assert_status(&response, StatusCode::OK);
+    complete(&app, "master_tiers", order.order_id.unwrap()).await;
+
+    let client = get_client_by_id(&mut app.conn().await, order.client_id)
+        .await
+        .unwrap();
+    assert_eq!(client.bonus_points, Some(BigDecimal::from(75)));
+    let accruals = accruals(&app, order.client_id).await;
+    assert_eq!(accruals.len(), 1);
+    assert_eq!(accruals[0].1, BigDecimal::from(75));
+    let lifetime = accruals[0].2.unwrap() - Utc::now();
+    assert!(lifetime > Duration::days(29) && lifetime <= Duration::days(30));
+}
+
+#[tokio::test]
+async fn client_status_follows_tier_thresholds() {
+    let app = TestApp::spawn().await;
+    app.user("admin_thresholds", "admin", 1).await;
+    let master = app.user("master_thresholds", "master", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let cookie = app.login("admin_thresholds", "admin_thresholds").await.unwrap();
+
+    let response = app
+        .post_form(
+            "/api/v1/admin/create_loyalty_tier",
+            Some(&cookie),
+            &[
+                ("name", "bronze"),
+                ("spend_threshold", "1000"),
+                ("accrual_rate", "15"),
+            ],
+        )
+        .await;
+    assert_status(&response, StatusCode::OK);
+    assert_eq!(json_body(response).await["points_lifetime_days"], 365);
+    complete(&app, "master_thresholds", order.order_id.unwrap()).await;
+
+    let client = get_client_by_id(&mut app.conn().await, order.client_id)
+        .await
+        .unwrap();
+    assert_eq!(client.status, "bronze");
+}
+
+#[tokio::test]
+async fn only_admins_edit_tiers() {
+    let app = TestApp::spawn().await;
+    app.user("manager_tiers", "manager", 1).await;
+    let cookie = app.login("manager_tiers", "manager_tiers").await.unwrap();
+    let casual = tier_id(&app, "casual").await.to_string();
+
+    let response = app
+        .post_form(
+            "/api/v1/admin/update_loyalty_tier",
+            Some(&cookie),
+            &[("tier_id", &casual), ("accrual_rate", "50")],
+        )
+        .await;
+    assert_status(&response, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn expiry_job_expires_only_due_accruals() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_expiry", "master", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    complete(&app, "master_expiry", order.order_id.unwrap()).await;
+    sqlx::query(
+        "INSERT INTO moto_auto.bonus_transaction (client_id, kind, points, remaining, expires_at)
+         VALUES ($1, 'accrual', 40, 40, NOW() - INTERVAL '1 day')",
+    )
+    .bind(order.client_id)
+    .execute(&app.db)
+    .await
+    .unwrap();
+
+    let expired = expire_bonus_points(&mut app.conn().await).await.unwrap();
+
+    assert_eq!(expired, 1);
+    let client = get_client_by_id(&mut app.conn().await, order.client_id)
+        .await
+        .unwrap();
+    assert_eq!(client.bonus_points, Some(BigDecimal::from(150)));
+    let remaining: Vec<_> = accruals(&app, order.client_id)
+        .await
+        .into_iter()
+        .map(|(_, remaining, _)| remaining)
+        .collect();
+    assert_eq!(remaining, vec![BigDecimal::from(150), BigDecimal::from(0)]);
+}
+
+#[tokio::test]
+async fn statement_explains_the_balance() {
+    let app = TestApp::spawn().await;
+    app.user("manager_statement", "manager", 1).await;
+    let master = app.user("master_statement", "master", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    complete(&app, "master_statement", order.order_id.unwrap()).await;
+    // Points are redeemed on the client's next order, still in progress.
+    let next = order_with_lines(&app, "casual", master.user_id.unwrap())
+        .await
+        .order_id
+        .unwrap();
+    sqlx::query("UPDATE moto_auto.orders SET client_id = $1 WHERE order_id = $2")
+        .bind(order.client_id)
+        .bind(next)
+        .execute(&app.db)
+        .await
+        .unwrap();
+    let cookie = app.login("manager_statement", "manager_statement").await.unwrap();
+    let client_id = order.client_id.to_string();
+
+    let response = app
+        .post_form(
+            "/api/v1/manager/adjust_bonus_points",
+            Some(&cookie),
+            &[("client_id", &client_id), ("points", "50"), ("reason", "Apology")],
+        )
+        .await;
+    assert_status(&response, StatusCode::OK);
+    let response = app
+        .post_form(
+            "/api/v1/manager/price_order",
+            Some(&cookie),
+            &[
+                ("order_id", &next.to_string()),
+                ("bonus_points_used", "100"),
+            ],
+        )
+        .await;
+    assert_status(&response, StatusCode::OK);
+
+    let response = app
+        .get(
+            &format!("/api/v1/manager/bonus_statement?client_id={}", client_id),
+            Some(&cookie),
+        )
+        .await;
+    assert_status(&response, StatusCode::OK);
+    let statement = json_body(response).await;
+    let entries = statement["entries"].as_array().unwrap();
+    let kinds: Vec<_> = entries.iter().map(|e| e["kind"].as_str().unwrap()).collect();
+    assert_eq!(kinds, ["redemption", "adjustment", "accrual"]);
+    assert_eq!(entries[0]["order_id"], next);
+    assert_eq!(entries[1]["reason"], "Apology");
+    assert!(entries[1]["actor_user_id"].is_number());
+    let total: BigDecimal = entries
+        .iter()
+        .map(|e| e["points"].as_str().unwrap().parse::<BigDecimal>().unwrap())
+        .sum();
+    assert_eq!(total, BigDecimal::from(100));
+    let balance: BigDecimal = statement["client"]["bonus_points"].as_str().unwrap().parse().unwrap();
+    assert_eq!(balance, total);
+}
+
+#[tokio::test]
+async fn adjustments_need_a_reason_and_enough_points() {
+    let app = TestApp::spawn().await;
+    app.user("manager_adjust", "manager", 1).await;
+    let master = app.user("master_adjust", "master", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    give_bonus_points(&app, order.client_id, 30).await;
+    let cookie = app.login("manager_adjust", "manager_adjust").await.unwrap();
+    let client_id = order.client_id.to_string();
+
+    for (points, reason) in [("10", ""), ("-31", "Correction")] {
+        let response = app
+            .post_form(
+                "/api/v1/manager/adjust_bonus_points",
+                Some(&cookie),
+                &[("client_id", &client_id), ("points", points), ("reason", reason)],
+            )
+            .await;
+        assert_status(&response, StatusCode::BAD_REQUEST);
+    }
+
+    let client = get_client_by_id(&mut app.conn().await, order.client_id)
+        .await
+        .unwrap();
+    assert_eq!(client.bonus_points, Some(BigDecimal::from(30)));
+}
+
+#[tokio::test]
+async fn balance_changes_only_through_the_ledger() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_ledger", "master", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+
+    let updated = sqlx::query("UPDATE moto_auto.client SET bonus_points = 1000 WHERE client_id = $1")
+        .bind(order.client_id)
+        .execute(&app.db)
+        .await;
+
+    assert!(updated.is_err());
+}
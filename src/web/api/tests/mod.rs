@@ -1 +1,17 @@
-
+mod clients;
+mod employees;
+mod handlers;
+mod harness;
+mod login;
+mod loyalty;
+mod notifications;
+mod orders;
+mod parts;
+mod payments;
+mod receipts;
+mod reminders;
+mod retention;
+mod search;
+mod shifts;
+mod stock;
+mod vehicles;
@@ -0,0 +1,276 @@
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use axum::http::StatusCode;
+use sqlx::{PgConnection, PgPool};
+
+use crate::database::{
+    client::{create_client, update_client_contacts},
+    orders::create_order,
+    schedule::{create_schedule, update_schedule},
+};
+use crate::models::{ClientContacts, Order, Schedule};
+use crate::notify::{deliver_outbox, Channel, Notification, Notifier, NotifyError, MAX_ATTEMPTS};
+
+use super::clients::client;
+use super::harness::TestApp;
+use super::orders::{complete, order_with_lines};
+use super::reminders::RecordingNotifier;
+
+struct FailingNotifier;
+
+#[async_trait]
+impl Notifier for FailingNotifier {
+    async fn send(&self, _: &Notification) -> Result<(), NotifyError> {
+        Err(NotifyError::Send("gateway is down".to_string()))
+    }
+}
+
+/// Counts, at each send, the notifications another connection already sees as sent.
+struct CommitCheckingNotifier {
+    db: PgPool,
+    seen_sent: Mutex<Vec<i64>>,
+}
+
+#[async_trait]
+impl Notifier for CommitCheckingNotifier {
+    async fn send(&self, _: &Notification) -> Result<(), NotifyError> {
+        let sent: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM moto_auto.outbox WHERE sent_at IS NOT NULL")
+                .fetch_one(&self.db)
+                .await
+                .unwrap();
+        self.seen_sent.lock().unwrap().push(sent);
+        Ok(())
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct OutboxRow {
+    kind: String,
+    attempts: i32,
+    last_error: Option<String>,
+    sent: bool,
+    failed: bool,
+}
+
+async fn outbox(conn: &mut PgConnection) -> Vec<OutboxRow> {
+    sqlx::query_as(
+        "SELECT kind, attempts, last_error, sent_at IS NOT NULL AS sent,
+                failed_at IS NOT NULL AS failed
+         FROM moto_auto.outbox
+         ORDER BY outbox_id",
+    )
+    .fetch_all(conn)
+    .await
+    .unwrap()
+}
+
+/// Makes retries due now instead of after the backoff.
+async fn hurry_retries(conn: &mut PgConnection) {
+    sqlx::query("UPDATE moto_auto.outbox SET next_attempt_at = NOW()")
+        .execute(conn)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn finished_order_is_announced_once() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_announce", "master", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let mut conn = app.conn().await;
+    assert!(outbox(&mut conn).await.is_empty());
+
+    assert_eq!(
+        complete(&app, "master_announce", order.order_id.unwrap()).await,
+        StatusCode::OK
+    );
+    let queued = outbox(&mut conn).await;
+    assert_eq!(queued.len(), 1);
+    assert_eq!(queued[0].kind, "order_status");
+
+    let notifier = RecordingNotifier::default();
+    assert_eq!(deliver_outbox(&mut conn, &notifier).await.unwrap(), 1);
+    assert_eq!(deliver_outbox(&mut conn, &notifier).await.unwrap(), 0);
+    let sent = notifier.sent_with_subject("Your order is ready");
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].channel, Channel::Sms);
+    assert!(sent[0].body.contains("Amount due: 1500"));
+    assert!(outbox(&mut conn).await[0].sent);
+}
+
+#[tokio::test]
+async fn each_notification_is_recorded_before_the_next_is_sent() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_outbox_commit", "master", 1).await;
+    for _ in 0..2 {
+        let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+        complete(&app, "master_outbox_commit", order.order_id.unwrap()).await;
+    }
+    let notifier = CommitCheckingNotifier {
+        db: app.db.clone(),
+        seen_sent: Mutex::new(Vec::new()),
+    };
+
+    let mut conn = app.conn().await;
+    assert_eq!(deliver_outbox(&mut conn, &notifier).await.unwrap(), 2);
+    assert_eq!(*notifier.seen_sent.lock().unwrap(), [0, 1]);
+}
+
+#[tokio::test]
+async fn confirmed_booking_is_emailed() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_booking", "master", 1).await;
+    let mut conn = app.conn().await;
+    let mut details = client("Booking client", "casual", 1);
+    details.email = Some("rider@example.com".to_string());
+    let booker = create_client(&mut conn, details).await.unwrap();
+    let order = create_order(
+        &mut conn,
+        Order {
+            client_id: booker.client_id.unwrap(),
+            branch_id: 1,
+            master_id: master.user_id.unwrap(),
+            order_date: chrono::Utc::now(),
+            status: "processing".to_string(),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    let schedule = create_schedule(
+        &mut conn,
+        Schedule {
+            schedule_id: None,
+            client_id: booker.client_id.unwrap(),
+            branch_id: 1,
+            order_id: order.order_id.unwrap(),
+            scheduled_datetime: chrono::Utc::now() + chrono::Duration::days(2),
+            status: "pending".to_string(),
+        },
+    )
+    .await
+    .unwrap();
+    assert!(outbox(&mut conn).await.is_empty());
+
+    update_schedule(&mut conn, None, Some("confirmed"), schedule.schedule_id.unwrap())
+        .await
+        .unwrap();
+    let notifier = RecordingNotifier::default();
+    assert_eq!(deliver_outbox(&mut conn, &notifier).await.unwrap(), 1);
+    let sent = notifier.sent_with_subject("Your visit is booked");
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].channel, Channel::Email);
+    assert_eq!(sent[0].recipient, "rider@example.com");
+    assert!(sent[0].body.contains("Hello, Booking client!"));
+}
+
+#[tokio::test]
+async fn failed_sends_are_retried_then_given_up() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_retries", "master", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    assert_eq!(
+        complete(&app, "master_retries", order.order_id.unwrap()).await,
+        StatusCode::OK
+    );
+    let mut conn = app.conn().await;
+
+    assert_eq!(deliver_outbox(&mut conn, &FailingNotifier).await.unwrap(), 0);
+    let row = &outbox(&mut conn).await[0];
+    assert_eq!(row.attempts, 1);
+    assert_eq!(row.last_error.as_deref(), Some("gateway is down"));
+    assert!(!row.failed);
+
+    // Not due again until the pause is over.
+    deliver_outbox(&mut conn, &FailingNotifier).await.unwrap();
+    assert_eq!(outbox(&mut conn).await[0].attempts, 1);
+
+    for _ in 1..MAX_ATTEMPTS {
+        hurry_retries(&mut conn).await;
+        deliver_outbox(&mut conn, &FailingNotifier).await.unwrap();
+    }
+    let row = &outbox(&mut conn).await[0];
+    assert_eq!(row.attempts, MAX_ATTEMPTS);
+    assert!(row.failed);
+
+    hurry_retries(&mut conn).await;
+    let notifier = RecordingNotifier::default();
+    assert_eq!(deliver_outbox(&mut conn, &notifier).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn clients_without_contact_are_skipped() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_no_contact", "master", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let mut conn = app.conn().await;
+    sqlx::query("UPDATE moto_auto.client SET phones = '{}' WHERE client_id = $1")
+        .bind(order.client_id)
+        .execute(&mut conn)
+        .await
+        .unwrap();
+    assert_eq!(
+        complete(&app, "master_no_contact", order.order_id.unwrap()).await,
+        StatusCode::OK
+    );
+
+    let notifier = RecordingNotifier::default();
+    assert_eq!(deliver_outbox(&mut conn, &notifier).await.unwrap(), 0);
+    let row = &outbox(&mut conn).await[0];
+    assert_eq!(row.attempts, 1);
+    assert_eq!(row.last_error.as_deref(), Some("no contact"));
+    assert!(row.failed && !row.sent);
+}
+
+#[tokio::test]
+async fn messages_follow_preferred_channel_and_consent() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_consent", "master", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let mut conn = app.conn().await;
+    let mut contacts = ClientContacts {
+        phones: vec!["+79001234567".to_string()],
+        email: Some("rider@example.com".to_string()),
+        preferred_channel: Some("sms".to_string()),
+        service_consent: true,
+        marketing_consent: false,
+        contact_note: None,
+    };
+    update_client_contacts(&mut conn, order.client_id, &contacts)
+        .await
+        .unwrap();
+    assert_eq!(
+        complete(&app, "master_consent", order.order_id.unwrap()).await,
+        StatusCode::OK
+    );
+    let notifier = RecordingNotifier::default();
+    assert_eq!(deliver_outbox(&mut conn, &notifier).await.unwrap(), 1);
+    let sent = notifier.sent_with_subject("Your order is ready");
+    assert_eq!(sent[0].channel, Channel::Sms);
+    assert_eq!(sent[0].recipient, "+79001234567");
+
+    contacts.service_consent = false;
+    update_client_contacts(&mut conn, order.client_id, &contacts)
+        .await
+        .unwrap();
+    create_schedule(
+        &mut conn,
+        Schedule {
+            schedule_id: None,
+            client_id: order.client_id,
+            branch_id: 1,
+            order_id: order.order_id.unwrap(),
+            scheduled_datetime: chrono::Utc::now() + chrono::Duration::days(2),
+            status: "confirmed".to_string(),
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(deliver_outbox(&mut conn, &notifier).await.unwrap(), 0);
+    let row = &outbox(&mut conn).await[1];
+    assert_eq!(row.kind, "booking_confirmed");
+    assert_eq!(row.last_error.as_deref(), Some("no consent"));
+    assert!(row.failed);
+}
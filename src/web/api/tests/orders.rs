@@ -0,0 +1,911 @@
+use bigdecimal::BigDecimal;
+
+use crate::database::{
+    branch::update_branch,
+    client::{create_client, get_client_by_id},
+    loyalty::adjust_bonus_points,
+    order_service::{create_order_service, get_order_service},
+    order_service_part::{
+        create_order_service_part, delete_order_service_part, get_order_service_part,
+        update_order_service_part,
+    },
+    orders::{create_order, delete_order, get_order_by_id, update_order},
+    service::create_service,
+    price_history::get_price_history,
+    service_branch::{create_service_branch, get_service_branch, update_service_branch},
+    spare_part::create_spare_part,
+    spare_part_branch::{create_spare_part_branch, get_spare_part_branch},
+};
+use crate::models::{
+    BranchUpdate, Order, OrderService, OrderServicePart, Service, ServiceBranch, SparePart, SparePartBranch,
+};
+use crate::pricing::{compute_order_total, OrderLines, PartLine};
+use axum::http::StatusCode;
+use serde_json::json;
+use sqlx::PgConnection;
+
+use super::clients::client;
+use super::harness::{assert_status, body_string, json_body, TestApp};
+
+/// Order with one service priced 1000 and two parts priced 250 each, all in branch 1.
+pub async fn order_with_lines(app: &TestApp, client_status: &str, master_id: i32) -> Order {
+    let mut conn = app.conn().await;
+    let client = create_client(&mut conn, client("Order client", client_status, 1))
+        .await
+        .unwrap();
+    let service = create_service(
+        &mut conn,
+        Service {
+            service_id: None,
+            service_name: "Chain replacement".to_string(),
+            description: String::new(),
+            deleted_at: None,
+            interval_months: None,
+            interval_km: None,
+        },
+    )
+    .await
+    .unwrap();
+    create_service_branch(
+        &mut conn,
+        ServiceBranch {
+            service_branch_id: None,
+            price: BigDecimal::from(1000),
+            branch_id: 1,
+            service_id: service.service_id.unwrap(),
+        },
+    )
+    .await
+    .unwrap();
+    let part = create_spare_part(
+        &mut conn,
+        SparePart {
+            part_id: None,
+            part_name: "Chain".to_string(),
+            description: String::new(),
+            sku: None,
+            oem_number: None,
+            manufacturer: None,
+            unit: "pcs".to_string(),
+            deleted_at: None,
+        },
+    )
+    .await
+    .unwrap();
+    create_spare_part_branch(
+        &mut conn,
+        SparePartBranch {
+            spare_part_branch_id: None,
+            part_id: part.part_id.unwrap(),
+            branch_id: 1,
+            stock_quantity: 10,
+            price: BigDecimal::from(250),
+            reorder_point: None,
+            reorder_quantity: None,
+        },
+    )
+    .await
+    .unwrap();
+    let order = create_order(
+        &mut conn,
+        Order {
+            order_id: None,
+            client_id: client.client_id.unwrap(),
+            branch_id: 1,
+            master_id,
+            order_date: chrono::Utc::now(),
+            completion_date: None,
+            total_amount: None,
+            status: "processing".to_string(),
+            deleted_at: None,
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    let order_service = create_order_service(
+        &mut conn,
+        OrderService {
+            order_service_id: None,
+            order_id: order.order_id.unwrap(),
+            service_id: service.service_id.unwrap(),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    create_order_service_part(
+        &mut conn,
+        OrderServicePart {
+            order_service_part_id: None,
+            part_id: part.part_id.unwrap(),
+            order_service_id: order_service.order_service_id.unwrap(),
+            quantity: 2,
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    get_order_by_id(&mut conn, order.order_id.unwrap())
+        .await
+        .unwrap()
+}
+
+/// Service and part lines of an order as stored in the database.
+pub async fn order_lines(conn: &mut PgConnection, order_id: i32) -> (OrderService, OrderServicePart) {
+    let order_service = get_order_service(conn, Some(order_id), None)
+        .await
+        .unwrap()
+        .remove(0);
+    let part = get_order_service_part(conn, None, order_service.order_service_id)
+        .await
+        .unwrap()
+        .remove(0);
+    (order_service, part)
+}
+
+/// Prices the order's service and part in branch 2 as well, at other prices than branch 1.
+async fn price_in_other_branch(conn: &mut PgConnection, order_id: i32) {
+    let (order_service, part) = order_lines(conn, order_id).await;
+    create_service_branch(
+        conn,
+        ServiceBranch {
+            service_branch_id: None,
+            price: BigDecimal::from(4000),
+            branch_id: 2,
+            service_id: order_service.service_id,
+        },
+    )
+    .await
+    .unwrap();
+    create_spare_part_branch(
+        conn,
+        SparePartBranch {
+            spare_part_branch_id: None,
+            part_id: part.part_id,
+            branch_id: 2,
+            stock_quantity: 10,
+            price: BigDecimal::from(900),
+            reorder_point: None,
+            reorder_quantity: None,
+        },
+    )
+    .await
+    .unwrap();
+}
+
+pub async fn complete(app: &TestApp, username: &str, order_id: i32) -> StatusCode {
+    let cookie = app.login(username, username).await.unwrap();
+    app.post_form(
+        "/api/v1/master/complete_order",
+        Some(&cookie),
+        &[("order_id", &order_id.to_string())],
+    )
+    .await
+    .status()
+}
+
+#[tokio::test]
+async fn order_total_sums_services_and_parts() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_totals", "master", 1).await;
+
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+
+    assert_eq!(order.total_amount, Some(BigDecimal::from(1500)));
+}
+
+#[tokio::test]
+async fn master_completes_own_order() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_complete", "master", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+
+    assert_eq!(
+        complete(&app, "master_complete", order.order_id.unwrap()).await,
+        StatusCode::OK
+    );
+
+    let order = get_order_by_id(&mut app.conn().await, order.order_id.unwrap())
+        .await
+        .unwrap();
+    assert_eq!(order.status, "finished");
+    assert!(order.completion_date.is_some());
+}
+
+#[tokio::test]
+async fn master_cannot_complete_someone_elses_order() {
+    let app = TestApp::spawn().await;
+    let owner = app.user("master_owner", "master", 1).await;
+    app.user("master_other", "master", 1).await;
+    let order = order_with_lines(&app, "casual", owner.user_id.unwrap()).await;
+
+    assert_status(
+        &app.post_form("/api/v1/master/complete_order", None, &[("order_id", "1")])
+            .await,
+        StatusCode::SEE_OTHER,
+    );
+    complete(&app, "master_other", order.order_id.unwrap()).await;
+
+    let order = get_order_by_id(&mut app.conn().await, order.order_id.unwrap())
+        .await
+        .unwrap();
+    assert_eq!(order.status, "processing");
+}
+
+#[tokio::test]
+async fn completion_accrues_bonus_by_client_status() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_bonus", "master", 1).await;
+    let casual = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let regular = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let mut conn = app.conn().await;
+    // An earlier finished order of 10000 makes the client regular and accrues 1000 as casual.
+    create_order(
+        &mut conn,
+        Order {
+            client_id: regular.client_id,
+            branch_id: 1,
+            master_id: master.user_id.unwrap(),
+            order_date: chrono::Utc::now(),
+            total_amount: Some(BigDecimal::from(10000)),
+            status: "finished".to_string(),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    complete(&app, "master_bonus", casual.order_id.unwrap()).await;
+    complete(&app, "master_bonus", regular.order_id.unwrap()).await;
+
+    let casual = get_client_by_id(&mut conn, casual.client_id).await.unwrap();
+    let regular = get_client_by_id(&mut conn, regular.client_id)
+        .await
+        .unwrap();
+    assert_eq!(casual.bonus_points, Some(BigDecimal::from(150)));
+    assert_eq!(casual.total_spent, BigDecimal::from(1500));
+    assert_eq!(regular.status, "regular");
+    assert_eq!(regular.bonus_points, Some(BigDecimal::from(1000 + 300)));
+}
+
+#[tokio::test]
+async fn order_total_uses_prices_of_the_order_branch() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_branch_prices", "master", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let order_id = order.order_id.unwrap();
+    let mut conn = app.conn().await;
+    price_in_other_branch(&mut conn, order_id).await;
+
+    let (_, part) = order_lines(&mut conn, order_id).await;
+    update_order_service_part(&mut conn, Some(3), part.order_service_part_id.unwrap())
+        .await
+        .unwrap();
+
+    let order = get_order_by_id(&mut conn, order_id).await.unwrap();
+    assert_eq!(order.total_amount, Some(BigDecimal::from(1750)));
+}
+
+#[tokio::test]
+async fn deleting_a_line_recalculates_the_total() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_delete_line", "master", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let order_id = order.order_id.unwrap();
+    let mut conn = app.conn().await;
+
+    let (_, part) = order_lines(&mut conn, order_id).await;
+    delete_order_service_part(&mut conn, part.order_service_part_id.unwrap())
+        .await
+        .unwrap();
+
+    let order = get_order_by_id(&mut conn, order_id).await.unwrap();
+    assert_eq!(order.total_amount, Some(BigDecimal::from(1000)));
+}
+
+#[tokio::test]
+async fn computed_total_matches_the_database() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_compute", "master", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let order_id = order.order_id.unwrap();
+    let mut conn = app.conn().await;
+    price_in_other_branch(&mut conn, order_id).await;
+
+    let (order_service, part) = order_lines(&mut conn, order_id).await;
+    let lines = OrderLines {
+        service_ids: vec![order_service.service_id],
+        parts: vec![PartLine {
+            part_id: part.part_id,
+            quantity: part.quantity,
+        }],
+        ..Default::default()
+    };
+    let service_prices = get_service_branch(&mut conn, None, Some(order_service.service_id))
+        .await
+        .unwrap();
+    let part_prices = get_spare_part_branch(&mut conn, None, Some(part.part_id))
+        .await
+        .unwrap();
+
+    let order = get_order_by_id(&mut conn, order_id).await.unwrap();
+    assert_eq!(
+        Some(compute_order_total(
+            order.branch_id,
+            &lines,
+            &service_prices,
+            &part_prices
+        )),
+        order.total_amount
+    );
+}
+
+#[tokio::test]
+async fn manager_previews_total_at_branch_prices() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_preview", "master", 1).await;
+    app.user("manager_preview", "manager", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let order_id = order.order_id.unwrap();
+    let mut conn = app.conn().await;
+    price_in_other_branch(&mut conn, order_id).await;
+    let (order_service, part) = order_lines(&mut conn, order_id).await;
+
+    let cookie = app.login("manager_preview", "manager_preview").await.unwrap();
+    let response = app
+        .post_json(
+            "/api/v1/manager/preview_total",
+            Some(&cookie),
+            &json!({
+                "service_ids": [order_service.service_id],
+                "parts": [{ "part_id": part.part_id, "quantity": part.quantity }],
+            }),
+        )
+        .await;
+
+    assert_status(&response, StatusCode::OK);
+    let preview: BigDecimal = json_body(response).await["total_amount"]
+        .as_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert_eq!(Some(preview), order.total_amount);
+}
+
+/// Raises the branch 1 price of the order's service from 1000 to 1200.
+async fn raise_service_price(conn: &mut PgConnection, service_id: i32) {
+    let service_branch = get_service_branch(conn, Some(1), Some(service_id))
+        .await
+        .unwrap()
+        .remove(0);
+    update_service_branch(
+        conn,
+        Some(BigDecimal::from(1200)),
+        service_branch.service_branch_id.unwrap(),
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn order_lines_snapshot_name_and_price() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_snapshot", "master", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+
+    let (order_service, part) = order_lines(&mut app.conn().await, order.order_id.unwrap()).await;
+
+    assert_eq!(order_service.service_name.as_deref(), Some("Chain replacement"));
+    assert_eq!(order_service.unit_price, Some(BigDecimal::from(1000)));
+    assert_eq!(part.part_name.as_deref(), Some("Chain"));
+    assert_eq!(part.unit_price, Some(BigDecimal::from(250)));
+}
+
+#[tokio::test]
+async fn price_changes_do_not_rewrite_existing_orders() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_price_change", "master", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let order_id = order.order_id.unwrap();
+    let mut conn = app.conn().await;
+    let (order_service, part) = order_lines(&mut conn, order_id).await;
+
+    raise_service_price(&mut conn, order_service.service_id).await;
+    update_order_service_part(&mut conn, Some(3), part.order_service_part_id.unwrap())
+        .await
+        .unwrap();
+
+    let order = get_order_by_id(&mut conn, order_id).await.unwrap();
+    assert_eq!(order.total_amount, Some(BigDecimal::from(1750)));
+}
+
+#[tokio::test]
+async fn price_changes_are_kept_in_history() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_history", "master", 1).await;
+    app.user("analyst_history", "analyst", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let mut conn = app.conn().await;
+    let (order_service, _) = order_lines(&mut conn, order.order_id.unwrap()).await;
+
+    raise_service_price(&mut conn, order_service.service_id).await;
+
+    let history = get_price_history(
+        &mut conn,
+        Some("service"),
+        Some(order_service.service_id),
+        Some(1),
+        10,
+    )
+    .await
+    .unwrap();
+    let prices: Vec<_> = history
+        .iter()
+        .map(|e| (e.old_price.clone(), e.new_price.clone()))
+        .collect();
+    assert_eq!(
+        prices,
+        vec![
+            (Some(BigDecimal::from(1000)), Some(BigDecimal::from(1200))),
+            (None, Some(BigDecimal::from(1000))),
+        ]
+    );
+
+    let cookie = app.login("analyst_history", "analyst_history").await.unwrap();
+    let response = app
+        .get(
+            &format!(
+                "/analyst/prices?item_type=service&item_id={}",
+                order_service.service_id
+            ),
+            Some(&cookie),
+        )
+        .await;
+    assert_status(&response, StatusCode::OK);
+    assert!(body_string(response).await.contains("1200"));
+}
+
+/// Sets VAT for branch 1. Orders snapshot the rate when created.
+async fn set_vat(app: &TestApp, vat_rate: i32) {
+    update_branch(
+        &mut app.conn().await,
+        1,
+        BranchUpdate {
+            vat_rate: Some(BigDecimal::from(vat_rate)),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn new_branches_keep_their_vat_and_bonus_share() {
+    let app = TestApp::spawn().await;
+    app.user("superadmin_new_branch", "superadmin", 1).await;
+    let cookie = app
+        .login("superadmin_new_branch", "superadmin_new_branch")
+        .await
+        .unwrap();
+
+    let response = app
+        .post_form(
+            "/api/v1/superadmin/create_branch",
+            Some(&cookie),
+            &[
+                ("city", "Tula"),
+                ("address", "Lenina 1"),
+                ("phone_number", "+74872000000"),
+                ("postal_code", "300000"),
+                ("vat_rate", "20"),
+                ("max_bonus_share", "50"),
+                ("admin_username", "admin_tula"),
+                ("admin_password", "admin_tula"),
+            ],
+        )
+        .await;
+    assert_status(&response, StatusCode::OK);
+    let branch_id = json_body(response).await["branch_id"].as_i64().unwrap();
+
+    let (vat_rate, max_bonus_share): (BigDecimal, BigDecimal) = sqlx::query_as(
+        "SELECT vat_rate, max_bonus_share FROM moto_auto.branch WHERE branch_id = $1",
+    )
+    .bind(branch_id as i32)
+    .fetch_one(&app.db)
+    .await
+    .unwrap();
+    assert_eq!(vat_rate, BigDecimal::from(20));
+    assert_eq!(max_bonus_share, BigDecimal::from(50));
+}
+
+pub async fn give_bonus_points(app: &TestApp, client_id: i32, points: i32) {
+    adjust_bonus_points(
+        &mut app.conn().await,
+        client_id,
+        BigDecimal::from(points),
+        "Welcome bonus",
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn discounts_and_vat_make_up_the_breakdown() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_breakdown", "master", 1).await;
+    app.user("manager_breakdown", "manager", 1).await;
+    set_vat(&app, 20).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let order_id = order.order_id.unwrap();
+    let (order_service, _) = order_lines(&mut app.conn().await, order_id).await;
+    let cookie = app
+        .login("manager_breakdown", "manager_breakdown")
+        .await
+        .unwrap();
+
+    let response = app
+        .post_form(
+            "/api/v1/manager/discount_line",
+            Some(&cookie),
+            &[
+                (
+                    "order_service_id",
+                    &order_service.order_service_id.unwrap().to_string(),
+                ),
+                ("discount_type", "percent"),
+                ("discount_value", "10"),
+                ("discount_reason", "Loyal customer"),
+            ],
+        )
+        .await;
+    assert_status(&response, StatusCode::OK);
+    let response = app
+        .post_form(
+            "/api/v1/manager/price_order",
+            Some(&cookie),
+            &[
+                ("order_id", &order_id.to_string()),
+                ("discount_type", "fixed"),
+                ("discount_value", "100"),
+                ("discount_reason", "Scratched tank"),
+            ],
+        )
+        .await;
+    assert_status(&response, StatusCode::OK);
+
+    let order = get_order_by_id(&mut app.conn().await, order_id)
+        .await
+        .unwrap();
+    assert_eq!(order.vat_rate, Some(BigDecimal::from(20)));
+    assert_eq!(order.subtotal, Some(BigDecimal::from(1500)));
+    assert_eq!(order.discount_amount, Some(BigDecimal::from(200)));
+    assert_eq!(order.tax_amount, Some(BigDecimal::from(260)));
+    assert_eq!(order.total_amount, Some(BigDecimal::from(1560)));
+}
+
+#[tokio::test]
+async fn discounts_need_a_reason() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_reason", "master", 1).await;
+    app.user("manager_reason", "manager", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let cookie = app.login("manager_reason", "manager_reason").await.unwrap();
+
+    let response = app
+        .post_form(
+            "/api/v1/manager/price_order",
+            Some(&cookie),
+            &[
+                ("order_id", &order.order_id.unwrap().to_string()),
+                ("discount_type", "percent"),
+                ("discount_value", "50"),
+            ],
+        )
+        .await;
+
+    assert_status(&response, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn only_orders_in_progress_are_repriced() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_reprice", "master", 1).await;
+    app.user("manager_reprice", "manager", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let order_id = order.order_id.unwrap();
+    let (order_service, part) = order_lines(&mut app.conn().await, order_id).await;
+    complete(&app, "master_reprice", order_id).await;
+    let cookie = app.login("manager_reprice", "manager_reprice").await.unwrap();
+
+    let response = app
+        .post_form(
+            "/api/v1/manager/price_order",
+            Some(&cookie),
+            &[
+                ("order_id", &order_id.to_string()),
+                ("discount_type", "fixed"),
+                ("discount_value", "100"),
+                ("discount_reason", "Asked after pickup"),
+            ],
+        )
+        .await;
+    assert_status(&response, StatusCode::BAD_REQUEST);
+    let response = app
+        .post_form(
+            "/api/v1/manager/discount_line",
+            Some(&cookie),
+            &[
+                (
+                    "order_service_id",
+                    &order_service.order_service_id.unwrap().to_string(),
+                ),
+                ("discount_type", "percent"),
+                ("discount_value", "10"),
+                ("discount_reason", "Asked after pickup"),
+            ],
+        )
+        .await;
+    assert_status(&response, StatusCode::BAD_REQUEST);
+    let response = app
+        .post_form(
+            "/api/v1/manager/discount_line",
+            Some(&cookie),
+            &[
+                (
+                    "order_service_part_id",
+                    &part.order_service_part_id.unwrap().to_string(),
+                ),
+                ("discount_type", "percent"),
+                ("discount_value", "10"),
+                ("discount_reason", "Asked after pickup"),
+            ],
+        )
+        .await;
+    assert_status(&response, StatusCode::BAD_REQUEST);
+
+    let order = get_order_by_id(&mut app.conn().await, order_id)
+        .await
+        .unwrap();
+    assert_eq!(order.total_amount, Some(BigDecimal::from(1500)));
+}
+
+#[tokio::test]
+async fn bonus_redemption_is_capped_by_branch_share() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_redeem", "master", 1).await;
+    app.user("manager_redeem", "manager", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    give_bonus_points(&app, order.client_id, 1000).await;
+    let cookie = app.login("manager_redeem", "manager_redeem").await.unwrap();
+
+    let response = app
+        .post_form(
+            "/api/v1/manager/price_order",
+            Some(&cookie),
+            &[
+                ("order_id", &order.order_id.unwrap().to_string()),
+                ("bonus_points_used", "1000"),
+            ],
+        )
+        .await;
+    assert_status(&response, StatusCode::OK);
+
+    let mut conn = app.conn().await;
+    let order = get_order_by_id(&mut conn, order.order_id.unwrap())
+        .await
+        .unwrap();
+    let client = get_client_by_id(&mut conn, order.client_id).await.unwrap();
+    assert_eq!(order.bonus_points_used, Some(BigDecimal::from(450)));
+    assert_eq!(order.total_amount, Some(BigDecimal::from(1050)));
+    assert_eq!(client.bonus_points, Some(BigDecimal::from(550)));
+}
+
+#[tokio::test]
+async fn clients_cannot_redeem_more_points_than_they_have() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_overdraw", "master", 1).await;
+    app.user("manager_overdraw", "manager", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    give_bonus_points(&app, order.client_id, 50).await;
+    let cookie = app
+        .login("manager_overdraw", "manager_overdraw")
+        .await
+        .unwrap();
+
+    let response = app
+        .post_form(
+            "/api/v1/manager/price_order",
+            Some(&cookie),
+            &[
+                ("order_id", &order.order_id.unwrap().to_string()),
+                ("bonus_points_used", "100"),
+            ],
+        )
+        .await;
+
+    assert_status(&response, StatusCode::BAD_REQUEST);
+    let order = get_order_by_id(&mut app.conn().await, order.order_id.unwrap())
+        .await
+        .unwrap();
+    assert_eq!(order.bonus_points_used, Some(BigDecimal::from(0)));
+    assert_eq!(order.total_amount, Some(BigDecimal::from(1500)));
+}
+
+#[tokio::test]
+async fn preview_breakdown_matches_the_priced_order() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_preview_breakdown", "master", 1).await;
+    app.user("manager_preview_breakdown", "manager", 1).await;
+    set_vat(&app, 20).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let order_id = order.order_id.unwrap();
+    give_bonus_points(&app, order.client_id, 100).await;
+    let (order_service, part) = order_lines(&mut app.conn().await, order_id).await;
+    let cookie = app
+        .login("manager_preview_breakdown", "manager_preview_breakdown")
+        .await
+        .unwrap();
+
+    let preview = json_body(
+        app.post_json(
+            "/api/v1/manager/preview_total",
+            Some(&cookie),
+            &json!({
+                "service_ids": [order_service.service_id],
+                "parts": [{ "part_id": part.part_id, "quantity": part.quantity }],
+                "discount": { "kind": "percent", "value": "15" },
+                "bonus_points": "100",
+            }),
+        )
+        .await,
+    )
+    .await;
+    app.post_form(
+        "/api/v1/manager/price_order",
+        Some(&cookie),
+        &[
+            ("order_id", &order_id.to_string()),
+            ("discount_type", "percent"),
+            ("discount_value", "15"),
+            ("discount_reason", "Promotion"),
+            ("bonus_points_used", "100"),
+        ],
+    )
+    .await;
+
+    let order = get_order_by_id(&mut app.conn().await, order_id)
+        .await
+        .unwrap();
+    let field = |name: &str| Some(preview[name].as_str().unwrap().parse::<BigDecimal>().unwrap());
+    assert_eq!(field("subtotal"), order.subtotal);
+    assert_eq!(field("discount_amount"), order.discount_amount);
+    assert_eq!(field("tax_amount"), order.tax_amount);
+    assert_eq!(field("bonus_points_used"), order.bonus_points_used);
+    assert_eq!(field("total_amount"), order.total_amount);
+}
+
+/// Lowers the `regular` tier threshold so a single 1500 order upgrades a client.
+async fn lower_regular_threshold(app: &TestApp) {
+    sqlx::query("UPDATE moto_auto.loyalty_tier SET spend_threshold = 1000 WHERE name = 'regular'")
+        .execute(&app.db)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn cancelling_a_finished_order_reverts_spend_tier_and_bonus() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_cancel", "master", 1).await;
+    lower_regular_threshold(&app).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    complete(&app, "master_cancel", order.order_id.unwrap()).await;
+    let mut conn = app.conn().await;
+    let client = get_client_by_id(&mut conn, order.client_id).await.unwrap();
+    assert_eq!(client.status, "regular");
+    assert_eq!(client.total_spent, BigDecimal::from(1500));
+
+    update_order(&mut conn, None, None, Some("cancelled".to_string()), order.order_id.unwrap())
+        .await
+        .unwrap();
+
+    let client = get_client_by_id(&mut conn, order.client_id).await.unwrap();
+    assert_eq!(client.status, "casual");
+    assert_eq!(client.total_spent, BigDecimal::from(0));
+    assert_eq!(client.bonus_points, Some(BigDecimal::from(0)));
+}
+
+#[tokio::test]
+async fn only_finished_orders_count_towards_spend() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_spend", "master", 1).await;
+    let finished = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    complete(&app, "master_spend", finished.order_id.unwrap()).await;
+    let mut conn = app.conn().await;
+    create_order(
+        &mut conn,
+        Order {
+            client_id: finished.client_id,
+            branch_id: 1,
+            master_id: master.user_id.unwrap(),
+            order_date: chrono::Utc::now(),
+            total_amount: Some(BigDecimal::from(700)),
+            status: "processing".to_string(),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    let client = get_client_by_id(&mut conn, finished.client_id).await.unwrap();
+    assert_eq!(client.total_spent, BigDecimal::from(1500));
+
+    delete_order(&mut conn, finished.order_id.unwrap()).await.unwrap();
+
+    let client = get_client_by_id(&mut conn, finished.client_id).await.unwrap();
+    assert_eq!(client.total_spent, BigDecimal::from(0));
+}
+
+#[tokio::test]
+async fn refund_reverses_accrued_and_returns_redeemed_points() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_refund", "master", 1).await;
+    app.user("manager_refund", "manager", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let order_id = order.order_id.unwrap().to_string();
+    give_bonus_points(&app, order.client_id, 100).await;
+    let cookie = app.login("manager_refund", "manager_refund").await.unwrap();
+    let response = app
+        .post_form(
+            "/api/v1/manager/price_order",
+            Some(&cookie),
+            &[("order_id", &order_id), ("bonus_points_used", "100")],
+        )
+        .await;
+    assert_status(&response, StatusCode::OK);
+    let response = app
+        .post_form(
+            "/api/v1/manager/refund_order",
+            Some(&cookie),
+            &[("order_id", &order_id), ("reason", "Not finished yet")],
+        )
+        .await;
+    assert_status(&response, StatusCode::BAD_REQUEST);
+    complete(&app, "master_refund", order.order_id.unwrap()).await;
+    let client = get_client_by_id(&mut app.conn().await, order.client_id)
+        .await
+        .unwrap();
+    assert_eq!(client.bonus_points, Some(BigDecimal::from(140)));
+
+    let response = app
+        .post_form(
+            "/api/v1/manager/refund_order",
+            Some(&cookie),
+            &[("order_id", &order_id), ("reason", "")],
+        )
+        .await;
+    assert_status(&response, StatusCode::BAD_REQUEST);
+    let response = app
+        .post_form(
+            "/api/v1/manager/refund_order",
+            Some(&cookie),
+            &[("order_id", &order_id), ("reason", "Chain snapped a day later")],
+        )
+        .await;
+    assert_status(&response, StatusCode::OK);
+    assert_eq!(json_body(response).await["status"], "refunded");
+
+    let client = get_client_by_id(&mut app.conn().await, order.client_id)
+        .await
+        .unwrap();
+    assert_eq!(client.bonus_points, Some(BigDecimal::from(100)));
+    assert_eq!(client.total_spent, BigDecimal::from(0));
+    let kinds: Vec<String> = sqlx::query_scalar(
+        "SELECT kind FROM moto_auto.bonus_transaction WHERE order_id = $1 ORDER BY transaction_id",
+    )
+    .bind(order.order_id.unwrap())
+    .fetch_all(&app.db)
+    .await
+    .unwrap();
+    assert_eq!(kinds, ["redemption", "accrual", "reversal", "redemption"]);
+}
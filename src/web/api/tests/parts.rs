@@ -0,0 +1,284 @@
+use axum::http::StatusCode;
+use bigdecimal::BigDecimal;
+
+use crate::database::{
+    part_compatibility::add_part_compatibility,
+    spare_part::{create_spare_part, link_part_substitute},
+    spare_part_branch::create_spare_part_branch,
+    vehicle::create_vehicle,
+};
+use crate::models::{Order, PartCompatibility, SparePart, SparePartBranch, Vehicle};
+
+use super::harness::{assert_status, body_string, json_body, post, TestApp};
+use super::orders::order_with_lines;
+
+fn part(name: &str, sku: &str, oem_number: Option<&str>, manufacturer: &str) -> SparePart {
+    SparePart {
+        part_id: None,
+        part_name: name.to_string(),
+        description: String::new(),
+        sku: Some(sku.to_string()),
+        oem_number: oem_number.map(String::from),
+        manufacturer: Some(manufacturer.to_string()),
+        unit: "set".to_string(),
+        deleted_at: None,
+    }
+}
+
+fn fits(
+    part_id: i32,
+    make: &str,
+    model: Option<&str>,
+    years: (Option<i32>, Option<i32>),
+) -> PartCompatibility {
+    PartCompatibility {
+        compatibility_id: None,
+        part_id,
+        make: make.to_string(),
+        model: model.map(String::from),
+        year_from: years.0,
+        year_to: years.1,
+    }
+}
+
+/// Order on a 2015 Honda CBR600RR with two brake pad sets in the catalogue: the original
+/// EBC pads listed for 2007-2012 only and Brembo pads for any Honda, linked as substitutes.
+/// Returns the order and the ids of the EBC and Brembo pads.
+async fn order_on_a_bike(app: &TestApp, master_id: i32) -> (Order, i32, i32) {
+    let order = order_with_lines(app, "casual", master_id).await;
+    let mut conn = app.conn().await;
+    let vehicle = create_vehicle(
+        &mut conn,
+        Vehicle {
+            vehicle_id: None,
+            client_id: order.client_id,
+            make: "Honda".to_string(),
+            model: "CBR600RR".to_string(),
+            year: Some(2015),
+            vin: None,
+            plate: None,
+            mileage: 0,
+            deleted_at: None,
+        },
+    )
+    .await
+    .unwrap();
+    sqlx::query("UPDATE moto_auto.orders SET vehicle_id = $1 WHERE order_id = $2")
+        .bind(vehicle.vehicle_id)
+        .bind(order.order_id)
+        .execute(&mut conn)
+        .await
+        .unwrap();
+    let ebc = create_spare_part(
+        &mut conn,
+        part("Brake pads", "EBC-FA390HH", Some("06455-MEL-D21"), "EBC"),
+    )
+    .await
+    .unwrap()
+    .part_id
+    .unwrap();
+    let brembo = create_spare_part(
+        &mut conn,
+        part("Brake pads", "BRM-07HO30SA", None, "Brembo"),
+    )
+    .await
+    .unwrap()
+    .part_id
+    .unwrap();
+    add_part_compatibility(
+        &mut conn,
+        fits(ebc, "Honda", Some("CBR600RR"), (Some(2007), Some(2012))),
+    )
+    .await
+    .unwrap();
+    add_part_compatibility(&mut conn, fits(brembo, "honda", None, (None, None)))
+        .await
+        .unwrap();
+    link_part_substitute(&mut conn, brembo, ebc).await.unwrap();
+    create_spare_part_branch(
+        &mut conn,
+        SparePartBranch {
+            spare_part_branch_id: None,
+            part_id: brembo,
+            branch_id: 1,
+            stock_quantity: 3,
+            price: BigDecimal::from(4200),
+            reorder_point: None,
+            reorder_quantity: None,
+        },
+    )
+    .await
+    .unwrap();
+    (order, ebc, brembo)
+}
+
+#[tokio::test]
+async fn master_finds_parts_that_fit_the_bike() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_parts", "master", 1).await;
+    app.user("master_parts_other", "master", 1).await;
+    let (order, ebc, brembo) = order_on_a_bike(&app, master.user_id.unwrap()).await;
+    let order_id = order.order_id.unwrap();
+    let cookie = app.login("master_parts", "master_parts").await.unwrap();
+
+    // The OEM number matches however it is written; the substitute that fits comes first.
+    let uri = format!(
+        "/api/v1/master/find_parts?q=06455meld21&order_id={}",
+        order_id
+    );
+    let response = app.get(&uri, Some(&cookie)).await;
+    assert_status(&response, StatusCode::OK);
+    let parts = json_body(response).await;
+    let parts = parts.as_array().unwrap();
+    assert_eq!(parts.len(), 2);
+    assert_eq!(parts[0]["part_id"], brembo);
+    assert_eq!(parts[0]["fits"], true);
+    assert_eq!(parts[0]["substitute_for"], ebc);
+    assert_eq!(parts[0]["stock_quantity"], 3);
+    assert_eq!(parts[1]["part_id"], ebc);
+    assert_eq!(parts[1]["fits"], false);
+    assert!(parts[1]["price"].is_null());
+
+    // Parts without compatibility entries are listed with the fit unknown.
+    let uri = format!("/api/v1/master/find_parts?q=chain&order_id={}", order_id);
+    let parts = json_body(app.get(&uri, Some(&cookie)).await).await;
+    assert_eq!(parts[0]["part_name"], "Chain");
+    assert!(parts[0]["fits"].is_null());
+
+    let uri = format!("/views/find_parts?q=brembo&order_id={}", order_id);
+    let body = body_string(app.get(&uri, Some(&cookie)).await).await;
+    assert!(body.contains("BRM-07HO30SA"));
+    assert!(body.contains("4200"));
+    let uri = format!("/views/find_parts?q=EBC-FA390&order_id={}", order_id);
+    let body = body_string(app.get(&uri, Some(&cookie)).await).await;
+    assert!(body.contains("replaces Brake pads"));
+    assert!(body.contains("does not fit"));
+
+    let cookie = app
+        .login("master_parts_other", "master_parts_other")
+        .await
+        .unwrap();
+    let uri = format!("/api/v1/master/find_parts?q=brake&order_id={}", order_id);
+    assert_status(&app.get(&uri, Some(&cookie)).await, StatusCode::NOT_FOUND);
+}
+
+/// Id of the first part the global search finds.
+async fn find_part(app: &TestApp, cookie: &str, q: &str) -> i64 {
+    let results = json_body(
+        app.get(&format!("/api/v1/search?q={}", q), Some(cookie))
+            .await,
+    )
+    .await;
+    results
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|r| r["kind"] == "part")
+        .unwrap()["id"]
+        .as_i64()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn admin_keeps_part_numbers_fitment_and_substitutes() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_catalog", "master", 1).await;
+    app.user("admin_catalog", "admin", 1).await;
+    let (order, ebc, brembo) = order_on_a_bike(&app, master.user_id.unwrap()).await;
+    let cookie = app.login("admin_catalog", "admin_catalog").await.unwrap();
+    let chain = find_part(&app, &cookie, "chain").await.to_string();
+    let details = |sku, unit| {
+        [
+            ("part_id", chain.as_str()),
+            ("sku", sku),
+            ("oem_number", " 40530-MEL-003 "),
+            ("manufacturer", "DID"),
+            ("unit", unit),
+        ]
+    };
+
+    let response = app
+        .post_form(
+            "/api/v1/admin/update_part_details",
+            Some(&cookie),
+            &details("DID-520VX3", "pcs"),
+        )
+        .await;
+    assert_status(&response, StatusCode::OK);
+    let updated = json_body(response).await;
+    assert_eq!(updated["oem_number"], "40530-MEL-003");
+    assert_eq!(updated["manufacturer"], "DID");
+    let uri = "/api/v1/admin/update_part_details";
+    assert_eq!(
+        post(&app, &cookie, uri, &details("DID-520VX3", "bucket")).await,
+        StatusCode::BAD_REQUEST
+    );
+    // SKUs are unique across the catalogue.
+    assert_eq!(
+        post(&app, &cookie, uri, &details("BRM-07HO30SA", "pcs")).await,
+        StatusCode::BAD_REQUEST
+    );
+
+    let compatibility = |from, to| {
+        [
+            ("part_id", chain.as_str()),
+            ("make", "Honda"),
+            ("model", ""),
+            ("year_from", from),
+            ("year_to", to),
+        ]
+    };
+    let uri = "/api/v1/admin/add_part_compatibility";
+    assert_eq!(
+        post(&app, &cookie, uri, &compatibility("2016", "2012")).await,
+        StatusCode::BAD_REQUEST
+    );
+    let response = app
+        .post_form(uri, Some(&cookie), &compatibility("2013", ""))
+        .await;
+    assert_status(&response, StatusCode::OK);
+    assert!(json_body(response).await["model"].is_null());
+
+    let (ebc, brembo) = (ebc.to_string(), brembo.to_string());
+    assert_eq!(
+        post(
+            &app,
+            &cookie,
+            "/api/v1/admin/link_part_substitute",
+            &[("part_id", &ebc), ("substitute_id", &ebc)],
+        )
+        .await,
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        post(
+            &app,
+            &cookie,
+            "/api/v1/admin/unlink_part_substitute",
+            &[("part_id", &ebc), ("substitute_id", &brembo)],
+        )
+        .await,
+        StatusCode::OK
+    );
+
+    let uri = format!("/views/part_fitment?part_id={}", ebc);
+    let body = body_string(app.get(&uri, Some(&cookie)).await).await;
+    assert!(body.contains("CBR600RR"));
+    assert!(!body.contains("BRM-07HO30SA"));
+    let body = body_string(app.get("/admin/parts", Some(&cookie)).await).await;
+    assert!(body.contains("40530-MEL-003"));
+
+    // The chain now fits the bike, and the global search finds it by its OEM number.
+    let cookie = app.login("master_catalog", "master_catalog").await.unwrap();
+    let uri = format!(
+        "/api/v1/master/find_parts?q=chain&order_id={}",
+        order.order_id.unwrap()
+    );
+    let parts = json_body(app.get(&uri, Some(&cookie)).await).await;
+    assert_eq!(parts[0]["fits"], true);
+    assert_eq!(parts[0]["unit"], "pcs");
+    assert_eq!(
+        find_part(&app, &cookie, "40530MEL").await.to_string(),
+        chain
+    );
+}
@@ -0,0 +1,394 @@
+use bigdecimal::BigDecimal;
+
+use crate::database::{
+    client::get_client_by_id, order_service_part::update_order_service_part,
+    orders::get_order_by_id, payment::get_order_payments,
+};
+use axum::http::StatusCode;
+
+use super::harness::{assert_status, decimal, json_body, TestApp};
+use super::orders::{complete, give_bonus_points, order_lines, order_with_lines};
+
+async fn close(app: &TestApp, cookie: &str, path: &str, order_id: i32, reason: &str) -> StatusCode {
+    app.post_form(
+        path,
+        Some(cookie),
+        &[
+            ("order_id", &order_id.to_string()),
+            ("override_reason", reason),
+        ],
+    )
+    .await
+    .status()
+}
+
+#[tokio::test]
+async fn partial_payments_reduce_the_outstanding_balance() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_payments", "master", 1).await;
+    let manager = app.user("manager_payments", "manager", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let order_id = order.order_id.unwrap();
+    let cookie = app
+        .login("manager_payments", "manager_payments")
+        .await
+        .unwrap();
+
+    // The drawer is closed until a shift is opened.
+    assert_eq!(
+        app.pay(&cookie, order_id, "500", "cash").await,
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(app.open_shift(&cookie).await, StatusCode::OK);
+    assert_eq!(
+        app.pay(&cookie, order_id, "500", "cash").await,
+        StatusCode::OK
+    );
+    let uri = format!("/api/v1/manager/order_payments?order_id={}", order_id);
+    let balance = json_body(app.get(&uri, Some(&cookie)).await).await;
+    assert_eq!(decimal(&balance["outstanding"]), BigDecimal::from(1000));
+    assert_eq!(
+        decimal(&balance["client_outstanding"]),
+        BigDecimal::from(1000)
+    );
+
+    assert_eq!(
+        app.pay(&cookie, order_id, "1000.01", "card").await,
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        app.pay(&cookie, order_id, "0", "card").await,
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        app.pay(&cookie, order_id, "10", "cheque").await,
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        app.pay(&cookie, order_id, "1000", "card").await,
+        StatusCode::OK
+    );
+
+    let balance = json_body(app.get(&uri, Some(&cookie)).await).await;
+    assert_eq!(decimal(&balance["outstanding"]), BigDecimal::from(0));
+    assert_eq!(decimal(&balance["client_outstanding"]), BigDecimal::from(0));
+    let payments = balance["payments"].as_array().unwrap();
+    assert_eq!(payments.len(), 2);
+    assert_eq!(payments[0]["method"], "cash");
+    assert_eq!(payments[0]["cashier_id"], manager.user_id.unwrap());
+}
+
+#[tokio::test]
+async fn managers_take_payments_only_for_their_branch() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_payment_branch", "master", 1).await;
+    app.user("manager_payment_home", "manager", 1).await;
+    app.user("manager_payment_branch", "manager", 2).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let home = app
+        .login("manager_payment_home", "manager_payment_home")
+        .await
+        .unwrap();
+    assert_eq!(app.open_shift(&home).await, StatusCode::OK);
+    let cookie = app
+        .login("manager_payment_branch", "manager_payment_branch")
+        .await
+        .unwrap();
+    assert_eq!(app.open_shift(&cookie).await, StatusCode::OK);
+
+    assert_eq!(
+        app.pay(&cookie, order.order_id.unwrap(), "100", "cash")
+            .await,
+        StatusCode::BAD_REQUEST
+    );
+    assert_status(
+        &app.get(
+            &format!(
+                "/api/v1/manager/order_payments?order_id={}",
+                order.order_id.unwrap()
+            ),
+            Some(&cookie),
+        )
+        .await,
+        StatusCode::NOT_FOUND,
+    );
+}
+
+#[tokio::test]
+async fn bonus_payments_spend_client_points() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_bonus_payment", "master", 1).await;
+    app.user("manager_bonus_payment", "manager", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let order_id = order.order_id.unwrap();
+    give_bonus_points(&app, order.client_id, 200).await;
+    let cookie = app
+        .login("manager_bonus_payment", "manager_bonus_payment")
+        .await
+        .unwrap();
+    assert_eq!(app.open_shift(&cookie).await, StatusCode::OK);
+
+    assert_eq!(
+        app.pay(&cookie, order_id, "300", "bonus").await,
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        app.pay(&cookie, order_id, "200", "bonus").await,
+        StatusCode::OK
+    );
+
+    let client = get_client_by_id(&mut app.conn().await, order.client_id)
+        .await
+        .unwrap();
+    assert_eq!(client.bonus_points, Some(BigDecimal::from(0)));
+    let redeemed: BigDecimal = sqlx::query_scalar(
+        "SELECT SUM(points) FROM moto_auto.bonus_transaction WHERE order_id = $1 AND kind = 'redemption'",
+    )
+    .bind(order_id)
+    .fetch_one(&app.db)
+    .await
+    .unwrap();
+    assert_eq!(redeemed, BigDecimal::from(-200));
+}
+
+#[tokio::test]
+async fn unpaid_orders_are_handed_over_only_with_a_manager_override() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_pickup", "master", 1).await;
+    let manager = app.user("manager_pickup", "manager", 1).await;
+    let unpaid = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let paid = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let master_cookie = app.login("master_pickup", "master_pickup").await.unwrap();
+    let manager_cookie = app.login("manager_pickup", "manager_pickup").await.unwrap();
+    assert_eq!(app.open_shift(&manager_cookie).await, StatusCode::OK);
+
+    // Not finished yet.
+    assert_eq!(
+        app.pay(&manager_cookie, paid.order_id.unwrap(), "1500", "transfer")
+            .await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        close(
+            &app,
+            &master_cookie,
+            "/api/v1/master/close_order",
+            paid.order_id.unwrap(),
+            ""
+        )
+        .await,
+        StatusCode::BAD_REQUEST
+    );
+    complete(&app, "master_pickup", paid.order_id.unwrap()).await;
+    complete(&app, "master_pickup", unpaid.order_id.unwrap()).await;
+
+    assert_eq!(
+        close(
+            &app,
+            &master_cookie,
+            "/api/v1/master/close_order",
+            paid.order_id.unwrap(),
+            ""
+        )
+        .await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        close(
+            &app,
+            &master_cookie,
+            "/api/v1/master/close_order",
+            unpaid.order_id.unwrap(),
+            ""
+        )
+        .await,
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        close(
+            &app,
+            &master_cookie,
+            "/api/v1/master/close_order",
+            unpaid.order_id.unwrap(),
+            "Regular client"
+        )
+        .await,
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        close(
+            &app,
+            &manager_cookie,
+            "/api/v1/manager/close_order",
+            unpaid.order_id.unwrap(),
+            ""
+        )
+        .await,
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        close(
+            &app,
+            &manager_cookie,
+            "/api/v1/manager/close_order",
+            unpaid.order_id.unwrap(),
+            "Pays on Friday"
+        )
+        .await,
+        StatusCode::OK
+    );
+
+    let mut conn = app.conn().await;
+    let paid = get_order_by_id(&mut conn, paid.order_id.unwrap())
+        .await
+        .unwrap();
+    let unpaid = get_order_by_id(&mut conn, unpaid.order_id.unwrap())
+        .await
+        .unwrap();
+    assert!(paid.picked_up_at.is_some());
+    assert_eq!(paid.pickup_override_reason, None);
+    assert!(unpaid.picked_up_at.is_some());
+    assert_eq!(
+        unpaid.pickup_override_reason.as_deref(),
+        Some("Pays on Friday")
+    );
+    assert_eq!(unpaid.pickup_override_by, manager.user_id);
+}
+
+#[tokio::test]
+async fn paid_orders_cannot_be_repriced() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_paid_pricing", "master", 1).await;
+    app.user("manager_paid_pricing", "manager", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let order_id = order.order_id.unwrap();
+    let cookie = app
+        .login("manager_paid_pricing", "manager_paid_pricing")
+        .await
+        .unwrap();
+    assert_eq!(app.open_shift(&cookie).await, StatusCode::OK);
+    assert_eq!(
+        app.pay(&cookie, order_id, "1500", "card").await,
+        StatusCode::OK
+    );
+
+    let response = app
+        .post_form(
+            "/api/v1/manager/price_order",
+            Some(&cookie),
+            &[
+                ("order_id", &order_id.to_string()),
+                ("discount_type", "fixed"),
+                ("discount_value", "300"),
+                ("discount_reason", "Paid in full"),
+            ],
+        )
+        .await;
+    assert_status(&response, StatusCode::BAD_REQUEST);
+    let mut conn = app.conn().await;
+    let (_, part) = order_lines(&mut conn, order_id).await;
+    assert!(
+        update_order_service_part(&mut conn, Some(1), part.order_service_part_id.unwrap())
+            .await
+            .is_err()
+    );
+
+    let order = get_order_by_id(&mut conn, order_id).await.unwrap();
+    assert_eq!(order.total_amount, Some(BigDecimal::from(1500)));
+}
+
+#[tokio::test]
+async fn overpaid_orders_do_not_offset_other_debts() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_overpaid", "master", 1).await;
+    app.user("manager_overpaid", "manager", 1).await;
+    let paid = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let unpaid = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    sqlx::query("UPDATE moto_auto.orders SET client_id = $1 WHERE order_id = $2")
+        .bind(paid.client_id)
+        .bind(unpaid.order_id.unwrap())
+        .execute(&app.db)
+        .await
+        .unwrap();
+    let cookie = app
+        .login("manager_overpaid", "manager_overpaid")
+        .await
+        .unwrap();
+    assert_eq!(app.open_shift(&cookie).await, StatusCode::OK);
+    assert_eq!(
+        app.pay(&cookie, paid.order_id.unwrap(), "1500", "cash")
+            .await,
+        StatusCode::OK
+    );
+    // A manual correction of the total leaves the order overpaid.
+    sqlx::query("UPDATE moto_auto.orders SET total_amount = 1000 WHERE order_id = $1")
+        .bind(paid.order_id.unwrap())
+        .execute(&app.db)
+        .await
+        .unwrap();
+
+    let uri = format!(
+        "/api/v1/manager/order_payments?order_id={}",
+        paid.order_id.unwrap()
+    );
+    let balance = json_body(app.get(&uri, Some(&cookie)).await).await;
+    assert_eq!(decimal(&balance["outstanding"]), BigDecimal::from(0));
+    assert_eq!(
+        decimal(&balance["client_outstanding"]),
+        BigDecimal::from(1500)
+    );
+}
+
+#[tokio::test]
+async fn refunds_return_payments_by_the_same_methods() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_refund_payments", "master", 1).await;
+    app.user("manager_refund_payments", "manager", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let order_id = order.order_id.unwrap();
+    let cookie = app
+        .login("manager_refund_payments", "manager_refund_payments")
+        .await
+        .unwrap();
+    assert_eq!(app.open_shift(&cookie).await, StatusCode::OK);
+    for (amount, method) in [("500", "cash"), ("300", "card"), ("700", "card")] {
+        assert_eq!(
+            app.pay(&cookie, order_id, amount, method).await,
+            StatusCode::OK
+        );
+    }
+    complete(&app, "master_refund_payments", order_id).await;
+
+    let response = app
+        .post_form(
+            "/api/v1/manager/refund_order",
+            Some(&cookie),
+            &[
+                ("order_id", &order_id.to_string()),
+                ("reason", "Wrong chain fitted"),
+            ],
+        )
+        .await;
+    assert_status(&response, StatusCode::OK);
+
+    let payments = get_order_payments(&mut app.conn().await, order_id)
+        .await
+        .unwrap();
+    let refunds: Vec<_> = payments
+        .iter()
+        .filter(|p| p.amount < BigDecimal::from(0))
+        .map(|p| (p.method.as_str(), p.amount.clone()))
+        .collect();
+    assert_eq!(refunds.len(), 2);
+    assert!(refunds.contains(&("cash", BigDecimal::from(-500))));
+    assert!(refunds.contains(&("card", BigDecimal::from(-1000))));
+    let uri = format!("/api/v1/manager/order_payments?order_id={}", order_id);
+    let balance = json_body(app.get(&uri, Some(&cookie)).await).await;
+    assert_eq!(decimal(&balance["outstanding"]), BigDecimal::from(0));
+    assert_eq!(decimal(&balance["client_outstanding"]), BigDecimal::from(0));
+    // Refunds cannot be taken by hand.
+    assert_eq!(
+        app.pay(&cookie, order_id, "-100", "cash").await,
+        StatusCode::BAD_REQUEST
+    );
+}
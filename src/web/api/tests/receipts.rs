@@ -0,0 +1,100 @@
+use bigdecimal::BigDecimal;
+
+use crate::database::receipt::{get_order_receipt, get_receipt_lines};
+use axum::http::{header, StatusCode};
+
+use super::harness::{assert_status, body_string, TestApp};
+use super::orders::{complete, order_with_lines};
+
+#[tokio::test]
+async fn finished_orders_get_sequential_receipts_per_branch() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_receipts", "master", 1).await;
+    let first = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let second = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+
+    complete(&app, "master_receipts", first.order_id.unwrap()).await;
+    complete(&app, "master_receipts", second.order_id.unwrap()).await;
+
+    let mut conn = app.conn().await;
+    let first = get_order_receipt(&mut conn, first.order_id.unwrap()).await.unwrap();
+    let second = get_order_receipt(&mut conn, second.order_id.unwrap()).await.unwrap();
+    assert_eq!(first.branch_id, 1);
+    assert_eq!(second.receipt_number, first.receipt_number + 1);
+    assert_eq!(second.total_amount, BigDecimal::from(1500));
+
+    let lines = get_receipt_lines(&mut conn, second.receipt_id).await.unwrap();
+    assert_eq!(lines[0].item_type, "service");
+    assert_eq!(lines[0].name, "Chain replacement");
+    let amount: BigDecimal = lines.iter().map(|l| l.amount.clone()).sum();
+    assert_eq!(amount, second.subtotal);
+}
+
+#[tokio::test]
+async fn receipt_keeps_what_was_sold() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_receipt_snapshot", "master", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    complete(&app, "master_receipt_snapshot", order.order_id.unwrap()).await;
+
+    sqlx::query("UPDATE moto_auto.branch SET address = 'Moved away' WHERE branch_id = 1")
+        .execute(&app.db)
+        .await
+        .unwrap();
+
+    let receipt = get_order_receipt(&mut app.conn().await, order.order_id.unwrap())
+        .await
+        .unwrap();
+    assert_ne!(receipt.branch_address, "Moved away");
+}
+
+#[tokio::test]
+async fn managers_print_and_download_receipts() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_receipt_print", "master", 1).await;
+    app.user("manager_receipt_print", "manager", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let order_id = order.order_id.unwrap();
+    complete(&app, "master_receipt_print", order_id).await;
+    let receipt = get_order_receipt(&mut app.conn().await, order_id).await.unwrap();
+    let cookie = app
+        .login("manager_receipt_print", "manager_receipt_print")
+        .await
+        .unwrap();
+
+    let response = app
+        .get(&format!("/views/receipt?order_id={}", order_id), Some(&cookie))
+        .await;
+    assert_status(&response, StatusCode::OK);
+    let html = body_string(response).await;
+    assert!(html.contains(&receipt.branch_address));
+    assert!(html.contains("Chain replacement"));
+
+    let response = app
+        .get(&format!("/views/receipt.pdf?order_id={}", order_id), Some(&cookie))
+        .await;
+    assert_status(&response, StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "application/pdf");
+    assert_eq!(
+        response.headers()[header::CONTENT_DISPOSITION],
+        format!(
+            "attachment; filename=\"receipt-1-{}.pdf\"",
+            receipt.receipt_number
+        )
+        .as_str()
+    );
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert!(bytes.starts_with(b"%PDF"));
+
+    let cookie = app
+        .login("master_receipt_print", "master_receipt_print")
+        .await
+        .unwrap();
+    assert_status(
+        &app.get(&format!("/views/receipt.pdf?order_id={}", order_id), Some(&cookie))
+            .await,
+        StatusCode::FORBIDDEN,
+    );
+}
@@ -0,0 +1,285 @@
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use axum::http::StatusCode;
+use sqlx::PgConnection;
+
+use crate::database::{
+    order_service::get_order_service,
+    orders::{assign_order_vehicle, create_order, get_order_by_id},
+    reminder::create_maintenance_reminders,
+    service::set_service_intervals,
+    vehicle::create_vehicle,
+};
+use crate::models::{Order, Vehicle};
+use crate::notify::{deliver_outbox, Channel, Notification, Notifier, NotifyError};
+
+use super::harness::{assert_status, json_body, TestApp};
+use super::orders::{complete, order_with_lines};
+
+#[derive(Default)]
+pub struct RecordingNotifier {
+    pub sent: Mutex<Vec<Notification>>,
+}
+
+#[async_trait]
+impl Notifier for RecordingNotifier {
+    async fn send(&self, notification: &Notification) -> Result<(), NotifyError> {
+        self.sent.lock().unwrap().push(notification.clone());
+        Ok(())
+    }
+}
+
+impl RecordingNotifier {
+    /// Notifications sent so far with the given subject.
+    pub fn sent_with_subject(&self, subject: &str) -> Vec<Notification> {
+        self.sent
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|n| n.subject == subject)
+            .cloned()
+            .collect()
+    }
+}
+
+async fn remind(conn: &mut PgConnection) -> usize {
+    create_maintenance_reminders(conn).await.unwrap().len()
+}
+
+/// Finished order with a vehicle brought in at `intake_mileage`; returns the order and
+/// the id of its service.
+async fn serviced_vehicle(app: &TestApp, master: &str, intake_mileage: i32) -> (Order, i32) {
+    let user = app.user(master, "master", 1).await;
+    let order = order_with_lines(app, "casual", user.user_id.unwrap()).await;
+    let order_id = order.order_id.unwrap();
+    let mut conn = app.conn().await;
+    let vehicle = create_vehicle(
+        &mut conn,
+        Vehicle {
+            vehicle_id: None,
+            client_id: order.client_id,
+            make: "Yamaha".to_string(),
+            model: "MT-07".to_string(),
+            year: Some(2020),
+            vin: None,
+            plate: None,
+            mileage: 0,
+            deleted_at: None,
+        },
+    )
+    .await
+    .unwrap();
+    assign_order_vehicle(
+        &mut conn,
+        order_id,
+        vehicle.vehicle_id.unwrap(),
+        Some(intake_mileage),
+    )
+    .await
+    .unwrap();
+    assert_eq!(complete(app, master, order_id).await, StatusCode::OK);
+    sqlx::query("UPDATE moto_auto.client SET marketing_consent = TRUE WHERE client_id = $1")
+        .bind(order.client_id)
+        .execute(&mut conn)
+        .await
+        .unwrap();
+    let service_id = get_order_service(&mut conn, Some(order_id), None)
+        .await
+        .unwrap()[0]
+        .service_id;
+    (order, service_id)
+}
+
+async fn backdate_completion(conn: &mut PgConnection, order_id: i32, months: i32) {
+    sqlx::query(
+        "UPDATE moto_auto.orders
+         SET completion_date = completion_date - make_interval(months => $2)
+         WHERE order_id = $1",
+    )
+    .bind(order_id)
+    .bind(months)
+    .execute(conn)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn admins_set_service_intervals() {
+    let app = TestApp::spawn().await;
+    app.user("admin_intervals", "admin", 1).await;
+    app.user("manager_intervals", "manager", 1).await;
+    let (_, service_id) = serviced_vehicle(&app, "master_intervals", 1000).await;
+
+    let cookie = app
+        .login("admin_intervals", "admin_intervals")
+        .await
+        .unwrap();
+    let response = app
+        .post_form(
+            "/api/v1/admin/update_service_intervals",
+            Some(&cookie),
+            &[
+                ("service_id", &service_id.to_string()),
+                ("interval_months", "12"),
+                ("interval_km", ""),
+            ],
+        )
+        .await;
+    assert_status(&response, StatusCode::OK);
+    let service = json_body(response).await;
+    assert_eq!(service["interval_months"], 12);
+    assert!(service["interval_km"].is_null());
+
+    let response = app
+        .post_form(
+            "/api/v1/admin/update_service_intervals",
+            Some(&cookie),
+            &[
+                ("service_id", &service_id.to_string()),
+                ("interval_km", "0"),
+            ],
+        )
+        .await;
+    assert_status(&response, StatusCode::BAD_REQUEST);
+
+    let cookie = app
+        .login("manager_intervals", "manager_intervals")
+        .await
+        .unwrap();
+    let response = app
+        .post_form(
+            "/api/v1/admin/update_service_intervals",
+            Some(&cookie),
+            &[
+                ("service_id", &service_id.to_string()),
+                ("interval_months", "6"),
+            ],
+        )
+        .await;
+    assert_status(&response, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn reminders_come_due_by_time_once_per_service() {
+    let app = TestApp::spawn().await;
+    app.user("manager_reminders", "manager", 1).await;
+    let (order, service_id) = serviced_vehicle(&app, "master_reminders", 1000).await;
+    let mut conn = app.conn().await;
+    set_service_intervals(&mut conn, service_id, Some(12), None)
+        .await
+        .unwrap();
+    let notifier = RecordingNotifier::default();
+
+    assert_eq!(remind(&mut conn).await, 0);
+
+    backdate_completion(&mut conn, order.order_id.unwrap(), 13).await;
+    assert_eq!(remind(&mut conn).await, 1);
+    assert_eq!(remind(&mut conn).await, 0);
+    deliver_outbox(&mut conn, &notifier).await.unwrap();
+    let sent = notifier.sent_with_subject("Chain replacement is due");
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].channel, Channel::Sms);
+    assert_eq!(sent[0].recipient, "+70000000000");
+    assert!(sent[0].body.contains("Yamaha MT-07"));
+
+    let cookie = app
+        .login("manager_reminders", "manager_reminders")
+        .await
+        .unwrap();
+    let reminders = json_body(
+        app.get("/api/v1/manager/maintenance_reminders", Some(&cookie))
+            .await,
+    )
+    .await;
+    let reminders = reminders.as_array().unwrap();
+    assert_eq!(reminders.len(), 1);
+    assert_eq!(reminders[0]["client_id"], order.client_id);
+    assert!(!reminders[0]["notified_at"].is_null());
+    let reminder_id = reminders[0]["reminder_id"].as_i64().unwrap();
+
+    let dismissed = app
+        .post_form(
+            "/api/v1/manager/dismiss_reminder",
+            Some(&cookie),
+            &[("reminder_id", &reminder_id.to_string())],
+        )
+        .await;
+    assert_status(&dismissed, StatusCode::OK);
+    let reminders = json_body(
+        app.get("/api/v1/manager/maintenance_reminders", Some(&cookie))
+            .await,
+    )
+    .await;
+    assert!(reminders.as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn reminders_come_due_by_mileage() {
+    let app = TestApp::spawn().await;
+    let (order, service_id) = serviced_vehicle(&app, "master_mileage", 10000).await;
+    let mut conn = app.conn().await;
+    set_service_intervals(&mut conn, service_id, None, Some(5000))
+        .await
+        .unwrap();
+    let notifier = RecordingNotifier::default();
+    assert_eq!(remind(&mut conn).await, 0);
+
+    // The bike comes in for something else with the interval run out.
+    let later = create_order(
+        &mut conn,
+        Order {
+            client_id: order.client_id,
+            branch_id: 1,
+            master_id: order.master_id,
+            order_date: chrono::Utc::now(),
+            status: "processing".to_string(),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    let vehicle_id = get_order_by_id(&mut conn, order.order_id.unwrap())
+        .await
+        .unwrap()
+        .vehicle_id
+        .unwrap();
+    assign_order_vehicle(&mut conn, later.order_id.unwrap(), vehicle_id, Some(15500))
+        .await
+        .unwrap();
+
+    assert_eq!(remind(&mut conn).await, 1);
+    deliver_outbox(&mut conn, &notifier).await.unwrap();
+    let sent = notifier.sent_with_subject("Chain replacement is due");
+    assert!(sent[0].body.contains("at 15000 km"));
+}
+
+#[tokio::test]
+async fn reminders_need_marketing_consent() {
+    let app = TestApp::spawn().await;
+    let (order, service_id) = serviced_vehicle(&app, "master_marketing", 1000).await;
+    let mut conn = app.conn().await;
+    sqlx::query("UPDATE moto_auto.client SET marketing_consent = FALSE WHERE client_id = $1")
+        .bind(order.client_id)
+        .execute(&mut conn)
+        .await
+        .unwrap();
+    set_service_intervals(&mut conn, service_id, Some(12), None)
+        .await
+        .unwrap();
+    backdate_completion(&mut conn, order.order_id.unwrap(), 13).await;
+    assert_eq!(remind(&mut conn).await, 1);
+
+    let notifier = RecordingNotifier::default();
+    deliver_outbox(&mut conn, &notifier).await.unwrap();
+    assert!(notifier
+        .sent_with_subject("Chain replacement is due")
+        .is_empty());
+    let error: Option<String> = sqlx::query_scalar(
+        "SELECT last_error FROM moto_auto.outbox WHERE kind = 'maintenance_due'",
+    )
+    .fetch_one(&mut conn)
+    .await
+    .unwrap();
+    assert_eq!(error.as_deref(), Some("no consent"));
+}
@@ -0,0 +1,121 @@
+use bigdecimal::BigDecimal;
+use sqlx::PgConnection;
+
+use crate::database::{
+    begin_as,
+    cash_shift::open_cash_shift,
+    client::{create_client, delete_client, share_client},
+    orders::{delete_order, get_order_by_id},
+    payment::{create_payment, get_order_payments},
+    receipt::get_order_receipt,
+    retention::purge_archived,
+    spare_part::delete_spare_part,
+    stock::detect_low_stock,
+};
+
+use super::clients::client;
+use super::harness::TestApp;
+use super::orders::{complete, order_with_lines};
+use super::stock::stocked_part;
+
+/// Moves the archival of every archived row ten years back.
+async fn age_archive(conn: &mut PgConnection) {
+    for table in ["orders", "client", "service", "spare_part"] {
+        sqlx::query(&format!(
+            "UPDATE moto_auto.{} SET deleted_at = NOW() - INTERVAL '10 years'
+            WHERE deleted_at IS NOT NULL",
+            table
+        ))
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+    }
+}
+
+async fn count(conn: &mut PgConnection, query: &str) -> i64 {
+    sqlx::query_scalar(query).fetch_one(conn).await.unwrap()
+}
+
+#[tokio::test]
+async fn purge_removes_shared_clients_and_parts_on_purchase_orders() {
+    let app = TestApp::spawn().await;
+    let mut conn = app.conn().await;
+    let shared = create_client(&mut conn, client("Shared", "casual", 2))
+        .await
+        .unwrap()
+        .client_id
+        .unwrap();
+    share_client(&mut conn, shared, 1).await.unwrap();
+    let part_id = stocked_part(&mut conn, "Clutch cable", 1).await;
+    detect_low_stock(&mut conn, true).await.unwrap();
+    delete_client(&mut conn, shared).await.unwrap();
+    delete_spare_part(&mut conn, part_id).await.unwrap();
+
+    // Nothing is old enough yet.
+    assert_eq!(purge_archived(&mut conn, 365).await.unwrap(), 0);
+    age_archive(&mut conn).await;
+    assert_eq!(purge_archived(&mut conn, 365).await.unwrap(), 2);
+
+    let left = count(
+        &mut conn,
+        &format!(
+            "SELECT COUNT(*) FROM moto_auto.client_branch WHERE client_id = {}",
+            shared
+        ),
+    )
+    .await;
+    assert_eq!(left, 0);
+    let left = count(
+        &mut conn,
+        &format!(
+            "SELECT COUNT(*) FROM moto_auto.purchase_order_line WHERE part_id = {}",
+            part_id
+        ),
+    )
+    .await;
+    assert_eq!(left, 0);
+}
+
+#[tokio::test]
+async fn purge_keeps_orders_with_receipts() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_retention", "master", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let order_id = order.order_id.unwrap();
+    complete(&app, "master_retention", order_id).await;
+    let mut conn = app.conn().await;
+    delete_order(&mut conn, order_id).await.unwrap();
+    age_archive(&mut conn).await;
+
+    purge_archived(&mut conn, 365).await.unwrap();
+
+    let receipt = get_order_receipt(&mut conn, order_id).await.unwrap();
+    assert_eq!(receipt.order_id, order_id);
+    assert!(get_order_by_id(&mut conn, order_id).await.is_ok());
+}
+
+#[tokio::test]
+async fn purge_keeps_paid_orders() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_retention_paid", "master", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let order_id = order.order_id.unwrap();
+    let manager = app.user("manager_retention_paid", "manager", 1).await;
+    let mut tx = begin_as(&app.db, &manager).await.unwrap();
+    open_cash_shift(&mut tx, 1, BigDecimal::from(0))
+        .await
+        .unwrap();
+    create_payment(&mut tx, order_id, BigDecimal::from(500), "cash")
+        .await
+        .unwrap();
+    tx.commit().await.unwrap();
+    let mut conn = app.conn().await;
+    delete_order(&mut conn, order_id).await.unwrap();
+    age_archive(&mut conn).await;
+
+    purge_archived(&mut conn, 365).await.unwrap();
+
+    let payments = get_order_payments(&mut conn, order_id).await.unwrap();
+    assert_eq!(payments.len(), 1);
+    assert!(get_order_by_id(&mut conn, order_id).await.is_ok());
+}
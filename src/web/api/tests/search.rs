@@ -0,0 +1,184 @@
+use axum::http::StatusCode;
+
+use crate::database::{
+    client::create_client, spare_part::create_spare_part, vehicle::create_vehicle,
+};
+use crate::models::{SparePart, Vehicle};
+
+use super::clients::client;
+use super::harness::{assert_status, body_string, json_body, TestApp};
+use super::orders::order_with_lines;
+
+fn encode(q: &str) -> String {
+    q.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+async fn search(app: &TestApp, cookie: &str, q: &str) -> Vec<(String, String)> {
+    let uri = format!("/api/v1/search?q={}", encode(q));
+    let response = app.get(&uri, Some(cookie)).await;
+    assert_status(&response, StatusCode::OK);
+    json_body(response)
+        .await
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| {
+            (
+                r["kind"].as_str().unwrap().to_string(),
+                r["title"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect()
+}
+
+fn found(results: &[(String, String)], kind: &str, title: &str) -> bool {
+    results.iter().any(|(k, t)| k == kind && t == title)
+}
+
+#[tokio::test]
+async fn manager_finds_clients_vehicles_parts_and_orders() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_search", "master", 1).await;
+    app.user("manager_search", "manager", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let mut conn = app.conn().await;
+    let mut details = client("Gregory Wolkonsky", "casual", 1);
+    details.phones = vec!["+79161234567".to_string()];
+    details.email = Some("greg@example.com".to_string());
+    let greg = create_client(&mut conn, details).await.unwrap();
+    create_vehicle(
+        &mut conn,
+        Vehicle {
+            vehicle_id: None,
+            client_id: greg.client_id.unwrap(),
+            make: "Kawasaki".to_string(),
+            model: "Versys 650".to_string(),
+            year: None,
+            vin: None,
+            plate: Some("X777XX77".to_string()),
+            mileage: 0,
+            deleted_at: None,
+        },
+    )
+    .await
+    .unwrap();
+    create_spare_part(
+        &mut conn,
+        SparePart {
+            part_id: None,
+            part_name: "Brake pads".to_string(),
+            description: "Sintered front pads".to_string(),
+            sku: None,
+            oem_number: None,
+            manufacturer: None,
+            unit: "pcs".to_string(),
+            deleted_at: None,
+        },
+    )
+    .await
+    .unwrap();
+    let cookie = app.login("manager_search", "manager_search").await.unwrap();
+
+    let results = search(&app, &cookie, "Wolk").await;
+    assert_eq!(
+        results[0],
+        ("client".to_string(), "Gregory Wolkonsky".to_string())
+    );
+    // A typo still finds the name.
+    assert!(found(
+        &search(&app, &cookie, "Wolkonksy").await,
+        "client",
+        "Gregory Wolkonsky"
+    ));
+    assert!(found(
+        &search(&app, &cookie, "916 123").await,
+        "client",
+        "Gregory Wolkonsky"
+    ));
+    assert!(found(
+        &search(&app, &cookie, "GREG@example").await,
+        "client",
+        "Gregory Wolkonsky"
+    ));
+    assert!(found(
+        &search(&app, &cookie, "versys").await,
+        "vehicle",
+        "Kawasaki Versys 650"
+    ));
+    assert!(found(
+        &search(&app, &cookie, "x777").await,
+        "vehicle",
+        "Kawasaki Versys 650"
+    ));
+    assert!(found(
+        &search(&app, &cookie, "sintered").await,
+        "part",
+        "Brake pads"
+    ));
+    assert!(found(
+        &search(&app, &cookie, "chain repl").await,
+        "service",
+        "Chain replacement"
+    ));
+    let order_title = format!("Order #{}", order.order_id.unwrap());
+    let results = search(&app, &cookie, &format!("#{}", order.order_id.unwrap())).await;
+    assert_eq!(results[0], ("order".to_string(), order_title));
+
+    assert!(search(&app, &cookie, "W").await.is_empty());
+    assert!(search(&app, &cookie, "%%").await.is_empty());
+}
+
+#[tokio::test]
+async fn search_finds_only_what_the_role_sees() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_search_scope", "master", 1).await;
+    app.user("manager_search_scope", "manager", 2).await;
+    order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let mut conn = app.conn().await;
+    create_client(&mut conn, client("Order stranger", "casual", 1))
+        .await
+        .unwrap();
+
+    let cookie = app
+        .login("master_search_scope", "master_search_scope")
+        .await
+        .unwrap();
+    let results = search(&app, &cookie, "Order").await;
+    assert!(found(&results, "client", "Order client"));
+    assert!(!found(&results, "client", "Order stranger"));
+
+    let cookie = app
+        .login("manager_search_scope", "manager_search_scope")
+        .await
+        .unwrap();
+    let results = search(&app, &cookie, "Order").await;
+    assert!(!results.iter().any(|(kind, _)| kind == "client"));
+}
+
+#[tokio::test]
+async fn search_box_lists_results() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_search_box", "master", 1).await;
+    app.user("manager_search_box", "manager", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let cookie = app
+        .login("manager_search_box", "manager_search_box")
+        .await
+        .unwrap();
+
+    let body = body_string(app.get("/views/search?q=order+cli", Some(&cookie)).await).await;
+    assert!(body.contains("Order client"));
+    assert!(body.contains(&format!(
+        "/views/client_contacts?client_id={}",
+        order.client_id
+    )));
+    let body = body_string(app.get("/views/search?q=zzzzqqq", Some(&cookie)).await).await;
+    assert!(body.contains("Nothing found"));
+    let body = body_string(app.get("/manager", Some(&cookie)).await).await;
+    assert!(body.contains("hx-get=\"/views/search\""));
+}
@@ -0,0 +1,233 @@
+use bigdecimal::BigDecimal;
+
+use axum::http::StatusCode;
+
+use super::harness::{assert_status, body_string, decimal, json_body, TestApp};
+use super::orders::{complete, order_with_lines};
+
+async fn refund(app: &TestApp, cookie: &str, order_id: i32) -> StatusCode {
+    app.post_form(
+        "/api/v1/manager/refund_order",
+        Some(cookie),
+        &[("order_id", &order_id.to_string()), ("reason", "Returned")],
+    )
+    .await
+    .status()
+}
+
+#[tokio::test]
+async fn closing_a_shift_reconciles_cash() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_shift", "master", 1).await;
+    let manager = app.user("manager_shift", "manager", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let order_id = order.order_id.unwrap();
+    let cookie = app.login("manager_shift", "manager_shift").await.unwrap();
+
+    let response = app
+        .post_form(
+            "/api/v1/manager/open_shift",
+            Some(&cookie),
+            &[("opening_cash", "100")],
+        )
+        .await;
+    assert_status(&response, StatusCode::OK);
+    let shift = json_body(response).await;
+    let shift_id = shift["shift_id"].to_string();
+    assert_eq!(shift["branch_id"], 1);
+    assert_eq!(shift["opened_by"], manager.user_id.unwrap());
+    assert_status(
+        &app.post_form("/api/v1/manager/open_shift", Some(&cookie), &[])
+            .await,
+        StatusCode::BAD_REQUEST,
+    );
+
+    assert_eq!(
+        app.pay(&cookie, order_id, "400", "cash").await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        app.pay(&cookie, order_id, "100", "cash").await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        app.pay(&cookie, order_id, "300", "card").await,
+        StatusCode::OK
+    );
+
+    let response = app
+        .post_form(
+            "/api/v1/manager/close_shift",
+            Some(&cookie),
+            &[("shift_id", &shift_id), ("counted_cash", "550")],
+        )
+        .await;
+    assert_status(&response, StatusCode::OK);
+    let shift = json_body(response).await;
+    assert_eq!(decimal(&shift["expected_cash"]), BigDecimal::from(600));
+    assert_eq!(decimal(&shift["discrepancy"]), BigDecimal::from(-50));
+    assert_eq!(shift["closed_by"], manager.user_id.unwrap());
+
+    // With the shift closed the drawer takes no payments.
+    assert_eq!(
+        app.pay(&cookie, order_id, "700", "transfer").await,
+        StatusCode::BAD_REQUEST
+    );
+
+    let report = json_body(
+        app.get(
+            &format!("/api/v1/manager/shift_report?shift_id={}", shift_id),
+            Some(&cookie),
+        )
+        .await,
+    )
+    .await;
+    let totals = report["totals"].as_array().unwrap();
+    assert_eq!(totals.len(), 2);
+    assert_eq!(totals[0]["method"], "card");
+    assert_eq!(decimal(&totals[0]["amount"]), BigDecimal::from(300));
+    assert_eq!(totals[1]["method"], "cash");
+    assert_eq!(totals[1]["payment_count"], 2);
+    assert_eq!(decimal(&totals[1]["amount"]), BigDecimal::from(500));
+
+    assert_status(
+        &app.post_form(
+            "/api/v1/manager/close_shift",
+            Some(&cookie),
+            &[("shift_id", &shift_id), ("counted_cash", "600")],
+        )
+        .await,
+        StatusCode::BAD_REQUEST,
+    );
+
+    let response = app
+        .get(
+            &format!("/views/shift_report?shift_id={}", shift_id),
+            Some(&cookie),
+        )
+        .await;
+    assert_status(&response, StatusCode::OK);
+    assert!(body_string(response).await.contains("Z-report"));
+}
+
+#[tokio::test]
+async fn shifts_belong_to_the_branch() {
+    let app = TestApp::spawn().await;
+    app.user("manager_shift_home", "manager", 1).await;
+    app.user("manager_shift_other", "manager", 2).await;
+    let home = app
+        .login("manager_shift_home", "manager_shift_home")
+        .await
+        .unwrap();
+    let other = app
+        .login("manager_shift_other", "manager_shift_other")
+        .await
+        .unwrap();
+    let shift = json_body(
+        app.post_form("/api/v1/manager/open_shift", Some(&home), &[])
+            .await,
+    )
+    .await;
+    let shift_id = shift["shift_id"].to_string();
+
+    // Each branch has its own drawer.
+    assert_status(
+        &app.post_form("/api/v1/manager/open_shift", Some(&other), &[])
+            .await,
+        StatusCode::OK,
+    );
+    assert_status(
+        &app.get(
+            &format!("/api/v1/manager/shift_report?shift_id={}", shift_id),
+            Some(&other),
+        )
+        .await,
+        StatusCode::NOT_FOUND,
+    );
+    assert_status(
+        &app.post_form(
+            "/api/v1/manager/close_shift",
+            Some(&other),
+            &[("shift_id", &shift_id), ("counted_cash", "0")],
+        )
+        .await,
+        StatusCode::NOT_FOUND,
+    );
+}
+
+#[tokio::test]
+async fn refunds_come_out_of_the_open_shift() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_shift_refund", "master", 1).await;
+    app.user("manager_shift_refund", "manager", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let order_id = order.order_id.unwrap();
+    let cookie = app
+        .login("manager_shift_refund", "manager_shift_refund")
+        .await
+        .unwrap();
+
+    let first = json_body(
+        app.post_form("/api/v1/manager/open_shift", Some(&cookie), &[])
+            .await,
+    )
+    .await;
+    assert_eq!(
+        app.pay(&cookie, order_id, "1500", "cash").await,
+        StatusCode::OK
+    );
+    complete(&app, "master_shift_refund", order_id).await;
+    assert_status(
+        &app.post_form(
+            "/api/v1/manager/close_shift",
+            Some(&cookie),
+            &[
+                ("shift_id", &first["shift_id"].to_string()),
+                ("counted_cash", "1500"),
+            ],
+        )
+        .await,
+        StatusCode::OK,
+    );
+
+    // No cash is handed back while the drawer is closed.
+    assert_eq!(
+        refund(&app, &cookie, order_id).await,
+        StatusCode::BAD_REQUEST
+    );
+
+    let response = app
+        .post_form(
+            "/api/v1/manager/open_shift",
+            Some(&cookie),
+            &[("opening_cash", "2000")],
+        )
+        .await;
+    let second = json_body(response).await;
+    let second_id = second["shift_id"].to_string();
+    assert_eq!(refund(&app, &cookie, order_id).await, StatusCode::OK);
+
+    let report = json_body(
+        app.get(
+            &format!("/api/v1/manager/shift_report?shift_id={}", second_id),
+            Some(&cookie),
+        )
+        .await,
+    )
+    .await;
+    let totals = report["totals"].as_array().unwrap();
+    assert_eq!(totals.len(), 1);
+    assert_eq!(decimal(&totals[0]["amount"]), BigDecimal::from(-1500));
+
+    let response = app
+        .post_form(
+            "/api/v1/manager/close_shift",
+            Some(&cookie),
+            &[("shift_id", &second_id), ("counted_cash", "500")],
+        )
+        .await;
+    assert_status(&response, StatusCode::OK);
+    let shift = json_body(response).await;
+    assert_eq!(decimal(&shift["expected_cash"]), BigDecimal::from(500));
+    assert_eq!(decimal(&shift["discrepancy"]), BigDecimal::from(0));
+}
@@ -0,0 +1,245 @@
+use axum::http::StatusCode;
+use bigdecimal::BigDecimal;
+use sqlx::PgConnection;
+
+use crate::database::{
+    spare_part::create_spare_part, spare_part_branch::create_spare_part_branch,
+    stock::detect_low_stock,
+};
+use crate::models::{SparePart, SparePartBranch};
+
+use super::harness::{assert_status, body_string, json_body, post, TestApp};
+
+/// Creates a part sold in branch 1 with `stock` in hand, and in branch 2 out of stock
+/// with a reorder point of 5. Returns the part id.
+pub async fn stocked_part(conn: &mut PgConnection, name: &str, stock: i32) -> i32 {
+    let part_id = create_spare_part(
+        conn,
+        SparePart {
+            part_id: None,
+            part_name: name.to_string(),
+            description: String::new(),
+            sku: None,
+            oem_number: None,
+            manufacturer: None,
+            unit: "pcs".to_string(),
+            deleted_at: None,
+        },
+    )
+    .await
+    .unwrap()
+    .part_id
+    .unwrap();
+    for (branch_id, stock_quantity, reorder) in [(1, stock, None), (2, 0, Some((5, 10)))] {
+        create_spare_part_branch(
+            conn,
+            SparePartBranch {
+                spare_part_branch_id: None,
+                part_id,
+                branch_id,
+                stock_quantity,
+                price: BigDecimal::from(300),
+                reorder_point: reorder.map(|(point, _)| point),
+                reorder_quantity: reorder.map(|(_, quantity)| quantity),
+            },
+        )
+        .await
+        .unwrap();
+    }
+    part_id
+}
+
+#[tokio::test]
+async fn manager_reorders_parts_running_low() {
+    let app = TestApp::spawn().await;
+    app.user("manager_stock", "manager", 1).await;
+    let mut conn = app.conn().await;
+    let filter = stocked_part(&mut conn, "Oil filter", 2).await.to_string();
+    stocked_part(&mut conn, "Spark plug", 20).await;
+    let cookie = app.login("manager_stock", "manager_stock").await.unwrap();
+
+    let uri = "/api/v1/manager/set_reorder_level";
+    let level = |point, quantity| {
+        [
+            ("part_id", filter.as_str()),
+            ("reorder_point", point),
+            ("reorder_quantity", quantity),
+        ]
+    };
+    // The point and the quantity go together.
+    assert_eq!(
+        post(&app, &cookie, uri, &level("5", "")).await,
+        StatusCode::BAD_REQUEST
+    );
+    let response = app.post_form(uri, Some(&cookie), &level("5", "12")).await;
+    assert_status(&response, StatusCode::OK);
+    assert_eq!(json_body(response).await["branch_id"], 1);
+
+    // The oil filter is low in both branches and the spark plug in branch 2; a second run
+    // raises nothing new.
+    let raised = detect_low_stock(&mut conn, true).await.unwrap();
+    assert_eq!(raised.len(), 3);
+    assert!(detect_low_stock(&mut conn, true).await.unwrap().is_empty());
+
+    let uri = "/api/v1/manager/stock_levels?low_only=true";
+    let levels = json_body(app.get(uri, Some(&cookie)).await).await;
+    let levels = levels.as_array().unwrap();
+    assert_eq!(levels.len(), 1);
+    assert_eq!(levels[0]["part_name"], "Oil filter");
+    assert_eq!(levels[0]["on_order"], 12);
+    assert!(!levels[0]["low_since"].is_null());
+
+    let orders = json_body(
+        app.get("/api/v1/manager/purchase_orders", Some(&cookie))
+            .await,
+    )
+    .await;
+    let orders = orders.as_array().unwrap();
+    assert_eq!(orders.len(), 1);
+    assert_eq!(orders[0]["purchase_order"]["status"], "draft");
+    assert_eq!(orders[0]["lines"][0]["quantity"], 12);
+    let purchase_order_id = orders[0]["purchase_order"]["purchase_order_id"].to_string();
+    let line_id = orders[0]["lines"][0]["purchase_order_line_id"].to_string();
+    let status = |status| {
+        [
+            ("purchase_order_id", purchase_order_id.as_str()),
+            ("status", status),
+        ]
+    };
+
+    let uri = "/api/v1/manager/update_purchase_order_line";
+    assert_eq!(
+        post(
+            &app,
+            &cookie,
+            uri,
+            &[("purchase_order_line_id", &line_id), ("quantity", "15")]
+        )
+        .await,
+        StatusCode::OK
+    );
+    let uri = "/api/v1/manager/set_purchase_order_status";
+    assert_eq!(
+        post(&app, &cookie, uri, &status("received")).await,
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        post(&app, &cookie, uri, &status("ordered")).await,
+        StatusCode::OK
+    );
+    // Lines of an ordered purchase order are fixed, and parts on order are not drafted again.
+    let line_uri = "/api/v1/manager/update_purchase_order_line";
+    assert_eq!(
+        post(
+            &app,
+            &cookie,
+            line_uri,
+            &[("purchase_order_line_id", &line_id), ("quantity", "0")]
+        )
+        .await,
+        StatusCode::BAD_REQUEST
+    );
+    detect_low_stock(&mut conn, true).await.unwrap();
+    let orders = json_body(
+        app.get("/api/v1/manager/purchase_orders", Some(&cookie))
+            .await,
+    )
+    .await;
+    assert_eq!(orders.as_array().unwrap().len(), 1);
+
+    let body = body_string(app.get("/manager/stock", Some(&cookie)).await).await;
+    assert!(body.contains("Oil filter"));
+    assert!(body.contains("Received"));
+
+    // Receiving the order restocks the branch and the next run resolves the alert.
+    let response = app.post_form(uri, Some(&cookie), &status("received")).await;
+    assert_status(&response, StatusCode::OK);
+    assert!(!json_body(response).await["received_at"].is_null());
+    detect_low_stock(&mut conn, true).await.unwrap();
+    let levels = json_body(app.get("/api/v1/manager/stock_levels", Some(&cookie)).await).await;
+    let filter = levels
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|l| l["part_name"] == "Oil filter")
+        .unwrap();
+    assert_eq!(filter["stock_quantity"], 17);
+    assert!(filter["low_since"].is_null());
+    let open: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM moto_auto.low_stock_alert WHERE resolved_at IS NULL",
+    )
+    .fetch_one(&mut conn)
+    .await
+    .unwrap();
+    assert_eq!(open, 2);
+
+    // The other branch's draft is out of reach.
+    let (other, other_line): (i32, i32) = sqlx::query_as(
+        "SELECT po.purchase_order_id, l.purchase_order_line_id
+        FROM moto_auto.purchase_order po
+        INNER JOIN moto_auto.purchase_order_line l
+        ON l.purchase_order_id = po.purchase_order_id
+        WHERE po.branch_id = 2",
+    )
+    .fetch_one(&mut conn)
+    .await
+    .unwrap();
+    let (other, other_line) = (other.to_string(), other_line.to_string());
+    assert_eq!(
+        post(
+            &app,
+            &cookie,
+            uri,
+            &[("purchase_order_id", &other), ("status", "cancelled")]
+        )
+        .await,
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        post(
+            &app,
+            &cookie,
+            line_uri,
+            &[("purchase_order_line_id", &other_line), ("quantity", "1")]
+        )
+        .await,
+        StatusCode::BAD_REQUEST
+    );
+}
+
+#[tokio::test]
+async fn low_stock_job_only_drafts_purchase_orders_when_asked() {
+    let app = TestApp::spawn().await;
+    app.user("manager_alerts", "manager", 2).await;
+    let mut conn = app.conn().await;
+    let part_id = stocked_part(&mut conn, "Brake fluid", 4).await.to_string();
+
+    assert_eq!(detect_low_stock(&mut conn, false).await.unwrap().len(), 1);
+    let drafted: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM moto_auto.purchase_order")
+        .fetch_one(&mut conn)
+        .await
+        .unwrap();
+    assert_eq!(drafted, 0);
+
+    // Clearing the reorder point stops watching the part.
+    let cookie = app.login("manager_alerts", "manager_alerts").await.unwrap();
+    let response = app
+        .post_form(
+            "/api/v1/manager/set_reorder_level",
+            Some(&cookie),
+            &[
+                ("part_id", &part_id),
+                ("reorder_point", ""),
+                ("reorder_quantity", ""),
+            ],
+        )
+        .await;
+    assert_status(&response, StatusCode::OK);
+    assert!(detect_low_stock(&mut conn, false).await.unwrap().is_empty());
+    let levels = json_body(
+        app.get("/api/v1/manager/stock_levels?low_only=true", Some(&cookie))
+            .await,
+    )
+    .await;
+    assert!(levels.as_array().unwrap().is_empty());
+}
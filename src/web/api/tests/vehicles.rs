@@ -0,0 +1,379 @@
+use axum::http::StatusCode;
+
+use crate::database::{
+    client::create_client,
+    orders::{create_order, get_order_by_id},
+    vehicle::get_vehicle_by_id,
+};
+use crate::models::Order;
+
+use super::clients::client;
+use super::harness::{assert_status, json_body, TestApp};
+use super::orders::order_with_lines;
+
+async fn add_vehicle(app: &TestApp, cookie: &str, client_id: i32, vin: &str) -> serde_json::Value {
+    let response = app
+        .post_form(
+            "/api/v1/manager/edit_vehicle",
+            Some(cookie),
+            &[
+                ("client_id", &client_id.to_string()),
+                ("make", "Honda"),
+                ("model", "CB500F"),
+                ("year", "2019"),
+                ("vin", vin),
+                ("plate", "A123BC"),
+                ("mileage", "12000"),
+            ],
+        )
+        .await;
+    assert_status(&response, StatusCode::OK);
+    json_body(response).await
+}
+
+async fn assign(
+    app: &TestApp,
+    cookie: &str,
+    order_id: i32,
+    vehicle_id: i64,
+    intake_mileage: &str,
+) -> StatusCode {
+    app.post_form(
+        "/api/v1/manager/assign_vehicle",
+        Some(cookie),
+        &[
+            ("order_id", &order_id.to_string()),
+            ("vehicle_id", &vehicle_id.to_string()),
+            ("intake_mileage", intake_mileage),
+        ],
+    )
+    .await
+    .status()
+}
+
+#[tokio::test]
+async fn manager_keeps_a_clients_vehicles() {
+    let app = TestApp::spawn().await;
+    app.user("manager_vehicles", "manager", 1).await;
+    let mut conn = app.conn().await;
+    let owner = create_client(&mut conn, client("Vehicle owner", "casual", 1))
+        .await
+        .unwrap();
+    let client_id = owner.client_id.unwrap();
+    let cookie = app
+        .login("manager_vehicles", "manager_vehicles")
+        .await
+        .unwrap();
+
+    let vehicle = add_vehicle(&app, &cookie, client_id, "jh2pc40jxkm000001").await;
+    assert_eq!(vehicle["vin"], "JH2PC40JXKM000001");
+    assert_eq!(vehicle["mileage"], 12000);
+    let vehicle_id = vehicle["vehicle_id"].as_i64().unwrap();
+
+    let duplicate = app
+        .post_form(
+            "/api/v1/manager/edit_vehicle",
+            Some(&cookie),
+            &[
+                ("client_id", &client_id.to_string()),
+                ("make", "Honda"),
+                ("model", "CB650R"),
+                ("vin", "JH2PC40JXKM000001"),
+            ],
+        )
+        .await;
+    assert_status(&duplicate, StatusCode::BAD_REQUEST);
+
+    let updated = app
+        .post_form(
+            "/api/v1/manager/edit_vehicle",
+            Some(&cookie),
+            &[
+                ("vehicle_id", &vehicle_id.to_string()),
+                ("client_id", &client_id.to_string()),
+                ("make", "Honda"),
+                ("model", "CB500X"),
+                ("year", ""),
+                ("vin", ""),
+                ("plate", "B456CD"),
+                ("mileage", "13000"),
+            ],
+        )
+        .await;
+    assert_status(&updated, StatusCode::OK);
+    let updated = json_body(updated).await;
+    assert_eq!(updated["model"], "CB500X");
+    assert!(updated["vin"].is_null());
+    assert!(updated["year"].is_null());
+
+    let uri = format!("/api/v1/manager/client_vehicles?client_id={}", client_id);
+    let vehicles = json_body(app.get(&uri, Some(&cookie)).await).await;
+    assert_eq!(vehicles.as_array().unwrap().len(), 1);
+
+    let deleted = app
+        .post_form(
+            "/api/v1/manager/delete_vehicle",
+            Some(&cookie),
+            &[("vehicle_id", &vehicle_id.to_string())],
+        )
+        .await;
+    assert_status(&deleted, StatusCode::OK);
+    let vehicles = json_body(app.get(&uri, Some(&cookie)).await).await;
+    assert!(vehicles.as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn mileage_only_grows_and_archived_vehicles_free_their_vin() {
+    let app = TestApp::spawn().await;
+    app.user("manager_vehicle_owner", "manager", 1).await;
+    let mut conn = app.conn().await;
+    let owner = create_client(&mut conn, client("First owner", "casual", 1))
+        .await
+        .unwrap();
+    let buyer = create_client(&mut conn, client("Second owner", "casual", 1))
+        .await
+        .unwrap();
+    let cookie = app
+        .login("manager_vehicle_owner", "manager_vehicle_owner")
+        .await
+        .unwrap();
+    let vehicle = add_vehicle(&app, &cookie, owner.client_id.unwrap(), "JH2PC40J5KM000004").await;
+    let vehicle_id = vehicle["vehicle_id"].to_string();
+    let edit = |mileage| {
+        [
+            ("vehicle_id", vehicle_id.clone()),
+            ("client_id", owner.client_id.unwrap().to_string()),
+            ("make", "Honda".to_string()),
+            ("model", "CB500F".to_string()),
+            ("vin", "JH2PC40J5KM000004".to_string()),
+            ("mileage", mileage),
+        ]
+    };
+
+    for (mileage, status) in [
+        ("11000", StatusCode::BAD_REQUEST),
+        ("", StatusCode::OK),
+        ("14000", StatusCode::OK),
+    ] {
+        let form = edit(mileage.to_string());
+        let form: Vec<(&str, &str)> = form.iter().map(|(k, v)| (*k, v.as_str())).collect();
+        let response = app
+            .post_form("/api/v1/manager/edit_vehicle", Some(&cookie), &form)
+            .await;
+        assert_status(&response, status);
+    }
+    let stored = get_vehicle_by_id(&mut conn, vehicle["vehicle_id"].as_i64().unwrap() as i32)
+        .await
+        .unwrap();
+    assert_eq!(stored.mileage, 14000);
+
+    // Sold on: the old record is archived and the bike is registered to the buyer.
+    assert_status(
+        &app.post_form(
+            "/api/v1/manager/delete_vehicle",
+            Some(&cookie),
+            &[("vehicle_id", &vehicle_id)],
+        )
+        .await,
+        StatusCode::OK,
+    );
+    add_vehicle(&app, &cookie, buyer.client_id.unwrap(), "JH2PC40J5KM000004").await;
+}
+
+#[tokio::test]
+async fn order_takes_only_its_clients_vehicle_and_records_mileage() {
+    let app = TestApp::spawn().await;
+    let master = app.user("master_vehicle_order", "master", 1).await;
+    app.user("manager_vehicle_order", "manager", 1).await;
+    let order = order_with_lines(&app, "casual", master.user_id.unwrap()).await;
+    let order_id = order.order_id.unwrap();
+    let mut conn = app.conn().await;
+    let stranger = create_client(&mut conn, client("Someone else", "casual", 1))
+        .await
+        .unwrap();
+    let cookie = app
+        .login("manager_vehicle_order", "manager_vehicle_order")
+        .await
+        .unwrap();
+    let own = add_vehicle(&app, &cookie, order.client_id, "JH2PC40J1KM000002").await;
+    let foreign = add_vehicle(
+        &app,
+        &cookie,
+        stranger.client_id.unwrap(),
+        "JH2PC40J3KM000003",
+    )
+    .await;
+
+    assert_eq!(
+        assign(
+            &app,
+            &cookie,
+            order_id,
+            foreign["vehicle_id"].as_i64().unwrap(),
+            ""
+        )
+        .await,
+        StatusCode::BAD_REQUEST
+    );
+    let own_id = own["vehicle_id"].as_i64().unwrap();
+    assert_eq!(
+        assign(&app, &cookie, order_id, own_id, "15500").await,
+        StatusCode::OK
+    );
+
+    let order = get_order_by_id(&mut conn, order_id).await.unwrap();
+    assert_eq!(order.vehicle_id, Some(own_id as i32));
+    assert_eq!(order.intake_mileage, Some(15500));
+    let vehicle = get_vehicle_by_id(&mut conn, own_id as i32).await.unwrap();
+    assert_eq!(vehicle.mileage, 15500);
+
+    // A lower reading at intake does not wind the vehicle's mileage back.
+    assert_eq!(
+        assign(&app, &cookie, order_id, own_id, "9000").await,
+        StatusCode::OK
+    );
+    let vehicle = get_vehicle_by_id(&mut conn, own_id as i32).await.unwrap();
+    assert_eq!(vehicle.mileage, 15500);
+}
+
+#[tokio::test]
+async fn master_sees_full_history_of_a_vehicle() {
+    let app = TestApp::spawn().await;
+    let previous = app.user("master_history_previous", "master", 1).await;
+    let current = app.user("master_history_current", "master", 1).await;
+    app.user("master_history_other", "master", 1).await;
+    app.user("manager_history", "manager", 1).await;
+    let earlier = order_with_lines(&app, "casual", previous.user_id.unwrap()).await;
+    let mut conn = app.conn().await;
+    let later = create_order(
+        &mut conn,
+        Order {
+            client_id: earlier.client_id,
+            branch_id: 1,
+            master_id: current.user_id.unwrap(),
+            order_date: chrono::Utc::now(),
+            status: "processing".to_string(),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    let manager = app
+        .login("manager_history", "manager_history")
+        .await
+        .unwrap();
+    let vehicle = add_vehicle(&app, &manager, earlier.client_id, "JH2PC40J5KM000004").await;
+    let vehicle_id = vehicle["vehicle_id"].as_i64().unwrap();
+    for order_id in [earlier.order_id.unwrap(), later.order_id.unwrap()] {
+        assert_eq!(
+            assign(&app, &manager, order_id, vehicle_id, "").await,
+            StatusCode::OK
+        );
+    }
+
+    let uri = format!("/api/v1/master/vehicle_history?vehicle_id={}", vehicle_id);
+    let cookie = app
+        .login("master_history_current", "master_history_current")
+        .await
+        .unwrap();
+    let history = json_body(app.get(&uri, Some(&cookie)).await).await;
+    let entries = history["entries"].as_array().unwrap();
+    assert!(entries
+        .iter()
+        .all(|e| e["order_id"] == earlier.order_id.unwrap()));
+    let names: Vec<&str> = entries
+        .iter()
+        .map(|e| e["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["Chain replacement", "Chain"]);
+
+    // A master with no orders for the owner does not see the vehicle.
+    let cookie = app
+        .login("master_history_other", "master_history_other")
+        .await
+        .unwrap();
+    assert_status(&app.get(&uri, Some(&cookie)).await, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn vins_are_checked_and_decoded() {
+    let app = TestApp::spawn().await;
+    app.user("manager_vin", "manager", 1).await;
+    let mut conn = app.conn().await;
+    let owner = create_client(&mut conn, client("VIN owner", "casual", 1))
+        .await
+        .unwrap();
+    let cookie = app.login("manager_vin", "manager_vin").await.unwrap();
+
+    let decoded = json_body(
+        app.get(
+            "/api/v1/manager/decode_vin?vin=%201hd1kb4117y000001",
+            Some(&cookie),
+        )
+        .await,
+    )
+    .await;
+    assert_eq!(decoded["vin"], "1HD1KB4117Y000001");
+    assert_eq!(decoded["wmi"], "1HD");
+    assert_eq!(decoded["manufacturer"], "Harley-Davidson");
+    assert_eq!(decoded["model_year"], 2007);
+    assert_eq!(decoded["serial_number"], "000001");
+
+    let unknown = json_body(
+        app.get(
+            "/api/v1/manager/decode_vin?vin=AAAAAAAA1AAAAAAAA",
+            Some(&cookie),
+        )
+        .await,
+    )
+    .await;
+    assert!(unknown["manufacturer"].is_null());
+
+    // European manufacturers need not fill in the check digit.
+    let ducati = json_body(
+        app.get(
+            "/api/v1/manager/decode_vin?vin=ZDM1BBEW0JB012345",
+            Some(&cookie),
+        )
+        .await,
+    )
+    .await;
+    assert_eq!(ducati["manufacturer"], "Ducati");
+    assert_eq!(ducati["model_year"], 2018);
+    let response = app
+        .post_form(
+            "/api/v1/manager/edit_vehicle",
+            Some(&cookie),
+            &[
+                ("client_id", &owner.client_id.unwrap().to_string()),
+                ("make", "KTM"),
+                ("model", "690 Enduro"),
+                ("vin", "VBKEXC403KM123456"),
+            ],
+        )
+        .await;
+    assert_status(&response, StatusCode::OK);
+
+    for vin in [
+        "1HD1KB4117Y00000",
+        "1HD1KB4117Y0000011",
+        "1HD1KB4O17Y000001",
+        "1HD1KB4127Y000001",
+    ] {
+        let uri = format!("/api/v1/manager/decode_vin?vin={}", vin);
+        assert_status(&app.get(&uri, Some(&cookie)).await, StatusCode::BAD_REQUEST);
+        let response = app
+            .post_form(
+                "/api/v1/manager/edit_vehicle",
+                Some(&cookie),
+                &[
+                    ("client_id", &owner.client_id.unwrap().to_string()),
+                    ("make", "Harley-Davidson"),
+                    ("model", "Sportster"),
+                    ("vin", vin),
+                ],
+            )
+            .await;
+        assert_status(&response, StatusCode::BAD_REQUEST);
+    }
+}
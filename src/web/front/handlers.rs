@@ -1,49 +1,232 @@
-use axum::http::StatusCode;
-use axum::{extract::Query, Extension};
-use sqlx::PgPool;
-use tower_sessions::Session;
+use axum::extract::{Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
+use serde::Deserialize;
 
-use crate::database::orders::get_orders;
-use crate::database::user::get_users;
+use crate::database::search::MIN_QUERY_LENGTH;
 use crate::models::Order;
-use crate::web::api::common::get_user_id;
+use crate::models::User;
+use crate::receipt;
+use crate::web::api::common::CurrentUser;
 use crate::web::front::views::AdminIndex;
-use crate::{models::User, web::session::Cache};
+use crate::web::state::AppState;
 
-use super::views::{AnalystIndex, Login, ManagerIndex, ManagerOrderView, MasterIndex, OrderEdit, UserEdit};
+use super::views::{
+    AdminArchive, AdminBranch, AdminLoyalty, AdminServices, AnalystIndex, AnalystPrices, AuditIndex, BranchCreate, BranchEdit, Login,
+    ClientStatement, ManagerIndex, ManagerReminders, ManagerShifts, ManagerStock, ManagerOrderView, MasterIndex, OrderEdit, OrderPaymentsView, ReceiptView, ShiftReportView, SuperadminIndex, UserEdit,
+    ClientContactsView, ClientVehiclesView, SearchResults, VehicleHistoryView, AdminParts, PartFitmentView, PartMatches,
+};
 
 pub async fn login() -> Login {
     Login {}
 }
 
 pub async fn admin_index(
-    db: Extension<PgPool>,
-    session: Session,
-    cache: Extension<Cache>,
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
 ) -> Result<AdminIndex, StatusCode> {
-    if let Ok(Some(user_id)) = get_user_id(cache, session).await {
-        if let Ok(users) = get_users(&db, user_id).await {
-            return Ok(AdminIndex { users });
+    let mut tx = state.store.begin_as(&user).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(users) = tx.get_users(user.branch_id).await {
+        return Ok(AdminIndex { users });
+    }
+    Err(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+pub async fn admin_branch(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+) -> Result<AdminBranch, StatusCode> {
+    let mut tx = state.store.begin_as(&user).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(branch) = tx.get_branch_by_id(user.branch_id).await {
+        return Ok(AdminBranch { branch });
+    }
+    Err(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+pub async fn admin_archive(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+) -> Result<AdminArchive, StatusCode> {
+    let branch_id = match user.role.as_ref() {
+        "superadmin" => None,
+        "admin" => Some(user.branch_id),
+        _ => return Err(StatusCode::FORBIDDEN),
+    };
+    let mut tx = state.store.begin_as(&user).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let (Ok(orders), Ok(mut clients), Ok(mut services), Ok(mut spare_parts)) = (
+        tx.get_archived_orders(branch_id).await,
+        tx.get_archived_clients().await,
+        tx.get_archived_service().await,
+        tx.get_archived_spare_part().await,
+    ) {
+        // Admins only see their branch's clients; the shared catalog is the superadmin's.
+        if let Some(branch_id) = branch_id {
+            clients.retain(|c| c.home_branch_id == branch_id);
+            services.clear();
+            spare_parts.clear();
         }
-        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        return Ok(AdminArchive { catalog: branch_id.is_none(), orders, clients, services, spare_parts });
+    }
+    Err(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+pub async fn admin_loyalty(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+) -> Result<AdminLoyalty, StatusCode> {
+    if user.role != "superadmin" && user.role != "admin" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state.store.begin_as(&user).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(tiers) = tx.get_loyalty_tiers().await {
+        return Ok(AdminLoyalty { tiers });
+    }
+    Err(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+pub async fn admin_services(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+) -> Result<AdminServices, StatusCode> {
+    if user.role != "superadmin" && user.role != "admin" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state.store.begin_as(&user).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(services) = tx.get_services().await {
+        return Ok(AdminServices { services });
     }
     Err(StatusCode::INTERNAL_SERVER_ERROR)
 }
 
+pub async fn admin_parts(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+) -> Result<AdminParts, StatusCode> {
+    if user.role != "superadmin" && user.role != "admin" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state.store.begin_as(&user).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(parts) = tx.get_spare_parts().await {
+        return Ok(AdminParts { parts });
+    }
+    Err(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[derive(Deserialize)]
+pub struct PartFitmentQuery {
+    pub part_id: i32,
+}
+
+pub async fn part_fitment(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Query(query): Query<PartFitmentQuery>,
+) -> Result<PartFitmentView, StatusCode> {
+    if user.role != "superadmin" && user.role != "admin" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state.store.begin_as(&user).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let parts = tx.get_spare_parts().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let part = parts
+        .into_iter()
+        .find(|p| p.part_id == Some(query.part_id))
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let compatibility = tx.get_part_compatibility(query.part_id).await;
+    let substitutes = tx.get_part_substitutes(query.part_id).await;
+    if let (Ok(compatibility), Ok(substitutes)) = (compatibility, substitutes) {
+        return Ok(PartFitmentView { part, compatibility, substitutes });
+    }
+    Err(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+const AUDIT_PAGE_SIZE: i64 = 200;
+
+#[derive(Default, Deserialize)]
+pub struct AuditQuery {
+    pub entity: Option<String>,
+    pub entity_id: Option<String>,
+    pub actor_user_id: Option<String>,
+    pub operation: Option<String>,
+}
+
+pub async fn audit_index(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Query(filter): Query<AuditQuery>,
+) -> Result<AuditIndex, StatusCode> {
+    let home = match user.role.as_ref() {
+        "superadmin" => "/superadmin",
+        "admin" => "/admin",
+        "analyst" => "/analyst",
+        _ => return Err(StatusCode::FORBIDDEN),
+    };
+    let non_empty = |v: &Option<String>| v.as_deref().filter(|v| !v.is_empty()).map(String::from);
+    let entity = non_empty(&filter.entity);
+    let operation = non_empty(&filter.operation);
+    let entity_id = non_empty(&filter.entity_id).and_then(|v| v.parse().ok());
+    let actor_user_id = non_empty(&filter.actor_user_id).and_then(|v| v.parse().ok());
+    let mut tx = state.store.begin_as(&user).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(entries) = tx
+        .get_audit_log(
+            entity.as_deref(),
+            entity_id,
+            actor_user_id,
+            operation.as_deref(),
+            AUDIT_PAGE_SIZE,
+        )
+        .await
+    {
+        return Ok(AuditIndex { home: home.to_string(), filter, entries });
+    }
+    Err(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+pub async fn superadmin_index(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+) -> Result<SuperadminIndex, StatusCode> {
+    if user.role != "superadmin" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state.store.begin_as(&user).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(branches) = tx.get_branch(None, true).await {
+        return Ok(SuperadminIndex { branches });
+    }
+    Err(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[derive(Deserialize)]
+pub struct BranchQuery {
+    pub branch_id: i32,
+}
+
+pub async fn branch_edit(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Query(query): Query<BranchQuery>,
+) -> Result<BranchEdit, StatusCode> {
+    let mut tx = state.store.begin_as(&user).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    tx.get_branch_by_id(query.branch_id)
+        .await
+        .map(|branch| BranchEdit { branch })
+        .map_err(|_| StatusCode::NOT_FOUND)
+}
+
+pub async fn branch_create() -> BranchCreate {
+    BranchCreate {}
+}
+
 pub async fn user_edit(Query(user): Query<User>) -> UserEdit {
     UserEdit { user }
 }
 
 pub async fn master_index(
-    db: Extension<PgPool>,
-    session: Session,
-    cache: Extension<Cache>,
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
 ) -> Result<MasterIndex, StatusCode> {
-    if let Ok(Some(user_id)) = get_user_id(cache, session).await {
-        if let Ok(orders) = get_orders(&db, None, Some(user_id), None).await {
-            return Ok(MasterIndex { orders });
-        }
-        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    let mut tx = state.store.begin_as(&user).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(orders) = tx.get_orders(None, user.user_id, None).await {
+        return Ok(MasterIndex { orders });
     }
     Err(StatusCode::INTERNAL_SERVER_ERROR)
 }
@@ -53,24 +236,352 @@ pub async fn order_view(Query(order): Query<Order>) -> OrderEdit {
 }
 
 pub async fn manager_index(
-    db: Extension<PgPool>,
-    session: Session,
-    cache: Extension<Cache>,
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
 ) -> Result<ManagerIndex, StatusCode> {
-    if let Ok(Some(user_id)) = get_user_id(cache, session).await {
-        if let Ok(orders) = get_orders(&db, None, Some(user_id), None).await {
-            return Ok(ManagerIndex { orders });
-        }
-        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    let mut tx = state.store.begin_as(&user).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(orders) = tx.get_orders(Some(user.branch_id), None, None).await {
+        return Ok(ManagerIndex { orders });
+    }
+    Err(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+pub async fn order_edit(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Query(order): Query<Order>,
+) -> Result<ManagerOrderView, StatusCode> {
+    let Some(order_id) = order.order_id.filter(|id| *id != 0) else {
+        return Ok(ManagerOrderView { order });
+    };
+    let mut tx = state.store.begin_as(&user).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    tx.get_order_by_id(order_id)
+        .await
+        .map(|order| ManagerOrderView { order })
+        .map_err(|_| StatusCode::NOT_FOUND)
+}
+
+#[derive(Deserialize)]
+pub struct ClientStatementQuery {
+    pub client_id: i32,
+}
+
+pub async fn client_statement(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Query(query): Query<ClientStatementQuery>,
+) -> Result<ClientStatement, StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state.store.begin_as(&user).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let client = tx.get_client_by_id(query.client_id).await.map_err(|_| StatusCode::NOT_FOUND)?;
+    if let Ok(entries) = tx.get_bonus_statement(query.client_id).await {
+        return Ok(ClientStatement { client, entries });
+    }
+    Err(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+pub async fn order_payments(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Query(query): Query<ReceiptQuery>,
+) -> Result<OrderPaymentsView, StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state.store.begin_as(&user).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let order = tx.get_order_by_id(query.order_id).await.map_err(|_| StatusCode::NOT_FOUND)?;
+    if let (Ok(payments), Ok(outstanding), Ok(client_outstanding)) = (
+        tx.get_order_payments(query.order_id).await,
+        tx.get_order_outstanding(query.order_id).await,
+        tx.get_client_outstanding(order.client_id).await,
+    ) {
+        return Ok(OrderPaymentsView { order, payments, outstanding, client_outstanding });
+    }
+    Err(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[derive(Deserialize)]
+pub struct ReceiptQuery {
+    pub order_id: i32,
+}
+
+pub async fn receipt_view(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Query(query): Query<ReceiptQuery>,
+) -> Result<ReceiptView, StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state.store.begin_as(&user).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let receipt = tx.get_order_receipt(query.order_id).await.map_err(|_| StatusCode::NOT_FOUND)?;
+    if let Ok(lines) = tx.get_receipt_lines(receipt.receipt_id).await {
+        return Ok(ReceiptView { receipt, lines });
+    }
+    Err(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+pub async fn receipt_pdf(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Query(query): Query<ReceiptQuery>,
+) -> Result<impl IntoResponse, StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state.store.begin_as(&user).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let receipt = tx.get_order_receipt(query.order_id).await.map_err(|_| StatusCode::NOT_FOUND)?;
+    let lines = tx
+        .get_receipt_lines(receipt.receipt_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let disposition = format!(
+        "attachment; filename=\"receipt-{}-{}.pdf\"",
+        receipt.branch_id, receipt.receipt_number
+    );
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/pdf".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        receipt::render_pdf(&receipt, &lines),
+    ))
+}
+
+pub async fn manager_shifts(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+) -> Result<ManagerShifts, StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state.store.begin_as(&user).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(shifts) = tx.get_cash_shifts(user.branch_id).await {
+        return Ok(ManagerShifts { shifts });
     }
     Err(StatusCode::INTERNAL_SERVER_ERROR)
 }
 
-pub async fn order_edit(Query(order): Query<Order>) -> ManagerOrderView {
-    ManagerOrderView { order }
+pub async fn manager_reminders(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+) -> Result<ManagerReminders, StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state.store.begin_as(&user).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(reminders) = tx.get_maintenance_reminders().await {
+        return Ok(ManagerReminders { reminders });
+    }
+    Err(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+pub async fn manager_stock(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+) -> Result<ManagerStock, StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state.store.begin_as(&user).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let levels = tx
+        .get_stock_levels(user.branch_id, false)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let purchase_orders = tx
+        .get_open_purchase_orders(user.branch_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let mut lines = Vec::new();
+    for purchase_order in &purchase_orders {
+        lines.extend(
+            tx.get_purchase_order_lines(purchase_order.purchase_order_id)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+        );
+    }
+    Ok(ManagerStock {
+        levels,
+        purchase_orders,
+        lines,
+    })
+}
+
+#[derive(Deserialize)]
+pub struct ShiftReportQuery {
+    pub shift_id: i32,
+}
+
+pub async fn shift_report(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Query(query): Query<ShiftReportQuery>,
+) -> Result<ShiftReportView, StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state.store.begin_as(&user).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let shift = tx.get_cash_shift(query.shift_id).await.map_err(|_| StatusCode::NOT_FOUND)?;
+    if let Ok(totals) = tx.get_shift_totals(query.shift_id).await {
+        return Ok(ShiftReportView { shift, totals });
+    }
+    Err(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[derive(Deserialize)]
+pub struct ClientContactsQuery {
+    pub client_id: i32,
+}
+
+pub async fn client_contacts(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Query(query): Query<ClientContactsQuery>,
+) -> Result<ClientContactsView, StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state.store.begin_as(&user).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(client) = tx.get_client_by_id(query.client_id).await {
+        return Ok(ClientContactsView { client });
+    }
+    Err(StatusCode::NOT_FOUND)
+}
+
+#[derive(Deserialize)]
+pub struct ClientVehiclesQuery {
+    pub client_id: i32,
+    pub order_id: Option<i32>,
+}
+
+pub async fn client_vehicles(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Query(query): Query<ClientVehiclesQuery>,
+) -> Result<ClientVehiclesView, StatusCode> {
+    if user.role != "manager" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state.store.begin_as(&user).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let client = tx.get_client_by_id(query.client_id).await.map_err(|_| StatusCode::NOT_FOUND)?;
+    let order = match query.order_id {
+        Some(order_id) => Some(tx.get_order_by_id(order_id).await.map_err(|_| StatusCode::NOT_FOUND)?),
+        None => None,
+    };
+    if let Ok(vehicles) = tx.get_client_vehicles(query.client_id).await {
+        return Ok(ClientVehiclesView { client, order, vehicles });
+    }
+    Err(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[derive(Deserialize)]
+pub struct VehicleHistoryQuery {
+    pub vehicle_id: i32,
+}
+
+pub async fn vehicle_history(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Query(query): Query<VehicleHistoryQuery>,
+) -> Result<VehicleHistoryView, StatusCode> {
+    if !matches!(user.role.as_ref(), "manager" | "master") {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state.store.begin_as(&user).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let vehicle = tx.get_vehicle_by_id(query.vehicle_id).await.map_err(|_| StatusCode::NOT_FOUND)?;
+    if let Ok(entries) = tx.get_vehicle_history(query.vehicle_id).await {
+        return Ok(VehicleHistoryView { vehicle, entries });
+    }
+    Err(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+const PRICE_HISTORY_PAGE_SIZE: i64 = 200;
+
+#[derive(Default, Deserialize)]
+pub struct PriceHistoryQuery {
+    pub item_type: Option<String>,
+    pub item_id: Option<String>,
+    pub branch_id: Option<String>,
+}
+
+pub async fn analyst_prices(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Query(filter): Query<PriceHistoryQuery>,
+) -> Result<AnalystPrices, StatusCode> {
+    if user.role != "analyst" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let non_empty = |v: &Option<String>| v.as_deref().filter(|v| !v.is_empty()).map(String::from);
+    let item_type = non_empty(&filter.item_type);
+    let item_id = non_empty(&filter.item_id).and_then(|v| v.parse().ok());
+    let branch_id = non_empty(&filter.branch_id).and_then(|v| v.parse().ok());
+    let mut tx = state.store.begin_as(&user).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(entries) = tx
+        .get_price_history(item_type.as_deref(), item_id, branch_id, PRICE_HISTORY_PAGE_SIZE)
+        .await
+    {
+        return Ok(AnalystPrices { filter, entries });
+    }
+    Err(StatusCode::INTERNAL_SERVER_ERROR)
 }
 
 pub async fn analyst_index(
 ) -> Result<AnalystIndex, StatusCode> {
     return Ok(AnalystIndex{})
 }
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    pub q: String,
+}
+
+/// Results shown under the search box in the header.
+const SEARCH_BOX_RESULTS: i32 = 10;
+
+pub async fn search(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Query(query): Query<SearchQuery>,
+) -> Result<SearchResults, StatusCode> {
+    let q = query.q.trim();
+    if q.chars().count() < MIN_QUERY_LENGTH {
+        return Ok(SearchResults { role: user.role, searched: false, results: Vec::new() });
+    }
+    let mut tx = state.store.begin_as(&user).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Ok(results) = tx.search(q, SEARCH_BOX_RESULTS).await {
+        return Ok(SearchResults { role: user.role, searched: true, results });
+    }
+    Err(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[derive(Deserialize)]
+pub struct FindPartsQuery {
+    pub order_id: i32,
+    #[serde(default)]
+    pub q: String,
+}
+
+const PART_FINDER_RESULTS: i32 = 20;
+
+pub async fn find_parts(
+    State(state): State<AppState>,
+    CurrentUser(user): CurrentUser,
+    Query(query): Query<FindPartsQuery>,
+) -> Result<PartMatches, StatusCode> {
+    if !matches!(user.role.as_ref(), "manager" | "master") {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut tx = state.store.begin_as(&user).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let order = tx.get_order_by_id(query.order_id).await.map_err(|_| StatusCode::NOT_FOUND)?;
+    let q = query.q.trim();
+    if q.chars().count() < MIN_QUERY_LENGTH {
+        return Ok(PartMatches { searched: false, parts: Vec::new() });
+    }
+    if let Ok(parts) = tx.find_parts(q, order.vehicle_id, order.branch_id, PART_FINDER_RESULTS).await {
+        return Ok(PartMatches { searched: true, parts });
+    }
+    Err(StatusCode::INTERNAL_SERVER_ERROR)
+}
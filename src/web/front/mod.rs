@@ -1,28 +1,65 @@
 use axum::{routing::get, Router};
 use handlers::{
-    admin_index, analyst_index, login, manager_index, master_index, order_edit, order_view, user_edit
+    admin_archive, admin_branch, admin_index, admin_loyalty, admin_parts, admin_services, analyst_index, analyst_prices, audit_index, branch_create,
+    branch_edit, client_contacts, client_statement, client_vehicles, find_parts, login, manager_index, manager_reminders, manager_shifts, manager_stock, master_index, order_edit, order_payments,
+    order_view, part_fitment, receipt_pdf, receipt_view, search, shift_report, superadmin_index, user_edit,
+    vehicle_history,
 };
 
+use crate::web::state::AppState;
+
 mod handlers;
 mod views;
 
-pub fn new_front_router() -> Router {
+pub fn new_front_router() -> Router<AppState> {
     let view_router = Router::new()
         .route("/user_edit", get(user_edit))
         .route("/order_view", get(order_view))
-        .route("/order_edit", get(order_edit));
+        .route("/order_edit", get(order_edit))
+        .route("/client_statement", get(client_statement))
+        .route("/order_payments", get(order_payments))
+        .route("/receipt", get(receipt_view))
+        .route("/shift_report", get(shift_report))
+        .route("/receipt.pdf", get(receipt_pdf))
+        .route("/client_contacts", get(client_contacts))
+        .route("/client_vehicles", get(client_vehicles))
+        .route("/vehicle_history", get(vehicle_history))
+        .route("/search", get(search))
+        .route("/find_parts", get(find_parts))
+        .route("/part_fitment", get(part_fitment))
+        .route("/branch_edit", get(branch_edit))
+        .route("/branch_create", get(branch_create));
+
+    let superadmin_router = Router::new()
+        .route("/", get(superadmin_index))
+        .route("/audit", get(audit_index));
 
-    let admin_router = Router::new().route("/", get(admin_index));
+    let admin_router = Router::new()
+        .route("/", get(admin_index))
+        .route("/branches", get(admin_branch))
+        .route("/archive", get(admin_archive))
+        .route("/loyalty", get(admin_loyalty))
+        .route("/services", get(admin_services))
+        .route("/parts", get(admin_parts))
+        .route("/audit", get(audit_index));
 
     let master_router = Router::new().route("/", get(master_index));
 
-    let manager_router = Router::new().route("/", get(manager_index));
+    let manager_router = Router::new()
+        .route("/", get(manager_index))
+        .route("/shifts", get(manager_shifts))
+        .route("/reminders", get(manager_reminders))
+        .route("/stock", get(manager_stock));
 
-    let analyst_router = Router::new().route("/", get(analyst_index));
+    let analyst_router = Router::new()
+        .route("/", get(analyst_index))
+        .route("/audit", get(audit_index))
+        .route("/prices", get(analyst_prices));
 
     let default_router = Router::new().route("/login", get(login));
 
     Router::new()
+        .nest("/superadmin", superadmin_router)
         .nest("/admin", admin_router)
         .nest("/master", master_router)
         .nest("/views/", view_router)
@@ -1,6 +1,14 @@
 use askama_axum::Template;
+use bigdecimal::BigDecimal;
 
-use crate::models::{Order, User};
+use crate::models::{
+    AuditLog, BonusTransaction, Branch, CashShift, Client, LoyaltyTier, MaintenanceReminder, Order, PartCompatibility, PartMatch, Payment, PriceHistory, PurchaseOrder, PurchaseOrderLine, Receipt, ReceiptLine, SearchResult, Service,
+    ServiceHistoryEntry, ShiftTotal, SparePart, StockLevel, User, Vehicle,
+};
+
+use crate::database::spare_part::PART_UNITS;
+
+use super::handlers::{AuditQuery, PriceHistoryQuery};
 
 #[derive(Template)]
 #[template(path = "login.html")]
@@ -18,6 +26,83 @@ pub struct UserEdit {
     pub user: User,
 }
 
+#[derive(Template)]
+#[template(path = "admin/branch.html")]
+pub struct AdminBranch {
+    pub branch: Branch,
+}
+
+#[derive(Template)]
+#[template(path = "admin/archive.html")]
+pub struct AdminArchive {
+    /// Whether the user archives and restores services and spare parts.
+    pub catalog: bool,
+    pub orders: Vec<Order>,
+    pub clients: Vec<Client>,
+    pub services: Vec<Service>,
+    pub spare_parts: Vec<SparePart>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/loyalty.html")]
+pub struct AdminLoyalty {
+    pub tiers: Vec<LoyaltyTier>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/services.html")]
+pub struct AdminServices {
+    pub services: Vec<Service>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/parts.html")]
+pub struct AdminParts {
+    pub parts: Vec<SparePart>,
+}
+
+impl AdminParts {
+    fn units(&self) -> &'static [&'static str] {
+        &PART_UNITS
+    }
+
+    fn unit_selected(&self, part: &SparePart, unit: &str) -> bool {
+        part.unit == unit
+    }
+}
+
+#[derive(Template)]
+#[template(path = "admin/part_fitment.html")]
+pub struct PartFitmentView {
+    pub part: SparePart,
+    pub compatibility: Vec<PartCompatibility>,
+    pub substitutes: Vec<SparePart>,
+}
+
+#[derive(Template)]
+#[template(path = "audit.html")]
+pub struct AuditIndex {
+    pub home: String,
+    pub filter: AuditQuery,
+    pub entries: Vec<AuditLog>,
+}
+
+#[derive(Template)]
+#[template(path = "superadmin/base.html")]
+pub struct SuperadminIndex {
+    pub branches: Vec<Branch>,
+}
+
+#[derive(Template)]
+#[template(path = "superadmin/branch_edit.html")]
+pub struct BranchEdit {
+    pub branch: Branch,
+}
+
+#[derive(Template)]
+#[template(path = "superadmin/branch_create.html")]
+pub struct BranchCreate {}
+
 #[derive(Template)]
 #[template(path = "master/base.html")]
 pub struct MasterIndex {
@@ -45,3 +130,145 @@ pub struct ManagerOrderView {
 #[derive(Template)]
 #[template(path = "analyst/base.html")]
 pub struct AnalystIndex {}
+
+#[derive(Template)]
+#[template(path = "analyst/prices.html")]
+pub struct AnalystPrices {
+    pub filter: PriceHistoryQuery,
+    pub entries: Vec<PriceHistory>,
+}
+
+#[derive(Template)]
+#[template(path = "manager/client_statement.html")]
+pub struct ClientStatement {
+    pub client: Client,
+    pub entries: Vec<BonusTransaction>,
+}
+
+#[derive(Template)]
+#[template(path = "manager/receipt.html")]
+pub struct ReceiptView {
+    pub receipt: Receipt,
+    pub lines: Vec<ReceiptLine>,
+}
+
+#[derive(Template)]
+#[template(path = "manager/order_payments.html")]
+pub struct OrderPaymentsView {
+    pub order: Order,
+    pub payments: Vec<Payment>,
+    pub outstanding: BigDecimal,
+    pub client_outstanding: BigDecimal,
+}
+
+#[derive(Template)]
+#[template(path = "manager/shifts.html")]
+pub struct ManagerShifts {
+    pub shifts: Vec<CashShift>,
+}
+
+impl ManagerShifts {
+    fn has_open_shift(&self) -> bool {
+        self.shifts.iter().any(|s| s.closed_at.is_none())
+    }
+}
+
+#[derive(Template)]
+#[template(path = "manager/shift_report.html")]
+pub struct ShiftReportView {
+    pub shift: CashShift,
+    pub totals: Vec<ShiftTotal>,
+}
+
+#[derive(Template)]
+#[template(path = "manager/client_contacts.html")]
+pub struct ClientContactsView {
+    pub client: Client,
+}
+
+#[derive(Template)]
+#[template(path = "manager/client_vehicles.html")]
+pub struct ClientVehiclesView {
+    pub client: Client,
+    pub order: Option<Order>,
+    pub vehicles: Vec<Vehicle>,
+}
+
+#[derive(Template)]
+#[template(path = "vehicle_history.html")]
+pub struct VehicleHistoryView {
+    pub vehicle: Vehicle,
+    pub entries: Vec<ServiceHistoryEntry>,
+}
+
+#[derive(Template)]
+#[template(path = "manager/reminders.html")]
+pub struct ManagerReminders {
+    pub reminders: Vec<MaintenanceReminder>,
+}
+
+#[derive(Template)]
+#[template(path = "manager/stock.html")]
+pub struct ManagerStock {
+    pub levels: Vec<StockLevel>,
+    pub purchase_orders: Vec<PurchaseOrder>,
+    pub lines: Vec<PurchaseOrderLine>,
+}
+
+impl ManagerStock {
+    fn low(&self) -> Vec<&StockLevel> {
+        self.levels.iter().filter(|l| self.is_low(l)).collect()
+    }
+
+    fn is_low(&self, level: &StockLevel) -> bool {
+        level.reorder_point.is_some_and(|point| level.stock_quantity < point)
+    }
+
+    fn lines_of(&self, purchase_order: &PurchaseOrder) -> Vec<&PurchaseOrderLine> {
+        self.lines
+            .iter()
+            .filter(|l| l.purchase_order_id == purchase_order.purchase_order_id)
+            .collect()
+    }
+}
+
+#[derive(Template)]
+#[template(path = "search_results.html")]
+pub struct SearchResults {
+    pub role: String,
+    /// Whether the query was long enough to run, so an empty list means nothing matched.
+    pub searched: bool,
+    pub results: Vec<SearchResult>,
+}
+
+impl SearchResults {
+    /// View a result opens in, for the kinds the role has one for.
+    fn detail_url(&self, result: &SearchResult) -> Option<String> {
+        match (self.role.as_str(), result.kind.as_str()) {
+            ("manager", "client") => Some(format!("/views/client_contacts?client_id={}", result.id)),
+            ("manager" | "master", "vehicle") => {
+                Some(format!("/views/vehicle_history?vehicle_id={}", result.id))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "part_matches.html")]
+pub struct PartMatches {
+    /// Whether the query was long enough to run, so an empty list means nothing matched.
+    pub searched: bool,
+    pub parts: Vec<PartMatch>,
+}
+
+impl PartMatches {
+    /// Name of the listed part a substitute stands in for.
+    fn replaced_name(&self, part_id: &i32) -> String {
+        self.parts
+            .iter()
+            .find(|p| p.part_id == *part_id)
+            .map(|p| p.part_name.clone())
+            .unwrap_or_else(|| format!("#{}", part_id))
+    }
+}
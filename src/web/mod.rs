@@ -1,59 +1,142 @@
 use api::new_api_router;
-use axum::{middleware, Extension, Router};
+use axum::{middleware, Router};
+use crate::config::Config;
+use crate::database::{
+    loyalty::expire_bonus_points, reminder::create_maintenance_reminders, repo::PgStore,
+    retention::purge_archived, stock::detect_low_stock,
+};
+use crate::notify::{deliver_outbox, notifier_from_config};
 use front::new_front_router;
 use middlewares::auth_middleware;
-use session::Cache;
 use sqlx::PgPool;
+use state::AppState;
 use tokio_cron_scheduler::{Job, JobScheduler};
 use tower::ServiceBuilder;
 use tower_http::trace::TraceLayer;
-use tower_sessions::{MemoryStore, SessionManagerLayer};
+use tower_sessions::SessionManagerLayer;
 use tracing::Level;
 
 mod api;
 mod front;
 mod middlewares;
 mod session;
+mod state;
 
 pub enum WebError {
     InitError,
     ServerError,
 }
 
-pub async fn serve(db: PgPool, addr: &str) -> Result<(), WebError> {
-    tracing_subscriber::fmt()
-        .with_max_level(Level::DEBUG)
-        .init();
+pub fn new_app(state: AppState) -> Router {
+    let session_layer = SessionManagerLayer::new(state.session_store.clone())
+        .with_secure(state.config.secure_cookies);
 
-    let session_store = MemoryStore::default();
-    let session_layer = SessionManagerLayer::new(session_store).with_secure(false);
-    let cache = Cache::default();
-
-    let app = Router::new()
+    Router::new()
         .nest("/api/v1", new_api_router())
         .nest("", new_front_router())
         .layer(
             ServiceBuilder::new()
                 .layer(TraceLayer::new_for_http())
-                .layer(Extension(db.clone()))
-                .layer(Extension(cache))
                 .layer(session_layer)
-                .layer(middleware::from_fn(auth_middleware))
-        );
+                .layer(middleware::from_fn_with_state(state.clone(), auth_middleware))
+        )
+        .with_state(state)
+}
+
+pub async fn serve(db: PgPool, config: Config) -> Result<(), WebError> {
+    tracing_subscriber::fmt()
+        .with_max_level(Level::DEBUG)
+        .init();
+
+    let addr = config.bind_addr.clone();
+    let retention_days = config.archive_retention_days;
+    let draft_purchase_orders = config.draft_purchase_orders;
+    let notifier = notifier_from_config(&config);
+    let app = new_app(AppState::new(PgStore::new(db.clone()), config));
 
     let scheduler = JobScheduler::new().await.unwrap();
+
+    let retention_db = db.clone();
+    scheduler.add(
+        Job::new_async("0 0 3 1 * *", move |_uuid, _l| {
+            let pool = retention_db.clone();
+            Box::pin(async move {
+                let mut conn = match pool.acquire().await {
+                    Ok(conn) => conn,
+                    Err(e) => return eprintln!("Error acquiring connection: {:?}", e),
+                };
+                match purge_archived(&mut conn, retention_days).await {
+                    Ok(purged) => println!("Purged {} archived records", purged),
+                    Err(e) => eprintln!("Error executing purge_archived_records: {:?}", e),
+                }
+            })
+        }).unwrap()
+    ).await.unwrap();
     
+    let expiry_db = db.clone();
+    scheduler.add(
+        Job::new_async("0 0 1 * * *", move |_uuid, _l| {
+            let pool = expiry_db.clone();
+            Box::pin(async move {
+                let mut conn = match pool.acquire().await {
+                    Ok(conn) => conn,
+                    Err(e) => return eprintln!("Error acquiring connection: {:?}", e),
+                };
+                match expire_bonus_points(&mut conn).await {
+                    Ok(expired) => println!("Expired {} bonus point accruals", expired),
+                    Err(e) => eprintln!("Error executing expire_bonus_points: {:?}", e),
+                }
+            })
+        }).unwrap()
+    ).await.unwrap();
+
+    let reminder_db = db.clone();
+    scheduler.add(
+        Job::new_async("0 0 9 * * *", move |_uuid, _l| {
+            let pool = reminder_db.clone();
+            Box::pin(async move {
+                let mut conn = match pool.acquire().await {
+                    Ok(conn) => conn,
+                    Err(e) => return eprintln!("Error acquiring connection: {:?}", e),
+                };
+                match create_maintenance_reminders(&mut conn).await {
+                    Ok(created) => println!("Created {} maintenance reminders", created.len()),
+                    Err(e) => eprintln!("Error executing create_maintenance_reminders: {:?}", e),
+                }
+            })
+        }).unwrap()
+    ).await.unwrap();
+
+    let stock_db = db.clone();
+    scheduler.add(
+        Job::new_async("0 30 * * * *", move |_uuid, _l| {
+            let pool = stock_db.clone();
+            Box::pin(async move {
+                let mut conn = match pool.acquire().await {
+                    Ok(conn) => conn,
+                    Err(e) => return eprintln!("Error acquiring connection: {:?}", e),
+                };
+                match detect_low_stock(&mut conn, draft_purchase_orders).await {
+                    Ok(raised) => println!("Raised {} low stock alerts", raised.len()),
+                    Err(e) => eprintln!("Error executing detect_low_stock: {:?}", e),
+                }
+            })
+        }).unwrap()
+    ).await.unwrap();
+
     scheduler.add(
-        Job::new_async("0 0 1 1 * *", move |_uuid, _l| {
-            let pool = db.clone(); 
+        Job::new_async("0 * * * * *", move |_uuid, _l| {
+            let pool = db.clone();
+            let notifier = notifier.clone();
             Box::pin(async move {
-                if let Err(e) = sqlx::query("SELECT expire_bonus_points()")
-                    .execute(&pool)
-                    .await
-                {
-                    eprintln!("Error executing expire_bonus_points: {:?}", e);
-                } else {
-                    println!("Bonus points expired successfully!");
+                let mut conn = match pool.acquire().await {
+                    Ok(conn) => conn,
+                    Err(e) => return eprintln!("Error acquiring connection: {:?}", e),
+                };
+                match deliver_outbox(&mut conn, notifier.as_ref()).await {
+                    Ok(0) => {}
+                    Ok(sent) => println!("Sent {} notifications", sent),
+                    Err(e) => eprintln!("Error delivering notifications: {:?}", e),
                 }
             })
         }).unwrap()
@@ -61,7 +144,7 @@ pub async fn serve(db: PgPool, addr: &str) -> Result<(), WebError> {
 
     scheduler.start().await.unwrap();
 
-    let listener = tokio::net::TcpListener::bind(addr)
+    let listener = tokio::net::TcpListener::bind(&addr)
         .await
         .map_err(|_| WebError::InitError)?;
 
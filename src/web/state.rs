@@ -0,0 +1,50 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use tower_sessions::MemoryStore;
+
+use crate::config::Config;
+use crate::database::repo::Store;
+use crate::web::session::Cache;
+
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Everything handlers share, passed to the router with `Router::with_state`.
+#[derive(Clone)]
+pub struct AppState {
+    pub store: Arc<dyn Store>,
+    /// Cookie sessions handled by `tower_sessions`.
+    pub session_store: MemoryStore,
+    /// API keys of logged-in sessions mapped to user ids.
+    pub sessions: Cache,
+    pub config: Arc<Config>,
+    pub clock: Arc<dyn Clock>,
+}
+
+impl AppState {
+    pub fn new(store: impl Store + 'static, config: Config) -> Self {
+        AppState {
+            store: Arc::new(store),
+            session_store: MemoryStore::default(),
+            sessions: Cache::default(),
+            config: Arc::new(config),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+}